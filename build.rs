@@ -1,3 +1,6 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 fn main() -> std::io::Result<()> {
     // 只有在目标是 Windows 平台时才执行此操作
     if cfg!(target_os = "windows") {
@@ -17,5 +20,73 @@ fn main() -> std::io::Result<()> {
         // 编译资源并链接到最终的 EXE
         res.compile()?;
     }
+    emit_build_info();
     Ok(())
-}
\ No newline at end of file
+}
+
+// 把构建信息写入编译期环境变量，供 --build-info 输出
+fn emit_build_info() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/logs/HEAD");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=10", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=MDDPLAYER_GIT_COMMIT={}", commit);
+
+    // 可复现构建时使用 SOURCE_DATE_EPOCH
+    let epoch = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()));
+    println!("cargo:rustc-env=MDDPLAYER_BUILD_DATE={}", utc_date(epoch));
+
+    println!("cargo:rustc-env=MDDPLAYER_TARGET={}", std::env::var("TARGET").unwrap_or_default());
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=MDDPLAYER_FEATURES={}", features.join(","));
+
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    for name in ["rodio", "cpal"] {
+        let version = locked_version(&lock, name).unwrap_or_else(|| "unknown".to_string());
+        println!("cargo:rustc-env=MDDPLAYER_{}_VERSION={}", name.to_uppercase(), version);
+    }
+}
+
+// 从 Cargo.lock 中查找依赖的版本
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() == format!("name = \"{}\"", name) {
+            let version = lines.next()?.trim().strip_prefix("version = \"")?.strip_suffix('"')?;
+            return Some(version.to_string());
+        }
+    }
+    None
+}
+
+// Unix 时间戳转换为 UTC 日期（YYYY-MM-DD）
+fn utc_date(epoch: u64) -> String {
+    let days = (epoch / 86_400) as i64;
+    // 公历日期换算（Howard Hinnant 的 civil_from_days 算法）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
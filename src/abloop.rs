@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+/// 单曲内的 A-B 循环状态：按 A 依次标记起点、标记终点并启用、清除。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AbLoop {
+    /// 未设置
+    Off,
+    /// 已标记起点，等待标记终点
+    StartMarked(Duration),
+    /// 循环已启用 (起点, 终点)
+    Active(Duration, Duration),
+}
+
+impl AbLoop {
+    /// 处理一次 A 键。`total` 为歌曲总时长（未知时为 0），标记点会被限制在总时长以内；
+    /// 终点早于起点时自动交换，两点相同则视为无效并清除。
+    pub fn toggle(self, pos: Duration, total: Duration) -> AbLoop {
+        let pos = if total.is_zero() { pos } else { pos.min(total) };
+        match self {
+            AbLoop::Off => AbLoop::StartMarked(pos),
+            AbLoop::StartMarked(a) if a == pos => AbLoop::Off,
+            AbLoop::StartMarked(a) => AbLoop::Active(a.min(pos), a.max(pos)),
+            AbLoop::Active(_, _) => AbLoop::Off,
+        }
+    }
+
+    /// 播放位置越过终点时返回需要跳回的起点
+    pub fn wrap_target(&self, pos: Duration) -> Option<Duration> {
+        match *self {
+            AbLoop::Active(a, b) if pos >= b => Some(a),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    /// 依次在各位置按 A
+    fn presses(positions: &[u64], total: u64) -> AbLoop {
+        positions.iter().fold(AbLoop::Off, |ab, &pos| ab.toggle(secs(pos), secs(total)))
+    }
+
+    #[test]
+    fn three_presses_mark_activate_and_clear() {
+        assert_eq!(presses(&[62], 300), AbLoop::StartMarked(secs(62)));
+        assert_eq!(presses(&[62, 78], 300), AbLoop::Active(secs(62), secs(78)));
+        assert_eq!(presses(&[62, 78, 90], 300), AbLoop::Off);
+        assert_eq!(presses(&[62, 78, 90, 10], 300), AbLoop::StartMarked(secs(10)));
+    }
+
+    #[test]
+    fn marks_are_swapped_clamped_or_cleared() {
+        // 终点早于起点：交换
+        assert_eq!(presses(&[78, 62], 300), AbLoop::Active(secs(62), secs(78)));
+        // 超出总时长的标记限制在结尾；总时长未知时不限制
+        assert_eq!(presses(&[250, 400], 300), AbLoop::Active(secs(250), secs(300)));
+        assert_eq!(presses(&[400, 250], 300), AbLoop::Active(secs(250), secs(300)));
+        assert_eq!(presses(&[250, 400], 0), AbLoop::Active(secs(250), secs(400)));
+        // 两点相同（包括都被限制到结尾）：无效并清除
+        assert_eq!(presses(&[62, 62], 300), AbLoop::Off);
+        assert_eq!(presses(&[310, 400], 300), AbLoop::Off);
+    }
+
+    #[test]
+    fn wraps_only_past_the_end_mark() {
+        let ab = presses(&[62, 78], 300);
+        assert_eq!(ab.wrap_target(secs(61)), None);
+        assert_eq!(ab.wrap_target(secs(70)), None);
+        assert_eq!(ab.wrap_target(secs(78)), Some(secs(62)));
+        assert_eq!(ab.wrap_target(secs(120)), Some(secs(62)));
+        // 只标记了起点或未设置时从不跳回
+        assert_eq!(presses(&[62], 300).wrap_target(secs(290)), None);
+        assert_eq!(AbLoop::Off.wrap_target(secs(290)), None);
+    }
+
+    #[test]
+    fn pausing_inside_the_loop_keeps_the_wrap_check() {
+        let ab = presses(&[62, 78], 300);
+        // 暂停时位置不再前进，每次刷新都以同一位置检查：循环内不跳回，也不会被清除
+        for _ in 0..10 {
+            assert_eq!(ab.wrap_target(secs(75)), None);
+        }
+        // 恰好停在终点上暂停：跳回起点后从起点继续检查
+        assert_eq!(ab.wrap_target(secs(78)), Some(secs(62)));
+        assert_eq!(ab.wrap_target(secs(62)), None);
+        // 继续播放后越过终点照常跳回
+        assert_eq!(ab.wrap_target(Duration::from_millis(78_200)), Some(secs(62)));
+        assert_eq!(ab, AbLoop::Active(secs(62), secs(78)));
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveTime, TimeDelta, TimeZone};
+
+use crate::utils::parse_duration;
+
+/// `--alarm` 指定的开始播放时刻
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmSpec {
+    /// 本地时间的某个时刻（已过时为明天的同一时刻）
+    At(NaiveTime),
+    /// 启动后经过的时长
+    After(Duration),
+}
+
+impl AlarmSpec {
+    /// 解析 `07:30`（本地时间）或 `+25m`、`+1h30m`、`+90s`（相对启动时，不带单位时按分钟）（作为 clap 的 value_parser）
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        match s.strip_prefix('+') {
+            Some(relative) => parse_relative(relative).map(AlarmSpec::After),
+            None => NaiveTime::parse_from_str(s, "%H:%M")
+                .map(AlarmSpec::At)
+                .map_err(|_| format!("闹钟时间应写作 时:分（如 07:30）或 +时长（如 +25m、+1h30m），而不是 '{}'", s)),
+        }
+    }
+
+    /// 换算为具体时刻；指定的时刻今天已过（或因夏令时跳变而不存在）时顺延到之后的第一天。
+    /// 夏令时结束、同一时刻出现两次时取较早的一次
+    pub fn resolve<Tz: TimeZone>(&self, now: DateTime<Tz>) -> DateTime<Tz> {
+        match *self {
+            AlarmSpec::After(delay) => now + TimeDelta::from_std(delay).unwrap_or(TimeDelta::MAX),
+            AlarmSpec::At(time) => (0..=2)
+                .filter_map(|days| (now.date_naive() + TimeDelta::days(days)).and_time(time).and_local_timezone(now.timezone()).earliest())
+                .find(|at| *at > now)
+                .unwrap_or(now),
+        }
+    }
+}
+
+/// 解析 `+` 之后的时长：共用的 parse_duration，不带单位的纯数字按分钟
+fn parse_relative(text: &str) -> Result<Duration, String> {
+    let minutes = || text.parse::<u64>().ok().map(|m| Duration::from_secs(m.saturating_mul(60)));
+    match parse_duration(text).or_else(minutes) {
+        Some(delay) if !delay.is_zero() => Ok(delay),
+        _ => Err(format!("相对时间应写作 +时长（如 +25m、+1h30m、+90s），而不是 '+{}'", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, MappedLocalTime, NaiveDate, NaiveDateTime};
+
+    fn offset(hours: i32) -> FixedOffset {
+        FixedOffset::east_opt(hours * 3600).unwrap()
+    }
+
+    fn naive(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    /// 测试用时区（不依赖运行环境的本地时区）：UTC+1，2026 年 3 月 29 日 01:00 至 10 月 25 日 01:00（UTC）为夏令时 UTC+2，
+    /// 即本地 3 月 29 日没有 02:00-03:00，10 月 25 日的 02:00-03:00 出现两次
+    #[derive(Debug, Clone, Copy)]
+    struct Cet;
+
+    impl TimeZone for Cet {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Cet
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> MappedLocalTime<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> MappedLocalTime<FixedOffset> {
+            // 换算回来偏移一致的才是有效的本地时刻：两种都有效为重复的一小时，都无效为跳过的一小时
+            let valid: Vec<_> = [offset(2), offset(1)].into_iter().filter(|o| self.offset_from_utc_datetime(&(*local - *o)) == *o).collect();
+            match valid[..] {
+                [] => MappedLocalTime::None,
+                [only] => MappedLocalTime::Single(only),
+                [earlier, later] => MappedLocalTime::Ambiguous(earlier, later),
+                _ => unreachable!(),
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            if (naive(3, 29, 1, 0)..naive(10, 25, 1, 0)).contains(utc) { offset(2) } else { offset(1) }
+        }
+    }
+
+    /// 本地时刻；重复的一小时取较早的一次
+    fn local(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Cet> {
+        naive(month, day, hour, minute).and_local_timezone(Cet).earliest().unwrap()
+    }
+
+    fn at(text: &str) -> AlarmSpec {
+        AlarmSpec::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_clock_times_and_relative_delays() {
+        let secs = Duration::from_secs;
+        for (text, expected) in [
+            ("07:30", AlarmSpec::At(NaiveTime::from_hms_opt(7, 30, 0).unwrap())),
+            (" 7:05 ", AlarmSpec::At(NaiveTime::from_hms_opt(7, 5, 0).unwrap())),
+            ("+90s", AlarmSpec::After(secs(90))),
+            ("+25m", AlarmSpec::After(secs(1500))),
+            ("+1h30m", AlarmSpec::After(secs(5400))),
+            ("+1:30:00", AlarmSpec::After(secs(5400))),
+            // 不带单位按分钟
+            ("+25", AlarmSpec::After(secs(1500))),
+        ] {
+            assert_eq!(AlarmSpec::parse(text), Ok(expected), "{:?}", text);
+        }
+        for text in ["24:00", "7.30", "tomorrow", "", "+", "+0m", "+0", "+1d", "+-5m", "+1h30"] {
+            assert!(AlarmSpec::parse(text).is_err(), "{:?}", text);
+        }
+        assert_eq!(AlarmSpec::parse("+1d"), Err("相对时间应写作 +时长（如 +25m、+1h30m、+90s），而不是 '+1d'".to_string()));
+        assert_eq!(AlarmSpec::parse("7.30"), Err("闹钟时间应写作 时:分（如 07:30）或 +时长（如 +25m、+1h30m），而不是 '7.30'".to_string()));
+    }
+
+    #[test]
+    fn clock_time_already_past_rolls_over_to_tomorrow() {
+        assert_eq!(at("07:30").resolve(local(6, 1, 6, 0)), local(6, 1, 7, 30));
+        assert_eq!(at("07:30").resolve(local(6, 1, 8, 0)), local(6, 2, 7, 30));
+        // 恰好到点也算已过
+        assert_eq!(at("07:30").resolve(local(6, 1, 7, 30)), local(6, 2, 7, 30));
+        assert_eq!(at("00:00").resolve(local(6, 1, 23, 59)), local(6, 2, 0, 0));
+        assert_eq!(at("+90s").resolve(local(6, 1, 23, 59)), local(6, 1, 23, 59) + TimeDelta::seconds(90));
+    }
+
+    #[test]
+    fn daylight_saving_gaps_and_folds() {
+        // 02:30 在夏令时开始那天不存在：顺延到下一天
+        assert_eq!(at("02:30").resolve(local(3, 28, 23, 0)), local(3, 30, 2, 30));
+        // 相对时长按实际经过的时间：跳过的一小时不计
+        assert_eq!(at("+1h").resolve(local(3, 29, 1, 30)), local(3, 29, 3, 30));
+        // 夏令时结束那天 02:30 出现两次：取较早（仍为夏令时）的一次
+        let fold = at("02:30").resolve(local(10, 25, 0, 0));
+        assert_eq!(fold.naive_local(), naive(10, 25, 2, 30));
+        assert_eq!(*fold.offset(), offset(2));
+        // 已过了较早的一次（处在重复的一小时中）：不在同一天再响一次
+        let in_fold = naive(10, 25, 2, 45).and_local_timezone(Cet).latest().unwrap();
+        assert_eq!(at("02:30").resolve(in_fold), local(10, 26, 2, 30));
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+// 音频播放库
+use rodio::{Sink, Source};
+use rodio::source::SeekError;
+
+/// 音频源缺少总时长时，模拟输出假定的歌曲长度
+const SIMULATED_FALLBACK_LENGTH: Duration = Duration::from_secs(180);
+
+/// 播放输出后端：播放循环只通过该接口控制播放，
+/// 既可以是真实的 rodio Sink，也可以是无需声卡的模拟输出。
+pub trait SinkBackend {
+    /// 追加一个音频源
+    fn append(&self, source: Box<dyn Source<Item = i16> + Send>);
+    /// 清空队列（与 rodio 一致，清空后处于暂停状态）
+    fn clear(&self);
+    /// 停止并丢弃当前音频源
+    fn stop(&self);
+    fn play(&self);
+    fn pause(&self);
+    fn is_paused(&self) -> bool;
+    /// 队列中是否已没有可播放的内容
+    fn empty(&self) -> bool;
+    fn set_volume(&self, volume: f32);
+    /// 当前音频源的播放位置
+    fn get_pos(&self) -> Duration;
+    fn try_seek(&self, pos: Duration) -> Result<(), SeekError>;
+}
+
+impl SinkBackend for Sink {
+    fn append(&self, source: Box<dyn Source<Item = i16> + Send>) {
+        Sink::append(self, source)
+    }
+    fn clear(&self) {
+        Sink::clear(self)
+    }
+    fn stop(&self) {
+        Sink::stop(self)
+    }
+    fn play(&self) {
+        Sink::play(self)
+    }
+    fn pause(&self) {
+        Sink::pause(self)
+    }
+    fn is_paused(&self) -> bool {
+        Sink::is_paused(self)
+    }
+    fn empty(&self) -> bool {
+        Sink::empty(self)
+    }
+    fn set_volume(&self, volume: f32) {
+        Sink::set_volume(self, volume)
+    }
+    fn get_pos(&self) -> Duration {
+        Sink::get_pos(self)
+    }
+    fn try_seek(&self, pos: Duration) -> Result<(), SeekError> {
+        Sink::try_seek(self, pos)
+    }
+}
+
+/// 模拟输出的内部状态
+struct SimState {
+    /// 当前音频源的长度；None 表示队列为空
+    length: Option<Duration>,
+    /// 最近一次暂停/跳转时的位置
+    base_pos: Duration,
+    /// 开始计时的时刻；暂停时为 None
+    resumed_at: Option<Instant>,
+    paused: bool,
+}
+
+/// 模拟输出：不解码、不出声，按虚拟时钟推进播放位置。
+/// 用于没有音频设备的环境（CI、基准测试），`speedup` 为虚拟时钟相对真实时间的倍速。
+pub struct SimulatedSink {
+    speedup: f32,
+    state: Mutex<SimState>,
+}
+
+impl SimulatedSink {
+    pub fn new(speedup: f32) -> Self {
+        SimulatedSink {
+            speedup: speedup.max(f32::MIN_POSITIVE),
+            state: Mutex::new(SimState { length: None, base_pos: Duration::ZERO, resumed_at: None, paused: false }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 按虚拟时钟计算当前位置（不超过音频长度）
+    fn position(&self, state: &SimState) -> Duration {
+        let elapsed = state.resumed_at.map_or(Duration::ZERO, |t| t.elapsed().mul_f32(self.speedup));
+        let pos = state.base_pos + elapsed;
+        state.length.map_or(Duration::ZERO, |len| pos.min(len))
+    }
+}
+
+impl SinkBackend for SimulatedSink {
+    fn append(&self, source: Box<dyn Source<Item = i16> + Send>) {
+        let length = source.total_duration().unwrap_or(SIMULATED_FALLBACK_LENGTH);
+        let mut state = self.state();
+        state.length = Some(length);
+        state.base_pos = Duration::ZERO;
+        state.resumed_at = (!state.paused).then(Instant::now);
+    }
+    fn clear(&self) {
+        let mut state = self.state();
+        state.length = None;
+        state.base_pos = Duration::ZERO;
+        state.resumed_at = None;
+        state.paused = true;
+    }
+    fn stop(&self) {
+        let mut state = self.state();
+        state.length = None;
+        state.base_pos = Duration::ZERO;
+        state.resumed_at = None;
+    }
+    fn play(&self) {
+        let mut state = self.state();
+        if state.paused {
+            state.paused = false;
+            state.resumed_at = Some(Instant::now());
+        }
+    }
+    fn pause(&self) {
+        let mut state = self.state();
+        if !state.paused {
+            state.base_pos = self.position(&state);
+            state.paused = true;
+            state.resumed_at = None;
+        }
+    }
+    fn is_paused(&self) -> bool {
+        self.state().paused
+    }
+    fn empty(&self) -> bool {
+        let state = self.state();
+        match state.length {
+            Some(len) => self.position(&state) >= len,
+            None => true,
+        }
+    }
+    fn set_volume(&self, _volume: f32) {}
+    fn get_pos(&self) -> Duration {
+        let state = self.state();
+        self.position(&state)
+    }
+    fn try_seek(&self, pos: Duration) -> Result<(), SeekError> {
+        let mut state = self.state();
+        state.base_pos = state.length.map_or(Duration::ZERO, |len| pos.min(len));
+        if !state.paused {
+            state.resumed_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::thread;
+
+    /// 8 kHz 单声道、时长 `seconds` 秒的静音音频源
+    fn silence(seconds: u32) -> Box<dyn Source<Item = i16> + Send> {
+        Box::new(SamplesBuffer::new(1, 8000, vec![0i16; 8000 * seconds as usize]))
+    }
+
+    #[test]
+    fn virtual_clock_runs_at_the_speedup() {
+        let sink = SimulatedSink::new(1000.0);
+        assert!(sink.empty());
+        assert_eq!(sink.get_pos(), Duration::ZERO);
+        sink.append(silence(60));
+        assert!(!sink.empty());
+        // 60 秒的歌曲在 1000 倍速下约 60ms 播完，位置不超过歌曲长度
+        thread::sleep(Duration::from_millis(150));
+        assert!(sink.empty());
+        assert_eq!(sink.get_pos(), Duration::from_secs(60));
+        // 追加下一首后从头计时
+        sink.append(silence(60));
+        assert!(!sink.empty());
+        assert!(sink.get_pos() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn pause_and_seek_hold_the_position() {
+        let sink = SimulatedSink::new(1000.0);
+        sink.append(silence(600));
+        sink.pause();
+        let paused_at = sink.get_pos();
+        thread::sleep(Duration::from_millis(30));
+        assert!(sink.is_paused());
+        assert_eq!(sink.get_pos(), paused_at);
+
+        sink.try_seek(Duration::from_secs(300)).unwrap();
+        assert_eq!(sink.get_pos(), Duration::from_secs(300));
+        sink.play();
+        thread::sleep(Duration::from_millis(30));
+        assert!(sink.get_pos() > Duration::from_secs(310));
+        // 跳转到末尾之后即播完
+        sink.try_seek(Duration::from_secs(900)).unwrap();
+        assert!(sink.empty());
+        assert_eq!(sink.get_pos(), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn clear_leaves_the_sink_paused() {
+        let sink = SimulatedSink::new(1000.0);
+        sink.append(silence(600));
+        sink.clear();
+        assert!(sink.empty() && sink.is_paused());
+        // 与 rodio 一致：清空后追加的歌曲要等 play 才开始
+        sink.append(silence(600));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(sink.get_pos(), Duration::ZERO);
+        sink.play();
+        thread::sleep(Duration::from_millis(20));
+        assert!(sink.get_pos() > Duration::ZERO);
+
+        // 没有总时长的音频源按假定长度计算
+        sink.pause();
+        sink.stop();
+        assert!(sink.empty());
+        sink.append(Box::new(rodio::source::Zero::<i16>::new(1, 8000)));
+        sink.try_seek(Duration::from_secs(3600)).unwrap();
+        assert_eq!(sink.get_pos(), SIMULATED_FALLBACK_LENGTH);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+// 音频源 Trait
+use rodio::Source;
+use rodio::source::SeekError;
+
+// --- 常量定义 ---
+const CHUNK_SAMPLES: usize = 4096; // 每个解码块包含的采样数
+const MAX_PENDING_CHUNKS: usize = 64; // 后台解码最多领先的块数，避免整首歌解码进内存
+
+/// 解码线程与播放端共享的状态
+struct Shared {
+    source: Mutex<Box<dyn Source<Item = i16> + Send>>,
+    /// 每次跳转递增，旧的解码线程发现代数变化后立即退出
+    generation: AtomicU64,
+}
+
+/// 先解码一小段“前导缓冲”再开始播放的音频源。
+/// 解码在后台线程持续进行，播放端只从通道中取出已解码的采样。
+pub struct LeadBufferedSource {
+    pending: VecDeque<i16>,
+    rx: Receiver<Vec<i16>>,
+    shared: Arc<Shared>,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+}
+
+/// 启动后台解码线程，返回接收解码块的通道
+fn spawn_decoder(shared: Arc<Shared>) -> Receiver<Vec<i16>> {
+    let (tx, rx) = sync_channel::<Vec<i16>>(MAX_PENDING_CHUNKS);
+    let generation = shared.generation.load(Ordering::SeqCst);
+    thread::spawn(move || {
+        loop {
+            let chunk: Vec<i16> = {
+                let Ok(mut source) = shared.source.lock() else { return };
+                // 已发生跳转，由新的解码线程接手
+                if shared.generation.load(Ordering::SeqCst) != generation {
+                    return;
+                }
+                source.by_ref().take(CHUNK_SAMPLES).collect()
+            };
+            let finished = chunk.len() < CHUNK_SAMPLES;
+            // 播放端已丢弃（切歌或跳转），停止解码
+            if !chunk.is_empty() && tx.send(chunk).is_err() {
+                return;
+            }
+            if finished {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+impl LeadBufferedSource {
+    /// 启动后台解码，并阻塞到至少缓冲了 `lead` 时长的音频（或音频已结束）为止。
+    pub fn start<S>(source: S, lead: Duration) -> Self
+    where
+        S: Source<Item = i16> + Send + 'static,
+    {
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let total_duration = source.total_duration();
+        let shared = Arc::new(Shared { source: Mutex::new(Box::new(source)), generation: AtomicU64::new(0) });
+        let rx = spawn_decoder(shared.clone());
+
+        // 等待前导缓冲就绪
+        let lead_samples = (lead.as_secs_f64() * sample_rate as f64 * channels as f64) as usize;
+        let mut pending = VecDeque::with_capacity(lead_samples + CHUNK_SAMPLES);
+        while pending.len() < lead_samples {
+            match rx.recv() {
+                Ok(chunk) => pending.extend(chunk),
+                Err(_) => break, // 音频比前导缓冲还短
+            }
+        }
+
+        LeadBufferedSource { pending, rx, shared, channels, sample_rate, total_duration }
+    }
+}
+
+impl Iterator for LeadBufferedSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.pending.is_empty() {
+            // 后台解码跟不上时在此等待；通道关闭表示音频结束
+            let chunk = self.rx.recv().ok()?;
+            self.pending.extend(chunk);
+        }
+        self.pending.pop_front()
+    }
+}
+
+impl Source for LeadBufferedSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    /// 跳转：在持有解码器锁的情况下递增代数并跳转，丢弃已缓冲的采样后重新启动解码线程
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let result = {
+            let mut source = self.shared.source.lock().map_err(|_| SeekError::NotSupported {
+                underlying_source: std::any::type_name::<Self>(),
+            })?;
+            self.shared.generation.fetch_add(1, Ordering::SeqCst);
+            source.try_seek(pos)
+        };
+        // 跳转失败时保留已缓冲的采样，从原位置继续解码
+        if result.is_ok() {
+            self.pending.clear();
+        }
+        self.rx = spawn_decoder(self.shared.clone());
+        result
+    }
+}
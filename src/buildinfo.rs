@@ -0,0 +1,92 @@
+// 音频播放库（cpal 为其底层的音频设备库）
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+
+use crate::cli::VERSION;
+use crate::output::{describe_output, OutputRequest};
+
+/// 构建信息（由 build.rs 在编译期写入）与运行环境信息
+struct BuildInfo {
+    version: &'static str,
+    commit: &'static str,
+    build_date: &'static str,
+    target: &'static str,
+    features: &'static str,
+    rodio: &'static str,
+    cpal: &'static str,
+    audio_host: String,
+    output_device: Option<String>,
+    /// 按命令行请求会使用的输出配置（采样率、声道、样本格式、缓冲）
+    output_config: Option<String>,
+}
+
+impl BuildInfo {
+    fn collect(request: OutputRequest) -> Self {
+        // 只枚举默认音频主机与输出设备，不打开输出流；无声卡的机器上设备为 None
+        let host = rodio::cpal::default_host();
+        let output_device = host.default_output_device().and_then(|d| d.name().ok());
+        BuildInfo {
+            version: VERSION,
+            commit: env!("MDDPLAYER_GIT_COMMIT"),
+            build_date: env!("MDDPLAYER_BUILD_DATE"),
+            target: env!("MDDPLAYER_TARGET"),
+            features: env!("MDDPLAYER_FEATURES"),
+            rodio: env!("MDDPLAYER_RODIO_VERSION"),
+            cpal: env!("MDDPLAYER_CPAL_VERSION"),
+            audio_host: host.id().name().to_string(),
+            output_device,
+            output_config: describe_output(request).ok(),
+        }
+    }
+}
+
+/// 输出构建信息（用于问题反馈），`json` 时输出单行 JSON
+pub fn print_build_info(json: bool, request: OutputRequest) {
+    let info = BuildInfo::collect(request);
+    let features: Vec<&str> = info.features.split(',').filter(|f| !f.is_empty()).collect();
+    if json {
+        let quoted: Vec<String> = features.iter().map(|f| json_string(f)).collect();
+        println!(
+            "{{\"version\":{},\"commit\":{},\"build_date\":{},\"target\":{},\"features\":[{}],\"rodio\":{},\"cpal\":{},\"audio_host\":{},\"output_device\":{},\"output_config\":{}}}",
+            json_string(info.version),
+            json_string(info.commit),
+            json_string(info.build_date),
+            json_string(info.target),
+            quoted.join(","),
+            json_string(info.rodio),
+            json_string(info.cpal),
+            json_string(&info.audio_host),
+            info.output_device.as_deref().map_or("null".to_string(), json_string),
+            info.output_config.as_deref().map_or("null".to_string(), json_string),
+        );
+        return;
+    }
+    println!("版本:       v{}", info.version);
+    println!("提交:       {}", info.commit);
+    println!("构建日期:   {}", info.build_date);
+    println!("目标平台:   {}", info.target);
+    println!("启用特性:   {}", if features.is_empty() { "无".to_string() } else { features.join(", ") });
+    println!("rodio:      {}", info.rodio);
+    println!("cpal:       {}", info.cpal);
+    println!("音频主机:   {}", info.audio_host);
+    println!("输出设备:   {}", info.output_device.as_deref().unwrap_or("未检测到"));
+    println!("输出配置:   {}", info.output_config.as_deref().unwrap_or("未检测到"));
+}
+
+/// 转换为 JSON 字符串字面量
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,5 +1,6 @@
 // 引入 clap 库的 Parser 宏，用于自动生成命令行解析逻辑
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 
 // --- 常量定义 ---
 pub const NAME: &str = "东东播放器";
@@ -13,9 +14,16 @@ pub const URL: &str = "github.com/xa1st/mddplayer";
 #[clap(author, version = VERSION, about = NAME, long_about = None)]
 // 命令行参数定义
 pub struct Args {
-    /// 音频文件或目录路径
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// 音频文件或目录路径；不指定时可继续上次的播放，或使用 MDDPLAYER_MUSIC_DIR 指定的目录
     #[arg(index = 1)]
     pub file: Option<String>,
+
+    /// 不指定路径时直接继续上次的播放，不再询问
+    #[clap(long = "resume")]
+    pub resume: bool,
     
     /// 启用纯净模式，不显示程序说明模式（如操作指南）
     #[clap(short = 's', long = "simple")]
@@ -24,12 +32,432 @@ pub struct Args {
     /// 启用随机模式，不使用则为顺序模式
     #[clap(short = 'r', long = "random")]
     pub random: bool,
-    
+
+    /// 随机模式的打乱方式：plain 普通打乱；smart 尽量拉开同一艺术家（无标签时为同一目录）歌曲的间隔
+    #[clap(long = "shuffle", value_enum, default_value = "plain")]
+    pub shuffle: crate::shuffle::ShuffleMode,
+
+    /// 随机种子：指定后每次打乱的顺序相同
+    #[clap(long = "shuffle-seed", value_name = "SEED")]
+    pub shuffle_seed: Option<u64>,
+
+    /// 记录已听完的歌曲（按输入路径区分），以后运行同一输入时跳过它们；用 reset-progress 子命令清除记录
+    #[clap(long = "no-repeat-across-sessions", conflicts_with = "random_stream")]
+    pub no_repeat_across_sessions: bool,
+
+    /// 把解析出的播放列表（截取、筛选、倒序/随机之后的顺序）导出为 m3u 文件后退出，不打开音频设备
+    #[clap(long = "export-playlist", value_name = "FILE", conflicts_with = "random_stream")]
+    pub export_playlist: Option<String>,
+
+    /// 倒序播放：把输入解析出的列表反转（开场曲/固定的第一首仍最先播放）
+    #[clap(long = "reverse")]
+    pub reverse: bool,
+
+    /// 只播放艺术家包含该文本的歌曲（不区分大小写，无标签时匹配路径）；可多次指定，满足任一即可
+    #[clap(long = "artist", value_name = "TEXT", conflicts_with = "random_stream")]
+    pub artist: Vec<String>,
+
+    /// 只播放专辑包含该文本的歌曲（规则同 --artist）；与 --artist 同用时两者都需满足
+    #[clap(long = "album", value_name = "TEXT", conflicts_with = "random_stream")]
+    pub album: Vec<String>,
+
+    /// 开场曲：先播放该文件，再播放输入中的歌曲（随机模式下开场曲不参与打乱）
+    #[clap(long = "intro", value_name = "FILE")]
+    pub intro: Option<String>,
+
+    /// 固定输入中的第一首（如播放列表的第一行）最先播放，随机模式只打乱其余歌曲
+    #[clap(long = "pin-first")]
+    pub pin_first: bool,
+
+    /// 循环播放时每轮都重新播放开场曲/固定的第一首（默认只在首轮播放）
+    #[clap(long = "intro-every-loop")]
+    pub intro_every_loop: bool,
+
+    /// 记住 F2 切换后的界面模式（纯净/完整），下次启动时沿用（指定 -s 时仍为纯净模式）
+    #[clap(long = "remember-modes")]
+    pub remember_modes: bool,
+
+    /// 界面配色：dark 适合深色背景，light 适合浅色背景；不指定时不着色
+    #[clap(long = "theme", value_enum)]
+    pub theme: Option<crate::theme::ThemeName>,
+
+    /// 覆盖主题中某个元素的颜色，写作 元素=颜色（如 title=#ffaa00、time=208、error=red）；可多次指定
+    #[clap(long = "theme-color", value_name = "KEY=COLOR")]
+    pub theme_color: Vec<String>,
+
+    /// 常驻说明横幅：横幅固定在顶部，状态行固定在横幅下方
+    #[clap(long = "keep-banner", conflicts_with = "clean")]
+    pub keep_banner: bool,
+
+    /// 流式随机播放：目录输入时不预先扫描，每次随机下探选出一首，适合超大曲库
+    #[clap(long = "random-stream")]
+    pub random_stream: bool,
+
+    /// 递归扫描目录下的所有子目录
+    #[clap(short = 'R', long = "recursive")]
+    pub recursive: bool,
+
+    /// 只播放列表中的第 START 到 END 首（从 1 开始，含两端），如 50-120；省略终点（200-）表示到末尾
+    #[clap(long = "range", value_name = "START-END", value_parser = crate::queue::PlaylistRange::parse, conflicts_with = "random_stream")]
+    pub range: Option<crate::queue::PlaylistRange>,
+
+    /// 只播放最先找到的 N 首（目录扫描达到 N 首即停止）
+    #[clap(long = "limit", value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub limit: Option<u64>,
+
+    /// 扫描的文件数超过 N 时先确认是否继续（0 为不检查）
+    #[clap(long = "scan-warn", value_name = "N", default_value = "50000")]
+    pub scan_warn: usize,
+
+    /// 文件数过多时不询问，直接继续扫描（用于脚本）
+    #[clap(short = 'y', long = "yes")]
+    pub yes: bool,
+
+    /// 连按累积切歌：快速连按 N 次 →/← 后一次跳过 N 首（默认连按只切一首）
+    #[clap(long = "skip-accumulate")]
+    pub skip_accumulate: bool,
+
+    /// 接受 mp4/mkv/webm 视频文件，只播放其中的第一条音轨（忽略视频）
+    #[clap(long = "allow-video-containers")]
+    pub allow_video_containers: bool,
+
+    /// 歧义宽度字符（…、±、希腊字母等）的计宽方式；终端把它们显示为两列时选 on，避免状态行溢出换行
+    #[clap(long = "cjk-width", value_enum, default_value = "auto")]
+    pub cjk_width: crate::utils::CjkWidth,
+
+    /// 允许按 W 修改当前歌曲的标题/艺术家并写回文件标签（会修改音频文件）
+    #[clap(long = "allow-tag-edit")]
+    pub allow_tag_edit: bool,
+
+    /// 不按 FLAC 内嵌 CUE 拆分分轨，整文件播放
+    #[clap(long = "no-cue-split")]
+    pub no_cue_split: bool,
+
+    /// 保存按 Ctrl+I 记录的各目录片头跳过时长，下次运行继续生效（默认只在本次运行内有效）
+    #[clap(long = "remember-trims")]
+    pub remember_trims: bool,
+
+    /// 计时诊断：退出时输出预加载各阶段与起播耗时的汇总（配合 --verbose 输出每首明细）
+    #[clap(long = "timing")]
+    pub timing: bool,
+
+    /// 扫描目录时展开其中的 .m3u/.m3u8 播放列表（递归解析列表中引用的列表，循环引用自动跳过）
+    #[clap(long = "expand-playlists")]
+    pub expand_playlists: bool,
+
+    /// 按扩展名强制使用某个解码器，写作 扩展名=解码器（如 m4a=mp3）；解码器: wav、flac、vorbis、mp3；可多次指定
+    #[clap(long = "decoder-override", value_name = "EXT=CODEC")]
+    pub decoder_override: Vec<String>,
+
+    /// 退出时把本次会话的状态变化（音量、暂停/继续、切歌、设备、每首的预加载耗时）以 JSON 写入该文件，用于排查问题
+    #[clap(long = "dump-session", value_name = "FILE")]
+    pub dump_session: Option<String>,
+
+    /// 播放前找出标签相同（艺术家与标题一致、时长相差 2 秒内）的可能重复歌曲并列出，不删除任何文件；
+    /// 写作 --report-duplicates=FILE 时同时把报告写入该文件
+    #[clap(long = "report-duplicates", value_name = "FILE", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+    pub report_duplicates: Option<String>,
+
+    /// 输出详细日志
+    #[clap(long = "verbose")]
+    pub verbose: bool,
+
+    /// 低内存模式（内存很小的设备）：首曲不使用前导缓冲，只预加载下一首（云端文件也不提前两首），读取文件的缓冲更小
+    #[clap(long = "low-memory")]
+    pub low_memory: bool,
+
+    /// 输出构建信息（版本、提交、构建日期、目标平台、依赖版本、音频主机），用于问题反馈，然后退出
+    #[clap(long = "build-info")]
+    pub build_info: bool,
+
+    /// 与 --build-info 同用：以 JSON 格式输出
+    #[clap(long = "json", requires = "build_info")]
+    pub json: bool,
+
+    /// 输出各项设置的生效值及其来源（默认值、环境变量或命令行），然后退出
+    #[clap(long = "print-config")]
+    pub print_config: bool,
+
     /// 是否循环播放
     #[clap(short = 'l', long = "loop")] 
     pub is_loop: bool, 
+
+    /// 列表播放完毕（非循环模式）后的处理：exit 退出；wait 等待按键从头重播
+    #[clap(long = "on-complete", value_enum, default_value = "exit")]
+    pub on_complete: crate::queue::CompletionPolicy,
     
-    /// 播放音量
-    #[clap(short = 'v', long = "volume", default_value = "75")]
+    /// 按键无效时（如音量已到上限、已是第一首）不响铃，只在状态行提示
+    #[clap(long = "no-bell")]
+    pub no_bell: bool,
+
+    /// 手动切歌：每首播完后停下，等按 → 再播放下一首（播放中可按 M 切换）
+    #[clap(long = "manual-advance")]
+    pub manual_advance: bool,
+
+    /// 标签缺失时解析文件名的模式，支持 {artist}、{title} 占位符
+    #[clap(long = "filename-pattern", default_value = crate::metadata::DEFAULT_FILENAME_PATTERN)]
+    pub filename_pattern: String,
+
+    /// 解析文件名时保留开头的曲目编号（默认去掉「01 - 」「01. 」「01 」等前缀，模式中含 {track} 时不去掉）
+    #[clap(long = "keep-track-number")]
+    pub keep_track_number: bool,
+
+    /// 单曲加载失败时的处理方式：skip 跳过、wait 等待后跳过、ask 询问、abort 退出
+    #[clap(long = "on-error", value_enum, default_value = "wait")]
+    pub on_error: crate::failure::ErrorPolicy,
+
+    /// 连续加载失败达到该数量时停止或询问（见 --on-max-errors），0 表示不限制
+    #[clap(long = "max-consecutive-errors", value_name = "N", default_value = "0")]
+    pub max_consecutive_errors: u32,
+
+    /// 连续失败达到上限时的处理方式：stop 停止并输出摘要；ask 询问是否继续
+    #[clap(long = "on-max-errors", value_enum, default_value = "stop")]
+    pub on_max_errors: crate::failure::MaxErrorsPolicy,
+
+    /// 不设置终端（标签页）标题，适合与终端复用器争夺标题的环境
+    #[clap(long = "no-title")]
+    pub no_title: bool,
+
+    /// 终端标题模板，支持 {title}、{artist}、{index}、{total}、{elapsed}、{duration}、{state} 占位符；不指定时为「状态 + 歌曲信息」
+    #[clap(long = "title-format", value_name = "FORMAT", conflicts_with = "no_title")]
+    pub title_format: Option<String>,
+
+    /// 使用 --title-format 时，歌曲与状态不变的情况下标题的刷新间隔（秒）
+    #[clap(long = "title-interval", value_name = "SECONDS", default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    pub title_interval: u64,
+
+    /// 在 tmux/screen 中运行时，切歌时同步设置窗口名（退出时恢复），其他终端中不生效
+    #[clap(long = "set-tmux-title")]
+    pub set_tmux_title: bool,
+
+    /// 窗口名模板，支持 {title}、{artist}、{index}、{total} 占位符
+    #[clap(long = "tmux-title-format", value_name = "FORMAT", default_value = crate::multiplexer::DEFAULT_WINDOW_TITLE_FORMAT)]
+    pub tmux_title_format: String,
+
+    /// 输出缓冲延迟（毫秒）：越小暂停/调音量响应越快，但过小容易因供数不及时出现爆音断续；不指定时使用设备默认值
+    #[clap(long = "latency", visible_alias = "buffer-ms", value_name = "MS", value_parser = clap::value_parser!(u32).range(1..=2000))]
+    pub latency: Option<u32>,
+
+    /// 输出采样率（Hz）：在默认设备支持的配置中选择该采样率，不支持时提示并使用设备默认值
+    #[clap(long = "sample-rate", value_name = "HZ", value_parser = clap::value_parser!(u32).range(8000..=384000))]
+    pub sample_rate: Option<u32>,
+
+    /// 列出音频输出设备（* 为默认设备）后退出；配合 --verbose 显示各设备支持的声道数、采样率、样本格式与缓冲范围
+    #[clap(long = "list-devices")]
+    pub list_devices: bool,
+
+    /// 暂停较久后恢复播放时先回退的秒数（适合有声书、播客），0 表示关闭
+    #[clap(long = "resume-rewind", value_name = "SECONDS", default_value = "0")]
+    pub resume_rewind: u64,
+
+    /// 暂停超过该秒数才在恢复时回退（见 --resume-rewind）
+    #[clap(long = "resume-rewind-after", value_name = "SECONDS", default_value = "60")]
+    pub resume_rewind_after: u64,
+
+    /// 退出确认：Q 与 Ctrl+C 需在 2 秒内按两次才退出（Ctrl+Q 始终立即退出）
+    #[clap(long = "confirm-quit")]
+    pub confirm_quit: bool,
+
+    /// 切歌保护：当前文件长于该分钟数且已播放超过 5 分钟时，←/→ 需在 1 秒内按两次才切歌；0 表示关闭
+    #[clap(long = "skip-confirm-length", value_name = "MINUTES", default_value = "30")]
+    pub skip_confirm_length: u64,
+
+    /// 每隔该分钟数在后台检查接下来的若干首（见 --health-check-ahead）是否仍然存在，已被移走的提前从队列中去掉
+    #[clap(long = "check-playlist-health", value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub check_playlist_health: Option<u64>,
+
+    /// --check-playlist-health 每次检查的歌曲数
+    #[clap(long = "health-check-ahead", value_name = "N", default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+    pub health_check_ahead: u64,
+
+    /// 暂停超过该分钟数（期间没有按键）后释放音频设备或退出，见 --pause-timeout-action
+    #[clap(long = "pause-timeout", value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub pause_timeout: Option<u64>,
+
+    /// 终端失去焦点时暂停播放，重新获得焦点时继续（失去焦点前已手动暂停的不会自动继续）
+    #[clap(long = "pause-on-unfocus")]
+    pub pause_on_unfocus: bool,
+
+    /// 终端失去焦点时界面暗淡显示，便于分辨当前窗口
+    #[clap(long = "dim-on-unfocus")]
+    pub dim_on_unfocus: bool,
+
+    /// 暂停超时后的处理：release 释放音频设备、按键后从暂停处继续；exit 直接退出
+    #[clap(long = "pause-timeout-action", value_enum, default_value = "release")]
+    pub pause_timeout_action: crate::output::PauseTimeoutAction,
+
+    /// 模拟输出：不打开声卡，按给定倍速推进虚拟时钟（用于无音频设备的测试与基准）
+    #[clap(long = "simulate", value_name = "SPEEDUP")]
+    pub simulate: Option<f32>,
+
+    /// 启动渐强：第一首开始时音量在该秒数内从 0 升到设定值（只在启动时进行一次，手动调音量会结束渐强），0 表示关闭
+    #[clap(long = "fade-in-start", value_name = "SECONDS", default_value = "0")]
+    pub fade_in_start: f32,
+
+    /// 音量百分比与实际增益的映射：log（默认）按听感映射，低音量段调节更细腻，50% 约 -6dB；linear 与增益成正比
+    #[clap(long = "volume-curve", value_enum, default_value = "log")]
+    pub volume_curve: crate::volume::VolumeCurve,
+
+    /// 闹钟：先等待（不占用音频设备），到点后再开始播放；写作 07:30（本地时间，已过则为明天）或 +25m、+1h30m（相对现在）。可与 --fade-in-start 搭配
+    #[clap(long = "alarm", value_name = "TIME", value_parser = crate::alarm::AlarmSpec::parse)]
+    pub alarm: Option<crate::alarm::AlarmSpec>,
+
+    /// 播放音量（0-100）
+    #[clap(short = 'v', long = "volume", default_value = "75", value_parser = clap::value_parser!(u8).range(0..=100))]
     pub volume: u8,
-}
\ No newline at end of file
+}
+/// 子命令（不指定时播放音乐）
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// 自检：逐项检查音频设备、各格式解码、数据目录与终端，任一关键项失败时以非零状态退出（反馈「没有声音」等问题时请附上输出）
+    Doctor,
+    /// 输出运行中播放器的状态（单行 JSON，或按 --format 模板填充），供状态栏脚本定时调用；没有运行中的播放器时不输出并以 1 退出。
+    /// 通过数据目录中只有当前用户可以连接的套接字查询（Windows 上为本机回环地址的随机端口，同一台机器上的其他用户也能查询）
+    Status {
+        /// 输出模板，支持 {title}、{artist}、{index}、{total}、{position}、{duration}、{volume}、{volume_db}、{state}、{muted} 占位符
+        #[arg(long = "format", value_name = "TEMPLATE")]
+        format: Option<String>,
+    },
+    /// 让运行中的播放器临时压低音量（on）或恢复（off），同 H 键，可用于通知或语音通话脚本；没有运行中的播放器时以 1 退出
+    Duck {
+        /// on 压低，off 恢复
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+    },
+    /// 清除 --no-repeat-across-sessions 为该输入（文件、目录或播放列表）记录的已听完歌曲
+    ResetProgress {
+        /// 与播放时相同的输入路径
+        path: String,
+    },
+    /// 递归分析目录中各歌曲的响度，写入 ReplayGain 单曲增益与专辑增益（同一目录下的歌曲视为一张专辑）；
+    /// 已有结果的目录默认跳过，中断后重新运行即可继续
+    Gain {
+        /// 音乐目录
+        path: String,
+        /// 不修改音频文件，把结果写入该目录下的 replaygain.csv
+        #[arg(long = "no-write")]
+        no_write: bool,
+        /// 重新分析已有结果的歌曲
+        #[arg(long = "force")]
+        force: bool,
+    },
+}
+
+/// 解析命令行参数，同时保留 ArgMatches，用于查询每个参数的来源
+pub fn parse_args() -> (Args, ArgMatches) {
+    let matches = Args::command().get_matches();
+    match Args::from_arg_matches(&matches) {
+        Ok(args) => (args, matches),
+        Err(e) => e.exit(),
+    }
+}
+
+/// `--print-config`：逐项输出生效的设置及其来源，格式为 `名称 = 值  # 来源`。
+/// `music_dir_env` 为未指定输入路径时使用的默认音乐目录环境变量。
+pub fn print_config(matches: &ArgMatches, music_dir_env: &str) {
+    for line in config_lines(matches, music_dir_env, std::env::var(music_dir_env).ok()) {
+        println!("{}", line);
+    }
+}
+
+/// 逐项给出生效值与来源：命令行 > 环境变量 > 默认值；输入路径未指定时回退到 `music_dir`（环境变量的值）
+fn config_lines(matches: &ArgMatches, music_dir_env: &str, music_dir: Option<String>) -> Vec<String> {
+    let mut lines = Vec::new();
+    let command = Args::command();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if matches!(id, "help" | "version" | "print_config" | "build_info" | "json" | "list_devices") {
+            continue;
+        }
+        let name = arg.get_long().unwrap_or(id);
+        let raw: Option<Vec<String>> = matches
+            .get_raw(id)
+            .map(|values| values.map(|v| v.to_string_lossy().into_owned()).collect());
+        let (value, source) = match (raw, matches.value_source(id)) {
+            (Some(values), Some(ValueSource::CommandLine)) => (values.join(","), "命令行".to_string()),
+            (Some(values), Some(ValueSource::EnvVariable)) => (values.join(","), "环境变量".to_string()),
+            (Some(values), _) => (values.join(","), "默认值".to_string()),
+            // 输入路径未指定时回退到环境变量
+            (None, _) if id == "file" => match music_dir.as_deref().map(str::trim) {
+                Some(dir) if !dir.is_empty() => (dir.to_string(), format!("环境变量 {}", music_dir_env)),
+                _ => {
+                    lines.push(format!("{} = （未设置）", name));
+                    continue;
+                }
+            },
+            (None, _) => {
+                lines.push(format!("{} = （未设置）", name));
+                continue;
+            }
+        };
+        lines.push(format!("{} = {}  # {}", name, quote_value(&value), source));
+    }
+    lines
+}
+
+/// 数字与布尔值原样输出，其余加引号（便于看出首尾空白）
+fn quote_value(value: &str) -> String {
+    if value.parse::<f64>().is_ok() || value == "true" || value == "false" {
+        value.to_string()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENV: &str = "MDDPLAYER_MUSIC_DIR";
+
+    fn lines(args: &[&str], music_dir: Option<&str>) -> Vec<String> {
+        let matches = Args::command().try_get_matches_from(std::iter::once("mddplayer").chain(args.iter().copied())).unwrap();
+        config_lines(&matches, ENV, music_dir.map(str::to_string))
+    }
+
+    fn line<'a>(lines: &'a [String], name: &str) -> &'a str {
+        let prefix = format!("{} = ", name);
+        lines.iter().find(|line| line.starts_with(&prefix)).unwrap_or_else(|| panic!("没有 {}：{:?}", name, lines))
+    }
+
+    #[test]
+    fn command_line_beats_environment_and_defaults() {
+        let config = lines(&["-r", "--shuffle", "smart", "--scan-warn", "10", "/cli"], Some("/env"));
+        assert_eq!(line(&config, "file"), "file = \"/cli\"  # 命令行");
+        assert_eq!(line(&config, "random"), "random = true  # 命令行");
+        assert_eq!(line(&config, "shuffle"), "shuffle = \"smart\"  # 命令行");
+        assert_eq!(line(&config, "scan-warn"), "scan-warn = 10  # 命令行");
+    }
+
+    #[test]
+    fn unset_values_fall_back_in_order() {
+        let config = lines(&[], Some("  /env  "));
+        assert_eq!(line(&config, "file"), format!("file = \"/env\"  # 环境变量 {}", ENV));
+        assert_eq!(line(&config, "random"), "random = false  # 默认值");
+        assert_eq!(line(&config, "shuffle"), "shuffle = \"plain\"  # 默认值");
+        assert_eq!(line(&config, "scan-warn"), "scan-warn = 50000  # 默认值");
+        assert_eq!(line(&config, "limit"), "limit = （未设置）");
+
+        // 环境变量为空白时视为未设置
+        for music_dir in [None, Some(" ")] {
+            assert_eq!(line(&lines(&[], music_dir), "file"), "file = （未设置）");
+        }
+    }
+
+    #[test]
+    fn skips_actions_that_are_not_settings() {
+        let config = lines(&[], None);
+        for name in ["help", "version", "print-config", "build-info", "json", "list-devices"] {
+            assert!(!config.iter().any(|line| line.starts_with(&format!("{} = ", name))), "{}", name);
+        }
+    }
+
+    #[test]
+    fn quotes_only_text_values() {
+        assert_eq!(quote_value("40"), "40");
+        assert_eq!(quote_value("-1.5"), "-1.5");
+        assert_eq!(quote_value("true"), "true");
+        assert_eq!(quote_value(" a b "), "\" a b \"");
+        assert_eq!(quote_value("一\"二"), "\"一\\\"二\"");
+    }
+}
@@ -0,0 +1,189 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// 双击确认：第一次按下只提示，窗口内再次按下同一个键才执行，用于切歌、退出等容易误触的操作。
+pub struct DoublePress<K> {
+    window: Duration,
+    /// 等待确认的键及其第一次按下的时刻
+    pending: Option<(K, Instant)>,
+}
+
+impl<K: PartialEq> DoublePress<K> {
+    pub fn new(window: Duration) -> Self {
+        DoublePress { window, pending: None }
+    }
+
+    /// 记录一次按键：窗口内第二次按下同一个键时返回 true（并清除等待状态），
+    /// 否则把这次按键记为第一次并返回 false
+    pub fn press(&mut self, key: K, now: Instant) -> bool {
+        let confirmed = self
+            .pending
+            .as_ref()
+            .is_some_and(|(pending, first)| *pending == key && now.saturating_duration_since(*first) <= self.window);
+        self.pending = if confirmed { None } else { Some((key, now)) };
+        confirmed
+    }
+
+    /// 是否有按键正在窗口内等待确认
+    pub fn is_pending(&self, now: Instant) -> bool {
+        self.pending.as_ref().is_some_and(|(_, first)| now.saturating_duration_since(*first) <= self.window)
+    }
+}
+
+/// 切歌确认：已播放超过该时长才需要确认
+const SKIP_CONFIRM_MIN_PLAYED: Duration = Duration::from_secs(5 * 60);
+
+/// 长于 `length`（`--skip-confirm-length`，None 为关闭）的音频已播放超过 5 分钟时，切歌需要双击确认
+pub fn skip_needs_confirm(length: Option<Duration>, duration: Duration, played: Duration) -> bool {
+    length.is_some_and(|length| duration > length) && played > SKIP_CONFIRM_MIN_PLAYED
+}
+
+/// 退出确认（`--confirm-quit`）的等待时间
+const QUIT_CONFIRM_WINDOW: Duration = Duration::from_secs(2);
+/// `--confirm-quit` 第一次按下退出键时的提示
+pub const QUIT_CONFIRM_MESSAGE: &str = "再按一次退出";
+
+/// 按下退出键后的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuitDecision {
+    /// 不是退出键
+    Ignore,
+    /// 需要再按一次（调用方提示「再按一次退出」）
+    AskAgain,
+    Quit,
+}
+
+/// 所有退出按键的统一判断（播放中，以及等待唤醒、重播、闹钟、失败处理等各个等待按键的界面）：
+/// Q/q、Ctrl+C（原始模式下为带 Ctrl 的 `c`）在 `--confirm-quit` 时需 2 秒内按两次；Ctrl+Q 始终立即退出。
+pub struct QuitConfirm {
+    confirm: bool,
+    presses: DoublePress<()>,
+}
+
+impl QuitConfirm {
+    pub fn new(confirm: bool) -> Self {
+        QuitConfirm { confirm, presses: DoublePress::new(QUIT_CONFIRM_WINDOW) }
+    }
+
+    pub fn press(&mut self, key: &KeyEvent, now: Instant) -> QuitDecision {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Char('Q') if ctrl => QuitDecision::Quit,
+            KeyCode::Char('q') | KeyCode::Char('Q') => self.confirm_press(now),
+            KeyCode::Char('c') | KeyCode::Char('C') if ctrl => self.confirm_press(now),
+            _ => QuitDecision::Ignore,
+        }
+    }
+
+    /// 第一次按下退出键后、确认窗口内（界面据此持续显示「再按一次退出」）
+    pub fn is_asking(&self, now: Instant) -> bool {
+        self.confirm && self.presses.is_pending(now)
+    }
+
+    fn confirm_press(&mut self, now: Instant) -> QuitDecision {
+        // 各退出键视为同一个键：按 Q 后再按 Ctrl+C 同样确认
+        if !self.confirm || self.presses.press((), now) {
+            QuitDecision::Quit
+        } else {
+            QuitDecision::AskAgain
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn second_press_within_the_window_confirms() {
+        let now = Instant::now();
+        let mut presses = DoublePress::new(ms(1000));
+        assert!(!presses.press(1, now));
+        assert!(presses.press(1, now + ms(1000)));
+        // 确认后重新开始：下一次又是第一次
+        assert!(!presses.press(1, now + ms(1100)));
+        // 超出窗口：这次按键成为新的第一次
+        assert!(!presses.press(1, now + ms(2200)));
+        assert!(presses.press(1, now + ms(2300)));
+    }
+
+    #[test]
+    fn different_key_restarts_the_confirmation() {
+        let now = Instant::now();
+        let mut presses = DoublePress::new(ms(1000));
+        assert!(!presses.press(1, now));
+        assert!(!presses.press(-1, now + ms(100)));
+        assert!(!presses.press(1, now + ms(200)));
+        assert!(presses.press(1, now + ms(300)));
+    }
+
+    #[test]
+    fn skip_confirm_only_for_long_tracks_played_a_while() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        let length = Some(minutes(30));
+        assert!(skip_needs_confirm(length, minutes(360), minutes(6)));
+        assert!(!skip_needs_confirm(length, minutes(360), minutes(5)));
+        assert!(!skip_needs_confirm(length, minutes(30), minutes(20)));
+        assert!(!skip_needs_confirm(None, minutes(360), minutes(60)));
+    }
+
+    /// 依次按下 (毫秒, 键)，返回每次的判断
+    fn quit_sequence(confirm: bool, keys: &[(u64, KeyEvent)]) -> Vec<QuitDecision> {
+        let start = Instant::now();
+        let mut quit = QuitConfirm::new(confirm);
+        keys.iter().map(|(at, key)| quit.press(key, start + ms(*at))).collect()
+    }
+
+    #[test]
+    fn quit_keys_share_one_confirmation() {
+        use QuitDecision::*;
+        let key = |c, modifiers| KeyEvent::new(KeyCode::Char(c), modifiers);
+        let (q, shift_q, ctrl_c, ctrl_q, space) = (
+            key('q', KeyModifiers::NONE),
+            key('Q', KeyModifiers::SHIFT),
+            key('c', KeyModifiers::CONTROL),
+            key('q', KeyModifiers::CONTROL),
+            key(' ', KeyModifiers::NONE),
+        );
+
+        // 不确认时任何退出键立即退出
+        assert_eq!(quit_sequence(false, &[(0, space), (0, q), (0, ctrl_c)]), [Ignore, Quit, Quit]);
+        // 2 秒内按两次：Ctrl+C 连按、Q 后 Ctrl+C 都算确认
+        assert_eq!(quit_sequence(true, &[(0, ctrl_c), (300, ctrl_c)]), [AskAgain, Quit]);
+        assert_eq!(quit_sequence(true, &[(0, q), (2000, shift_q)]), [AskAgain, Quit]);
+        // 超过 2 秒重新计时；中间的其他按键不影响
+        assert_eq!(quit_sequence(true, &[(0, q), (2001, q), (2500, space), (3000, q)]), [AskAgain, AskAgain, Ignore, Quit]);
+        // Ctrl+Q 始终立即退出
+        assert_eq!(quit_sequence(true, &[(0, ctrl_q)]), [Quit]);
+        assert_eq!(quit_sequence(true, &[(0, q), (100, ctrl_q)]), [AskAgain, Quit]);
+    }
+
+    #[test]
+    fn bare_c_is_not_a_quit_key_and_the_prompt_lasts_the_window() {
+        use QuitDecision::*;
+        let key = |c, modifiers| KeyEvent::new(KeyCode::Char(c), modifiers);
+        let (c, shift_c, ctrl_shift_c, q) =
+            (key('c', KeyModifiers::NONE), key('C', KeyModifiers::SHIFT), key('C', KeyModifiers::CONTROL | KeyModifiers::SHIFT), key('q', KeyModifiers::NONE));
+        assert_eq!(quit_sequence(false, &[(0, c), (10, shift_c), (20, ctrl_shift_c)]), [Ignore, Ignore, Quit]);
+
+        // 等待按键的界面据 is_asking 显示「再按一次退出」，直到确认或超过 2 秒
+        let start = Instant::now();
+        let mut quit = QuitConfirm::new(true);
+        assert!(!quit.is_asking(start));
+        assert_eq!(quit.press(&q, start), AskAgain);
+        assert!(quit.is_asking(start + ms(2000)));
+        assert!(!quit.is_asking(start + ms(2001)));
+        assert_eq!(quit.press(&c, start + ms(100)), Ignore);
+        assert_eq!(quit.press(&q, start + ms(200)), Quit);
+        assert!(!quit.is_asking(start + ms(300)));
+        // 不需要确认时从不提示
+        let mut quit = QuitConfirm::new(false);
+        assert_eq!(quit.press(&q, start), Quit);
+        assert!(!quit.is_asking(start));
+    }
+}
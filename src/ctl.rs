@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::iter::Peekable;
+#[cfg(not(unix))]
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::{MetadataExt, PermissionsExt},
+    net::{UnixListener, UnixStream},
+};
+use std::path::PathBuf;
+use std::str::Chars;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::buildinfo::json_string;
+use crate::metadata::Template;
+use crate::store::data_dir;
+#[cfg(not(unix))]
+use crate::store::replace_data_file;
+use crate::utils::format_position;
+use crate::volume::ui_volume_to_db;
+
+/// 运行中实例的控制套接字的文件名（位于数据目录，只有当前用户可以连接）
+#[cfg(unix)]
+const CTL_SOCKET_FILE: &str = "ctl.sock";
+
+/// 记录运行中实例控制端口的文件名（位于数据目录）。
+/// 没有 Unix 套接字的平台退而监听本机回环地址，同一台机器上的其他用户也能连接
+#[cfg(not(unix))]
+const CTL_PORT_FILE: &str = "ctl_port";
+
+/// status 子命令连接与读取的超时
+const CTL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 播放器当前状态（status 子命令输出的内容）
+pub struct PlayerStatus<'a> {
+    pub paused: bool,
+    pub title: &'a str,
+    pub artist: &'a str,
+    /// 当前歌曲的序号（从 1 开始）
+    pub index: usize,
+    pub total: usize,
+    pub position: Duration,
+    pub duration: Duration,
+    /// 主音量（0-100，静音时仍为静音前的音量）
+    pub volume: u8,
+    pub muted: bool,
+}
+
+impl PlayerStatus<'_> {
+    /// 单行 JSON；时间以秒为单位，volume_db 为主音量相对满音量的 dB（0% 时为 null）
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"state\":{},\"title\":{},\"artist\":{},\"index\":{},\"total\":{},\"position\":{},\"duration\":{},\"volume\":{},\"volume_db\":{},\"muted\":{}}}",
+            json_string(if self.paused { "paused" } else { "playing" }),
+            json_string(self.title),
+            json_string(self.artist),
+            self.index,
+            self.total,
+            self.position.as_secs(),
+            self.duration.as_secs(),
+            self.volume,
+            ui_volume_to_db(self.volume as f32 / 100.0).map_or("null".to_string(), |db| format!("{:.1}", db)),
+            self.muted,
+        )
+    }
+}
+
+/// 外部发给运行中实例、由主循环执行的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtlCommand {
+    /// `duck on|off`：临时压低音量或恢复（同 H 键）
+    Duck(bool),
+}
+
+/// 运行中实例的控制端点：Unix 上为数据目录中的套接字（权限 0600），其他平台为本机回环地址上的随机端口（记入数据目录）。
+/// 每个连接发送一行命令（`status`、`duck on`、`duck off`），收到一行回复后即被关闭；
+/// 同时运行多个实例时以最后启动的为准。
+pub struct CtlServer {
+    status: Arc<Mutex<String>>,
+    commands: Receiver<CtlCommand>,
+    endpoint: Endpoint,
+}
+
+/// 退出时要清理的端点文件，以及用来确认它仍属于本实例的标识
+#[cfg(unix)]
+struct Endpoint {
+    path: PathBuf,
+    inode: u64,
+}
+
+#[cfg(not(unix))]
+struct Endpoint {
+    path: PathBuf,
+    port: u16,
+}
+
+impl CtlServer {
+    /// 启动失败（如数据目录不可写）时返回 None，不影响播放
+    pub fn start() -> Option<Self> {
+        let status = Arc::new(Mutex::new("{}".to_string()));
+        let (sender, commands) = mpsc::channel();
+        let shared = status.clone();
+        let endpoint = listen(move |stream| serve(stream, &shared, &sender))?;
+        Some(CtlServer { status, commands, endpoint })
+    }
+
+    /// 更新对外提供的状态（随状态行一起刷新）
+    pub fn update(&self, status: &PlayerStatus) {
+        *self.status.lock().unwrap_or_else(|e| e.into_inner()) = status.to_json();
+    }
+
+    /// 取出一条待执行的命令（主循环每轮调用）
+    pub fn poll_command(&self) -> Option<CtlCommand> {
+        self.commands.try_recv().ok()
+    }
+}
+
+/// 在数据目录创建控制套接字并在后台线程接受连接
+#[cfg(unix)]
+fn listen(mut handle: impl FnMut(UnixStream) + Send + 'static) -> Option<Endpoint> {
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir).ok()?;
+    let path = dir.join(CTL_SOCKET_FILE);
+    // 上次异常退出留下的套接字，或仍在运行的其他实例的（以最后启动的为准）
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).ok()?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).ok()?;
+    let inode = fs::metadata(&path).ok()?.ino();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(CTL_TIMEOUT));
+            handle(stream);
+        }
+    });
+    Some(Endpoint { path, inode })
+}
+
+#[cfg(not(unix))]
+fn listen(mut handle: impl FnMut(TcpStream) + Send + 'static) -> Option<Endpoint> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).ok()?;
+    let port = listener.local_addr().ok()?.port();
+    // 同时运行多个实例时以最后启动的为准
+    let path = replace_data_file(CTL_PORT_FILE, &port.to_string()).ok()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(CTL_TIMEOUT));
+            handle(stream);
+        }
+    });
+    Some(Endpoint { path, port })
+}
+
+impl Drop for CtlServer {
+    fn drop(&mut self) {
+        // 之后启动的实例可能已换上自己的端点文件，只删除仍属于本实例的
+        #[cfg(unix)]
+        let ours = fs::metadata(&self.endpoint.path).is_ok_and(|meta| meta.ino() == self.endpoint.inode);
+        #[cfg(not(unix))]
+        let ours = fs::read_to_string(&self.endpoint.path).is_ok_and(|content| content.trim() == self.endpoint.port.to_string());
+        if ours {
+            let _ = fs::remove_file(&self.endpoint.path);
+        }
+    }
+}
+
+/// 处理一个连接：读取一行命令并回复一行
+fn serve<S: Read + Write>(stream: S, status: &Mutex<String>, commands: &Sender<CtlCommand>) {
+    let mut reader = BufReader::new(stream);
+    let mut command = String::new();
+    if reader.read_line(&mut command).is_err() {
+        return;
+    }
+    let reply = match command.trim() {
+        "status" => status.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        "duck on" | "duck off" => {
+            let _ = commands.send(CtlCommand::Duck(command.trim() == "duck on"));
+            "ok".to_string()
+        }
+        other => format!("error: 未知命令 '{}'", other),
+    };
+    let _ = writeln!(reader.into_inner(), "{}", reply);
+}
+
+/// `mddplayer status`：读取运行中实例的状态并输出一行（默认为 JSON，指定模板时按模板填充）。
+/// 没有运行中的实例时不输出并返回 false，由调用方以 1 退出，状态栏据此不显示内容。
+pub fn print_status(format: Option<&Template>) -> bool {
+    let Some(line) = request("status") else { return false };
+    // 端口文件过期、端口已被其他程序占用时读到的内容不是状态
+    let Some(fields) = parse_flat_json(&line).filter(|fields| fields.contains_key("state")) else { return false };
+    match format {
+        Some(template) => println!(
+            "{}",
+            template.render(|name| {
+                let value = fields.get(name)?;
+                match name {
+                    "position" | "duration" => Some(format_position(Duration::from_secs(value.parse().ok()?))),
+                    "volume_db" if value == "null" => Some("-∞".to_string()),
+                    _ => Some(value.clone()),
+                }
+            })
+        ),
+        None => println!("{}", line),
+    }
+    true
+}
+
+/// `mddplayer duck on|off`：让运行中的实例压低或恢复音量。没有运行中的实例时返回 false
+pub fn send_duck(active: bool) -> bool {
+    request(if active { "duck on" } else { "duck off" }).is_some_and(|reply| reply == "ok")
+}
+
+/// 连接运行中的实例，发送一行命令并读取一行回复
+#[cfg(unix)]
+fn request(command: &str) -> Option<String> {
+    let stream = UnixStream::connect(data_dir()?.join(CTL_SOCKET_FILE)).ok()?;
+    stream.set_read_timeout(Some(CTL_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(CTL_TIMEOUT)).ok()?;
+    exchange(stream, command)
+}
+
+#[cfg(not(unix))]
+fn request(command: &str) -> Option<String> {
+    let port: u16 = fs::read_to_string(data_dir()?.join(CTL_PORT_FILE)).ok()?.trim().parse().ok()?;
+    let stream = TcpStream::connect_timeout(&SocketAddr::from((Ipv4Addr::LOCALHOST, port)), CTL_TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(CTL_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(CTL_TIMEOUT)).ok()?;
+    exchange(stream, command)
+}
+
+fn exchange<S: Read + Write>(mut stream: S, command: &str) -> Option<String> {
+    writeln!(stream, "{}", command).ok()?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    Some(line.trim_end().to_string())
+}
+
+/// 解析只含字符串、数字与布尔值的单层 JSON 对象（即 PlayerStatus::to_json 的输出），值一律转为文本
+fn parse_flat_json(text: &str) -> Option<HashMap<String, String>> {
+    let mut chars = text.trim().strip_prefix('{')?.strip_suffix('}')?.chars().peekable();
+    let mut fields = HashMap::new();
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.peek().is_none() {
+            break;
+        }
+        let key = parse_json_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+        let value = if chars.peek() == Some(&'"') {
+            parse_json_string(&mut chars)?
+        } else {
+            let mut raw = String::new();
+            while let Some(&c) = chars.peek()
+                && c != ','
+            {
+                raw.push(c);
+                chars.next();
+            }
+            raw.trim().to_string()
+        };
+        fields.insert(key, value);
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => break,
+            Some(',') => {}
+            Some(_) => return None,
+        }
+    }
+    Some(fields)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// 解析 JSON 字符串字面量（含开头与结尾的引号）
+fn parse_json_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut out = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(out),
+            '\\' => match chars.next()? {
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => {
+                    let hex: String = (0..4).filter_map(|_| chars.next()).collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                other => out.push(other),
+            },
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status<'a>(title: &'a str, artist: &'a str) -> PlayerStatus<'a> {
+        PlayerStatus {
+            paused: true,
+            title,
+            artist,
+            index: 3,
+            total: 12,
+            position: Duration::from_secs(75),
+            duration: Duration::from_secs(240),
+            volume: 0,
+            muted: false,
+        }
+    }
+
+    #[test]
+    fn status_json_round_trips() {
+        let title = "Say \"Hi\", \\ back\tslash\n第二行";
+        let artist = "Beyoncé \u{1}\u{1f}, 周杰伦";
+        let fields = parse_flat_json(&status(title, artist).to_json()).unwrap();
+        assert_eq!(fields["title"], title);
+        assert_eq!(fields["artist"], artist);
+        assert_eq!(fields["state"], "paused");
+        assert_eq!(fields["index"], "3");
+        assert_eq!(fields["total"], "12");
+        assert_eq!(fields["position"], "75");
+        assert_eq!(fields["duration"], "240");
+        assert_eq!(fields["volume_db"], "null");
+        assert_eq!(fields["muted"], "false");
+        assert_eq!(fields.len(), 10);
+    }
+
+    #[test]
+    fn parses_unicode_escapes() {
+        let fields = parse_flat_json(r#"{"title": "\u5468\u0022x\u00e9" , "n": 5}"#).unwrap();
+        assert_eq!(fields["title"], "周\"xé");
+        assert_eq!(fields["n"], "5");
+        assert!(parse_flat_json(r#"{"title": "\u12"}"#).is_none());
+        assert!(parse_flat_json("not json").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serves_status_and_duck_commands() {
+        let shared = Mutex::new("{\"state\":\"playing\"}".to_string());
+        let (sender, commands) = mpsc::channel();
+        for (command, reply) in [
+            ("status", "{\"state\":\"playing\"}"),
+            ("duck on", "ok"),
+            ("duck off", "ok"),
+            ("volume 5", "error: 未知命令 'volume 5'"),
+        ] {
+            let (client, server) = UnixStream::pair().unwrap();
+            serve_after_write(client, server, command, reply, &shared, &sender);
+        }
+        assert_eq!(commands.try_iter().collect::<Vec<_>>(), [CtlCommand::Duck(true), CtlCommand::Duck(false)]);
+    }
+
+    #[cfg(unix)]
+    fn serve_after_write(mut client: UnixStream, server: UnixStream, command: &str, reply: &str, status: &Mutex<String>, commands: &Sender<CtlCommand>) {
+        writeln!(client, "{}", command).unwrap();
+        serve(server, status, commands);
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        assert_eq!(line.trim_end(), reply, "{}", command);
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+// 音频播放库
+use rodio::decoder::DecoderError;
+use rodio::source::SeekError;
+use rodio::{Decoder, Source};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{self, DecoderOptions, CODEC_TYPE_NULL},
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+use crate::metadata::probe_codec;
+use crate::utils::{format_position, is_video_container};
+
+/// 播放用的音频源
+pub type BoxedSource = Box<dyn Source<Item = i16> + Send>;
+
+/// `--decoder-override` 可指定的解码器（即本构建中 rodio 提供的全部解码器）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Wav,
+    Flac,
+    Vorbis,
+    Mp3,
+}
+
+const CODECS: [(Codec, &str); 4] = [(Codec::Wav, "wav"), (Codec::Flac, "flac"), (Codec::Vorbis, "vorbis"), (Codec::Mp3, "mp3")];
+
+impl Codec {
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        // ogg 是 vorbis 最常见的叫法
+        let name = if name == "ogg" { "vorbis" } else { name.as_str() };
+        CODECS.iter().find(|(_, n)| *n == name).map(|(codec, _)| *codec)
+    }
+
+    fn name(self) -> &'static str {
+        CODECS.iter().find(|(c, _)| *c == self).map_or("", |(_, name)| *name)
+    }
+
+    /// 跳过按内容探测，直接用该解码器打开
+    fn decode<R>(self, data: R) -> Result<Decoder<R>, DecoderError>
+    where
+        R: Read + Seek + Send + Sync + 'static,
+    {
+        match self {
+            Codec::Wav => Decoder::new_wav(data),
+            Codec::Flac => Decoder::new_flac(data),
+            Codec::Vorbis => Decoder::new_vorbis(data),
+            Codec::Mp3 => Decoder::new_mp3(data),
+        }
+    }
+}
+
+/// 按扩展名强制使用的解码器（`--decoder-override ext=codec`），未指定的扩展名按内容自动探测
+#[derive(Debug, Clone, Default)]
+pub struct DecoderOverrides {
+    by_extension: HashMap<String, Codec>,
+}
+
+impl DecoderOverrides {
+    /// 解析 `ext=codec` 列表；扩展名不区分大小写，可带前导点，同一扩展名以最后一次为准
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let mut by_extension = HashMap::new();
+        for entry in entries {
+            let (ext, codec) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("解码器覆盖应写作 扩展名=解码器（如 m4a=mp3），而不是 '{}'", entry))?;
+            let ext = ext.trim().trim_start_matches('.').to_lowercase();
+            if ext.is_empty() {
+                return Err(format!("解码器覆盖 '{}' 缺少扩展名", entry));
+            }
+            let codec = Codec::from_name(codec.trim()).ok_or_else(|| {
+                let names: Vec<&str> = CODECS.iter().map(|(_, name)| *name).collect();
+                format!("未知的解码器 '{}'（可用: {}）", codec.trim(), names.join(", "))
+            })?;
+            by_extension.insert(ext, codec);
+        }
+        Ok(DecoderOverrides { by_extension })
+    }
+
+    /// 该文件被强制使用的解码器
+    pub fn for_path(&self, path: &Path) -> Option<Codec> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        self.by_extension.get(&ext).copied()
+    }
+
+    /// 打开解码器：有覆盖时只用指定的解码器，视频容器只解码第一条音轨，否则按内容自动探测
+    pub fn open<R>(&self, path: &Path, data: R) -> Result<BoxedSource, DecoderError>
+    where
+        R: Read + Seek + Send + Sync + 'static,
+    {
+        let extension = path.extension().and_then(|s| s.to_str());
+        Ok(match self.for_path(path) {
+            Some(codec) => Box::new(codec.decode(data)?),
+            None if extension.is_some_and(is_video_container) => Box::new(ContainerSource::new(data, extension)?),
+            None => Box::new(Decoder::new(data)?),
+        })
+    }
+
+    /// verbose 日志中的解码器说明：强制指定的解码器，或按内容探测到的编码
+    pub fn describe(&self, path: &Path) -> String {
+        match self.for_path(path) {
+            Some(codec) => format!("{}（--decoder-override 指定）", codec.name()),
+            None => match probe_codec(path) {
+                Some((format, true)) => format!("{}（自动探测）", format.to_lowercase()),
+                Some((format, false)) => format!("{}（自动探测，本构建无法解码）", format.to_lowercase()),
+                None => "未能探测编码（自动探测）".to_string(),
+            },
+        }
+    }
+}
+
+/// 连续解码失败超过该次数时视为文件损坏，结束播放
+const MAX_DECODE_ERRORS: usize = 3;
+
+/// 向 symphonia 报告长度的媒体源（Matroska 等格式初始化时需要知道文件长度）
+struct SizedSource<R> {
+    inner: R,
+    len: Option<u64>,
+}
+
+impl<R: Read + Seek> SizedSource<R> {
+    fn new(mut inner: R) -> Self {
+        let len = inner.stream_position().and_then(|pos| {
+            let len = inner.seek(SeekFrom::End(0))?;
+            inner.seek(SeekFrom::Start(pos))?;
+            Ok(len)
+        });
+        SizedSource { inner, len: len.ok() }
+    }
+}
+
+impl<R: Read> Read for SizedSource<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for SizedSource<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<R: Read + Seek + Send + Sync> MediaSource for SizedSource<R> {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.len
+    }
+}
+
+/// 视频容器（`--allow-video-containers`）中第一条音轨的解码器。
+/// rodio 按内容探测时不向 symphonia 报告文件长度（Matroska 因此无法打开），
+/// 也不区分数据包所属的轨道，所以视频容器直接用 symphonia 解码，跳过其他轨道的数据包
+struct ContainerSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn codecs::Decoder>,
+    track_id: u32,
+    /// 当前数据包解码出的交错采样及读取位置
+    samples: Vec<i16>,
+    offset: usize,
+    /// 跳转后还需丢弃的采样数（容器只能跳到目标之前的数据包）
+    skip: usize,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+}
+
+impl ContainerSource {
+    fn new<R>(data: R, extension: Option<&str>) -> Result<Self, DecoderError>
+    where
+        R: Read + Seek + Send + Sync + 'static,
+    {
+        let stream = MediaSourceStream::new(Box::new(SizedSource::new(data)), Default::default());
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
+        }
+        let format = symphonia::default::get_probe()
+            .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+            .map_err(|_| DecoderError::UnrecognizedFormat)?
+            .format;
+        // 与 metadata::first_audio_track 的选择一致：symphonia 不认识的编码（包括视频）都是 CODEC_TYPE_NULL
+        let track = format.tracks().iter().find(|track| track.codec_params.codec != CODEC_TYPE_NULL).ok_or(DecoderError::NoStreams)?;
+        // Matroska 不记录每个数据包的最大帧数，而 PCM 解码器需要它来分配缓冲：按 1 秒估计
+        let mut params = track.codec_params.clone();
+        if params.max_frames_per_packet.is_none() {
+            params.with_max_frames_per_packet(params.sample_rate.unwrap_or(48000) as u64);
+        }
+        let decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .map_err(|_| DecoderError::UnrecognizedFormat)?;
+        let total_duration = track
+            .codec_params
+            .time_base
+            .zip(track.codec_params.n_frames)
+            .map(|(base, frames)| base.calc_time(frames))
+            .map(|time| Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac));
+        let mut source = ContainerSource {
+            track_id: track.id,
+            format,
+            decoder,
+            samples: Vec::new(),
+            offset: 0,
+            skip: 0,
+            channels: 1,
+            sample_rate: 44100,
+            total_duration,
+        };
+        // 先解码第一个数据包，取得声道数与采样率
+        if !source.decode_next() {
+            return Err(DecoderError::NoStreams);
+        }
+        Ok(source)
+    }
+
+    /// 解码该音轨的下一个数据包；没有更多数据或文件损坏时返回 false
+    fn decode_next(&mut self) -> bool {
+        let mut errors = 0;
+        loop {
+            let Ok(packet) = self.format.next_packet() else { return false };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let mut buffer = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+                    buffer.copy_interleaved_ref(decoded);
+                    self.samples = buffer.samples().to_vec();
+                    self.offset = 0;
+                    self.channels = spec.channels.count() as u16;
+                    self.sample_rate = spec.rate;
+                    return true;
+                }
+                Err(SymphoniaError::DecodeError(_)) if errors < MAX_DECODE_ERRORS => errors += 1,
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+impl Iterator for ContainerSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        loop {
+            if self.offset >= self.samples.len() && !self.decode_next() {
+                return None;
+            }
+            let sample = self.samples[self.offset];
+            self.offset += 1;
+            if self.skip == 0 {
+                return Some(sample);
+            }
+            self.skip -= 1;
+        }
+    }
+}
+
+impl Source for ContainerSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len())
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let seeked = self
+            .format
+            .seek(SeekMode::Accurate, SeekTo::Time { time: pos.as_secs_f64().into(), track_id: Some(self.track_id) })
+            .map_err(|e| SeekError::Other(Box::new(e)))?;
+        self.decoder.reset();
+        self.samples.clear();
+        self.offset = 0;
+        // 丢弃实际落点到目标之间的采样
+        let base = self.format.tracks().iter().find(|track| track.id == self.track_id).and_then(|track| track.codec_params.time_base);
+        self.skip = base.map_or(0, |base| {
+            let time = base.calc_time(seeked.required_ts.saturating_sub(seeked.actual_ts));
+            let frames = ((time.seconds as f64 + time.frac) * self.sample_rate as f64).round() as usize;
+            frames * self.channels as usize
+        });
+        Ok(())
+    }
+}
+
+/// verbose 模式下记录每首歌曲使用的解码器（及解码提前结束等诊断），退出时输出（与 TimingLog 一样在 Drop 时输出，终端已恢复正常模式）
+pub struct DecoderLog {
+    enabled: bool,
+    entries: Vec<(String, String)>,
+}
+
+impl DecoderLog {
+    pub fn new(enabled: bool) -> Self {
+        DecoderLog { enabled, entries: Vec::new() }
+    }
+
+    pub fn record(&mut self, label: &str, codec: Option<&str>) {
+        if self.enabled
+            && let Some(codec) = codec
+        {
+            self.entries.push((label.to_string(), codec.to_string()));
+        }
+    }
+
+    /// 解码器早于报告的时长结束（见 outcome::ended_early）
+    pub fn record_early_end(&mut self, label: &str, position: Duration, duration: Duration) {
+        if self.enabled {
+            let note = format!("解码提前结束于 {}，报告的时长为 {}", format_position(position), format_position(duration));
+            self.entries.push((label.to_string(), note));
+        }
+    }
+}
+
+impl Drop for DecoderLog {
+    fn drop(&mut self) {
+        for (label, codec) in &self.entries {
+            eprintln!("[详细][解码器]{}: {}", label, codec);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mkv_with_video, ogg_opus, silent_wav, TempDir};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    fn open(path: &Path) -> Result<BoxedSource, DecoderError> {
+        DecoderOverrides::default().open(path, BufReader::new(File::open(path).unwrap()))
+    }
+
+    #[test]
+    fn video_container_plays_its_audio_track() {
+        let dir = TempDir::new();
+        let video = dir.write("clip.mkv", mkv_with_video(Some(1500)));
+        let source = open(&video).unwrap();
+        assert_eq!((source.channels(), source.sample_rate()), (1, 8000));
+        assert_eq!(source.total_duration(), Some(Duration::from_millis(1500)));
+        assert_eq!(source.count(), 12000);
+
+        // 跳转后从目标位置继续
+        let mut source = open(&video).unwrap();
+        source.try_seek(Duration::from_millis(500)).unwrap();
+        assert_eq!(source.count(), 8000);
+    }
+
+    #[test]
+    fn video_without_audio_is_rejected() {
+        let dir = TempDir::new();
+        let video = dir.write("silent-film.webm", mkv_with_video(None));
+        assert!(matches!(open(&video), Err(DecoderError::NoStreams)));
+    }
+
+    #[test]
+    fn overrides_and_probing_still_apply() {
+        let dir = TempDir::new();
+        let wav = dir.write("tone.wav", silent_wav(250));
+        assert_eq!(open(&wav).unwrap().count(), 2000);
+        // 伪装成 mp3 的 WAV 也按内容探测
+        let disguised = dir.write("tone.mp3", silent_wav(250));
+        assert_eq!(open(&disguised).unwrap().count(), 2000);
+        let forced = DecoderOverrides::parse(&["mp3=wav".to_string()]).unwrap();
+        assert_eq!(forced.open(&disguised, File::open(&disguised).unwrap()).unwrap().count(), 2000);
+        let wrong = DecoderOverrides::parse(&["wav=flac".to_string()]).unwrap();
+        assert!(wrong.open(&wav, File::open(&wav).unwrap()).is_err());
+    }
+
+    #[test]
+    fn parses_overrides() {
+        let overrides = DecoderOverrides::parse(&[" .OGG = Vorbis".to_string(), "m4a=mp3".to_string(), "m4a=ogg".to_string()]).unwrap();
+        assert_eq!(overrides.for_path(Path::new("a/Song.ogg")), Some(Codec::Vorbis));
+        // 同一扩展名以最后一次为准；ogg 是 vorbis 的别名
+        assert_eq!(overrides.for_path(Path::new("song.M4A")), Some(Codec::Vorbis));
+        assert_eq!(overrides.for_path(Path::new("song.mp3")), None);
+        assert_eq!(overrides.for_path(Path::new("ogg")), None);
+
+        let error = |entry: &str| DecoderOverrides::parse(&[entry.to_string()]).unwrap_err();
+        assert_eq!(error("ogg=opus"), "未知的解码器 'opus'（可用: wav, flac, vorbis, mp3）");
+        assert_eq!(error(".=wav"), "解码器覆盖 '.=wav' 缺少扩展名");
+        assert!(error("ogg").contains("扩展名=解码器"));
+    }
+
+    #[test]
+    fn opus_in_ogg_fails_cleanly_with_or_without_override() {
+        let dir = TempDir::new();
+        let opus = dir.write("podcast.ogg", ogg_opus());
+        // 自动探测：识别出 Opus 并说明无法解码，而不是交给其他解码器播放出杂音
+        assert!(open(&opus).is_err());
+        assert_eq!(DecoderOverrides::default().describe(&opus), "opus（自动探测，本构建无法解码）");
+        // 强制指定的解码器只用它自己，同样报错
+        let forced = DecoderOverrides::parse(&["ogg=vorbis".to_string()]).unwrap();
+        assert_eq!(forced.describe(&opus), "vorbis（--decoder-override 指定）");
+        assert!(forced.open(&opus, File::open(&opus).unwrap()).is_err());
+    }
+}
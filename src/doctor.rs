@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::Cursor;
+use std::time::Duration;
+// 音频播放库（cpal 为其底层的音频设备库）
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{Decoder, Source};
+use crossterm::terminal;
+
+use crate::output::{describe_output, open_output, OutputRequest};
+use crate::store::data_dir;
+use crate::utils::SUPPORTED_EXTENSIONS;
+
+/// 内置的 0.1 秒样本（440Hz 正弦或静音），用于验证各格式的解码器是否可用
+const SAMPLES: [(&str, &[u8]); 3] = [
+    ("wav", include_bytes!("../assets/doctor/sample.wav")),
+    ("flac", include_bytes!("../assets/doctor/sample.flac")),
+    ("mp3", include_bytes!("../assets/doctor/sample.mp3")),
+];
+
+/// 解码一段内置样本，返回解码出的时长及采样率、声道数
+pub fn decode_sample(bytes: &'static [u8]) -> Result<(Duration, u32, u16), String> {
+    let decoder = Decoder::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let (sample_rate, channels) = (decoder.sample_rate(), decoder.channels());
+    let samples = decoder.count();
+    if samples == 0 || sample_rate == 0 || channels == 0 {
+        return Err("没有解码出任何样本".to_string());
+    }
+    let frames = samples as f64 / channels as f64;
+    Ok((Duration::from_secs_f64(frames / sample_rate as f64), sample_rate, channels))
+}
+
+/// 逐项输出检查结果并统计关键项失败数
+struct Report {
+    critical_failures: usize,
+}
+
+impl Report {
+    /// 非关键项失败时显示为「警告」，不影响退出码
+    fn check(&mut self, name: &str, critical: bool, result: Result<String, String>) {
+        match result {
+            Ok(detail) => println!("[通过] {}: {}", name, detail),
+            Err(detail) if critical => {
+                self.critical_failures += 1;
+                println!("[失败] {}: {}", name, detail);
+            }
+            Err(detail) => println!("[警告] {}: {}", name, detail),
+        }
+    }
+
+    fn skip(&self, name: &str, reason: &str) {
+        println!("[跳过] {}: {}", name, reason);
+    }
+}
+
+/// `mddplayer doctor`：依次检查音频设备、解码器、数据目录与终端，输出每项结果。
+/// 返回关键项是否全部通过（调用方据此决定退出码）。
+pub fn run_doctor(request: OutputRequest) -> bool {
+    let mut report = Report { critical_failures: 0 };
+
+    let host = rodio::cpal::default_host();
+    report.check("音频主机", true, Ok(host.id().name().to_string()));
+    let device = host.default_output_device();
+    report.check(
+        "默认输出设备",
+        true,
+        device.as_ref().map(|d| d.name().unwrap_or_else(|_| "（无法读取名称）".to_string())).ok_or_else(|| "未检测到".to_string()),
+    );
+    // 只打开后立即关闭，不播放声音
+    report.check(
+        "打开输出流",
+        true,
+        open_output(request, false).map(|_| describe_output(request).unwrap_or_else(|_| "成功".to_string())).map_err(|e| e.to_string()),
+    );
+
+    for ext in SUPPORTED_EXTENSIONS {
+        let name = format!("解码 {}", ext);
+        match SAMPLES.iter().find(|(sample_ext, _)| *sample_ext == ext) {
+            Some((_, bytes)) => report.check(
+                &name,
+                true,
+                decode_sample(bytes).map(|(duration, rate, channels)| {
+                    format!("{:.2} 秒（{}Hz，{} 声道）", duration.as_secs_f64(), rate, channels)
+                }),
+            ),
+            None => report.skip(&name, "没有内置样本"),
+        }
+    }
+
+    report.skip("配置文件", "本版本没有配置文件，设置均来自命令行与环境变量");
+    report.check("数据目录可写", false, check_data_dir());
+
+    // 原始模式是读取按键所必需的；终端尺寸读不到时界面按 80 列显示
+    report.check(
+        "终端原始模式",
+        true,
+        terminal::enable_raw_mode()
+            .and_then(|_| terminal::disable_raw_mode())
+            .map(|_| "支持".to_string())
+            .map_err(|e| e.to_string()),
+    );
+    report.check(
+        "终端尺寸",
+        false,
+        terminal::size().map_err(|e| e.to_string()).and_then(|(cols, rows)| match (cols, rows) {
+            (0, _) | (_, 0) => Err("终端报告的尺寸为 0".to_string()),
+            _ => Ok(format!("{} 列 × {} 行", cols, rows)),
+        }),
+    );
+
+    let features = env!("MDDPLAYER_FEATURES");
+    report.check("可选特性", false, Ok(if features.is_empty() { "无".to_string() } else { features.replace(',', ", ") }));
+
+    println!();
+    if report.critical_failures == 0 {
+        println!("全部关键检查通过");
+    } else {
+        println!("{} 项关键检查失败", report.critical_failures);
+    }
+    report.critical_failures == 0
+}
+
+/// 在数据目录中写入并删除一个临时文件
+fn check_data_dir() -> Result<String, String> {
+    let dir = data_dir().ok_or_else(|| "无法确定数据目录".to_string())?;
+    let probe = dir.join(".doctor");
+    fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe))
+        .map(|_| dir.display().to_string())
+        .map_err(|e| format!("{}: {}", dir.display(), e))
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::metadata::{read_track_metadata, FilenamePattern, TrackMetadata};
+use crate::utils::{format_position, Track};
+
+/// 读取进度的刷新间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// 时长相差不超过该值时视为同一首歌（不同码率、不同来源的同一曲目时长略有出入）
+const DURATION_TOLERANCE: Duration = Duration::from_secs(2);
+/// 表示合作艺术家的词，其后的内容在比较时忽略
+const FEAT_MARKERS: [&str; 5] = ["feat.", "feat", "ft.", "ft", "featuring"];
+
+/// 一组可能重复的歌曲（至少两首），按时长排序
+pub struct DuplicateGroup {
+    artist: String,
+    title: String,
+    tracks: Vec<(PathBuf, Duration)>,
+}
+
+/// 比较用的艺术家/标题：转为小写，去掉「(feat. X)」「[ft. X]」括号段与不加括号的「feat. X」后缀，合并空白。
+/// 其他括号段（如「(Live)」）保留，现场版与录音室版不算重复。
+fn normalize_tag(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let mut kept = String::new();
+    let mut rest = lower.as_str();
+    while let Some(open) = rest.find(['(', '[']) {
+        let close_char = if rest[open..].starts_with('(') { ')' } else { ']' };
+        let Some(close) = rest[open + 1..].find(close_char).map(|i| open + 1 + i) else { break };
+        kept.push_str(&rest[..open]);
+        let inner = &rest[open + 1..close];
+        if !inner.split_whitespace().next().is_some_and(|word| FEAT_MARKERS.contains(&word)) {
+            kept.push_str(&rest[open..=close]);
+        }
+        rest = &rest[close + 1..];
+    }
+    kept.push_str(rest);
+    let words: Vec<&str> = kept.split_whitespace().collect();
+    // 以 feat 开头的（如标题就叫「Feat」）保留原样；被截断的标签中可能留下未闭合的「(feat. X」
+    let cut = words
+        .iter()
+        .position(|word| FEAT_MARKERS.contains(&word.trim_start_matches(['(', '['])))
+        .filter(|&i| i > 0)
+        .unwrap_or(words.len());
+    words[..cut].join(" ")
+}
+
+/// 并行读取各歌曲的标签与时长，按规范化后的（艺术家, 标题）分组，组内时长相差 DURATION_TOLERANCE 以内的连成一组。
+/// 文件内的章节（CUE 拆分）共用同一个文件，不参与比较。`progress(已读取, 总数)` 在调用线程中定期调用。
+pub fn find_duplicates(tracks: &[Track], pattern: &FilenamePattern, progress: fn(usize, usize)) -> Vec<DuplicateGroup> {
+    let files: Vec<&PathBuf> = tracks.iter().filter(|t| t.start.is_none() && t.end.is_none()).map(|t| &t.path).collect();
+    let total = files.len();
+    let workers = thread::available_parallelism().map_or(4, |n| n.get()).min(total.max(1));
+    let chunk_size = total.div_ceil(workers).max(1);
+    let read = AtomicUsize::new(0);
+    let metadata: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let read = &read;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|path| {
+                            let meta = read_track_metadata(path, pattern);
+                            read.fetch_add(1, Ordering::Relaxed);
+                            meta
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        while !handles.iter().all(|h| h.is_finished()) {
+            progress(read.load(Ordering::Relaxed), total);
+            thread::sleep(PROGRESS_INTERVAL);
+        }
+        progress(total, total);
+        // 读取标签的线程不会 panic；万一发生则该段歌曲不参与比较
+        handles.into_iter().flat_map(|h| h.join().unwrap_or_default()).collect()
+    });
+
+    let mut by_tags: BTreeMap<(String, String), Vec<(&PathBuf, TrackMetadata)>> = BTreeMap::new();
+    for (path, meta) in files.into_iter().zip(metadata) {
+        let key = (normalize_tag(&meta.artist), normalize_tag(&meta.title));
+        if key.1.is_empty() {
+            continue;
+        }
+        by_tags.entry(key).or_default().push((path, meta));
+    }
+    let mut groups = Vec::new();
+    for mut candidates in by_tags.into_values() {
+        candidates.sort_by_key(|(_, meta)| meta.duration);
+        let mut start = 0;
+        for end in 1..=candidates.len() {
+            let split = end == candidates.len() || candidates[end].1.duration - candidates[end - 1].1.duration > DURATION_TOLERANCE;
+            if !split {
+                continue;
+            }
+            if end - start > 1 {
+                let first = &candidates[start].1;
+                groups.push(DuplicateGroup {
+                    artist: first.artist.clone(),
+                    title: first.title.clone(),
+                    tracks: candidates[start..end].iter().map(|(path, meta)| ((*path).clone(), meta.duration)).collect(),
+                });
+            }
+            start = end;
+        }
+    }
+    groups
+}
+
+/// 报告文本：每组一行「艺术家 - 标题」，其下逐行列出时长与路径
+pub fn format_report(groups: &[DuplicateGroup]) -> String {
+    let mut out = String::new();
+    for group in groups {
+        if group.artist.is_empty() {
+            out.push_str(&format!("{}\n", group.title));
+        } else {
+            out.push_str(&format!("{} - {}\n", group.artist, group.title));
+        }
+        for (path, duration) in &group.tracks {
+            out.push_str(&format!("    {}  {}\n", format_position(*duration), path.display()));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{silent_wav, TempDir};
+
+    #[test]
+    fn normalizes_case_features_and_spacing() {
+        let cases = [
+            ("Hello World", "hello world"),
+            ("  HELLO   world ", "hello world"),
+            ("Song (feat. Guest)", "song"),
+            ("Song [ft. Guest & Other]", "song"),
+            ("Song (Featuring Guest)", "song"),
+            ("Artist feat. Guest", "artist"),
+            ("Artist ft Guest", "artist"),
+            ("Song (Live)", "song (live)"),
+            ("Song (Live) (feat. Guest)", "song (live)"),
+            ("Feat", "feat"),
+            ("Song (feat. unclosed", "song"),
+            ("周杰伦", "周杰伦"),
+            ("", ""),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(normalize_tag(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn groups_by_tags_and_nearby_durations() {
+        let dir = TempDir::new();
+        let song = |name: &str, seconds: u32| Track::from(dir.write(name, silent_wav(seconds * 1000)));
+        let tracks = vec![
+            song("Artist - Song.wav", 10),
+            song("other/ARTIST - song (feat. Guest).wav", 11),
+            // 与上一首相差 2 秒以内，连成一组
+            song("rip2/Artist - Song.wav", 13),
+            // 相差太多：不同的版本
+            song("long/Artist - Song.wav", 30),
+            song("Artist - Song (Live).wav", 10),
+            song("Other - Song.wav", 10),
+            Track { start: Some(Duration::ZERO), end: Some(Duration::from_secs(5)), ..song("chapters/Artist - Song.wav", 10) },
+        ];
+        let groups = find_duplicates(&tracks, &FilenamePattern::default(), |_, _| {});
+        assert_eq!(groups.len(), 1);
+        let report = format_report(&groups);
+        let paths: Vec<String> = ["Artist - Song.wav", "other/ARTIST - song (feat. Guest).wav", "rip2/Artist - Song.wav"]
+            .iter()
+            .zip(["0:10", "0:11", "0:13"])
+            .map(|(name, time)| format!("    {}  {}\n", time, dir.join(name).display()))
+            .collect();
+        assert_eq!(report, format!("Artist - Song\n{}", paths.concat()));
+    }
+
+    #[test]
+    fn report_without_artist_shows_the_title_only() {
+        let group = DuplicateGroup { artist: String::new(), title: "Untitled".to_string(), tracks: vec![(PathBuf::from("a.mp3"), Duration::from_secs(61))] };
+        assert_eq!(format_report(&[group]), "Untitled\n    1:01  a.mp3\n");
+        assert_eq!(format_report(&[]), "");
+    }
+}
@@ -0,0 +1,366 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+// 终端交互库：读取按键
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use crossterm::terminal;
+
+use crate::confirm::{QuitConfirm, QuitDecision, QUIT_CONFIRM_MESSAGE};
+use crate::theme::Element;
+use crate::ui::{poll_event, read_event, Layout, Screen};
+use crate::utils::{display_width, truncate_string};
+
+/// 单曲加载失败（解码失败或加载超时）时的处理策略
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// 立即跳过，不等待
+    Skip,
+    /// 显示错误并等待片刻后跳过，按任意键可提前跳过
+    Wait,
+    /// 显示错误并等待用户选择重试、跳过或退出
+    Ask,
+    /// 立即以失败退出码退出
+    Abort,
+}
+
+/// 连续失败次数达到 `--max-consecutive-errors` 上限时的处理方式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaxErrorsPolicy {
+    /// 停止播放并输出摘要
+    Stop,
+    /// 询问是否继续
+    Ask,
+}
+
+/// 失败处理的结果，由主循环执行
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureAction {
+    /// 跳到下一首
+    Skip,
+    /// 重新加载当前歌曲
+    Retry,
+    /// 用户主动退出
+    Quit,
+    /// 以失败退出码退出
+    Abort,
+}
+
+/// 同一类错误连续出现达到该次数后，之后的失败不再逐首等待，改为快速跳过
+const FAST_SKIP_AFTER: u32 = 3;
+
+/// 连续的同类失败（如整个文件夹都是受 DRM 保护的文件）：类别相同的失败累计，类别改变时重新计数，成功播放时清零
+#[derive(Debug, Default)]
+pub struct FailureRun {
+    category: String,
+    count: u32,
+}
+
+impl FailureRun {
+    /// 记录一次失败，返回本轮同类失败的次数
+    pub fn record(&mut self, category: &str) -> u32 {
+        if self.count == 0 || self.category != category {
+            self.category = category.to_string();
+            self.count = 0;
+        }
+        self.count += 1;
+        self.count
+    }
+
+    /// 成功播放一首，结束本轮
+    pub fn reset(&mut self) {
+        self.count = 0;
+    }
+
+    /// 本轮同类失败已超过 FAST_SKIP_AFTER 次，应快速跳过
+    pub fn is_fast_skipping(&self) -> bool {
+        self.count > FAST_SKIP_AFTER
+    }
+}
+
+/// 单曲失败的描述信息
+pub struct TrackFailure<'a> {
+    pub current_index: usize,
+    pub total_tracks: usize,
+    pub err_type: &'a str,
+    pub filename: &'a str,
+}
+
+/// 错误信息行：文件名截断到前后缀之外的剩余宽度，前后缀本身放不下时整行截断，宽度不超过 `width`
+fn failure_line(width: usize, failure: &TrackFailure, hint: &str) -> String {
+    let prefix = format!("[{}/{}] [错误:{}]: ", failure.current_index + 1, failure.total_tracks, failure.err_type);
+    let suffix = format!(" -> {}", hint);
+    let filename_width = width.saturating_sub(display_width(&prefix) + display_width(&suffix));
+    let line = format!("{}{}{}", prefix, truncate_string(failure.filename, filename_width), suffix);
+    truncate_string(&line, width)
+}
+
+/// 不等待按键、直接决定的动作：Skip 立即跳过，Abort 立即退出；Wait 与 Ask 需要等待（返回 None）
+fn immediate_action(policy: ErrorPolicy) -> Option<FailureAction> {
+    match policy {
+        ErrorPolicy::Skip => Some(FailureAction::Skip),
+        ErrorPolicy::Abort => Some(FailureAction::Abort),
+        ErrorPolicy::Wait | ErrorPolicy::Ask => None,
+    }
+}
+
+/// 等待期间按下 `code` 对应的动作，`quit` 为该键经 QuitConfirm 的判断；None 表示继续等待。
+/// 退出键确认后退出、需要再按一次时继续等待；Wait 下其他任意键提前跳过，Ask 下只响应 R/S；其余策略不等待按键
+fn key_action(policy: ErrorPolicy, code: KeyCode, quit: QuitDecision) -> Option<FailureAction> {
+    match (policy, quit) {
+        (ErrorPolicy::Skip | ErrorPolicy::Abort, _) => None,
+        (_, QuitDecision::Quit) => Some(FailureAction::Quit),
+        (_, QuitDecision::AskAgain) => None,
+        (ErrorPolicy::Wait, QuitDecision::Ignore) => Some(FailureAction::Skip),
+        (ErrorPolicy::Ask, QuitDecision::Ignore) => match code {
+            KeyCode::Char('r') | KeyCode::Char('R') => Some(FailureAction::Retry),
+            KeyCode::Char('s') | KeyCode::Char('S') => Some(FailureAction::Skip),
+            _ => None,
+        },
+    }
+}
+
+/// 提示前加上「再按一次退出」（`--confirm-quit` 下第一次按下退出键后）
+fn with_quit_hint(hint: &str, quit: &QuitConfirm) -> String {
+    if quit.is_asking(Instant::now()) {
+        format!("{} {}", QUIT_CONFIRM_MESSAGE, hint)
+    } else {
+        hint.to_string()
+    }
+}
+
+/// 在状态行输出错误信息，`hint` 为附加在末尾的提示。
+/// 文件名按终端剩余宽度截断，整行不超过终端宽度，避免折行弄乱界面。
+fn show_failure(stdout: &mut Screen, layout: &Layout, failure: &TrackFailure, hint: &str) -> io::Result<()> {
+    layout.clear_status(stdout)?;
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    write!(stdout, "{}", layout.paint(&failure_line(terminal_width, failure, hint), Element::Error))?;
+    stdout.flush()
+}
+
+/// 按策略处理一次单曲失败，返回主循环应执行的动作。
+/// `wait` 为 Wait 策略下的最长等待时间，期间任意键提前跳过，退出键（经 `quit` 判断）退出。
+pub fn handle_failure(
+    stdout: &mut Screen,
+    layout: &Layout,
+    policy: ErrorPolicy,
+    failure: &TrackFailure,
+    wait: Duration,
+    quit: &mut QuitConfirm,
+) -> Result<FailureAction, Box<dyn std::error::Error>> {
+    if let Some(action) = immediate_action(policy) {
+        if action == FailureAction::Abort {
+            show_failure(stdout, layout, failure, "退出")?;
+        }
+        return Ok(action);
+    }
+    let action = match policy {
+        ErrorPolicy::Wait => {
+            // 每秒刷新剩余秒数，让用户知道播放器没有卡住；重绘前会清空整行，不留残字
+            let deadline = Instant::now() + wait;
+            let mut shown = None;
+            let mut action = FailureAction::Skip;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+                let asking = quit.is_asking(Instant::now());
+                if shown != Some((seconds, asking)) {
+                    shown = Some((seconds, asking));
+                    show_failure(stdout, layout, failure, &with_quit_hint(&format!("{}… 后跳过 [任意键跳过]", seconds), quit))?;
+                }
+                // 最多等到下一个整秒，届时刷新倒计时
+                let until_tick = remaining - Duration::from_secs(seconds - 1);
+                if !poll_event(until_tick)? {
+                    continue;
+                }
+                if let Event::Key(key) = read_event()?
+                    && key.kind == KeyEventKind::Press
+                    && let Some(chosen) = key_action(policy, key.code, quit.press(&key, Instant::now()))
+                {
+                    action = chosen;
+                    break;
+                }
+            }
+            action
+        }
+        // Ask（Skip 与 Abort 已在上面返回）
+        _ => {
+            let hint = "[R]重试 [S]跳过 [Q]退出";
+            show_failure(stdout, layout, failure, hint)?;
+            loop {
+                if let Event::Key(key) = read_event()?
+                    && key.kind == KeyEventKind::Press
+                {
+                    let decision = quit.press(&key, Instant::now());
+                    if let Some(action) = key_action(policy, key.code, decision) {
+                        break action;
+                    }
+                    if decision == QuitDecision::AskAgain {
+                        show_failure(stdout, layout, failure, &with_quit_hint(hint, quit))?;
+                    }
+                }
+            }
+        }
+    };
+    layout.clear_status(stdout)?;
+    Ok(action)
+}
+
+/// 快速跳过一轮同类失败中的一首：不等待，只刷新提示行。
+/// 期间按 A 中止（以失败退出码退出），退出键（经 `quit` 判断）退出，其他按键忽略。
+pub fn fast_skip_failure(stdout: &mut Screen, layout: &Layout, run: &FailureRun, quit: &mut QuitConfirm) -> Result<FailureAction, Box<dyn std::error::Error>> {
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    let what = if run.category == "解码失败" { run.category.clone() } else { format!("加载失败（{}）", run.category) };
+    let line = format!("连续 {} 个文件{}，正在快速跳过… [A]中止", run.count, what);
+    let show = |stdout: &mut Screen, quit: &QuitConfirm| -> io::Result<()> {
+        layout.clear_status(stdout)?;
+        write!(stdout, "{}", layout.paint(&truncate_string(&with_quit_hint(&line, quit), terminal_width), Element::Error))?;
+        stdout.flush()
+    };
+    show(stdout, quit)?;
+    while poll_event(Duration::ZERO)? {
+        if let Event::Key(key) = read_event()?
+            && key.kind == KeyEventKind::Press
+        {
+            match quit.press(&key, Instant::now()) {
+                QuitDecision::Quit => return Ok(FailureAction::Quit),
+                QuitDecision::AskAgain => show(stdout, quit)?,
+                QuitDecision::Ignore => {
+                    if matches!(key.code, KeyCode::Char('a') | KeyCode::Char('A')) {
+                        return Ok(FailureAction::Abort);
+                    }
+                }
+            }
+        }
+    }
+    Ok(FailureAction::Skip)
+}
+
+/// 连续失败达到上限时询问是否继续：返回 true 表示继续（计数随后清零），false 表示退出
+pub fn ask_continue_after_failures(
+    stdout: &mut Screen,
+    layout: &Layout,
+    count: u32,
+    quit: &mut QuitConfirm,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let question = format!("已连续 {} 首加载失败，是否继续？[Y]继续 [Q]退出", count);
+    layout.clear_status(stdout)?;
+    write!(stdout, "{}", question)?;
+    stdout.flush()?;
+    let keep_going = loop {
+        if let Event::Key(key) = read_event()?
+            && key.kind == KeyEventKind::Press
+        {
+            match quit.press(&key, Instant::now()) {
+                QuitDecision::Quit => break false,
+                QuitDecision::AskAgain => {
+                    layout.clear_status(stdout)?;
+                    write!(stdout, "{}", with_quit_hint(&question, quit))?;
+                    stdout.flush()?;
+                }
+                QuitDecision::Ignore => {
+                    if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter) {
+                        break true;
+                    }
+                }
+            }
+        }
+    };
+    layout.clear_status(stdout)?;
+    Ok(keep_going)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_skip_starts_on_the_fourth_identical_failure() {
+        let mut run = FailureRun::default();
+        for expected in 1..=3 {
+            assert_eq!(run.record("解码失败"), expected);
+            assert!(!run.is_fast_skipping());
+        }
+        assert_eq!(run.record("解码失败"), 4);
+        assert!(run.is_fast_skipping());
+        assert_eq!(run.record("解码失败"), 5);
+        assert!(run.is_fast_skipping());
+    }
+
+    #[test]
+    fn category_change_restarts_the_run() {
+        let mut run = FailureRun::default();
+        for _ in 0..4 {
+            run.record("解码失败");
+        }
+        assert!(run.is_fast_skipping());
+        assert_eq!(run.record("加载超时"), 1);
+        assert!(!run.is_fast_skipping());
+        assert_eq!(run.category, "加载超时");
+    }
+
+    #[test]
+    fn reset_clears_the_run() {
+        let mut run = FailureRun::default();
+        for _ in 0..5 {
+            run.record("解码失败");
+        }
+        run.reset();
+        assert!(!run.is_fast_skipping());
+        // 成功播放后即使类别相同也重新计数
+        assert_eq!(run.record("解码失败"), 1);
+    }
+
+    #[test]
+    fn failure_line_fits_the_width() {
+        let long_ascii = "a-very-long-directory-name/".repeat(10) + "track.flac";
+        let long_cjk = "周杰伦/叶惠美/".repeat(10) + "晴天（现场版）.flac";
+        for filename in [long_ascii.as_str(), long_cjk.as_str(), "短.mp3"] {
+            for err_type in ["解码失败", "timeout"] {
+                let failure = TrackFailure { current_index: 120, total_tracks: 3000, err_type, filename };
+                for width in 10..=120 {
+                    let line = failure_line(width, &failure, "5… 后跳过 [任意键跳过]");
+                    assert!(display_width(&line) <= width, "{} > {}: {}", display_width(&line), width, line);
+                }
+            }
+        }
+        // 宽度足够时文件名完整，不足时截断文件名而保留提示
+        let failure = TrackFailure { current_index: 0, total_tracks: 2, err_type: "解码失败", filename: &long_cjk };
+        assert!(failure_line(400, &failure, "退出").contains("晴天（现场版）.flac"));
+        assert!(failure_line(60, &failure, "退出").ends_with(" -> 退出"));
+    }
+
+    #[test]
+    fn policy_decides_without_keys() {
+        assert_eq!(immediate_action(ErrorPolicy::Skip), Some(FailureAction::Skip));
+        assert_eq!(immediate_action(ErrorPolicy::Abort), Some(FailureAction::Abort));
+        assert_eq!(immediate_action(ErrorPolicy::Wait), None);
+        assert_eq!(immediate_action(ErrorPolicy::Ask), None);
+    }
+
+    #[test]
+    fn keys_map_to_actions_per_policy() {
+        use FailureAction::*;
+        // 按键及其经 QuitConfirm 的判断：确认退出、需要再按一次（--confirm-quit 第一次按下）
+        let keys = [
+            (KeyCode::Char('r'), QuitDecision::Ignore),
+            (KeyCode::Char('S'), QuitDecision::Ignore),
+            (KeyCode::Char('q'), QuitDecision::Quit),
+            (KeyCode::Char('q'), QuitDecision::AskAgain),
+            (KeyCode::Char('c'), QuitDecision::Ignore),
+            (KeyCode::Enter, QuitDecision::Ignore),
+        ];
+        let cases = [
+            (ErrorPolicy::Wait, [Some(Skip), Some(Skip), Some(Quit), None, Some(Skip), Some(Skip)]),
+            (ErrorPolicy::Ask, [Some(Retry), Some(Skip), Some(Quit), None, None, None]),
+            (ErrorPolicy::Skip, [None; 6]),
+            (ErrorPolicy::Abort, [None; 6]),
+        ];
+        for (policy, expected) in cases {
+            for ((key, quit), action) in keys.iter().zip(expected) {
+                assert_eq!(key_action(policy, *key, *quit), action, "{:?} {:?} {:?}", policy, key, quit);
+            }
+        }
+    }
+}
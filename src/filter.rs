@@ -0,0 +1,80 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::metadata::get_artist_album_tags;
+use crate::utils::Track;
+
+/// 筛选进度的刷新间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `--artist`/`--album` 筛选条件：同类条件之间为「或」，艺术家与专辑之间为「且」。
+/// 按子串匹配，不区分大小写；标签缺失时改为匹配文件路径（通常含艺术家/专辑目录名）。
+pub struct TrackFilter {
+    artists: Vec<String>,
+    albums: Vec<String>,
+}
+
+impl TrackFilter {
+    pub fn new(artists: &[String], albums: &[String]) -> Self {
+        let lower = |items: &[String]| items.iter().map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty()).collect();
+        TrackFilter { artists: lower(artists), albums: lower(albums) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.artists.is_empty() && self.albums.is_empty()
+    }
+
+    /// 判断一首歌是否满足条件（需要时读取标签）
+    fn matches(&self, path: &Path) -> bool {
+        let (artist, album) = get_artist_album_tags(path);
+        let path_text = path.to_string_lossy().to_lowercase();
+        let field_matches = |needles: &[String], tag: Option<String>| {
+            if needles.is_empty() {
+                return true;
+            }
+            let haystack = tag.map_or_else(|| path_text.clone(), |t| t.to_lowercase());
+            needles.iter().any(|needle| haystack.contains(needle.as_str()))
+        };
+        field_matches(&self.artists, artist) && field_matches(&self.albums, album)
+    }
+
+    /// 并行读取标签并筛选，保持原有顺序；`progress(已检查, 总数)` 在调用线程中定期调用
+    pub fn apply(&self, tracks: Vec<Track>, progress: fn(usize, usize)) -> Vec<Track> {
+        let total = tracks.len();
+        let workers = thread::available_parallelism().map_or(4, |n| n.get()).min(total.max(1));
+        let chunk_size = total.div_ceil(workers).max(1);
+        let checked = AtomicUsize::new(0);
+        let keep: Vec<bool> = thread::scope(|scope| {
+            let handles: Vec<_> = tracks
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let checked = &checked;
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|track| {
+                                let keep = self.matches(&track.path);
+                                checked.fetch_add(1, Ordering::Relaxed);
+                                keep
+                            })
+                            .collect::<Vec<bool>>()
+                    })
+                })
+                .collect();
+            while !handles.iter().all(|h| h.is_finished()) {
+                progress(checked.load(Ordering::Relaxed), total);
+                thread::sleep(PROGRESS_INTERVAL);
+            }
+            progress(total, total);
+            // 读取标签的线程不会 panic；万一发生则保留该段歌曲
+            handles
+                .into_iter()
+                .zip(tracks.chunks(chunk_size))
+                .flat_map(|(h, chunk)| h.join().unwrap_or_else(|_| vec![true; chunk.len()]))
+                .collect()
+        });
+        tracks.into_iter().zip(keep).filter_map(|(track, keep)| keep.then_some(track)).collect()
+    }
+}
@@ -0,0 +1,33 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::event::{DisableFocusChange, EnableFocusChange};
+use crossterm::execute;
+
+/// 当前暂停的原因：自动暂停（如失去焦点）只在自己造成的暂停上自动恢复，
+/// 用户事先手动暂停的不会因此被恢复播放
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// 空格键暂停
+    Manual,
+    /// `--pause-on-unfocus`：终端失去焦点
+    Unfocused,
+}
+
+/// 是否已让终端报告焦点变化（只在 `--pause-on-unfocus`/`--dim-on-unfocus` 时开启）
+static FOCUS_REPORTING: AtomicBool = AtomicBool::new(false);
+
+/// 让终端报告焦点变化；不支持的终端忽略该序列，也就不会收到焦点事件
+pub fn enable_focus_reporting(out: &mut impl Write) -> io::Result<()> {
+    execute!(out, EnableFocusChange)?;
+    FOCUS_REPORTING.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 退出前关闭焦点报告（未开启时不输出），否则之后在该终端中切换窗口会出现 `^[[I`/`^[[O`
+pub fn disable_focus_reporting(out: &mut impl Write) -> io::Result<()> {
+    if FOCUS_REPORTING.swap(false, Ordering::Relaxed) {
+        execute!(out, DisableFocusChange)?;
+    }
+    Ok(())
+}
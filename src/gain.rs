@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
+// 音频播放库
+use rodio::{Decoder, Source};
+
+use crate::metadata::{has_replaygain, write_replaygain, ReplayGainTags};
+use crate::utils::{format_position, scan_audio_files, ScanOptions};
+
+/// ReplayGain 2.0 的参考响度（LUFS）：增益 = 参考响度 - 歌曲响度
+const REFERENCE_LUFS: f64 = -18.0;
+/// 绝对门限（LUFS）：低于该响度的块视为静音，不参与计算
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// 相对门限（LU）：低于初步响度该值的块不参与计算
+const RELATIVE_GATE_LU: f64 = 10.0;
+/// 每个子块 100ms，连续 4 个子块组成一个 400ms 的测量块（相邻块重叠 75%）
+const SUBBLOCKS_PER_BLOCK: usize = 4;
+/// `--no-write` 时结果写入的文件（位于分析的目录下）
+const SIDECAR_FILE: &str = "replaygain.csv";
+const SIDECAR_HEADER: &str = "path,track_gain_db,track_peak,album_gain_db,album_peak";
+
+/// gain 子命令的选项
+pub struct GainOptions {
+    /// 不修改音频文件，把结果写入 SIDECAR_FILE
+    pub no_write: bool,
+    /// 重新分析已有结果的文件
+    pub force: bool,
+}
+
+/// 二阶 IIR 滤波器（转置直接 II 型）
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// ITU-R BS.1770 的 K 计权（高架滤波 + 高通滤波），系数按采样率换算（与 libebur128 相同）
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate.max(1) as f64;
+    let k = (std::f64::consts::PI * 1681.974450955533 / rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+    let k = (std::f64::consts::PI * 38.13547087602444 / rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad { b: [1.0, -2.0, 1.0], a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0], z: [0.0; 2] };
+    [shelf, high_pass]
+}
+
+/// 单首歌曲的分析结果：各 400ms 块的均方能量（各声道之和）与采样峰值
+struct Analysis {
+    blocks: Vec<f64>,
+    peak: f64,
+}
+
+/// 解码整首歌曲并计算响度块。近似之处：各声道权重都为 1（BS.1770 中环绕声道为 1.41），
+/// 峰值为采样峰值而非过采样的真峰值，采样率或声道数中途变化时仍按开头的参数计算
+fn analyze(path: &Path) -> Result<Analysis, String> {
+    let file = File::open(path).map_err(|e| format!("无法打开: {}", e))?;
+    let decoder = Decoder::new(BufReader::new(file)).map_err(|e| format!("无法解码: {}", e))?;
+    let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+    Ok(analyze_samples(decoder, channels, sample_rate))
+}
+
+/// 由交错排列的采样计算响度块与峰值
+fn analyze_samples(samples: impl Iterator<Item = i16>, channels: u16, sample_rate: u32) -> Analysis {
+    let channels = channels.max(1) as usize;
+    let subblock_frames = (sample_rate as usize / 10).max(1);
+    let mut filters: Vec<[Biquad; 2]> = (0..channels).map(|_| k_weighting(sample_rate)).collect();
+    let mut subblocks = Vec::new();
+    let mut energy = 0.0;
+    let mut peak: f64 = 0.0;
+    for (i, sample) in samples.enumerate() {
+        let x = sample as f64 / 32768.0;
+        peak = peak.max(x.abs());
+        let [shelf, high_pass] = &mut filters[i % channels];
+        let y = high_pass.process(shelf.process(x));
+        energy += y * y;
+        if (i + 1) % (channels * subblock_frames) == 0 {
+            subblocks.push(energy / subblock_frames as f64);
+            energy = 0.0;
+        }
+    }
+    let blocks = subblocks.windows(SUBBLOCKS_PER_BLOCK).map(|w| w.iter().sum::<f64>() / SUBBLOCKS_PER_BLOCK as f64).collect();
+    Analysis { blocks, peak }
+}
+
+fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// 按 BS.1770 的双重门限计算积分响度；块全部低于绝对门限（无声或短于 400ms）时为 None
+fn gated_loudness<'a>(blocks: impl Iterator<Item = &'a f64> + Clone) -> Option<f64> {
+    let mean = |blocks: &mut dyn Iterator<Item = &f64>| {
+        let (sum, count) = blocks.fold((0.0, 0usize), |(sum, count), e| (sum + e, count + 1));
+        (count > 0).then(|| sum / count as f64)
+    };
+    let audible = blocks.filter(|e| energy_to_lufs(**e) > ABSOLUTE_GATE_LUFS);
+    let threshold = energy_to_lufs(mean(&mut audible.clone())?) - RELATIVE_GATE_LU;
+    mean(&mut audible.filter(|e| energy_to_lufs(**e) > threshold)).map(energy_to_lufs)
+}
+
+/// 由响度换算增益（dB）
+fn gain_for(loudness: f64) -> f64 {
+    REFERENCE_LUFS - loudness
+}
+
+/// `mddplayer gain <目录>`：递归扫描目录，多线程解码分析各歌曲响度，写入单曲增益与专辑增益
+/// （同一目录下的歌曲视为一张专辑）。每个目录分析完立即写入结果，中断后重新运行时跳过已有结果的目录。
+/// 进度与预计剩余时间输出到 stderr。有文件失败时返回 false。
+pub fn run_gain(dir: &Path, opts: &GainOptions) -> bool {
+    if !dir.is_dir() {
+        eprintln!("[错误]'{}' 不是目录", dir.display());
+        return false;
+    }
+    let scan_opts = ScanOptions { recursive: true, no_cue_split: true, quiet: true, ..ScanOptions::default() };
+    let files = match scan_audio_files(dir, &scan_opts) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("[错误]无法扫描 '{}': {}", dir.display(), e);
+            return false;
+        }
+    };
+
+    // 按所在目录分组；--no-write 时以 CSV 中已有的行判断是否已分析
+    let sidecar = dir.join(SIDECAR_FILE);
+    let mut sidecar_rows = if opts.no_write { load_sidecar(&sidecar) } else { HashMap::new() };
+    let mut albums: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for file in files {
+        albums.entry(album_of(&file)).or_default().push(file);
+    }
+    let done = |path: &Path| if opts.no_write { sidecar_rows.contains_key(&path.to_string_lossy().into_owned()) } else { has_replaygain(path) };
+    let total_albums = albums.len();
+    albums.retain(|_, tracks| opts.force || !tracks.iter().all(|track| done(track)));
+    let skipped = total_albums - albums.len();
+    if skipped > 0 {
+        eprintln!("跳过 {} 个已有结果的目录（--force 重新分析）", skipped);
+    }
+    let queue: Vec<PathBuf> = albums.values().flatten().cloned().collect();
+    if queue.is_empty() {
+        eprintln!("没有需要分析的歌曲");
+        return true;
+    }
+
+    // 工作线程按目录顺序取歌曲，一个目录的结果收齐后立即计算专辑增益并写入，中断时已完成的目录不会丢失
+    let mut remaining: HashMap<&Path, usize> = albums.iter().map(|(album, tracks)| (album.as_path(), tracks.len())).collect();
+    let mut results: HashMap<PathBuf, Result<Analysis, String>> = HashMap::new();
+    let mut failures = 0;
+    let mut sidecar_ok = true;
+    let started = Instant::now();
+    for (done, (path, result)) in analyze_all(&queue).into_iter().enumerate() {
+        let album = album_of(&path);
+        results.insert(path, result);
+        if let Some(count) = remaining.get_mut(album.as_path()) {
+            *count -= 1;
+            if *count == 0 {
+                eprint!("\r\x1b[K");
+                failures += finish_album(&album, &albums[&album], &mut results, opts, &mut sidecar_rows);
+                if opts.no_write && let Err(e) = write_sidecar(&sidecar, &sidecar_rows) {
+                    eprintln!("[错误]无法写入 '{}': {}", sidecar.display(), e);
+                    sidecar_ok = false;
+                }
+            }
+        }
+        let done = done + 1;
+        let eta = started.elapsed().mul_f64((queue.len() - done) as f64 / done as f64);
+        eprint!("\r\x1b[K分析中 {}/{}，预计剩余 {}", done, queue.len(), format_position(eta));
+    }
+    eprintln!();
+
+    if opts.no_write && sidecar_ok {
+        eprintln!("结果已写入 '{}'", sidecar.display());
+    }
+    eprintln!("已分析 {} 首，失败 {} 首", queue.len() - failures, failures);
+    failures == 0 && sidecar_ok
+}
+
+/// 歌曲所属的专辑（所在目录）
+fn album_of(track: &Path) -> PathBuf {
+    track.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// 一个目录的歌曲都已分析完：计算专辑增益并写入标签（或记入结果文件的行），返回失败的歌曲数
+fn finish_album(
+    album: &Path,
+    tracks: &[PathBuf],
+    results: &mut HashMap<PathBuf, Result<Analysis, String>>,
+    opts: &GainOptions,
+    sidecar_rows: &mut HashMap<String, String>,
+) -> usize {
+    let mut failures = 0;
+    let analyses: Vec<(&PathBuf, Analysis)> = tracks
+        .iter()
+        .filter_map(|track| match results.remove(track) {
+            Some(Ok(analysis)) => Some((track, analysis)),
+            Some(Err(e)) => {
+                eprintln!("[错误]{}: {}", track.display(), e);
+                failures += 1;
+                None
+            }
+            None => None,
+        })
+        .collect();
+    let album_loudness = gated_loudness(analyses.iter().flat_map(|(_, a)| a.blocks.iter()));
+    let album_peak = analyses.iter().map(|(_, a)| a.peak).fold(0.0, f64::max);
+    for (track, analysis) in &analyses {
+        let (Some(track_loudness), Some(album_loudness)) = (gated_loudness(analysis.blocks.iter()), album_loudness) else {
+            eprintln!("[警告]{}: 无声或过短，无法计算响度", track.display());
+            continue;
+        };
+        let tags = ReplayGainTags {
+            track_gain: gain_for(track_loudness),
+            track_peak: analysis.peak,
+            album_gain: gain_for(album_loudness),
+            album_peak,
+        };
+        if opts.no_write {
+            let key = track.to_string_lossy().into_owned();
+            sidecar_rows.insert(key.clone(), sidecar_row(&key, &tags));
+        } else if let Err(e) = write_replaygain(track, &tags) {
+            eprintln!("[错误]{}: {}", track.display(), e);
+            failures += 1;
+        }
+    }
+    if album_loudness.is_none() && !analyses.is_empty() {
+        eprintln!("[警告]目录 '{}' 整体无声，未写入增益", album.display());
+    }
+    failures
+}
+
+/// 整体重写结果文件（每个目录完成后调用，按路径排序）
+fn write_sidecar(sidecar: &Path, rows: &HashMap<String, String>) -> std::io::Result<()> {
+    let mut rows: Vec<&String> = rows.values().collect();
+    rows.sort();
+    let content: String = std::iter::once(SIDECAR_HEADER).chain(rows.iter().map(|r| r.as_str())).map(|line| format!("{}\n", line)).collect();
+    fs::write(sidecar, content)
+}
+
+/// 在工作线程池中分析全部文件（线程数为 CPU 核数），按完成顺序逐个给出结果
+fn analyze_all(queue: &[PathBuf]) -> mpsc::Receiver<(PathBuf, Result<Analysis, String>)> {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(queue.len());
+    let queue = Arc::new(queue.to_vec());
+    let next = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..workers {
+        let (queue, next, tx) = (queue.clone(), next.clone(), tx.clone());
+        thread::spawn(move || {
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = queue.get(index) else { break };
+                if tx.send((path.clone(), analyze(path))).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    rx
+}
+
+/// CSV 字段：含逗号、引号或换行时加引号
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+fn sidecar_row(path: &str, tags: &ReplayGainTags) -> String {
+    format!(
+        "{},{:.2},{:.6},{:.2},{:.6}",
+        csv_field(path),
+        tags.track_gain,
+        tags.track_peak,
+        tags.album_gain,
+        tags.album_peak
+    )
+}
+
+/// 读取已有的结果文件：路径 → 整行
+fn load_sidecar(path: &Path) -> HashMap<String, String> {
+    let Ok(content) = fs::read_to_string(path) else { return HashMap::new() };
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| Some((parse_first_field(line)?, line.to_string())))
+        .collect()
+}
+
+/// 解析 CSV 行的第一个字段（路径）
+fn parse_first_field(line: &str) -> Option<String> {
+    let Some(quoted) = line.strip_prefix('"') else {
+        return line.split_once(',').map(|(field, _)| field.to_string());
+    };
+    let mut field = String::new();
+    let mut chars = quoted.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => return Some(field),
+            c => field.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    /// `seconds` 秒、`dbfs` 电平（满幅正弦为 0 dBFS）的正弦波，各声道相同，交错排列
+    fn sine(freq: f64, dbfs: f64, seconds: f64, channels: u16, rate: u32) -> Vec<i16> {
+        let amplitude = 10f64.powf(dbfs / 20.0) * 32767.0;
+        (0..(seconds * rate as f64) as usize)
+            .flat_map(|n| {
+                let x = (amplitude * (2.0 * std::f64::consts::PI * freq * n as f64 / rate as f64).sin()).round() as i16;
+                std::iter::repeat_n(x, channels as usize)
+            })
+            .collect()
+    }
+
+    /// K 计权在 `freq` 处的增益（dB）：滤波稳定后的输出与输入的能量比
+    fn weighting_db(freq: f64, rate: u32) -> f64 {
+        let [mut shelf, mut high_pass] = k_weighting(rate);
+        let input: Vec<f64> = sine(freq, 0.0, 2.0, 1, rate).iter().map(|&x| x as f64 / 32768.0).collect();
+        let settled = rate as usize;
+        let (mut energy_in, mut energy_out) = (0.0, 0.0);
+        for (n, &x) in input.iter().enumerate() {
+            let y = high_pass.process(shelf.process(x));
+            if n >= settled {
+                energy_in += x * x;
+                energy_out += y * y;
+            }
+        }
+        10.0 * (energy_out / energy_in).log10()
+    }
+
+    #[test]
+    fn k_weighting_response() {
+        for rate in [44100, 48000, 96000] {
+            // 997 Hz 处约 +0.69 dB（正是响度公式中 -0.691 抵消的部分）
+            assert!((weighting_db(997.0, rate) - 0.691).abs() < 0.05, "{}", rate);
+            // 高频架升约 +4 dB，低频被高通滤波衰减
+            assert!((weighting_db(10000.0, rate) - 4.0).abs() < 0.5, "{}", rate);
+            assert!(weighting_db(20.0, rate) < -10.0, "{}", rate);
+        }
+    }
+
+    #[test]
+    fn sine_at_minus_20_dbfs_reads_minus_20_lufs() {
+        // BS.1770：满幅 997 Hz 正弦同时出现在左右声道时约为 0 LUFS（只在一个声道时约为 -3 LUFS）
+        let analysis = analyze_samples(sine(997.0, -20.0, 5.0, 2, RATE).into_iter(), 2, RATE);
+        let loudness = gated_loudness(analysis.blocks.iter()).unwrap();
+        assert!((loudness + 20.0).abs() < 0.1, "{}", loudness);
+        assert!((analysis.peak - 0.1).abs() < 0.001, "{}", analysis.peak);
+        assert!((gain_for(loudness) - 2.0).abs() < 0.1);
+
+        let mono = analyze_samples(sine(997.0, -20.0, 5.0, 1, RATE).into_iter(), 1, RATE);
+        let loudness = gated_loudness(mono.blocks.iter()).unwrap();
+        assert!((loudness + 23.01).abs() < 0.1, "{}", loudness);
+    }
+
+    #[test]
+    fn silence_and_short_audio_have_no_loudness() {
+        let silence = analyze_samples(std::iter::repeat_n(0, RATE as usize * 4), 2, RATE);
+        assert!(!silence.blocks.is_empty());
+        assert_eq!(gated_loudness(silence.blocks.iter()), None);
+        assert_eq!(silence.peak, 0.0);
+        // 不足 400ms 没有完整的测量块
+        let short = analyze_samples(sine(997.0, -20.0, 0.3, 2, RATE).into_iter(), 2, RATE);
+        assert!(short.blocks.is_empty());
+        assert_eq!(gated_loudness(short.blocks.iter()), None);
+    }
+
+    #[test]
+    fn gating_drops_quiet_blocks() {
+        let loud = 10f64.powf((-20.0 + 0.691) / 10.0);
+        assert!((energy_to_lufs(loud) + 20.0).abs() < 1e-9);
+        // 比初步响度低 10 LU 以上的块（-45 LUFS）不参与计算
+        let quiet = 10f64.powf((-45.0 + 0.691) / 10.0);
+        let blocks = [loud, loud, quiet, quiet, quiet];
+        assert!((gated_loudness(blocks.iter()).unwrap() + 20.0).abs() < 1e-9);
+        // 低于绝对门限 -70 LUFS 的块不参与计算
+        let silent = 10f64.powf((-80.0 + 0.691) / 10.0);
+        assert!((gated_loudness([quiet, silent, silent].iter()).unwrap() + 45.0).abs() < 1e-9);
+        assert_eq!(gated_loudness([silent, 0.0].iter()), None);
+        assert_eq!(gated_loudness([].iter()), None);
+    }
+
+    #[test]
+    fn gain_is_relative_to_reference() {
+        assert_eq!(gain_for(-18.0), 0.0);
+        assert_eq!(gain_for(-23.0), 5.0);
+        assert_eq!(gain_for(-9.5), -8.5);
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// `--check-playlist-health`：每隔一段时间在后台检查接下来的若干首是否仍然存在，
+/// 让被同步客户端移走的文件在轮到它之前就从队列中去掉，而不是播放到时才出现错误等待。
+/// 后台线程只检查路径的快照，删除由主循环执行（队列只由主循环修改）。
+pub struct HealthChecker {
+    interval: Duration,
+    /// 每次检查的歌曲数
+    pub ahead: usize,
+    next_check: Instant,
+    /// 进行中的检查，完成后给出已不存在的路径
+    pending: Option<Receiver<HashSet<PathBuf>>>,
+}
+
+impl HealthChecker {
+    pub fn new(interval: Duration, ahead: usize, now: Instant) -> Self {
+        HealthChecker { interval, ahead, next_check: now + interval, pending: None }
+    }
+
+    /// 到了检查时间且上一次检查已经结束
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.pending.is_none() && now >= self.next_check
+    }
+
+    /// 在后台线程检查 `paths`（网络共享上的 stat 可能很慢，不阻塞播放）
+    pub fn start(&mut self, paths: Vec<PathBuf>, now: Instant) {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let missing = paths.into_iter().filter(|path| is_missing(path)).collect();
+            let _ = tx.send(missing);
+        });
+        self.pending = Some(rx);
+        self.next_check = now + self.interval;
+    }
+
+    /// 取得已完成的检查结果；尚未完成或没有进行中的检查时为 None
+    pub fn poll(&mut self) -> Option<HashSet<PathBuf>> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(missing) => Some(missing),
+            Err(TryRecvError::Empty) => return None,
+            // 检查线程意外退出：当作没有发现问题
+            Err(TryRecvError::Disconnected) => None,
+        };
+        self.pending = None;
+        result
+    }
+}
+
+/// 文件已被移走或删除：文件不存在而所在目录仍可访问。
+/// 目录也无法访问时多半是网络共享或外接盘暂时断开，不算缺失（播放到时按常规流程等待或报错）。
+fn is_missing(path: &Path) -> bool {
+    match path.metadata() {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.parent().is_some_and(|dir| dir.is_dir()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn wait_for_result(checker: &mut HealthChecker) -> HashSet<PathBuf> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(missing) = checker.poll() {
+                return missing;
+            }
+            assert!(Instant::now() < deadline, "检查没有在 5 秒内完成");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn reports_deleted_files_only() {
+        let dir = TempDir::new();
+        let kept = dir.write("kept.mp3", b"");
+        let deleted = dir.write("deleted.mp3", b"");
+        // 所在目录也无法访问（如网络共享断开）的文件不算缺失
+        let offline = dir.join("unmounted/song.mp3");
+        std::fs::remove_file(&deleted).unwrap();
+
+        let now = Instant::now();
+        let mut checker = HealthChecker::new(Duration::from_secs(60), 20, now);
+        assert!(!checker.is_due(now));
+        assert!(checker.is_due(now + Duration::from_secs(60)));
+        checker.start(vec![kept, deleted.clone(), offline], now);
+        // 检查进行中不会再次开始
+        assert!(!checker.is_due(now + Duration::from_secs(600)));
+        assert_eq!(wait_for_result(&mut checker), HashSet::from([deleted]));
+        assert_eq!(checker.poll(), None);
+        assert!(checker.is_due(now + Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+// 本地时间
+use chrono::Local;
+
+use crate::outcome::PlaybackOutcome;
+
+/// 最近播放区域显示的条目数（同时也是占用的行数）
+pub const RECENT_ROWS: u16 = 3;
+
+/// 播放记录的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryStatus {
+    /// 正在播放
+    Playing,
+    /// 听过（播完，或达到阈值后切走）
+    Played,
+    /// 未达到阈值就被切走
+    Skipped,
+    /// 加载失败
+    Failed,
+}
+
+impl EntryStatus {
+    fn symbol(self) -> &'static str {
+        match self {
+            EntryStatus::Playing => "▶",
+            EntryStatus::Played => "✓",
+            EntryStatus::Skipped => "↷",
+            EntryStatus::Failed => "✗",
+        }
+    }
+}
+
+/// 一条播放记录
+#[derive(Debug, Clone)]
+pub struct PlayedEntry {
+    /// 开始播放（或失败）的本地时间，格式 HH:MM
+    pub time: String,
+    pub status: EntryStatus,
+    /// 显示文本：艺术家 – 标题，失败时为文件名
+    pub label: String,
+}
+
+impl PlayedEntry {
+    /// 渲染为一行文本，如 `14:32 ✓ Artist – Title`
+    pub fn line(&self) -> String {
+        format!("{} {} {}", self.time, self.status.symbol(), self.label)
+    }
+}
+
+/// 最近播放记录：只保留最新的 RECENT_ROWS 条，最新的在前
+#[derive(Debug, Default)]
+pub struct RecentPlays {
+    entries: VecDeque<PlayedEntry>,
+}
+
+impl RecentPlays {
+    pub fn new() -> Self {
+        RecentPlays::default()
+    }
+
+    fn push(&mut self, status: EntryStatus, label: String) {
+        let time = Local::now().format("%H:%M").to_string();
+        self.entries.push_front(PlayedEntry { time, status, label });
+        self.entries.truncate(RECENT_ROWS as usize);
+    }
+
+    /// 记录一首开始播放的歌曲（结束时由 finish 更新状态）
+    pub fn push_played(&mut self, title: &str, artist: &str) {
+        let label = if artist.is_empty() { title.to_string() } else { format!("{} – {}", artist, title) };
+        self.push(EntryStatus::Playing, label);
+    }
+
+    /// 记录一次加载失败
+    pub fn push_failed(&mut self, label: &str) {
+        self.push(EntryStatus::Failed, label.to_string());
+    }
+
+    /// 按结束结果更新正在播放的那条记录
+    pub fn finish(&mut self, outcome: &PlaybackOutcome) {
+        if let Some(entry) = self.entries.front_mut()
+            && entry.status == EntryStatus::Playing
+        {
+            entry.status = match outcome {
+                PlaybackOutcome::Failed(_) => EntryStatus::Failed,
+                o if o.counts_as_played() => EntryStatus::Played,
+                _ => EntryStatus::Skipped,
+            };
+        }
+    }
+
+    /// 按从新到旧的顺序遍历
+    pub fn iter(&self) -> impl Iterator<Item = &PlayedEntry> {
+        self.entries.iter()
+    }
+}
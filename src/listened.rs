@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+/// 时长达到该值的歌曲（如长篇 DJ 混音）才记录听过的区间
+pub const LONG_TRACK_MIN: Duration = Duration::from_secs(20 * 60);
+/// 相隔不超过该值的区间合并为一段（轮询与跳转的误差）
+const MERGE_GAP_MS: u64 = 1000;
+/// 短于该值的区间不记录（如刚开播就跳走）
+const MIN_SEGMENT_MS: u64 = 5000;
+/// 每个文件最多保存的区间数，超出时合并相隔最近的两段
+const MAX_SEGMENTS: usize = 32;
+/// 相邻两次取得的播放位置前进超过该值时视为跳转（而不是连续播放）
+const JUMP_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// 听过的区间（毫秒），按起点排序、互不重叠
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Segments(Vec<(u64, u64)>);
+
+impl Segments {
+    /// 解析 `起-止,起-止`（毫秒）；无法解析的段被忽略
+    pub fn parse(text: &str) -> Self {
+        let mut segments = Segments::default();
+        for (start, end) in text.split(',').filter_map(|s| s.split_once('-')) {
+            if let (Ok(start), Ok(end)) = (start.trim().parse(), end.trim().parse()) {
+                segments.insert(start, end);
+            }
+        }
+        segments
+    }
+
+    pub fn format(&self) -> String {
+        self.0.iter().map(|(start, end)| format!("{}-{}", start, end)).collect::<Vec<_>>().join(",")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 加入一段听过的区间，与重叠或相邻的区间合并；过短的区间忽略
+    pub fn add(&mut self, start: Duration, end: Duration) {
+        let (start, end) = (start.as_millis() as u64, end.as_millis() as u64);
+        if end.saturating_sub(start) < MIN_SEGMENT_MS {
+            return;
+        }
+        self.insert(start, end);
+    }
+
+    fn insert(&mut self, mut start: u64, mut end: u64) {
+        if end <= start {
+            return;
+        }
+        // 与新区间重叠或相隔 MERGE_GAP_MS 以内的都并入
+        self.0.retain(|&(s, e)| {
+            let touches = s <= end + MERGE_GAP_MS && start <= e + MERGE_GAP_MS;
+            if touches {
+                start = start.min(s);
+                end = end.max(e);
+            }
+            !touches
+        });
+        let at = self.0.partition_point(|&(s, _)| s < start);
+        self.0.insert(at, (start, end));
+        while self.0.len() > MAX_SEGMENTS {
+            self.merge_closest();
+        }
+    }
+
+    /// 合并间隔最小的相邻两段（中间未听的部分随之视为听过）
+    fn merge_closest(&mut self) {
+        let Some(i) = (0..self.0.len().saturating_sub(1)).min_by_key(|&i| self.0[i + 1].0 - self.0[i].1) else { return };
+        self.0[i].1 = self.0[i + 1].1;
+        self.0.remove(i + 1);
+    }
+
+    /// 第一个未听部分的起点；除去不足 MIN_SEGMENT_MS 的尾巴都已听过时为 None
+    pub fn first_unheard(&self, duration: Duration) -> Option<Duration> {
+        let duration = duration.as_millis() as u64;
+        let mut position = 0;
+        for &(start, end) in &self.0 {
+            if start > position + MERGE_GAP_MS {
+                break;
+            }
+            position = position.max(end);
+        }
+        (position + MIN_SEGMENT_MS < duration).then(|| Duration::from_millis(position))
+    }
+
+    /// 按 `width` 格缩略显示：一格中一半以上听过时为 `▓`，否则为 `░`
+    pub fn coverage_map(&self, duration: Duration, width: usize) -> String {
+        let duration = duration.as_millis() as u64;
+        if duration == 0 || width == 0 {
+            return String::new();
+        }
+        (0..width as u64)
+            .map(|cell| {
+                let (from, to) = (duration * cell / width as u64, duration * (cell + 1) / width as u64);
+                let heard: u64 = self.0.iter().map(|&(s, e)| e.min(to).saturating_sub(s.max(from))).sum();
+                if heard * 2 >= to - from { '▓' } else { '░' }
+            })
+            .collect()
+    }
+}
+
+/// 跟踪当前歌曲正在连续播放的区间：位置后退或大幅前进（跳转）时结束上一段
+#[derive(Debug, Default)]
+pub struct ListenTracker {
+    /// 当前区间的起点与最近一次的位置
+    open: Option<(Duration, Duration)>,
+}
+
+impl ListenTracker {
+    /// 记录最新的播放位置；发生跳转时返回刚结束的区间
+    pub fn observe(&mut self, position: Duration) -> Option<(Duration, Duration)> {
+        match &mut self.open {
+            Some((_, last)) if position >= *last && position <= *last + JUMP_THRESHOLD => {
+                *last = position;
+                None
+            }
+            open => open.replace((position, position)),
+        }
+    }
+
+    /// 正在进行、尚未结束的区间
+    pub fn current(&self) -> Option<(Duration, Duration)> {
+        self.open
+    }
+
+    /// 歌曲结束或切走：结束当前区间
+    pub fn finish(&mut self) -> Option<(Duration, Duration)> {
+        self.open.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn segments(ranges: &[(u64, u64)]) -> Segments {
+        let mut segments = Segments::default();
+        for &(start, end) in ranges {
+            segments.add(ms(start), ms(end));
+        }
+        segments
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_ranges() {
+        let cases: &[(&[(u64, u64)], &str)] = &[
+            (&[(0, 10_000), (20_000, 30_000)], "0-10000,20000-30000"),
+            // 重叠
+            (&[(0, 10_000), (5_000, 15_000)], "0-15000"),
+            // 相隔 1 秒以内视为相邻
+            (&[(0, 10_000), (11_000, 20_000)], "0-20000"),
+            (&[(0, 10_000), (11_001, 20_000)], "0-10000,11001-20000"),
+            // 被已有区间包含
+            (&[(0, 60_000), (10_000, 20_000)], "0-60000"),
+            // 新区间同时连上两边
+            (&[(0, 10_000), (20_000, 30_000), (9_000, 21_000)], "0-30000"),
+            // 乱序加入后仍按起点排序
+            (&[(40_000, 50_000), (0, 10_000), (20_000, 30_000)], "0-10000,20000-30000,40000-50000"),
+            // 过短的区间不记录
+            (&[(0, 4_999), (10_000, 15_000)], "10000-15000"),
+        ];
+        for (ranges, expected) in cases {
+            assert_eq!(segments(ranges).format(), *expected, "{:?}", ranges);
+        }
+    }
+
+    #[test]
+    fn keeps_at_most_32_segments() {
+        // 间隔依次变大：超出上限时先合并间隔最小的前两段
+        let ranges: Vec<(u64, u64)> = (0..33).map(|i| (i * 20_000 + i * i * 10, i * 20_000 + i * i * 10 + 10_000)).collect();
+        let capped = segments(&ranges);
+        assert_eq!(capped.0.len(), MAX_SEGMENTS);
+        assert_eq!(capped.0[0], (0, 30_010));
+        assert_eq!(capped.0[1], ranges[2]);
+        assert_eq!(capped.0.last(), ranges.last());
+    }
+
+    #[test]
+    fn parse_and_format_round_trip() {
+        let text = "0-60000,120000-180000";
+        assert_eq!(Segments::parse(text).format(), text);
+        // 无法解析的段被忽略，重叠的段合并
+        assert_eq!(Segments::parse("x-1, 5000 - 9000 ,bad,8000-12000,3-2").format(), "5000-12000");
+        assert!(Segments::parse("").is_empty());
+    }
+
+    #[test]
+    fn first_unheard_skips_the_heard_prefix() {
+        let hour = ms(3_600_000);
+        assert_eq!(Segments::default().first_unheard(hour), Some(ms(0)));
+        assert_eq!(segments(&[(0, 600_000), (1_200_000, 1_800_000)]).first_unheard(hour), Some(ms(600_000)));
+        // 开头一秒以内没听到的部分忽略
+        assert_eq!(segments(&[(1_000, 600_000)]).first_unheard(hour), Some(ms(600_000)));
+        assert_eq!(segments(&[(1_001, 600_000)]).first_unheard(hour), Some(ms(0)));
+        // 只剩不足 5 秒的尾巴时视为听完
+        assert_eq!(segments(&[(0, 3_595_000)]).first_unheard(hour), None);
+        assert_eq!(segments(&[(0, 3_594_999)]).first_unheard(hour), Some(ms(3_594_999)));
+    }
+
+    #[test]
+    fn coverage_map_shades_heard_cells() {
+        let heard = segments(&[(0, 25_000), (70_000, 100_000)]);
+        assert_eq!(heard.coverage_map(ms(100_000), 10), "▓▓▓░░░░▓▓▓");
+        // 一格中恰好一半听过时算听过
+        assert_eq!(segments(&[(0, 5_000)]).coverage_map(ms(100_000), 10), "▓░░░░░░░░░");
+        assert_eq!(heard.coverage_map(Duration::ZERO, 10), "");
+        assert_eq!(heard.coverage_map(ms(100_000), 0), "");
+    }
+
+    #[test]
+    fn tracker_splits_on_seeks() {
+        let secs = Duration::from_secs;
+        let mut tracker = ListenTracker::default();
+        for position in [0, 1, 2, 3, 6] {
+            assert_eq!(tracker.observe(secs(position)), None);
+        }
+        assert_eq!(tracker.current(), Some((secs(0), secs(6))));
+        // 向后跳转结束上一段
+        assert_eq!(tracker.observe(secs(2)), Some((secs(0), secs(6))));
+        assert_eq!(tracker.observe(secs(3)), None);
+        // 向前跳转超过 3 秒也结束上一段
+        assert_eq!(tracker.observe(secs(7)), Some((secs(2), secs(3))));
+        assert_eq!(tracker.finish(), Some((secs(7), secs(7))));
+        assert_eq!(tracker.current(), None);
+        assert_eq!(tracker.finish(), None);
+    }
+}
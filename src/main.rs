@@ -4,39 +4,132 @@
 mod cli;
 mod utils;
 mod metadata;
+mod ui;
+mod buffer;
+mod volume;
+mod store;
+mod abloop;
+mod failure;
+mod stream;
+mod history;
+mod backend;
+mod queue;
+mod shuffle;
+mod timing;
+mod multiplexer;
+mod output;
+mod filter;
+mod buildinfo;
+mod outcome;
+mod toast;
+mod theme;
+mod skip;
+mod doctor;
+mod confirm;
+mod ctl;
+mod decoder;
+mod session;
+mod gain;
+mod duplicates;
+mod alarm;
+mod preload;
+mod focus;
+mod listened;
+mod health;
+#[cfg(test)]
+mod test_support;
 
 // 从各个模块引入所需的项
-use clap::Parser;
-use rodio::{Decoder, OutputStream, Sink};
+use rodio::Source;
 use std::time::{Instant, Duration};
-use std::{fs::File, io::{self, BufReader, Write}};
-use std::sync::mpsc::{channel, Sender, Receiver};
-use std::path::PathBuf;
+use std::{fs::File, io::{self, BufReader, IsTerminal, Write}};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::thread;
 
-use rand::seq::SliceRandom;
-use unicode_width::UnicodeWidthStr;
-
 // 从 cli 模块引入常量和参数结构体
-use cli::{Args, NAME, VERSION, URL};
+use cli::{Args, Command, NAME, VERSION};
 // 从 utils 模块引入所有公共函数
-use utils::{get_playlist_from_input, truncate_string, format_duration};
+use utils::{allow_video_containers, set_cjk_width, is_video_container, NO_AUDIO_STREAM_MESSAGE, expand_path, retry_transient, TRANSIENT_RETRY_DELAYS, format_position, sanitize_display, UNDECODABLE_MESSAGE, get_playlist_from_input, mark_cloud_placeholders, write_playlist_file, ScanOptions, FileStamp, Track};
 // 从 metadata 模块引入元数据获取函数
-use metadata::{get_title_artist_info, get_total_duration};
+use metadata::{write_title_artist, get_title_artist_info, read_track_metadata, FilenamePattern, Template};
+// 从 ui 模块引入界面布局与绘制函数
+use ui::{choose_screen_target, poll_event, read_event, set_key_input, AdvanceChoice, Layout, Screen, ScreenTarget, StatusView, TerminalTitle, TitleFields, prompt_line, select_item, show_splash, update_progress_display, wait_for_advance, wait_for_alarm, wait_for_replay, wait_for_wake};
+// 从 volume 模块引入音量键加速器
+use volume::{effective_volume, format_volume_db, scale_ui_volume, set_volume_curve, ui_volume_to_gain, Duck, FadeIn, VolumeAccelerator, TRACK_GAIN_MAX_DB, TRACK_GAIN_MIN_DB, TRACK_GAIN_STEP_DB};
+// 从 store 模块引入单曲增益存储
+use store::{load_recent_inputs, load_ui_mode, remember_input, save_ui_mode, ResumeRecorder, ResumeState, IntroTrimStore, ListenedStore, PlayedStore, TrackGainStore};
+// 长音频听过的区间
+use listened::LONG_TRACK_MIN;
+// 从 abloop 模块引入 A-B 循环状态
+use abloop::AbLoop;
+// 单曲失败处理策略
+use failure::{ask_continue_after_failures, fast_skip_failure, handle_failure, ErrorPolicy, FailureAction, FailureRun, MaxErrorsPolicy, TrackFailure};
+// 流式随机播放
+use stream::RandomWalker;
+// 最近播放记录
+use history::RecentPlays;
+// 单曲结束结果（听过/跳过/失败）
+use outcome::{ended_early, PlaybackOutcome};
+use confirm::{skip_needs_confirm, DoublePress, QuitConfirm, QuitDecision, QUIT_CONFIRM_MESSAGE};
+use ctl::{CtlCommand, CtlServer, PlayerStatus};
+// 按扩展名强制指定解码器
+use decoder::{BoxedSource, DecoderLog, DecoderOverrides};
+// 会话状态变化记录
+use session::{SessionLog, Transition};
+// 预加载请求与结果
+use preload::{PreloadSlots, PreloadTicket};
+// 终端焦点事件与暂停原因
+use focus::{disable_focus_reporting, enable_focus_reporting, PauseReason};
+// 按键反馈提示
+use toast::{NoOp, Toast};
+// 切歌按键合并
+use skip::SkipCoalescer;
+// 界面配色
+use theme::{Theme, ThemeName};
+// 播放输出后端（真实声卡或模拟输出）
+use backend::{SimulatedSink, SinkBackend};
+// 列表剩余时间估算
+use queue::{next_after_resize, remove_indexes, skip_index, step_index, upcoming_indexes, CompletionPolicy, Direction, DurationTable, QueueEdit, UndoStack};
+// 后台检查后续歌曲是否仍然存在
+use health::HealthChecker;
+// 随机打乱
+use shuffle::{shuffle_with_seed, spread_by_key, ShuffleMode};
+// 预加载计时诊断
+use timing::{PreloadTiming, TimingLog};
+// tmux/screen 窗口名
+use multiplexer::WindowTitle;
+// 艺术家/专辑筛选
+use filter::TrackFilter;
+// 音频输出设备（可指定缓冲延迟）
+use output::{open_output_on, output_device_names, print_devices, AudioOutput, OutputRequest, PauseTimeoutAction};
+// 从 buffer 模块引入前导缓冲音频源
+use buffer::LeadBufferedSource;
 
 // 终端交互库：用于控制终端（raw mode, 键入事件, 光标/清屏）
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
-    terminal::{self, disable_raw_mode, enable_raw_mode, ClearType, SetTitle, SetSize},
+    terminal::{self, disable_raw_mode, enable_raw_mode, ClearType, SetSize},
     cursor,
 };
 
 // --- 常量定义 ---
 const MIN_SKIP_INTERVAL: Duration = Duration::from_millis(250); // 最小切歌间隔
-const VOLUME_STEP: f32 = 0.01; // 音量调节步长
 const UPDATE_INTERVAL: Duration = Duration::from_millis(1000); // 进度更新频率
+/// 未提供输入路径时使用的默认音乐目录环境变量
+const MUSIC_DIR_ENV: &str = "MDDPLAYER_MUSIC_DIR";
 const ERROR_WAIT_DURATION: Duration = Duration::from_secs(1);
+const PRELOAD_TIMEOUT: Duration = Duration::from_secs(5); // 等待预加载结果的最长时间；暂时性错误退避重试期间按退避时长顺延
+const CLOUD_PRELOAD_TIMEOUT: Duration = Duration::from_secs(60); // 云端占位文件需要先下载，等待更久
+const LOW_VOLUME_THRESHOLD: f32 = 0.05; // 启动音量低于该值时视为静音并提示
+const VOLUME_FLASH_DURATION: Duration = Duration::from_millis(1500); // 调节音量后音量条高亮显示的时长
+const TOAST_DURATION: Duration = Duration::from_millis(1500); // 按键反馈提示的显示时长
+const INTRO_TRIM_WINDOW: Duration = Duration::from_secs(30); // 只有在歌曲开头这段时间内才能记录片头跳过
+const MIN_INTRO_TRIM: Duration = Duration::from_secs(1); // 短于该时长的片头跳过视为清除
+const LEAD_BUFFER_DURATION: Duration = Duration::from_millis(500); // 首曲起播前的前导缓冲时长
+const LOW_MEMORY_READ_BUFFER: usize = 2 * 1024; // --low-memory 时读取文件的缓冲大小（默认为 8 KiB）
+const SKIP_CONFIRM_WINDOW: Duration = Duration::from_secs(1); // 切歌确认：第二次按键须在该时间内
 
 // ===============================================
 // 异步预加载数据结构
@@ -44,153 +137,377 @@ const ERROR_WAIT_DURATION: Duration = Duration::from_secs(1);
 
 // 定义用于线程间发送成功加载结果的数据结构
 struct PreloadedData {
-    decoder: rodio::Decoder<std::io::BufReader<std::fs::File>>,
+    decoder: Box<dyn Source<Item = i16> + Send>,
     title: String,
     artist: String,
     total_duration: Duration,
+    channels: u16, // 解码器输出的声道数
+    timing: PreloadTiming,
+    stamp: Option<FileStamp>, // 打开文件时的修改时间与大小（起播前据此发现被替换的文件）
+    codec: Option<String>, // verbose 模式下记录的解码器说明
 }
 
-// 定义用于线程间发送预加载结果的消息
+// 预加载线程交付的结果（经 PreloadSlots 按请求对应到播放列表索引）
 enum PreloadResult {
-    Success(PreloadedData, usize), // (数据, 预加载的歌曲在播放列表中的索引)
-    Failure(usize, String, String), // (索引, 错误信息类型, 文件名)
+    Success(Box<PreloadedData>),
+    Failure(String, String), // (错误信息类型, 文件名)
+}
+
+// 预加载线程所需的配置（随每个预加载线程一起克隆）
+#[derive(Clone)]
+struct PreloadOptions {
+    filename_pattern: FilenamePattern, // 标签缺失时解析文件名的模式
+    decoder_overrides: DecoderOverrides, // --decoder-override 指定的解码器
+    verbose: bool,
+    low_memory: bool, // --low-memory：不使用前导缓冲，读取缓冲更小
 }
 
 // 统一的退出清理逻辑，确保终端恢复正常
-fn graceful_exit(stdout: &mut io::Stdout) -> Result<(), Box<dyn std::error::Error>> {
+fn graceful_exit(stdout: &mut Screen, layout: &Layout) -> Result<(), Box<dyn std::error::Error>> {
     // 尝试清空可视区域并移到 (0, 0) (类似 cls 的效果)
     // execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::All))?;
-    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
-    println!("👋 播放器退出。");
+    layout.clear_status(stdout)?;
+    if layout.mini {
+        // 只有一两行时换行会把状态行滚走，告别语留在原行
+        write!(stdout, "👋 播放器退出。")?;
+        stdout.flush()?;
+    } else {
+        writeln!(stdout, "👋 播放器退出。")?;
+    }
+    disable_focus_reporting(stdout)?;
     disable_raw_mode()?;
     execute!(stdout, cursor::Show)?;
     Ok(())
 }
 
+// 读取歌曲的标题、艺术家与总时长，并返回读取耗时
+fn read_track_info(path: &Path, opts: &PreloadOptions, extend: impl Fn(Duration)) -> (String, String, Duration, Duration) {
+    let started = Instant::now();
+    // 网络共享短暂断开时先等待文件可访问，避免标签与时长读取失败后被当作未知；重试期间顺延主循环的等待
+    let _ = retry_transient(&TRANSIENT_RETRY_DELAYS, |delay| extend(delay + PRELOAD_TIMEOUT), || std::fs::metadata(path));
+    let meta = read_track_metadata(path, &opts.filename_pattern);
+    (meta.title, meta.artist, meta.duration, started.elapsed())
+}
+
+// 判断该格式是否值得使用前导缓冲（WAV 为未压缩 PCM，无需增量解码）
+fn supports_lead_buffer(path: &Path) -> bool {
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    ext != "wav"
+}
+
 // 在后台线程启动下一首歌曲的预加载。
+// lead_buffer 为 true 时优先缩短起播时间：元数据与解码并行，只缓冲一小段即交付播放。
 fn start_preloader_thread(
-    path: PathBuf,
-    index: usize,
-    tx: Sender<PreloadResult>,
+    track: Track,
+    ticket: PreloadTicket<PreloadResult>,
+    opts: PreloadOptions,
+    lead_buffer: bool,
 ) {
+    let path = track.path.clone();
     let filename_display = path.file_name().map_or_else(
         || path.as_os_str().to_string_lossy().into_owned(),
         |os_str| os_str.to_string_lossy().into_owned(),
     );
 
     thread::spawn(move || {
-        let (title, artist) = get_title_artist_info(path.as_path());
-        let total_duration = get_total_duration(path.as_path());
+        let lead_buffer = lead_buffer && !opts.low_memory && supports_lead_buffer(&path);
+        // 前导缓冲模式下，元数据在独立线程中读取，不阻塞解码；否则按原顺序先读元数据
+        let meta_path = path.clone();
+        let meta_opts = opts.clone();
+        let meta_handle = lead_buffer.then(|| {
+            let extend = ticket.extender();
+            thread::spawn(move || read_track_info(&meta_path, &meta_opts, extend))
+        });
+        let meta = if lead_buffer { None } else { Some(read_track_info(&path, &opts, ticket.extender())) };
 
-        let file = match File::open(&path) {
-            Ok(f) => BufReader::new(f),
+        let open_started = Instant::now();
+        // 网络共享短暂断开时按退避间隔重试（期间顺延主循环的等待）；文件不存在等错误立即失败
+        let extend = ticket.extender();
+        let (file, stamp) = match retry_transient(&TRANSIENT_RETRY_DELAYS, |delay| extend(delay + PRELOAD_TIMEOUT), || File::open(&path)) {
+            Ok(f) => {
+                let stamp = f.metadata().ok().map(|meta| FileStamp::from_metadata(&meta));
+                let reader = if opts.low_memory { BufReader::with_capacity(LOW_MEMORY_READ_BUFFER, f) } else { BufReader::new(f) };
+                (reader, stamp)
+            }
             Err(_e) => {
-                if tx.send(PreloadResult::Failure(index, "无法打开或读取".to_string(), filename_display)).is_err() {}
+                ticket.fulfill(PreloadResult::Failure("无法打开或读取".to_string(), filename_display));
                 return;
             }
         };
-        let decoder = match Decoder::new(file) {
+        let open_elapsed = open_started.elapsed();
+        let decoder_started = Instant::now();
+        let codec = opts.verbose.then(|| opts.decoder_overrides.describe(&path));
+        let decoder = match opts.decoder_overrides.open(&path, file) {
             Ok(d) => d,
             Err(_e) => {
-                if tx.send(PreloadResult::Failure(index, "解码失败".to_string(), filename_display)).is_err() {}
+                // 视频容器与扩展名未知、按内容探测的文件给出更明确的提示
+                let is_video = path.extension().and_then(|s| s.to_str()).is_some_and(is_video_container);
+                let err_type = if is_video {
+                    NO_AUDIO_STREAM_MESSAGE
+                } else if track.probed_format.is_some() {
+                    UNDECODABLE_MESSAGE
+                } else {
+                    "解码失败"
+                };
+                ticket.fulfill(PreloadResult::Failure(err_type.to_string(), filename_display));
                 return;
             }
         };
 
-        let data = PreloadedData{decoder, title, artist, total_duration};
-        if tx.send(PreloadResult::Success(data, index)).is_err() {
-            // 主线程已退出，忽略发送失败
+        let source = chapter_source(decoder, &track);
+        let start = track.start.unwrap_or_default();
+
+        let decoder: Box<dyn Source<Item = i16> + Send> = if lead_buffer {
+            Box::new(LeadBufferedSource::start(source, LEAD_BUFFER_DURATION))
+        } else {
+            source
+        };
+        let decoder_elapsed = decoder_started.elapsed();
+        let channels = decoder.channels();
+        let unknown = || ("未知".to_string(), "未知".to_string(), Duration::from_secs(0), Duration::ZERO);
+        let (title, artist, file_duration, metadata_elapsed) = match meta_handle {
+            Some(handle) => handle.join().unwrap_or_else(|_| unknown()),
+            None => meta.unwrap_or_else(unknown),
+        };
+        // 章节的时长为区间长度；文件时长未知且章节无终点时仍为 0（未知）
+        let total_duration = match track.end {
+            Some(end) => end.saturating_sub(start),
+            None => file_duration.saturating_sub(start),
+        };
+
+        let timing = PreloadTiming {
+            metadata: metadata_elapsed,
+            open: open_elapsed,
+            decoder: decoder_elapsed,
+            completed_at: Instant::now(),
+        };
+        let data = PreloadedData{decoder, title, artist, total_duration, channels, timing, stamp, codec};
+        ticket.fulfill(PreloadResult::Success(Box::new(data)));
+    });
+}
+
+// 章节区间：跳转到起点（不支持跳转的格式逐样丢弃），并在终点截断
+fn chapter_source(decoder: BoxedSource, track: &Track) -> Box<dyn Source<Item = i16> + Send> {
+    let mut source = decoder;
+    let start = track.start.unwrap_or_default();
+    if !start.is_zero() && source.try_seek(start).is_err() {
+        source = Box::new(source.skip_duration(start));
+    }
+    if let Some(end) = track.end {
+        source = Box::new(source.take_duration(end.saturating_sub(start)));
+    }
+    source
+}
+
+// 音频输出流（模拟模式下为 None，播放期间必须保持存活）与 Sink
+type OpenedSink = (Option<AudioOutput>, Box<dyn SinkBackend>);
+
+// 恢复播放时的回退位置：暂停超过 `threshold` 时回退 `rewind`（不早于开头）；不需要回退时返回 None
+fn rewind_target(paused_for: Duration, position: Duration, rewind: Duration, threshold: Duration) -> Option<Duration> {
+    (!rewind.is_zero() && paused_for >= threshold && !position.is_zero()).then(|| position.saturating_sub(rewind))
+}
+
+// 命令行中的输出配置请求（缓冲延迟与采样率）
+fn output_request(args: &Args) -> OutputRequest {
+    OutputRequest { latency_ms: args.latency, sample_rate: args.sample_rate }
+}
+
+// 打开音频输出并创建 Sink（模拟模式下不打开声卡，按虚拟时钟推进）；`device` 为 None 时使用默认设备
+fn open_sink(args: &Args, device: Option<&str>) -> Result<OpenedSink, Box<dyn std::error::Error>> {
+    Ok(match args.simulate {
+        Some(speedup) => (None, Box::new(SimulatedSink::new(speedup))),
+        None => {
+            let (stream, sink) = open_output_on(device, output_request(args), args.verbose)?;
+            (Some(stream), Box::new(sink))
+        }
+    })
+}
+
+// 重新打开音频设备（暂停超时后唤醒、F8 切换设备）：重建 Sink，恢复音量，重新解码当前歌曲并跳回原位置。
+// 返回的 bool 表示是否跳回成功（不支持跳转的格式只能从头播放）
+fn reacquire_output(
+    args: &Args,
+    device: Option<&str>,
+    track: &Track,
+    overrides: &DecoderOverrides,
+    volume: f32,
+    pos: Duration,
+) -> Result<(OpenedSink, bool), Box<dyn std::error::Error>> {
+    let (stream, sink) = open_sink(args, device)?;
+    let decoder = overrides.open(&track.path, BufReader::new(File::open(&track.path)?))?;
+    sink.set_volume(volume);
+    sink.append(chapter_source(decoder, track));
+    let seeked = pos.is_zero() || sink.try_seek(pos).is_ok();
+    Ok(((stream, sink), seeked))
+}
+
+// 终端标题中的歌曲信息（不含静音/暂停前缀）
+fn track_caption(title: &str, artist: &str) -> String {
+    if artist.is_empty() {
+        format!("{}-{}v{}", title, NAME, VERSION)
+    } else {
+        format!("{}-{}-{}v{}", title, artist, NAME, VERSION)
+    }
+}
+
+// --set-tmux-title：按模板同步 tmux/screen 窗口名
+fn sync_window_title(window_title: Option<&WindowTitle>, title: &str, artist: &str, index: usize, total: usize) {
+    if let Some(window_title) = window_title {
+        window_title.update(|name| match name {
+            "title" => Some(title.to_string()),
+            "artist" => Some(artist.to_string()),
+            "index" => Some((index + 1).to_string()),
+            "total" => Some(total.to_string()),
+            _ => None,
+        });
+    }
+}
+
+// 写回标签：先清空 Sink 释放解码器占用的文件（Windows 下被占用的文件无法写入），
+// 写入后重新打开当前歌曲并跳回原位置、恢复原来的播放/暂停状态
+fn rewrite_tags(sink: &dyn SinkBackend, track: &Track, overrides: &DecoderOverrides, title: &str, artist: &str) -> Result<(), String> {
+    let pos = sink.get_pos();
+    let paused = sink.is_paused();
+    sink.clear();
+    let written = write_title_artist(&track.path, title, artist);
+    let reopened = File::open(&track.path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| overrides.open(&track.path, BufReader::new(file)).map_err(|e| e.to_string()));
+    match reopened {
+        Ok(decoder) => {
+            sink.append(chapter_source(decoder, track));
+            // 不支持跳转的格式只能从头播放
+            let _ = sink.try_seek(pos);
+            if !paused {
+                sink.play();
+            }
+        }
+        // 无法重新打开时 Sink 为空，播放循环按本首结束处理
+        Err(e) => return Err(format!("写入后无法重新打开: {}", e)),
+    }
+    written
+}
+
+// 把已完成、尚未轮到播放的预加载结果中的时长记入时长表（估算列表剩余时间）
+fn record_ready_durations(preloads: &PreloadSlots<PreloadResult>, durations: &mut DurationTable) {
+    preloads.for_each_ready(|index, result| {
+        if let PreloadResult::Success(data) = result {
+            durations.record(index, data.total_duration);
         }
     });
 }
 
 // 启动指定索引的预加载（如果索引有效）
 fn start_preload_if_valid(
-    playlist: &[PathBuf],
+    playlist: &[Track],
     index: usize,
-    tx: &Sender<PreloadResult>,
+    preloads: &PreloadSlots<PreloadResult>,
+    opts: &PreloadOptions,
 ) {
     if index < playlist.len() {
-        let path = playlist[index].clone();
-        start_preloader_thread(path, index, tx.clone());
+        start_preloader_thread(playlist[index].clone(), preloads.request(index), opts.clone(), false);
     }
 }
 
-// 显示错误信息并等待
-fn display_error_and_wait(
-    stdout: &mut io::Stdout,
-    current_index: usize,
-    total_tracks: usize,
-    err_type: &str,
-    filename: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
-    let track_info = format!("[{}/{}]", current_index + 1, total_tracks);
-    let error_msg_truncated = truncate_string(filename, 30);
-    eprint!("{} [错误:{}]: {} -> 跳过...", track_info, err_type, error_msg_truncated);
-    thread::sleep(ERROR_WAIT_DURATION);
-    execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
-    Ok(())
+// 流式随机播放：从曲库中继续抽取，直到播放列表至少有 len 首（曲库为空时提前停止）
+fn extend_stream(playlist: &mut Vec<Track>, walker: &mut RandomWalker, len: usize) {
+    let start = playlist.len();
+    while playlist.len() < len {
+        match walker.next_track() {
+            Some(path) => playlist.push(Track::from(path)),
+            None => break,
+        }
+    }
+    mark_cloud_placeholders(&mut playlist[start..]);
 }
 
-// 更新进度显示
-fn update_progress_display(
-    stdout: &mut io::Stdout,
-    current_index: usize,
-    total_tracks: usize,
-    is_random: bool,
-    is_loop: bool,
-    title: &str,
-    artist: &str,
-    track_path: &str,
-    current_time: Duration,
-    total_duration: Duration,
-    volume: f32,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let current_time_str = format_duration(current_time);
-    let total_duration_str = format_duration(total_duration);
-    let track_count_str = format!("[{}/{}]", current_index + 1, total_tracks);
-    let ext = track_path.split('.').last().unwrap_or("未知").to_uppercase();
-    let random_str = if is_random { "随" } else { "顺" };
-    let loop_str = if is_loop { "循" } else { "单" };
-    let play_mode_str = format!("{}|{}", random_str, loop_str);
-
-    let mut display_text_unpadded = format!(
-        "{}[{}][{}][][{}/{}][{:.0}%]",
-        track_count_str, play_mode_str, ext, current_time_str, total_duration_str, volume * 100.0
-    );
+// 扫描大目录时在同一行刷新已找到的数量（终端初始化时会被清屏覆盖）
+fn report_scan_progress(count: usize) {
+    eprint!("\r正在扫描… 已找到 {} 首", count);
+}
 
-    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
-    let current_unpadded_width = display_text_unpadded.as_str().width();
-    let music_info_width = terminal_width.saturating_sub(current_unpadded_width);
-    let music_info_content = format!("{}-{}", title, artist);
-    let music_info = if music_info_width < 15 {
-        truncate_string(title, music_info_width)
-    } else {
-        truncate_string(&music_info_content, music_info_width)
-    };
+// 扫描的文件数过多（如误把 / 当作音乐目录）时询问是否继续；在进入原始模式前调用。
+// stdin 不是终端时无法询问，按取消处理
+fn confirm_large_scan(threshold: usize) -> bool {
+    eprintln!();
+    if !io::stdin().is_terminal() {
+        eprintln!("[错误]已发现超过 {} 个文件，输入不是终端无法确认，已取消（可用 --yes 跳过确认）", threshold);
+        return false;
+    }
+    eprint!("已发现超过 {} 个文件，继续？[y/N] ", threshold);
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes" | "YES")
+}
 
-    display_text_unpadded = format!(
-        "{}[{}][{}][{}][{}/{}][{:.0}%]",
-        track_count_str, play_mode_str, ext, music_info, current_time_str, total_duration_str, volume * 100.0
-    );
+// 不带参数启动且有上次的播放位置时询问是否继续（在进入原始模式前调用）；
+// 指定 --resume 或 stdin 不是终端（无法询问）时直接继续
+fn confirm_resume(state: &ResumeState, resume: bool) -> bool {
+    if resume || !io::stdin().is_terminal() {
+        return true;
+    }
+    let track = state.track.file_name().map_or_else(|| state.track.to_string_lossy(), |name| name.to_string_lossy());
+    eprint!("继续上次播放? {}（{} {}）[Y/n] ", state.input, track, format_position(state.position));
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    !matches!(answer.trim(), "n" | "N" | "no" | "NO")
+}
 
-    let new_len = display_text_unpadded.as_str().width();
-    let padding_needed = terminal_width.saturating_sub(new_len);
-    let padding = " ".repeat(padding_needed);
-    let display_text = format!("{}{}", display_text_unpadded, padding);
+// 按 --shuffle/--shuffle-seed 打乱歌曲
+fn shuffle_playlist(tracks: &mut [Track], args: &Args, opts: &PreloadOptions) {
+    shuffle_with_seed(tracks, args.shuffle_seed);
+    if args.shuffle == ShuffleMode::Smart {
+        spread_artists(tracks, opts);
+    }
+}
 
-    execute!(stdout, cursor::MoveToColumn(0))?;
-    print!("{}", display_text);
-    stdout.flush()?;
-    Ok(())
+// 筛选时在同一行刷新读取标签的进度（终端初始化时会被清屏覆盖）
+fn report_duplicate_progress(read: usize, total: usize) {
+    eprint!("\r正在读取标签查找重复… {}/{}", read, total);
 }
 
-// 调整音量
-fn adjust_volume(sink: &Sink, delta: f32) {
-    let current_volume = sink.volume();
-    let new_volume = (current_volume + delta).clamp(0.0, 1.0);
-    sink.set_volume(new_volume);
+fn report_filter_progress(checked: usize, total: usize) {
+    eprint!("\r正在读取标签筛选… {}/{}", checked, total);
+}
+
+// 智能随机：拉开同一艺术家（无艺术家信息时为同一目录）歌曲的间隔
+fn spread_artists(tracks: &mut [Track], opts: &PreloadOptions) {
+    let keys: Vec<String> = tracks
+        .iter()
+        .map(|track| {
+            let (_, artist) = get_title_artist_info(&track.path, &opts.filename_pattern);
+            if artist.is_empty() {
+                let parent = track.path.parent().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                format!("dir:{}", parent)
+            } else {
+                format!("artist:{}", artist.to_lowercase())
+            }
+        })
+        .collect();
+    let order = spread_by_key(&keys);
+    let spread: Vec<Track> = order.iter().map(|&i| tracks[i].clone()).collect();
+    tracks.clone_from_slice(&spread);
+}
+
+// 把单曲结束结果分发给关心它的功能（目前为最近播放记录）
+fn report_outcome(stdout: &mut Screen, layout: &Layout, recent: &mut RecentPlays, outcome: &PlaybackOutcome) -> io::Result<()> {
+    recent.finish(outcome);
+    layout.draw_recent(stdout, recent)
+}
+
+// 临时压低与启动渐强叠加后的音量倍数
+fn ramp_factor(duck: &Duck, fade_in: &FadeIn, now: Instant) -> f32 {
+    duck.factor(now) * fade_in.factor(now)
+}
+
+// 调整主音量，并按单曲增益与临时压低倍数写入 Sink
+fn adjust_volume(sink: &dyn SinkBackend, master_volume: &mut f32, gain_db: f32, duck: f32, delta: f32) {
+    *master_volume = (*master_volume + delta).clamp(0.0, 1.0);
+    sink.set_volume(effective_volume(*master_volume, gain_db, duck));
 }
 
 
@@ -198,163 +515,528 @@ fn adjust_volume(sink: &Sink, delta: f32) {
 // MAIN 函数
 // ===============================================
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-
-    // 如果没有提供文件参数，显示帮助信息
-    let input_path_str = match &args.file {
-        Some(path) => path,
-        None => {
-            Args::parse_from(&["mddplayer", "--help"]);
-            return Ok(());
+fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let (args, matches) = cli::parse_args();
+    match &args.command {
+        Some(Command::Doctor) => {
+            let passed = doctor::run_doctor(output_request(&args));
+            std::process::exit(if passed { 0 } else { 1 });
         }
+        Some(Command::Status { format }) => {
+            let template = match format.as_deref().map(Template::parse).transpose() {
+                Ok(template) => template,
+                Err(e) => {
+                    eprintln!("[错误]{}", e);
+                    std::process::exit(2);
+                }
+            };
+            std::process::exit(if ctl::print_status(template.as_ref()) { 0 } else { 1 });
+        }
+        Some(Command::Duck { state }) => {
+            std::process::exit(if ctl::send_duck(state == "on") { 0 } else { 1 });
+        }
+        Some(Command::ResetProgress { path }) => {
+            let path = expand_path(path);
+            match PlayedStore::reset(Path::new(&path)) {
+                Ok(0) => println!("'{}' 没有已听完的记录", path),
+                Ok(removed) => println!("已清除 '{}' 的 {} 条已听完记录", path, removed),
+                Err(e) => {
+                    eprintln!("[错误]无法清除记录: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Gain { path, no_write, force }) => {
+            let opts = gain::GainOptions { no_write: *no_write, force: *force };
+            let passed = gain::run_gain(Path::new(&expand_path(path)), &opts);
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        None => {}
+    }
+    if args.build_info {
+        buildinfo::print_build_info(args.json, output_request(&args));
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.list_devices {
+        if let Err(e) = print_devices(args.verbose) {
+            eprintln!("[错误]无法列出音频设备: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.print_config {
+        cli::print_config(&matches, MUSIC_DIR_ENV);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // 没有提供文件参数时，先询问是否继续上次的播放，其次使用环境变量指定的默认音乐目录（命令行参数始终优先）
+    let resume_state = if args.file.is_none() { ResumeState::load().filter(|state| confirm_resume(state, args.resume)) } else { None };
+    let input_path_str = match (&args.file, &resume_state) {
+        (Some(path), _) => path.clone(),
+        (None, Some(state)) => state.input.clone(),
+        (None, None) => match std::env::var(MUSIC_DIR_ENV) {
+            Ok(dir) if !dir.trim().is_empty() => expand_path(dir.trim()),
+            _ => {
+                eprintln!("[错误]未指定要播放的文件或目录。");
+                eprintln!("请在命令行中指定路径，或设置环境变量 {} 作为默认音乐目录，例如：", MUSIC_DIR_ENV);
+                eprintln!("  export {}=~/Music", MUSIC_DIR_ENV);
+                let recent_inputs = load_recent_inputs();
+                if !recent_inputs.is_empty() {
+                    eprintln!("最近播放过：");
+                    for input in recent_inputs {
+                        eprintln!("  mddplayer {}", input);
+                    }
+                }
+                eprintln!("使用 --help 查看全部参数。");
+                return Ok(ExitCode::SUCCESS);
+            }
+        },
     };
+    let input_path_str = input_path_str.as_str();
 
-    let is_simple_mode = args.clean;
+    // 界面画在是终端的那个输出上；标准输出与标准错误都不是终端时退化为纯净模式。
+    // 标准输入不是终端时不读取按键，需要按键才能继续的策略改为不需要按键的
+    let setup = choose_screen_target(io::stdin().is_terminal(), io::stdout().is_terminal(), io::stderr().is_terminal());
+    let screen_target = setup.target;
+    let plain = screen_target == ScreenTarget::Plain;
+    set_key_input(setup.keys);
+    let on_complete = if setup.keys { args.on_complete } else { CompletionPolicy::Exit };
+    let on_max_errors = if setup.keys { args.on_max_errors } else { MaxErrorsPolicy::Stop };
+    // --remember-modes：未指定 -s 时沿用上次 F2 切换后的界面模式
+    let is_simple_mode = plain || args.clean || (args.remember_modes && load_ui_mode().unwrap_or(false));
+    // 配色：只指定 --theme-color 时以深色主题为基础
+    let theme = match args.theme.or((!args.theme_color.is_empty()).then_some(ThemeName::Dark)) {
+        Some(name) => match Theme::load(name, &args.theme_color) {
+            Ok(theme) => Some(theme),
+            Err(e) => {
+                eprintln!("[错误]{}", e);
+                return Ok(ExitCode::SUCCESS);
+            }
+        },
+        None => None,
+    };
+    let mut layout = Layout { clean: is_simple_mode, keep_banner: args.keep_banner, theme, mini: false, dimmed: false };
+    if let Ok((_, rows)) = terminal::size() {
+        layout.fit_height(rows);
+    }
     let is_random_enabled = args.random;
     let is_loop_enabled = args.is_loop;
-    let initial_volume = args.volume as f32 / 100.0;
-
-    let mut playlist = match get_playlist_from_input(input_path_str) {
+    let on_error = match args.on_error {
+        ErrorPolicy::Ask if !setup.keys => ErrorPolicy::Wait,
+        policy => policy,
+    };
+    let mut initial_volume = args.volume as f32 / 100.0;
+    let filename_pattern = match FilenamePattern::parse(&args.filename_pattern) {
+        Ok(p) if args.keep_track_number => p.keep_track_number(),
         Ok(p) => p,
-        Err(_e) => {
-            eprintln!("[错误]处理输入路径 '{}' 时失败", input_path_str);
-            return Ok(());
+        Err(e) => {
+            eprintln!("[错误]{}", e);
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    let decoder_overrides = match DecoderOverrides::parse(&args.decoder_override) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("[错误]--decoder-override: {}", e);
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    let preload_opts = PreloadOptions { filename_pattern, decoder_overrides, verbose: args.verbose, low_memory: args.low_memory };
+    let title_format = match args.title_format.as_deref().map(Template::parse).transpose() {
+        Ok(template) => template,
+        Err(e) => {
+            eprintln!("[错误]--title-format: {}", e);
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    let window_title = match args.set_tmux_title.then(|| Template::parse(&args.tmux_title_format)).transpose() {
+        Ok(template) => template.map(|t| WindowTitle::new(t, args.verbose)),
+        Err(e) => {
+            eprintln!("[错误]{}", e);
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+
+    if args.allow_video_containers {
+        allow_video_containers();
+    }
+    set_cjk_width(args.cjk_width);
+    set_volume_curve(args.volume_curve);
+    let scan_opts = ScanOptions {
+        recursive: args.recursive,
+        verbose: args.verbose,
+        no_cue_split: args.no_cue_split,
+        quiet: false,
+        progress: Some(report_scan_progress),
+        limit: args.limit.map(|n| n as usize),
+        large_threshold: args.scan_warn,
+        confirm_large: (!args.yes).then_some(confirm_large_scan as fn(usize) -> bool),
+        expand_playlists: args.expand_playlists,
+    };
+    // 流式随机播放只对目录输入生效，其余输入仍按常规方式解析
+    let mut walker = (args.random_stream && Path::new(input_path_str).is_dir())
+        .then(|| RandomWalker::new(Path::new(input_path_str), args.verbose));
+    let mut playlist = match walker.as_mut() {
+        Some(w) => {
+            let mut p = Vec::new();
+            extend_stream(&mut p, w, 1);
+            p
         }
+        None => match get_playlist_from_input(input_path_str, &scan_opts) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("[错误]处理输入路径 '{}' 时失败: {}", input_path_str, e);
+                return Ok(ExitCode::SUCCESS);
+            }
+        },
     };
 
     if playlist.is_empty() {
         eprintln!("[错误]在指定的路径中未找到支持的音频文件。");
-        return Ok(());
+        return Ok(ExitCode::SUCCESS);
+    }
+    mark_cloud_placeholders(&mut playlist);
+    // 播放列表文件、内嵌 CUE 拆分等来源不经过目录扫描的上限，这里统一截断
+    if let Some(limit) = scan_opts.limit {
+        playlist.truncate(limit);
+    }
+    // 截取区间：在随机打乱之前，状态行的序号相对于截取后的列表
+    if let Some(range) = &args.range
+        && let Err(e) = range.slice(&mut playlist)
+    {
+        eprintln!("[错误]--range: {}", e);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // 按艺术家/专辑筛选（需要读取每首歌的标签）
+    let track_filter = TrackFilter::new(&args.artist, &args.album);
+    if !track_filter.is_empty() {
+        let scanned = playlist.len();
+        playlist = track_filter.apply(playlist, report_filter_progress);
+        eprintln!("\r筛选完成：{} 首中有 {} 首匹配", scanned, playlist.len());
+        if playlist.is_empty() {
+            eprintln!("[错误]没有符合 --artist/--album 条件的歌曲。");
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+
+    // 可能重复的歌曲：只报告，不改动播放列表
+    if let Some(report_path) = &args.report_duplicates {
+        let groups = duplicates::find_duplicates(&playlist, &preload_opts.filename_pattern, report_duplicate_progress);
+        let report = duplicates::format_report(&groups);
+        eprintln!("\r\x1b[K发现 {} 组可能重复的歌曲", groups.len());
+        eprint!("{}", report);
+        if !report_path.is_empty()
+            && let Err(e) = std::fs::write(expand_path(report_path), &report)
+        {
+            eprintln!("[错误]无法写入重复歌曲报告 '{}': {}", report_path, e);
+        }
+    }
+
+    // 跨会话不重复：去掉此前已听完的歌曲（在倒序/随机之前，开场曲不参与）
+    let mut played_store = args.no_repeat_across_sessions.then(|| PlayedStore::load(Path::new(input_path_str)));
+    let mut unheard = None; // (尚未听完的数量, 输入的总数)
+    if let Some(store) = &played_store {
+        let total = playlist.len();
+        playlist = store.exclude(playlist);
+        if playlist.is_empty() {
+            eprintln!("[错误]{} 首歌曲均已听完。使用 `mddplayer reset-progress <路径>` 清除记录后可重新播放。", total);
+            return Ok(ExitCode::SUCCESS);
+        }
+        unheard = Some((playlist.len(), total));
     }
 
-    if is_random_enabled {
-        let mut rng = rand::thread_rng();
-        playlist.shuffle(&mut rng);
+    // 开场曲：插入到列表最前面，与 --pin-first 一样固定在第一首
+    if let Some(intro) = &args.intro {
+        playlist.insert(0, Track::from(PathBuf::from(expand_path(intro))));
+    }
+    let pinned = args.intro.is_some() || args.pin_first;
+
+    // 倒序与随机都只作用于固定的第一首之后的部分
+    let start = usize::from(pinned).min(playlist.len());
+    if args.reverse && walker.is_none() {
+        playlist[start..].reverse();
+    }
+    if is_random_enabled && walker.is_none() {
+        shuffle_playlist(&mut playlist[start..], &args, &preload_opts);
+    }
+    // 导出播放列表：按最终的播放顺序写出，附上当前生效的片头跳过，然后退出
+    if let Some(export_path) = &args.export_playlist {
+        let trim_store = IntroTrimStore::load(args.remember_trims);
+        let tracks: Vec<Track> = playlist
+            .iter()
+            .map(|track| match track.trim {
+                None if track.start.is_none() && track.end.is_none() => Track { trim: trim_store.get(&track.path), ..track.clone() },
+                _ => track.clone(),
+            })
+            .collect();
+        let export_path = expand_path(export_path);
+        match write_playlist_file(Path::new(&export_path), &tracks) {
+            Ok(()) => println!("已导出 {} 首到 '{}'", tracks.len(), export_path),
+            Err(e) => eprintln!("[错误]无法导出播放列表 '{}': {}", export_path, e),
+        }
+        return Ok(ExitCode::SUCCESS);
     }
+    // 循环回到开头时的起始索引：默认只在首轮播放固定的第一首
+    let loop_start = if pinned && !args.intro_every_loop && playlist.len() > 1 { 1 } else { 0 };
 
     // ----------------------------------------------------
     // --- 核心播放逻辑：初始化 ---
     // ----------------------------------------------------
 
-    let mut stdout = io::stdout();
+    let mut stdout = Screen::new(screen_target);
 
-    // 终端初始化
-    execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-    if !is_simple_mode {
-        execute!(stdout, SetSize(60, 8))?;
-    } else {
-        execute!(stdout, SetSize(60, 1))?;
+    // 终端初始化（纯净模式下输出不是终端，不清屏、不调整尺寸）
+    if !plain {
+        execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        if !layout.mini {
+            execute!(stdout, SetSize(60, layout.total_rows()))?;
+        }
     }
+    let mut terminal_title = TerminalTitle::new(!args.no_title && !plain, title_format, Duration::from_secs(args.title_interval));
     let mut initial_title = format!("{} - v{}", cli::NAME, cli::VERSION);
-    execute!(stdout, SetTitle(initial_title.clone()))?;
-    enable_raw_mode()?;
+    terminal_title.update(&mut stdout, &initial_title, None, false, false)?;
+    if setup.keys {
+        enable_raw_mode()?;
+    }
     execute!(stdout, cursor::Hide)?;
+    if args.pause_on_unfocus || args.dim_on_unfocus {
+        enable_focus_reporting(&mut stdout)?;
+    }
 
-    // 初始化音频输出和 Sink
-    let (_stream, stream_handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&stream_handle)?;
-    // 初始设置音量
-    sink.set_volume(initial_volume.min(1.0).max(0.0));
+    let mut quit_confirm = QuitConfirm::new(args.confirm_quit); // 所有退出按键经此判断（--confirm-quit 时需按两次）
+    // 闹钟：到点之前不打开音频设备、不预加载；等待中按音量键预设开始播放的音量
+    if let Some(alarm) = args.alarm
+        && !wait_for_alarm(&mut stdout, &layout, alarm.resolve(chrono::Local::now()), &mut initial_volume, &mut quit_confirm)?
+    {
+        graceful_exit(&mut stdout, &layout)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // 初始化音频输出和 Sink（暂停超时释放设备后会重新打开）
+    let mut output_device: Option<String> = None; // F8 选择的输出设备，None 为默认设备
+    let (mut _stream, mut sink) = open_sink(&args, None)?;
+    let pause_timeout = args.pause_timeout.map(|minutes| Duration::from_secs(minutes * 60));
+    let mut health_checker = args
+        .check_playlist_health
+        .map(|minutes| HealthChecker::new(Duration::from_secs(minutes * 60), args.health_check_ahead as usize, Instant::now()));
+    // 初始设置音量（主音量，不含单曲增益）
+    let mut master_volume = initial_volume; // 范围已由命令行解析保证
+    sink.set_volume(ui_volume_to_gain(master_volume));
+    let mut gain_store = TrackGainStore::load();
+    let mut trim_store = IntroTrimStore::load(args.remember_trims);
+    let mut listened_store = ListenedStore::load();
 
     // 显示界面信息（非纯净模式下）
-    if !is_simple_mode {
-        println!("=====================【 {} 】======================", NAME);
-        println!(" 版本:v{}          主页:{}", VERSION, URL);
-        println!("===========================================================");
-        println!("====================【 控 制 说 明 】======================");
-        println!(" [P]静音/取消静音   [空格]暂停/播放    [Q/Ctrl+C]退出播放");
-        println!(" [←]上一首      [→]下一首    [↑]音量增    [↓]音量减");
-        println!("============================================================");
-    }
+    let mut recent = RecentPlays::new();
+    layout.redraw(&mut stdout, &recent)?;
 
     // --- 异步初始化和预加载设置 ---
-    let (tx, rx): (Sender<PreloadResult>, Receiver<PreloadResult>) = channel();
-    let total_tracks = playlist.len();
-    let mut current_track_index: usize = 0;
+    let preloads: PreloadSlots<PreloadResult> = PreloadSlots::new();
+    let mut total_tracks = playlist.len();
+    // 继续上次的播放：从上次的歌曲开始（列表中已没有该歌曲时从头开始），起播后跳到上次的位置
+    let resume_index = resume_state.as_ref().and_then(|state| state.track_index(&playlist));
+    let mut resume_position = resume_index.and(resume_state.as_ref().map(|state| state.position));
+    let mut current_track_index: usize = resume_index.unwrap_or(0);
+    // 记住本次输入（不带参数启动时列出），退出时保存播放位置
+    let _ = remember_input(input_path_str);
+    let mut resume_recorder = ResumeRecorder::new(input_path_str);
+
+    // 启动提示：第一首开始播放前，状态行显示列表概况与加载进度
+    let splash_head = if walker.is_some() { "流式随机".to_string() } else { format!("已找到 {} 首", total_tracks) };
+    show_splash(&mut stdout, &layout, &format!("{}，正在加载第一首…", splash_head))?;
+    let mut splash_pending = true;
 
-    // 🌟 启动第一首歌的预加载
-    start_preload_if_valid(&playlist, 0, &tx);
+    // 🌟 启动第一首歌的预加载（使用前导缓冲，尽快出声）
+    start_preloader_thread(playlist[current_track_index].clone(), preloads.request(current_track_index), preload_opts.clone(), true);
 
-    let mut index_offset: i32 = 0;
-    let mut last_skip_time = Instant::now() - MIN_SKIP_INTERVAL;
-    let mut muted_volume: Option<f32> = None; // 静音状态（移到外层循环，避免切歌时丢失）
+    let mut skip_target: Option<usize> = None; // 手动切歌的目标索引
+    let mut repeat_remaining: u32 = 0; // 当前歌曲还需额外重复播放的次数（数字+R 设置）
+    let mut skip_keys = SkipCoalescer::new(MIN_SKIP_INTERVAL, args.skip_accumulate); // 合并按住/连按方向键产生的切歌
+    let mut skip_confirm = DoublePress::new(SKIP_CONFIRM_WINDOW); // 长音频切歌需连按两次
+    let resume_rewind = Duration::from_secs(args.resume_rewind);
+    let resume_rewind_after = Duration::from_secs(args.resume_rewind_after);
+    let skip_confirm_length = (args.skip_confirm_length > 0).then(|| Duration::from_secs(args.skip_confirm_length * 60));
+    // 启动音量过低（多半是误用了 -v 0）：按静音处理并在状态行提示，直到第一次调节音量
+    let mut low_volume_notice = master_volume < LOW_VOLUME_THRESHOLD;
+    let start_percent = (master_volume * 100.0).round() as u8;
+    let low_volume_text = if start_percent == 0 { "音量为 0，按 ↑ 调高".to_string() } else { format!("音量仅 {}%，按 ↑ 调高", start_percent) };
+    let mut muted_volume: Option<f32> = low_volume_notice.then_some(master_volume); // 静音状态（移到外层循环，避免切歌时丢失）
+    let mut volume_accel = VolumeAccelerator::new(); // 音量键长按加速
+    let mut duck = Duck::new(); // 临时压低音量（H 键），不改变主音量
+    // 启动渐强：只在第一首开始时进行一次
+    let mut fade_in = FadeIn::new(Duration::try_from_secs_f32(args.fade_in_start).unwrap_or(Duration::ZERO));
+    let mut volume_changed_at: Option<Instant> = None; // 最近一次调节音量的时间（音量条高亮）
+    let mut toast = Toast::new(TOAST_DURATION, !args.no_bell); // 按键反馈提示（切歌后仍保留到期满）
+    let mut durations = DurationTable::new(total_tracks); // 已知的各曲目时长（随预加载填充）
+    let mut timing_log = TimingLog::new(args.timing, args.verbose, !io::stderr().is_terminal()); // --timing 诊断，汇总在退出时输出
+    let mut decoder_log = DecoderLog::new(args.verbose); // 每首使用的解码器，退出时输出
+    let mut session_log = SessionLog::new(args.verbose, args.dump_session.as_ref().map(PathBuf::from)); // 状态变化记录，退出时输出
+    let mut consecutive_failures: u32 = 0; // 连续加载失败的歌曲数，成功播放时清零
+    let mut failure_run = FailureRun::default(); // 连续的同类失败，超过阈值后快速跳过
+    let mut show_queue_remaining = false; // T 键切换：状态行显示整个列表的剩余时间
+    let mut manual_advance = args.manual_advance && setup.keys; // M 键切换：单曲播完后等待按 → 再继续
+    let mut direction = Direction::Forward; // D 键切换：→ 与自动切歌沿列表向前还是向后
+    let mut undo_stack = UndoStack::default(); // U 键撤销：本次运行内的队列修改
+    let ctl_server = CtlServer::start(); // 供 `mddplayer status` 查询（启动失败时不影响播放）
 
     // --- 主循环：迭代播放列表 ---
     'outer: loop {
         // 🌟 关键修正：在进入阻塞等待前，快速检查是否有 Ctrl+C/Q 按下
-        if event::poll(Duration::from_millis(0))? {
-            if let Event::Key(key_event) = event::read()? {
-                if key_event.code == KeyCode::Char('q') || key_event.code == KeyCode::Char('Q') || key_event.code == KeyCode::Char('c') {
-                    graceful_exit(&mut stdout)?;
-                    return Ok(());
+        if poll_event(Duration::from_millis(0))?
+            && let Event::Key(key_event) = read_event()?
+        {
+            match quit_confirm.press(&key_event, Instant::now()) {
+                QuitDecision::Quit => {
+                    graceful_exit(&mut stdout, &layout)?;
+                    return Ok(ExitCode::SUCCESS);
                 }
+                QuitDecision::AskAgain => show_splash(&mut stdout, &layout, QUIT_CONFIRM_MESSAGE)?,
+                QuitDecision::Ignore => {}
             }
         }
         
+        // 流式随机：保证当前与下一首都已抽取（历史记录保留在播放列表中，供上一首回退）
+        if let Some(w) = walker.as_mut() {
+            extend_stream(&mut playlist, w, current_track_index + 2);
+            total_tracks = playlist.len();
+        }
+
         // 循环播放检查 (如果当前索引超限，则尝试循环或退出)
         if current_track_index >= total_tracks {
             if is_loop_enabled {
-                current_track_index = 0;
-                start_preload_if_valid(&playlist, 0, &tx);
+                current_track_index = loop_start;
+                start_preload_if_valid(&playlist, loop_start, &preloads, &preload_opts);
+            } else if on_complete == CompletionPolicy::Wait && walker.is_none() && wait_for_replay(&mut stdout, &layout, &mut quit_confirm)? {
+                // 从头重播：随机模式重新打乱（开场曲/固定的第一首仍最先播放），按当前行进方向从头开始
+                if is_random_enabled {
+                    shuffle_playlist(&mut playlist[start..], &args, &preload_opts);
+                    durations = DurationTable::new(total_tracks);
+                }
+                current_track_index = match direction {
+                    Direction::Forward => 0,
+                    Direction::Backward => total_tracks - 1,
+                };
+                consecutive_failures = 0;
+                failure_run.reset();
+                start_preloader_thread(playlist[current_track_index].clone(), preloads.request(current_track_index), preload_opts.clone(), true);
             } else {
+                // 整个列表已播放完毕，下次没有可继续的位置
+                resume_recorder.finish();
                 break;
             }
         }
 
         // --- 5. 文件加载、解码、添加到 Sink (使用预加载结果) ---
-        let (preloaded_data, _preloaded_index) = loop {
-            // 尝试接收预加载结果，等待时间较长以确保有时间加载
-            match rx.recv_timeout(Duration::from_secs(5)) {
-                // ⚠️ 接收到成功结果
-                Ok(PreloadResult::Success(data, index)) => {
-                    if index == current_track_index {
-                        break (data, index);
-                    } else {
-                        continue;
-                    }
-                },
-                // ⚠️ 接收到失败结果
-                Ok(PreloadResult::Failure(index, err_type, filename)) => {
-                    if index == current_track_index {
-                        display_error_and_wait(&mut stdout, current_track_index, total_tracks, &err_type, &filename)?;
-                        current_track_index += 1;
-                        start_preload_if_valid(&playlist, current_track_index, &tx);
-                        continue 'outer;
-                    } else {
-                        continue;
+        if splash_pending {
+            show_splash(&mut stdout, &layout, &format!("{}，解码中…", splash_head))?;
+            splash_pending = false;
+        }
+        let wait_started = Instant::now(); // 开始等待当前歌曲的时刻（计算预加载提前量）
+        let is_cloud = playlist[current_track_index].cloud;
+        // 只等待当前歌曲最近一次预加载请求的结果；其他请求的结果不会占用等待时间
+        record_ready_durations(&preloads, &mut durations);
+        let received = match preloads.wait(current_track_index, if is_cloud { CLOUD_PRELOAD_TIMEOUT } else { PRELOAD_TIMEOUT }) {
+            Some(PreloadResult::Success(data)) => {
+                durations.record(current_track_index, data.total_duration);
+                Ok(data)
+            }
+            // 占位文件打不开或读不完多半是下载没能及时完成，而不是文件本身损坏
+            Some(PreloadResult::Failure(err_type, filename)) => Err((if is_cloud { "云端文件下载超时".to_string() } else { err_type }, filename)),
+            None => Err((if is_cloud { "云端文件下载超时" } else { "加载超时" }.to_string(), String::new())),
+        };
+        // 加载失败：按 --on-error 策略处理
+        let preloaded_data = match received {
+            Ok(data) => data,
+            Err((err_type, filename)) => {
+                recent.push_failed(if filename.is_empty() { &err_type } else { &filename });
+                report_outcome(&mut stdout, &layout, &mut recent, &PlaybackOutcome::Failed(err_type.clone()))?;
+                // 连续失败达到上限：停止并输出摘要，或询问是否继续
+                consecutive_failures += 1;
+                failure_run.record(&err_type);
+                if args.max_consecutive_errors > 0 && consecutive_failures >= args.max_consecutive_errors {
+                    match on_max_errors {
+                        MaxErrorsPolicy::Stop => {
+                            graceful_exit(&mut stdout, &layout)?;
+                            eprintln!(
+                                "[错误]已连续 {} 首加载失败，停止播放（最后一首: {} [{}]）",
+                                consecutive_failures, filename, err_type
+                            );
+                            // 返回而不是直接 exit：会话记录、计时汇总、播放位置等在 Drop 时保存
+                            return Ok(ExitCode::FAILURE);
+                        }
+                        MaxErrorsPolicy::Ask => {
+                            if !ask_continue_after_failures(&mut stdout, &layout, consecutive_failures, &mut quit_confirm)? {
+                                graceful_exit(&mut stdout, &layout)?;
+                                return Ok(ExitCode::SUCCESS);
+                            }
+                            consecutive_failures = 0;
+                        }
                     }
-                },
-                // 如果超时...
-                Err(e) if e == std::sync::mpsc::RecvTimeoutError::Timeout => {
-                    display_error_and_wait(&mut stdout, current_track_index, total_tracks, "加载超时", "")?;
-                    current_track_index += 1;
-                    start_preload_if_valid(&playlist, current_track_index, &tx);
-                    continue 'outer;
                 }
-                // 接收通道断开
-                Err(_) => {
-                    eprintln!("\n[致命错误] 预加载通道关闭，退出播放器...");
-                    break 'outer;
+                let failure = TrackFailure { current_index: direction.position(current_track_index, total_tracks), total_tracks, err_type: &err_type, filename: &filename };
+                // 同类错误连续出现多次（如整个文件夹都无法解码）：不再逐首等待，快速跳过
+                let action = if on_error == ErrorPolicy::Wait && failure_run.is_fast_skipping() {
+                    fast_skip_failure(&mut stdout, &layout, &failure_run, &mut quit_confirm)?
+                } else {
+                    handle_failure(&mut stdout, &layout, on_error, &failure, ERROR_WAIT_DURATION, &mut quit_confirm)?
+                };
+                match action {
+                    FailureAction::Skip => {
+                        // 到达列表末尾时由循环开头统一处理（循环、等待重播或退出）
+                        let from = current_track_index;
+                        current_track_index = step_index(current_track_index, total_tracks, direction, is_loop_enabled, loop_start)
+                            .unwrap_or(total_tracks);
+                        if current_track_index < total_tracks {
+                            session_log.record(Transition::Skip { from, to: current_track_index, trigger: "error" });
+                        }
+                        start_preload_if_valid(&playlist, current_track_index, &preloads, &preload_opts);
+                    }
+                    FailureAction::Retry => {
+                        start_preload_if_valid(&playlist, current_track_index, &preloads, &preload_opts);
+                    }
+                    FailureAction::Quit => {
+                        graceful_exit(&mut stdout, &layout)?;
+                        return Ok(ExitCode::SUCCESS);
+                    }
+                    FailureAction::Abort => {
+                        graceful_exit(&mut stdout, &layout)?;
+                        return Ok(ExitCode::FAILURE);
+                    }
                 }
+                continue 'outer;
             }
         };
+        // 预加载之后文件被原地替换（如重新编码）：丢弃旧的解码器，重新加载（失败时按常规流程处理）
+        if preloaded_data.stamp.is_some() && preloaded_data.stamp != FileStamp::read(&playlist[current_track_index].path) {
+            start_preload_if_valid(&playlist, current_track_index, &preloads, &preload_opts);
+            continue 'outer;
+        }
         // ... (歌曲预加载成功后的逻辑，与原代码一致)
-        let track_path_str = playlist[current_track_index].to_string_lossy().to_string();
+        consecutive_failures = 0;
+        failure_run.reset();
+        let track_path_str = playlist[current_track_index].path.to_string_lossy().to_string();
+        timing_log.record(&track_path_str, preloaded_data.timing, wait_started);
+        decoder_log.record(&track_path_str, preloaded_data.codec.as_deref());
+        session_log.record(Transition::TrackStarted { index: current_track_index, path: track_path_str.clone(), timing: preloaded_data.timing });
         sink.clear();
+        let channels = preloaded_data.channels;
         sink.append(preloaded_data.decoder);
+        let appended_at = Instant::now();
+        let mut audible_recorded = false;
         
         // -----------------------------------------------------------------
         // 🌟 BUG 修复：切歌后重新应用静音状态或恢复音量
         // -----------------------------------------------------------------
+        let mut track_gain_db = gain_store.get(&playlist[current_track_index].path);
+        fade_in.start(Instant::now());
         if let Some(_vol) = muted_volume {
             // 如果处于静音状态，保持静音（音量 0.0）
             sink.set_volume(0.0);
         } else {
-            // 如果不是静音状态，确保音量是上次退出内部循环时的音量（或初始音量）
-            // 注意：初始音量已经在 main 277 行设置过，这里可以不处理，
-            // 但为了健壮性（例如用户在切歌过程中调整了系统音量），可以重新应用。
-            // 保持当前 sink 的 volume 即可，因为 adjust_volume 已经更新了它
+            // 如果不是静音状态，按主音量叠加当前歌曲的单曲增益
+            sink.set_volume(effective_volume(master_volume, track_gain_db, ramp_factor(&duck, &fade_in, Instant::now())));
         }
         // -----------------------------------------------------------------
 
@@ -363,184 +1045,826 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             sink.play();
         }
 
-        let title = preloaded_data.title;
-        let artist = preloaded_data.artist;
+        // 播放列表中指定的显示标题优先于标签中的标题/艺术家
+        let (title, artist) = match &playlist[current_track_index].title {
+            Some(display_title) => (display_title.clone(), String::new()),
+            None => (preloaded_data.title, preloaded_data.artist),
+        };
+        // 标签中可能含有控制字符，显示前统一清理（也用于终端标题）
+        let (mut title, mut artist) = (sanitize_display(&title), sanitize_display(&artist));
         let total_duration = preloaded_data.total_duration;
-
-        let current_initial_title = format!("{}-{}-{}v{}", title, artist, NAME, VERSION); // 使用新的局部变量
-
-        // 根据静音状态设置标题
-        let display_title = if muted_volume.is_some() {
-            format!("[静音]{}", current_initial_title)
+        // 片头跳过：播放列表注释指定的时长优先，否则按所在目录记录的时长跳转（章节不适用；时长不超过该值的歌曲从头播放）
+        let track = &playlist[current_track_index];
+        let trim = if track.start.is_none() && track.end.is_none() {
+            track.trim.or_else(|| trim_store.get(&track.path)).filter(|&trim| total_duration > trim)
+        } else {
+            None
+        };
+        // 跳转失败（解码器不支持）时从头播放，不算作跳过了片头
+        let intro_trim = if let Some(trim) = trim
+            && sink.try_seek(trim).is_ok()
+        {
+            Some(trim)
         } else {
-            current_initial_title.clone()
+            None
         };
-        execute!(stdout, SetTitle(display_title))?;
-        // 更新外层 initial_title 以便内部循环使用
-        initial_title = current_initial_title;
+        // 长音频（整个文件）记录听过的区间，便于分段收听
+        let tracks_listened = track.start.is_none() && track.end.is_none() && total_duration >= LONG_TRACK_MIN;
+        // 继续上次的播放：只在第一首跳到上次的位置
+        if let Some(position) = resume_position.take()
+            && !position.is_zero()
+            && sink.try_seek(position).is_ok()
+        {
+            toast.show(format!("从上次的位置 {} 继续", format_position(position)), Instant::now());
+        }
+        recent.push_played(&title, &artist);
+        layout.draw_recent(&mut stdout, &recent)?;
+        // 纯净模式没有状态行，每首开始时输出一行
+        if stdout.is_plain() {
+            layout.clear_status(&mut stdout)?;
+            if artist.is_empty() {
+                writeln!(stdout, "正在播放: {}", title)?;
+            } else {
+                writeln!(stdout, "正在播放: {} - {}", title, artist)?;
+            }
+        }
 
+        // 终端标题中的歌曲信息（前缀由内部循环按播放状态统一加上）
+        initial_title = track_caption(&title, &artist);
+        sync_window_title(window_title.as_ref(), &title, &artist, current_track_index, total_tracks);
 
-        let next_index = (current_track_index + 1) % total_tracks;
 
-        if next_index != current_track_index && (is_loop_enabled || current_track_index < total_tracks.saturating_sub(1)) {
-            start_preload_if_valid(&playlist, next_index, &tx);
+        // 单曲循环时下一首就是当前歌曲，同样提前预加载，重播时无需等待
+        if let Some(next_index) = step_index(current_track_index, total_tracks, direction, is_loop_enabled, loop_start) {
+            start_preload_if_valid(&playlist, next_index, &preloads, &preload_opts);
+            // 再下一首是云端文件：提前两首开始读取以触发下载，这次的结果会被丢弃，轮到它时从本地重新加载
+            // （--low-memory 时只预加载一首）
+            if !args.low_memory
+                && let Some(after_next) = step_index(next_index, total_tracks, direction, is_loop_enabled, loop_start)
+                && after_next != current_track_index
+                && playlist[after_next].cloud
+            {
+                start_preload_if_valid(&playlist, after_next, &preloads, &preload_opts);
+            }
         }
 
-        let start_time = Instant::now();
-        let mut paused_duration = Duration::from_secs(0);
-        let mut last_pause_time: Option<Instant> = None;
-        let mut last_running_time = Duration::from_secs(0);
+        let mut ab_loop = AbLoop::Off; // A-B 循环（切歌时重置）
         let mut last_progress_update = Instant::now();
+        let mut redraw_now = true; // 立即刷新状态行（切歌、按键反馈、暂停/恢复时）
         let mut forced_stop = false;
         let mut last_toggle_time = Instant::now() - Duration::from_millis(300); // 按键防抖
+        let mut pending_repeat: Option<u32> = None; // 已输入、等待 R 确认的重复次数
+        let mut last_position = Duration::ZERO; // 最近一次取得的播放位置（判定切走时是否已听过）
+        let mut last_key_time = Instant::now(); // 最近一次按键的时间（暂停超时从这里开始计时）
+        let mut paused_at: Option<Instant> = None; // 按空格暂停的时刻（恢复时据此决定是否回退）
+        let mut pause_reason: Option<PauseReason> = None; // 当前暂停的原因（失去焦点自动暂停的，重新获得焦点时才自动恢复）
 
         // 8. 内部播放循环 
         'inner: while !sink.empty() {
-            // ... (时间计算)
+            // 播放位置取自 Sink（暂停时自然冻结，跳转后自动更新）
+            let mut current_time = sink.get_pos();
+            // 终端标题随歌曲与暂停/静音状态变化（未变化时不发送）
+            let title_fields = TitleFields {
+                title: &title,
+                artist: &artist,
+                index: direction.position(current_track_index, total_tracks),
+                total: total_tracks,
+                elapsed: current_time,
+                duration: total_duration,
+            };
+            terminal_title.update(&mut stdout, &initial_title, Some(&title_fields), sink.is_paused(), muted_volume.is_some())?;
+            if !audible_recorded && !current_time.is_zero() {
+                timing_log.record_audible(appended_at.elapsed());
+                audible_recorded = true;
+            }
 
-            if sink.is_paused() {
-                if last_pause_time.is_none() {
-                    last_pause_time = Some(Instant::now());
-                    last_running_time = start_time.elapsed().saturating_sub(paused_duration);
+            // A-B 循环：越过终点时跳回起点
+            if let Some(start) = ab_loop.wrap_target(current_time)
+                && sink.try_seek(start).is_ok()
+            {
+                current_time = start;
+            }
+            last_position = current_time;
+            resume_recorder.update(&playlist[current_track_index].path, current_time);
+            // 保存失败不影响播放，下次只是少了这一段
+            if tracks_listened {
+                let _ = listened_store.observe(&playlist[current_track_index].path, current_time);
+            }
+            // 后台检查接下来的歌曲是否仍然存在，已被移走的提前从队列中去掉（流式随机没有确定的后续歌曲；开场曲不删除）
+            if let Some(checker) = health_checker.as_mut()
+                && walker.is_none()
+            {
+                let ahead = checker.ahead;
+                let upcoming = || {
+                    upcoming_indexes(current_track_index, total_tracks, direction, is_loop_enabled, loop_start, ahead)
+                        .into_iter()
+                        .filter(|&i| i >= loop_start)
+                };
+                if checker.is_due(Instant::now()) {
+                    let paths = upcoming().map(|i| playlist[i].path.clone()).collect();
+                    checker.start(paths, Instant::now());
+                } else if let Some(missing) = checker.poll() {
+                    let mut removed: Vec<usize> = upcoming().filter(|&i| missing.contains(&playlist[i].path)).collect();
+                    removed.sort_unstable();
+                    if !removed.is_empty() {
+                        for &index in &removed {
+                            session_log.record(Transition::Removed { index, path: playlist[index].path.display().to_string(), reason: "missing" });
+                        }
+                        remove_indexes(&mut playlist, &mut current_track_index, &removed);
+                        undo_stack.after_removal(&removed);
+                        durations.remove(&removed);
+                        total_tracks = playlist.len();
+                        // 索引已变化：作废按旧索引登记的所有预加载（含云端的再下一首），避免结果或时长落到错误的歌曲上，
+                        // 再按新的索引重新预加载下一首（云端文件的下载已经触发，不必重新请求）
+                        preloads.clear();
+                        if let Some(next_index) = step_index(current_track_index, total_tracks, direction, is_loop_enabled, loop_start) {
+                            start_preload_if_valid(&playlist, next_index, &preloads, &preload_opts);
+                        }
+                        toast.show(format!("{} 首歌曲已不存在，已从队列中移除", removed.len()), Instant::now());
+                        redraw_now = true;
+                    }
                 }
-            } else {
-                if let Some(pause_start) = last_pause_time.take() {
-                    paused_duration += pause_start.elapsed();
+            }
+
+            // 音量条高亮到期后恢复正常显示
+            if let Some(changed_at) = volume_changed_at
+                && changed_at.elapsed() >= VOLUME_FLASH_DURATION
+            {
+                volume_changed_at = None;
+                redraw_now = true;
+            }
+            if toast.expire(Instant::now()) {
+                redraw_now = true;
+            }
+            // 外部命令（`mddplayer duck on|off`）
+            while let Some(command) = ctl_server.as_ref().and_then(CtlServer::poll_command) {
+                match command {
+                    CtlCommand::Duck(active) => {
+                        if active != duck.is_active() {
+                            duck.set(active, Instant::now());
+                            // 静音时听到的音量不变（仍为 0），不记录
+                            if muted_volume.is_none() {
+                                session_log.record(Transition::Volume { volume: (duck.settled_volume(master_volume) * 100.0).round() as u8, source: "ipc" });
+                            }
+                        }
+                        redraw_now = true;
+                    }
                 }
             }
-            let current_time = if sink.is_paused() {
-                last_running_time
-            } else {
-                start_time.elapsed().saturating_sub(paused_duration)
-            };
+            // 压低/恢复音量与启动渐强的平滑过渡（静音时保持 0）
+            let now = Instant::now();
+            let duck_ramping = duck.poll(now).is_some();
+            let fade_ramping = fade_in.poll(now).is_some();
+            if (duck_ramping || fade_ramping) && muted_volume.is_none() {
+                sink.set_volume(effective_volume(master_volume, track_gain_db, ramp_factor(&duck, &fade_in, now)));
+            }
+
+            // 暂停超时：退出，或释放音频设备，按键后重新打开并从暂停处继续
+            if let Some(timeout) = pause_timeout
+                && sink.is_paused()
+                && last_key_time.elapsed() >= timeout
+            {
+                if args.pause_timeout_action == PauseTimeoutAction::Exit {
+                    graceful_exit(&mut stdout, &layout)?;
+                    return Ok(ExitCode::SUCCESS);
+                }
+                // 先停止 Sink（丢弃解码器），再关闭输出流
+                sink.stop();
+                _stream = None;
+                session_log.record(Transition::DeviceReleased);
+                if !wait_for_wake(&mut stdout, &layout, &mut quit_confirm)? {
+                    graceful_exit(&mut stdout, &layout)?;
+                    return Ok(ExitCode::SUCCESS);
+                }
+                let volume = if muted_volume.is_some() { 0.0 } else { effective_volume(master_volume, track_gain_db, ramp_factor(&duck, &fade_in, Instant::now())) };
+                let paused_for = paused_at.take().map_or(Duration::ZERO, |t| t.elapsed());
+                let rewound = rewind_target(paused_for, current_time, resume_rewind, resume_rewind_after);
+                match reacquire_output(&args, output_device.as_deref(), &playlist[current_track_index], &preload_opts.decoder_overrides, volume, rewound.unwrap_or(current_time)) {
+                    Ok(((stream, new_sink), _)) => {
+                        _stream = stream;
+                        sink = new_sink;
+                        session_log.record(Transition::DeviceOpened { name: output_device.clone() });
+                        session_log.record(Transition::Resumed);
+                        pause_reason = None;
+                        match rewound {
+                            Some(_) => toast.show(format!("已恢复播放，回退 {} 秒", resume_rewind.as_secs()), Instant::now()),
+                            None => toast.show("已恢复播放", Instant::now()),
+                        }
+                    }
+                    Err(e) => {
+                        graceful_exit(&mut stdout, &layout)?;
+                        eprintln!("[错误]重新打开音频设备失败: {}", e);
+                        return Ok(ExitCode::SUCCESS);
+                    }
+                }
+                last_key_time = Instant::now();
+                redraw_now = true;
+                continue;
+            }
 
-            // 刷新显示 (与原代码一致)
-            if last_progress_update.elapsed() >= UPDATE_INTERVAL {
+            // 刷新显示：暂停时内容不变，只在需要立即刷新时重绘
+            if redraw_now || (!sink.is_paused() && last_progress_update.elapsed() >= UPDATE_INTERVAL) {
                 // BUG 修复：如果处于静音状态，在 update_progress_display 中显示 0% 音量，否则显示实际音量
                 let display_volume = if muted_volume.is_some() {
                     0.0
                 } else {
-                    sink.volume()
+                    master_volume
                 };
 
-                update_progress_display(
-                    &mut stdout,
-                    current_track_index,
+                let listened = tracks_listened.then(|| listened_store.heard(&playlist[current_track_index].path));
+                let view = StatusView {
+                    current_index: direction.position(current_track_index, total_tracks),
                     total_tracks,
-                    is_random_enabled,
-                    is_loop_enabled,
-                    &title,
-                    &artist,
-                    &track_path_str,
+                    is_random: is_random_enabled,
+                    is_stream: walker.is_some(),
+                    is_loop: is_loop_enabled,
+                    title: &title,
+                    artist: &artist,
+                    track_path: &track_path_str,
+                    probed_format: playlist[current_track_index].probed_format.as_deref(),
+                    channels,
                     current_time,
                     total_duration,
-                    display_volume, // 使用修复后的音量
-                )?;
+                    volume: display_volume, // 使用修复后的音量
+                    repeat_remaining,
+                    track_gain_db,
+                    ab_loop,
+                    is_paused: sink.is_paused(),
+                    volume_flash: volume_changed_at.is_some(),
+                    intro_trim,
+                    ducked: duck.is_active(),
+                    notice: low_volume_notice.then_some(low_volume_text.as_str()),
+                    // 流式随机没有确定的列表长度，不显示
+                    queue_remaining: (show_queue_remaining && walker.is_none())
+                        .then(|| {
+                            record_ready_durations(&preloads, &mut durations);
+                            durations.remaining(current_track_index, current_time, total_duration)
+                        }),
+                    toast: toast.message(Instant::now()),
+                    unheard,
+                    cloud: playlist[current_track_index].cloud,
+                    listened: listened.as_ref(),
+                };
+                update_progress_display(&mut stdout, &layout, &view)?;
+                if let Some(server) = &ctl_server {
+                    server.update(&PlayerStatus {
+                        paused: view.is_paused,
+                        title: &title,
+                        artist: &artist,
+                        index: view.current_index + 1,
+                        total: total_tracks,
+                        position: current_time,
+                        duration: total_duration,
+                        volume: (master_volume * 100.0).round() as u8,
+                        muted: muted_volume.is_some(),
+                    });
+                }
                 last_progress_update = Instant::now();
+                redraw_now = false;
             }
 
             // --- 用户输入处理 (非阻塞) ---
-            if event::poll(Duration::from_millis(100))? {
-                if let Event::Key(key_event) = event::read()? {
-                    match key_event.code {
+            let mut skip_steps: Option<i32> = None; // 本次按键要求立即切歌的步数
+            if poll_event(Duration::from_millis(100))? {
+                let event = read_event()?;
+                if matches!(event, Event::Key(_)) {
+                    last_key_time = Instant::now();
+                }
+                match event {
+                    // 只处理按下事件：部分终端（如 Windows）还会报告重复与松开，否则一次按键会被处理两次
+                    Event::Key(key_event) if key_event.kind != KeyEventKind::Press => {}
+                    // 终端尺寸变化：按新行数选择完整/迷你界面，重绘横幅并立即刷新状态行
+                    Event::Resize(_, rows) => {
+                        layout.fit_height(rows);
+                        layout.redraw(&mut stdout, &recent)?;
+                        redraw_now = true;
+                    }
+                    // 终端失去焦点：按需暗淡显示、暂停播放（已手动暂停的保持原样）
+                    Event::FocusLost => {
+                        if args.dim_on_unfocus {
+                            layout.dimmed = true;
+                            layout.redraw(&mut stdout, &recent)?;
+                        }
+                        if args.pause_on_unfocus && !sink.is_paused() {
+                            sink.pause();
+                            paused_at = Some(Instant::now());
+                            pause_reason = Some(PauseReason::Unfocused);
+                            session_log.record(Transition::Paused);
+                            toast.show("失去焦点，已暂停", Instant::now());
+                        }
+                        redraw_now = true;
+                    }
+                    // 重新获得焦点：恢复正常显示；只恢复因失去焦点而自动暂停的播放
+                    Event::FocusGained => {
+                        if layout.dimmed {
+                            layout.dimmed = false;
+                            layout.redraw(&mut stdout, &recent)?;
+                        }
+                        if pause_reason == Some(PauseReason::Unfocused) && sink.is_paused() {
+                            let paused_for = paused_at.take().map_or(Duration::ZERO, |t| t.elapsed());
+                            match rewind_target(paused_for, current_time, resume_rewind, resume_rewind_after) {
+                                Some(target) if sink.try_seek(target).is_ok() => {
+                                    toast.show(format!("继续播放，回退 {} 秒", resume_rewind.as_secs()), Instant::now());
+                                }
+                                _ => toast.show("继续播放", Instant::now()),
+                            }
+                            sink.play();
+                            pause_reason = None;
+                            session_log.record(Transition::Resumed);
+                        }
+                        redraw_now = true;
+                    }
+                    Event::Key(key_event) => match key_event.code {
+                        // F2：在纯净模式与完整界面之间切换
+                        KeyCode::F(2) => {
+                            layout.clean = !layout.clean;
+                            let cols = terminal::size().map(|(cols, _)| cols).unwrap_or(60);
+                            if !layout.mini && !plain {
+                                execute!(stdout, SetSize(cols, layout.total_rows()))?;
+                            }
+                            layout.redraw(&mut stdout, &recent)?;
+                            if args.remember_modes {
+                                let _ = save_ui_mode(layout.clean);
+                            }
+                            toast.show(if layout.clean { "纯净模式" } else { "完整界面" }, Instant::now());
+                            redraw_now = true;
+                        }
+                        // F8：选择输出设备，在新设备上从当前位置继续播放（打开失败时留在原设备）
+                        KeyCode::F(8) => {
+                            redraw_now = true;
+                            if args.simulate.is_some() {
+                                toast.show("模拟输出模式下无法切换设备", Instant::now());
+                                continue;
+                            }
+                            let (names, default_index) = match output_device_names() {
+                                Ok(devices) if !devices.0.is_empty() => devices,
+                                _ => {
+                                    toast.show("未找到可用的音频输出设备", Instant::now());
+                                    continue;
+                                }
+                            };
+                            let current = output_device.as_ref().and_then(|name| names.iter().position(|n| n == name)).or(default_index).unwrap_or(0);
+                            let Some(choice) = select_item(&mut stdout, &layout, "输出设备: ", &names, current)? else { continue };
+                            if choice == current {
+                                continue;
+                            }
+                            let paused = sink.is_paused();
+                            let volume = if muted_volume.is_some() { 0.0 } else { effective_volume(master_volume, track_gain_db, ramp_factor(&duck, &fade_in, Instant::now())) };
+                            match reacquire_output(&args, Some(&names[choice]), &playlist[current_track_index], &preload_opts.decoder_overrides, volume, sink.get_pos()) {
+                                Ok(((stream, new_sink), seeked)) => {
+                                    if paused {
+                                        new_sink.pause();
+                                    }
+                                    // 先停止原设备上的播放，再关闭原输出流
+                                    sink.stop();
+                                    _stream = stream;
+                                    sink = new_sink;
+                                    output_device = Some(names[choice].clone());
+                                    session_log.record(Transition::DeviceOpened { name: output_device.clone() });
+                                    let suffix = if seeked { "" } else { "（该格式不支持跳转，已从头播放）" };
+                                    toast.show(format!("已切换到 {}{}", names[choice], suffix), Instant::now());
+                                }
+                                Err(e) => toast.show(format!("无法打开 {}，继续使用原设备: {}", names[choice], e), Instant::now()),
+                            }
+                        }
                         // P键：静音/取消静音
                         KeyCode::Char('p') | KeyCode::Char('P') => {
                             if last_toggle_time.elapsed() < Duration::from_millis(200) { continue; }
                             last_toggle_time = Instant::now();
+                            low_volume_notice = false;
                             if let Some(vol) = muted_volume {
                                 // 取消静音
-                                sink.set_volume(vol);
+                                master_volume = vol;
+                                sink.set_volume(effective_volume(master_volume, track_gain_db, ramp_factor(&duck, &fade_in, Instant::now())));
                                 muted_volume = None;
-                                execute!(stdout, SetTitle(initial_title.clone()))?;
+                                volume_changed_at = Some(Instant::now());
+                                session_log.record(Transition::Volume { volume: (master_volume * 100.0).round() as u8, source: "unmute" });
+                                toast.show(format!("取消静音 {:.0}% / {}", master_volume * 100.0, format_volume_db(master_volume)), Instant::now());
                             } else {
                                 // 静音
-                                muted_volume = Some(sink.volume());
+                                muted_volume = Some(master_volume);
+                                volume_changed_at = Some(Instant::now());
+                                session_log.record(Transition::Volume { volume: 0, source: "mute" });
+                                toast.show("静音", Instant::now());
                                 sink.set_volume(0.0);
-                                let mute_title = format!("[静音]{}", initial_title);
-                                execute!(stdout, SetTitle(mute_title))?;
                             }
+                            redraw_now = true;
                         }
                         // 空格键：暂停/播放
                         KeyCode::Char(' ') => {
                             if last_toggle_time.elapsed() < Duration::from_millis(200) { continue; }
                             last_toggle_time = Instant::now();
                             if sink.is_paused() {
+                                // 暂停较久后恢复：先回退几秒，找回上下文（显示的进度在下一次刷新时随 Sink 位置更新）
+                                let paused_for = paused_at.take().map_or(Duration::ZERO, |t| t.elapsed());
+                                match rewind_target(paused_for, current_time, resume_rewind, resume_rewind_after) {
+                                    Some(target) if sink.try_seek(target).is_ok() => {
+                                        toast.show(format!("回退 {} 秒", resume_rewind.as_secs()), Instant::now());
+                                    }
+                                    _ => toast.show("继续播放", Instant::now()),
+                                }
                                 sink.play();
-                                // BUG 修复：播放时标题应恢复正常（如果非静音）或保持静音（如果静音）
-                                let display_title = if muted_volume.is_some() {
-                                    format!("[静音]{}", initial_title)
-                                } else {
-                                    initial_title.clone()
-                                };
-                                execute!(stdout, SetTitle(display_title))?;
+                                pause_reason = None;
+                                session_log.record(Transition::Resumed);
                             } else {
                                 sink.pause();
-                                let pause_title = format!("[暂停]{}", initial_title);
-                                execute!(stdout, SetTitle(pause_title))?;
+                                paused_at = Some(Instant::now());
+                                pause_reason = Some(PauseReason::Manual);
+                                session_log.record(Transition::Paused);
+                                toast.show("暂停", Instant::now());
                             }
+                            redraw_now = true;
+                        }
+                        // Alt+↑/↓：调整当前歌曲的单曲增益并保存
+                        KeyCode::Up | KeyCode::Down if key_event.modifiers.contains(KeyModifiers::ALT) => {
+                            let delta = if key_event.code == KeyCode::Up { TRACK_GAIN_STEP_DB } else { -TRACK_GAIN_STEP_DB };
+                            if let Some(reason) = NoOp::gain_limit(delta > 0.0, track_gain_db) {
+                                toast.reject(&mut stdout, reason, Instant::now())?;
+                                redraw_now = true;
+                                continue;
+                            }
+                            track_gain_db = (track_gain_db + delta).clamp(TRACK_GAIN_MIN_DB, TRACK_GAIN_MAX_DB);
+                            // 保存失败不影响播放，下次启动时该增益不会恢复
+                            let _ = gain_store.set(&playlist[current_track_index].path, track_gain_db);
+                            if muted_volume.is_none() {
+                                sink.set_volume(effective_volume(master_volume, track_gain_db, ramp_factor(&duck, &fade_in, Instant::now())));
+                            }
+                            session_log.record(Transition::TrackGain { db: track_gain_db });
+                            toast.show(format!("单曲增益 {:+.0}dB", track_gain_db), Instant::now());
+                            redraw_now = true;
                         }
                         // 音量控制
                         KeyCode::Up => {
+                            low_volume_notice = false;
+                            let mut changed = false; // 取消静音或接管渐强本身就是效果
                             // 调整音量时，如果处于静音状态，应先取消静音，恢复音量并增加
                             if let Some(vol) = muted_volume.take() {
                                 // 先恢复到静音前的音量
-                                sink.set_volume(vol);
-                                execute!(stdout, SetTitle(initial_title.clone()))?;
+                                master_volume = vol;
+                                changed = true;
+                            }
+                            // 启动渐强中手动调音量：以当前实际的音量为起点接管
+                            if let Some(factor) = fade_in.cancel(Instant::now()) {
+                                master_volume = scale_ui_volume(master_volume, factor);
+                                changed = true;
                             }
-                            adjust_volume(&sink, VOLUME_STEP);
+                            if !changed && let Some(reason) = NoOp::volume_limit(true, master_volume) {
+                                toast.reject(&mut stdout, reason, Instant::now())?;
+                                redraw_now = true;
+                                continue;
+                            }
+                            adjust_volume(sink.as_ref(), &mut master_volume, track_gain_db, ramp_factor(&duck, &fade_in, Instant::now()), volume_accel.step(Instant::now()));
+                            volume_changed_at = Some(Instant::now());
+                            session_log.record(Transition::Volume { volume: (master_volume * 100.0).round() as u8, source: "key" });
+                            toast.show(format!("音量 {:.0}% / {}", master_volume * 100.0, format_volume_db(master_volume)), Instant::now());
+                            // 音量变化后立即刷新状态行
+                            redraw_now = true;
                         },
                         KeyCode::Down => {
+                            low_volume_notice = false;
+                            let mut changed = false; // 取消静音或接管渐强本身就是效果
                             // 调整音量时，如果处于静音状态，应先取消静音，恢复音量并减小
                             if let Some(vol) = muted_volume.take() {
                                 // 先恢复到静音前的音量
-                                sink.set_volume(vol);
-                                execute!(stdout, SetTitle(initial_title.clone()))?;
+                                master_volume = vol;
+                                changed = true;
+                            }
+                            // 启动渐强中手动调音量：以当前实际的音量为起点接管
+                            if let Some(factor) = fade_in.cancel(Instant::now()) {
+                                master_volume = scale_ui_volume(master_volume, factor);
+                                changed = true;
                             }
-                            adjust_volume(&sink, -VOLUME_STEP);
+                            if !changed && let Some(reason) = NoOp::volume_limit(false, master_volume) {
+                                toast.reject(&mut stdout, reason, Instant::now())?;
+                                redraw_now = true;
+                                continue;
+                            }
+                            adjust_volume(sink.as_ref(), &mut master_volume, track_gain_db, ramp_factor(&duck, &fade_in, Instant::now()), -volume_accel.step(Instant::now()));
+                            volume_changed_at = Some(Instant::now());
+                            session_log.record(Transition::Volume { volume: (master_volume * 100.0).round() as u8, source: "key" });
+                            toast.show(format!("音量 {:.0}% / {}", master_volume * 100.0, format_volume_db(master_volume)), Instant::now());
+                            // 音量变化后立即刷新状态行
+                            redraw_now = true;
                         },
-                        // 切歌：下一首
-                        KeyCode::Right => {
-                            if last_skip_time.elapsed() < MIN_SKIP_INTERVAL { continue; }
-                            if current_track_index < total_tracks.saturating_sub(1) || is_loop_enabled {
-                                sink.stop(); index_offset = 1; forced_stop = true; last_skip_time = Instant::now(); break 'inner; }
+                        // 切歌：→ 下一首（沿当前行进方向），← 上一首；在循环末尾统一执行
+                        KeyCode::Right | KeyCode::Left => {
+                            let delta = if key_event.code == KeyCode::Right { 1 } else { -1 };
+                            // 长音频（如有声书）已播放较久时，需在 1 秒内再按一次才切歌
+                            if skip_needs_confirm(skip_confirm_length, total_duration, current_time) && !skip_confirm.press(delta, Instant::now()) {
+                                toast.show("再按一次确认切换", Instant::now());
+                                redraw_now = true;
+                                continue;
+                            }
+                            skip_steps = skip_keys.press(delta, Instant::now());
+                            if args.skip_accumulate && skip_keys.pending() != 0 {
+                                toast.show(format!("切歌 {:+}", skip_keys.pending()), Instant::now());
+                                redraw_now = true;
+                            }
+                        }
+                        // E键：输入路径，追加到播放队列末尾（解析规则与命令行输入相同）
+                        KeyCode::Char('e') | KeyCode::Char('E') => {
+                            let Some(input) = prompt_line(&mut stdout, &layout, "加入队列（Enter 确认，Esc 取消）: ", "")? else {
+                                redraw_now = true;
+                                continue;
+                            };
+                            let input = input.trim().trim_matches(['"', '\'']);
+                            if input.is_empty() {
+                                redraw_now = true;
+                                continue;
+                            }
+                            let message = match get_playlist_from_input(input, &ScanOptions { quiet: true, progress: None, confirm_large: None, ..scan_opts.clone() }) {
+                                Ok(mut tracks) => {
+                                    mark_cloud_placeholders(&mut tracks);
+                                    let added = tracks.len();
+                                    undo_stack.push(QueueEdit::Appended { start: playlist.len(), count: added });
+                                    playlist.extend(tracks);
+                                    let old_total = std::mem::replace(&mut total_tracks, playlist.len());
+                                    // 下一首随之变化（原本已是最后一首、或倒序循环时绕回的最后一首）：立即预加载
+                                    if let Some(next) = next_after_resize(current_track_index, old_total, total_tracks, direction, is_loop_enabled, loop_start) {
+                                        start_preload_if_valid(&playlist, next, &preloads, &preload_opts);
+                                    }
+                                    format!("已加入 {} 首，队列共 {} 首", added, total_tracks)
+                                }
+                                Err(e) => format!("[错误]无法加入 '{}': {}", input, e),
+                            };
+                            // 提示保留到下一次进度刷新
+                            show_splash(&mut stdout, &layout, &message)?;
+                            last_progress_update = Instant::now();
                         }
-                        // 切歌：上一首
-                        KeyCode::Left => {
-                            if last_skip_time.elapsed() < MIN_SKIP_INTERVAL { continue; }
-                            if current_track_index > 0 || is_loop_enabled {
-                                sink.stop(); index_offset = -1; forced_stop = true; last_skip_time = Instant::now(); break 'inner; }
+                        // U键：撤销最近一次队列修改（如按 E 加入的歌曲）
+                        KeyCode::Char('u') | KeyCode::Char('U') => {
+                            let message = match undo_stack.undo(&mut playlist, current_track_index) {
+                                Some(Ok(message)) => {
+                                    let old_total = std::mem::replace(&mut total_tracks, playlist.len());
+                                    durations.truncate(total_tracks);
+                                    if let Some(next) = next_after_resize(current_track_index, old_total, total_tracks, direction, is_loop_enabled, loop_start) {
+                                        start_preload_if_valid(&playlist, next, &preloads, &preload_opts);
+                                    }
+                                    message
+                                }
+                                Some(Err(message)) => message,
+                                None => "没有可撤销的操作".to_string(),
+                            };
+                            toast.show(message, Instant::now());
+                            redraw_now = true;
                         }
-                        // 退出 (Q/q 或 Ctrl+C)
-                        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('c') => {
-                            graceful_exit(&mut stdout)?;
-                            return Ok(());
+                        // W键：修改标题/艺术家并写回文件标签（需 --allow-tag-edit；章节共用一个文件，不支持）
+                        KeyCode::Char('w') | KeyCode::Char('W') => {
+                            let track = &playlist[current_track_index];
+                            let refusal = if !args.allow_tag_edit {
+                                Some("写回标签需要 --allow-tag-edit")
+                            } else if track.start.is_some() || track.end.is_some() {
+                                Some("章节不支持写回标签")
+                            } else {
+                                None
+                            };
+                            if let Some(refusal) = refusal {
+                                toast.show(refusal, Instant::now());
+                                redraw_now = true;
+                                continue;
+                            }
+                            // 输入期间主循环停在 prompt_line 中，先暂停播放，结束后恢复原来的状态
+                            let was_paused = sink.is_paused();
+                            sink.pause();
+                            let edited = match prompt_line(&mut stdout, &layout, "标题（Enter 确认，Esc 取消）: ", &title)? {
+                                Some(new_title) => prompt_line(&mut stdout, &layout, "艺术家（Enter 确认，Esc 取消）: ", &artist)?.map(|new_artist| (new_title, new_artist)),
+                                None => None,
+                            };
+                            if !was_paused {
+                                sink.play();
+                            }
+                            let Some((new_title, new_artist)) = edited else {
+                                redraw_now = true;
+                                continue;
+                            };
+                            let (new_title, new_artist) = (sanitize_display(new_title.trim()), sanitize_display(new_artist.trim()));
+                            if new_title.is_empty() {
+                                toast.show("标题不能为空", Instant::now());
+                                redraw_now = true;
+                                continue;
+                            }
+                            let message = match rewrite_tags(sink.as_ref(), track, &preload_opts.decoder_overrides, &new_title, &new_artist) {
+                                Ok(()) => {
+                                    (title, artist) = (new_title, new_artist);
+                                    initial_title = track_caption(&title, &artist);
+                                    sync_window_title(window_title.as_ref(), &title, &artist, current_track_index, total_tracks);
+                                    "已写入标签".to_string()
+                                }
+                                Err(e) => format!("[错误]{}", e),
+                            };
+                            show_splash(&mut stdout, &layout, &message)?;
+                            last_progress_update = Instant::now();
                         }
+                        // D键：反转行进方向（流式随机只能向前；忽略 Ctrl+D，避免误触）
+                        KeyCode::Char('d') | KeyCode::Char('D')
+                            if walker.is_none() && !key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                        {
+                            direction = direction.reversed();
+                            // 方向改变后下一首随之改变，重新预加载
+                            if let Some(next_index) = step_index(current_track_index, total_tracks, direction, is_loop_enabled, loop_start)
+                                && next_index != current_track_index
+                            {
+                                start_preload_if_valid(&playlist, next_index, &preloads, &preload_opts);
+                            }
+                            toast.show(if direction == Direction::Backward { "方向: 倒序" } else { "方向: 正序" }, Instant::now());
+                            redraw_now = true;
+                        }
+                        // Ctrl+I（终端中通常与 Tab 相同）：在开头 30 秒内按下，把当前位置记为本目录后续歌曲的片头跳过时长
+                        KeyCode::Tab | KeyCode::Char('i') | KeyCode::Char('I')
+                            if key_event.code == KeyCode::Tab || key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                        {
+                            let track = &playlist[current_track_index];
+                            let pos = sink.get_pos();
+                            if pos > INTRO_TRIM_WINDOW || track.start.is_some() || track.end.is_some() {
+                                continue;
+                            }
+                            // 在 1 秒内按下视为清除
+                            let trim = (pos >= MIN_INTRO_TRIM).then_some(pos);
+                            let _ = trim_store.set(&track.path, trim);
+                            let message = match trim {
+                                Some(trim) => format!("本目录后续歌曲将跳过前 {}", format_position(trim)),
+                                None => "已清除本目录的片头跳过".to_string(),
+                            };
+                            // 提示保留到下一次进度刷新
+                            show_splash(&mut stdout, &layout, &message)?;
+                            last_progress_update = Instant::now();
+                        }
+                        // H键：临时压低音量到 20%，再按一次平滑恢复（主音量不变）
+                        KeyCode::Char('h') | KeyCode::Char('H') => {
+                            duck.set(!duck.is_active(), Instant::now());
+                            if muted_volume.is_none() {
+                                session_log.record(Transition::Volume { volume: (duck.settled_volume(master_volume) * 100.0).round() as u8, source: "duck" });
+                            }
+                            toast.show(if duck.is_active() { "压低音量" } else { "恢复音量" }, Instant::now());
+                            redraw_now = true;
+                        }
+                        // M键：切换手动切歌（单曲播完后等待按 → 再播放下一首）
+                        KeyCode::Char('m') | KeyCode::Char('M') => {
+                            manual_advance = !manual_advance;
+                            toast.show(if manual_advance { "手动切歌: 开" } else { "手动切歌: 关" }, Instant::now());
+                            redraw_now = true;
+                        }
+                        // J键：长音频跳到第一个没听过的部分
+                        KeyCode::Char('j') | KeyCode::Char('J') => {
+                            if !tracks_listened {
+                                toast.reject(&mut stdout, NoOp::NotLongTrack, Instant::now())?;
+                            } else {
+                                match listened_store.heard(&playlist[current_track_index].path).first_unheard(total_duration) {
+                                    Some(target) if sink.try_seek(target).is_ok() => {
+                                        toast.show(format!("跳到未听的部分 {}", format_position(target)), Instant::now());
+                                    }
+                                    Some(_) => toast.show("该格式不支持跳转", Instant::now()),
+                                    None => toast.reject(&mut stdout, NoOp::AllHeard, Instant::now())?,
+                                }
+                            }
+                            redraw_now = true;
+                        }
+                        // T键：显示/隐藏整个列表的剩余时间
+                        KeyCode::Char('t') | KeyCode::Char('T') => {
+                            show_queue_remaining = !show_queue_remaining;
+                            toast.show(if show_queue_remaining { "列表剩余: 开" } else { "列表剩余: 关" }, Instant::now());
+                            redraw_now = true;
+                        }
+                        // A键：标记 A 点 → 标记 B 点并启用循环 → 清除
+                        KeyCode::Char('a') | KeyCode::Char('A') => {
+                            ab_loop = ab_loop.toggle(sink.get_pos(), total_duration);
+                            let message = match ab_loop {
+                                AbLoop::Off => "A-B 循环: 关",
+                                AbLoop::StartMarked(_) => "已标记 A 点",
+                                AbLoop::Active(_, _) => "A-B 循环: 开",
+                            };
+                            toast.show(message, Instant::now());
+                            redraw_now = true;
+                        }
+                        // 数字键：输入重复次数，随后按 R 确认
+                        KeyCode::Char(c @ '0'..='9') => {
+                            let digit = c.to_digit(10).unwrap_or(0);
+                            pending_repeat = Some(pending_repeat.unwrap_or(0).saturating_mul(10).saturating_add(digit).min(999));
+                        }
+                        // R键：当前歌曲共播放 N 遍后再继续（不带数字则取消重复）
+                        KeyCode::Char('r') | KeyCode::Char('R') => {
+                            repeat_remaining = pending_repeat.take().unwrap_or(0).saturating_sub(1);
+                            let message = if repeat_remaining > 0 { format!("共播放 {} 遍", repeat_remaining + 1) } else { "重复: 关".to_string() };
+                            toast.show(message, Instant::now());
+                            redraw_now = true;
+                        }
+                        // 退出 (Q/q、Ctrl+C 或 Ctrl+Q)
+                        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Char('c') | KeyCode::Char('C') => match quit_confirm.press(&key_event, Instant::now()) {
+                            QuitDecision::Quit => {
+                                graceful_exit(&mut stdout, &layout)?;
+                                return Ok(ExitCode::SUCCESS);
+                            }
+                            QuitDecision::AskAgain => {
+                                toast.show(QUIT_CONFIRM_MESSAGE, Instant::now());
+                                redraw_now = true;
+                            }
+                            QuitDecision::Ignore => {}
+                        },
                         _ => {}
+                    },
+                    _ => {}
+                }
+            }
+
+            // 执行切歌：按键立即生效，或累积模式下连按结束后一次跳过多首
+            if let Some(steps) = skip_steps.or_else(|| skip_keys.poll(Instant::now())) {
+                let forward = steps > 0;
+                // 列表只有一首：跳回开头重播，无需重新解码
+                if forward && total_tracks == 1 && walker.is_none() && sink.try_seek(Duration::ZERO).is_ok() {
+                    ab_loop = AbLoop::Off;
+                    toast.show("从头重播", Instant::now());
+                    redraw_now = true;
+                    continue;
+                }
+                // 流式随机只能回退到已播放过的歌曲
+                let (step_direction, wrap) = if forward {
+                    (direction, is_loop_enabled)
+                } else {
+                    (direction.reversed(), is_loop_enabled && walker.is_none())
+                };
+                let target = skip_index(current_track_index, total_tracks, step_direction, steps.unsigned_abs() as usize, wrap, loop_start)
+                    .or_else(|| (forward && total_tracks == 1).then_some(current_track_index));
+                match target {
+                    Some(target) => {
+                        sink.stop();
+                        session_log.record(Transition::Skip { from: current_track_index, to: target, trigger: "key" });
+                        skip_target = Some(target);
+                        forced_stop = true;
+                        break 'inner;
+                    }
+                    // 不循环时已到列表一端
+                    None => {
+                        toast.reject(&mut stdout, NoOp::track_end(forward), Instant::now())?;
+                        redraw_now = true;
                     }
                 }
             }
         } // 内部播放循环结束
+        if tracks_listened {
+            let _ = listened_store.finish();
+        }
+
+        // 单曲结束结果：统一在这里判定，并通知各个关心它的功能
+        let outcome = PlaybackOutcome::classify(!forced_stop, last_position, total_duration);
+        report_outcome(&mut stdout, &layout, &mut recent, &outcome)?;
+        // 解码器早于报告的时长结束：仍按正常结束处理，记录诊断并以实际时长修正剩余时间估算（章节的时长由区间决定，不检查）
+        let finished_track = &playlist[current_track_index];
+        if outcome == PlaybackOutcome::Completed
+            && finished_track.start.is_none()
+            && finished_track.end.is_none()
+            && ended_early(last_position, total_duration)
+        {
+            decoder_log.record_early_end(&track_path_str, last_position, total_duration);
+            durations.record(current_track_index, last_position);
+        }
+        // 记录失败（如数据目录不可写）不影响播放
+        let is_intro = args.intro.is_some() && current_track_index == 0;
+        if let Some(store) = played_store.as_mut()
+            && outcome.counts_as_played()
+            && !is_intro
+            && let Ok(true) = store.record(&playlist[current_track_index])
+            && let Some((left, _)) = unheard.as_mut()
+        {
+            *left = left.saturating_sub(1);
+        }
 
         // 9. 索引更新逻辑 (处理自动播放和强制切歌)
         if forced_stop {
-            if index_offset > 0 {
-                current_track_index = (current_track_index + 1) % total_tracks;
-            } else if index_offset < 0 {
-                current_track_index = if current_track_index == 0 { total_tracks.saturating_sub(1) } else { current_track_index - 1 };
+            // 手动切歌取消剩余的重复次数
+            repeat_remaining = 0;
+            // 目标已在按键时确定
+            if let Some(target) = skip_target.take() {
+                current_track_index = target;
             }
-            index_offset = 0;
             // -----------------------------------------------------------------
             // 🌟 修复：手动切歌后，必须立即启动新目标歌曲的预加载
             // -----------------------------------------------------------------
-            start_preload_if_valid(&playlist, current_track_index, &tx);
+            start_preload_if_valid(&playlist, current_track_index, &preloads, &preload_opts);
+        } else if repeat_remaining > 0 {
+            // 还有重复次数：重新加载当前歌曲
+            repeat_remaining -= 1;
+            layout.clear_status(&mut stdout)?;
+            start_preload_if_valid(&playlist, current_track_index, &preloads, &preload_opts);
         } else {
-            execute!(stdout, cursor::MoveToColumn(0), terminal::Clear(ClearType::CurrentLine))?;
-            current_track_index += 1;
+            layout.clear_status(&mut stdout)?;
+            let next_index = step_index(current_track_index, total_tracks, direction, is_loop_enabled, loop_start);
+            // 手动切歌：播完后停在这里（下一首已在预加载），按 → 才继续；到达列表末尾时不等待
+            let choice = if manual_advance && next_index.is_some() {
+                wait_for_advance(&mut stdout, &layout, &mut quit_confirm)?
+            } else {
+                AdvanceChoice::Next
+            };
+            match choice {
+                // 到达列表末尾时由循环开头统一处理
+                AdvanceChoice::Next => current_track_index = next_index.unwrap_or(total_tracks),
+                AdvanceChoice::Replay => start_preload_if_valid(&playlist, current_track_index, &preloads, &preload_opts),
+                AdvanceChoice::Quit => break 'outer,
+            }
         }
     } // 主循环结束 'outer
 
     // 10. 播放列表结束后的清理工作
-    graceful_exit(&mut stdout)?;
+    graceful_exit(&mut stdout, &layout)?;
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
\ No newline at end of file
@@ -1,56 +1,356 @@
+use std::fs::File;
+use std::io::{BufReader, Seek, SeekFrom};
 use std::path::Path;
 use std::time::Duration;
 // 引入 lofty 库的 Trait 和函数
 use lofty::prelude::TaggedFileExt; 
 use lofty::read_from_path; 
+use lofty::file::{FileType, TaggedFile};
+use lofty::probe::Probe;
 // 添加 Accessor Trait
 use lofty::tag::Accessor;
+use lofty::config::WriteOptions;
+use lofty::file::AudioFile;
+use lofty::tag::{ItemKey, Tag};
 // 引入 symphonia 库的格式和元数据选项
 use symphonia::core::{
-    formats::FormatOptions, meta::MetadataOptions, probe::Hint,
+    codecs::{CodecType, CODEC_TYPE_AAC, CODEC_TYPE_ALAC, CODEC_TYPE_NULL, CODEC_TYPE_OPUS},
+    formats::{FormatOptions, FormatReader, Track},
+    meta::MetadataOptions, probe::Hint,
     io::{MediaSource, MediaSourceStream},
 };
 
-/// 安全地获取标题和艺术家信息，优先使用主标签。
-/// 返回 (title, artist)
-pub fn get_title_artist_info(path: &Path) -> (String, String) {
+/// 第一条可解码的音轨（与播放时的选择一致）：视频容器中默认轨道可能是视频，
+/// 而 symphonia 不认识的编码（包括视频）都记为 CODEC_TYPE_NULL
+fn first_audio_track(format: &dyn FormatReader) -> Option<&Track> {
+    format.tracks().iter().find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+}
+
+/// 文件名解析模式中的一段：字面文本或占位符
+#[derive(Debug, Clone)]
+enum PatternToken {
+    Literal(String),
+    Field(String),
+}
+
+/// 文件名解析模式，例如 `{artist} - {title}`。
+/// 支持 `{artist}`、`{title}` 占位符，其他占位符（如 `{track}`）匹配但忽略。
+#[derive(Debug, Clone)]
+pub struct FilenamePattern {
+    tokens: Vec<PatternToken>,
+    /// 匹配前去掉文件名开头的曲目编号（见 split_track_number）；模式中含 `{track}` 时由模式自行处理
+    strip_track_number: bool,
+}
+
+/// 曲目编号与标题之间的分隔，按从长到短的顺序尝试
+const TRACK_NUMBER_SEPARATORS: [&str; 6] = [" - ", ". ", ".", " ", "-", "_"];
+
+/// 拆分文件名开头的曲目编号（如 `01 - `、`01. `、`01 `、`101.`），返回编号与其余部分。
+/// 只认 2-3 位数字（避免误伤「7 Rings」这类以数字开头的标题），去掉后为空时不拆分。
+fn split_track_number(stem: &str) -> Option<(u32, &str)> {
+    let digits = stem.len() - stem.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if !(2..=3).contains(&digits) {
+        return None;
+    }
+    let (number, rest) = stem.split_at(digits);
+    let rest = TRACK_NUMBER_SEPARATORS.iter().find_map(|sep| rest.strip_prefix(sep))?.trim_start();
+    if rest.is_empty() {
+        return None;
+    }
+    Some((number.parse().ok()?, rest))
+}
+
+/// 默认的文件名解析模式
+pub const DEFAULT_FILENAME_PATTERN: &str = "{artist} - {title}";
+
+/// 把含 `{name}` 占位符的字符串拆分为字面文本与占位符；占位符未闭合时返回错误。
+/// 文件名解析模式与标题模板共用这一套占位符语法。
+fn tokenize(pattern: &str) -> Result<Vec<PatternToken>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = pattern;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('{') {
+            let end = after.find('}').ok_or_else(|| format!("模式中的占位符未闭合: {}", pattern))?;
+            tokens.push(PatternToken::Field(after[..end].to_string()));
+            rest = &after[end + 1..];
+        } else {
+            let end = rest.find('{').unwrap_or(rest.len());
+            tokens.push(PatternToken::Literal(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+    }
+    Ok(tokens)
+}
+
+impl FilenamePattern {
+    /// 解析模式字符串；占位符未闭合或两个占位符相邻时返回错误。
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let tokens = tokenize(pattern)?;
+        let adjacent = tokens.windows(2).any(|w| matches!(w, [PatternToken::Field(_), PatternToken::Field(_)]));
+        if adjacent {
+            return Err(format!("文件名模式中两个占位符之间必须有分隔文本: {}", pattern));
+        }
+        let strip_track_number = !tokens.iter().any(|t| matches!(t, PatternToken::Field(name) if name == "track"));
+        Ok(FilenamePattern { tokens, strip_track_number })
+    }
+
+    /// 保留文件名开头的曲目编号（`--keep-track-number`）
+    pub fn keep_track_number(mut self) -> Self {
+        self.strip_track_number = false;
+        self
+    }
+
+    /// 用模式匹配文件名（不含扩展名），返回 (title, artist)。
+    /// 占位符按最短匹配，最后一个占位符吃掉剩余部分；任何占位符匹配为空则视为不匹配。
+    pub fn match_stem(&self, stem: &str) -> Option<(Option<String>, Option<String>)> {
+        let mut rest = stem;
+        let mut title = None;
+        let mut artist = None;
+        let mut i = 0;
+        while i < self.tokens.len() {
+            match &self.tokens[i] {
+                PatternToken::Literal(lit) => rest = rest.strip_prefix(lit.as_str())?,
+                PatternToken::Field(name) => {
+                    let value = match self.tokens.get(i + 1) {
+                        Some(PatternToken::Literal(lit)) => {
+                            let pos = rest.find(lit.as_str())?;
+                            let value = &rest[..pos];
+                            rest = &rest[pos..];
+                            value
+                        }
+                        _ => std::mem::take(&mut rest),
+                    };
+                    let value = value.trim();
+                    if value.is_empty() {
+                        return None;
+                    }
+                    match name.as_str() {
+                        "title" => title = Some(value.to_string()),
+                        "artist" => artist = Some(value.to_string()),
+                        _ => {}
+                    }
+                }
+            }
+            i += 1;
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        Some((title, artist))
+    }
+}
+
+impl Default for FilenamePattern {
+    fn default() -> Self {
+        FilenamePattern::parse(DEFAULT_FILENAME_PATTERN).expect("默认文件名模式有效")
+    }
+}
+
+/// 输出用的格式模板，例如 `{artist} - {title}`，占位符语法与文件名解析模式相同。
+#[derive(Debug, Clone)]
+pub struct Template {
+    tokens: Vec<PatternToken>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        Ok(Template { tokens: tokenize(template)? })
+    }
+
+    /// 用 `lookup` 填充占位符；未知占位符原样保留，便于发现拼写错误
+    pub fn render(&self, lookup: impl Fn(&str) -> Option<String>) -> String {
+        let mut out = String::new();
+        for token in &self.tokens {
+            match token {
+                PatternToken::Literal(lit) => out.push_str(lit),
+                PatternToken::Field(name) => match lookup(name) {
+                    Some(value) => out.push_str(&value),
+                    None => {
+                        out.push('{');
+                        out.push_str(name);
+                        out.push('}');
+                    }
+                },
+            }
+        }
+        out
+    }
+}
+
+/// 一次读取得到的歌曲信息（见 read_track_metadata）
+pub struct TrackMetadata {
+    pub title: String,
+    /// 无法得知艺术家时为空字符串
+    pub artist: String,
+    /// 无法读取时为 0
+    pub duration: Duration,
+}
+
+/// 只打开文件一次，读取标题、艺术家与总时长（网络存储上每次打开都有明显延迟）。
+/// 标题与艺术家的回退规则同 get_title_artist_info，时长的计算同 get_total_duration。
+pub fn read_track_metadata(path: &Path, pattern: &FilenamePattern) -> TrackMetadata {
+    let Ok(mut file) = File::open(path) else {
+        let (title, artist) = title_artist_fallback(path, pattern, None, None);
+        return TrackMetadata { title, artist, duration: Duration::ZERO };
+    };
+    let tagged_file = read_tags(&mut file, path);
+    let tag = tagged_file.as_ref().and_then(|f| f.primary_tag());
+    let non_empty = |s: Option<std::borrow::Cow<'_, str>>| s.map(|s| s.to_string()).filter(|s| !s.trim().is_empty());
+    let (title, artist) = title_artist_fallback(
+        path,
+        pattern,
+        tag.and_then(|t| non_empty(t.title())),
+        tag.and_then(|t| non_empty(t.artist())),
+    );
+    // 标签读取后回到文件开头，同一个文件句柄交给 symphonia 探测时长
+    let duration = match file.seek(SeekFrom::Start(0)) {
+        Ok(_) => duration_from_source(Box::new(file), path),
+        Err(_) => Duration::ZERO,
+    };
+    TrackMetadata { title, artist, duration }
+}
+
+/// 从已打开的文件读取标签：文件类型优先按扩展名判断（同 read_from_path），扩展名未知时按内容探测
+fn read_tags(file: &mut File, path: &Path) -> Option<TaggedFile> {
+    let probe = Probe::new(BufReader::new(file));
+    let probe = match FileType::from_path(path) {
+        Some(file_type) => probe.set_file_type(file_type),
+        None => probe.guess_file_type().ok()?,
+    };
+    probe.read().ok()
+}
+
+/// 安全地获取标题和艺术家信息，优先使用主标签；
+/// 标签缺失时按文件名模式解析，仍无法匹配则以文件名（不含扩展名）作为标题。
+/// 返回 (title, artist)，无法得知艺术家时 artist 为空字符串。
+pub fn get_title_artist_info(path: &Path, pattern: &FilenamePattern) -> (String, String) {
+    let mut tag_title = None;
+    let mut tag_artist = None;
     // 尝试从路径读取 tagged file
     match read_from_path(path) { 
         Ok(tagged_file) => {
             // 获取文件的主要标签（如 ID3v2, Vorbis Comment 等）
             if let Some(tag) = tagged_file.primary_tag() {
-                
-                // 获取标题，使用 and_then 链式调用
-                let title = tag.title()
-                    // 闭包返回 Some(String)，以满足 and_then 对 Option<U> 的要求
-                    .and_then(|t| Some(t.to_string())) 
-                    .unwrap_or_else(|| "未知音乐名".to_string());
-                
-                // 获取艺术家
-                let artist = tag.artist()
-                    // 闭包返回 Some(String)，以满足 and_then 对 Option<U> 的要求
-                    .and_then(|a| Some(a.to_string())) 
-                    .unwrap_or_else(|| "未知作者".to_string());
-
-                return (title, artist);
+                tag_title = tag.title().map(|t| t.to_string()).filter(|t| !t.trim().is_empty());
+                tag_artist = tag.artist().map(|a| a.to_string()).filter(|a| !a.trim().is_empty());
             }
         },
         Err(_) => {
             // 错误处理：文件可能不是支持的格式，或标签已损坏。
         }
     }
-    // 所有方法失败，则回退到默认值
-    ("未知".to_string(), "未知".to_string())
+    title_artist_fallback(path, pattern, tag_title, tag_artist)
+}
+
+/// 标签中的标题或艺术家缺失时，按文件名模式补全，仍无法匹配则以文件名（不含扩展名）作为标题
+fn title_artist_fallback(
+    path: &Path,
+    pattern: &FilenamePattern,
+    tag_title: Option<String>,
+    tag_artist: Option<String>,
+) -> (String, String) {
+    if let (Some(title), Some(artist)) = (&tag_title, &tag_artist) {
+        return (title.clone(), artist.clone());
+    }
+
+    // 标签不完整，回退到文件名解析
+    let mut stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    // 编号暂无排序用途，只用于显示时去掉
+    if pattern.strip_track_number
+        && let Some((_number, rest)) = split_track_number(&stem)
+    {
+        stem = rest.to_string();
+    }
+    let (name_title, name_artist) = pattern.match_stem(&stem).unwrap_or((None, None));
+    let title = tag_title.or(name_title).unwrap_or_else(|| stem.clone());
+    let artist = tag_artist.or(name_artist).unwrap_or_default();
+    (title, artist)
+}
+
+/// 把标题与艺术家写回文件的主标签（ID3v2、Vorbis Comment、MP4 等，由文件类型决定）；
+/// 没有主标签时新建一个。艺术家为空时删除该字段。调用前文件不能被解码器占用（Windows 下会写入失败）。
+pub fn write_title_artist(path: &Path, title: &str, artist: &str) -> Result<(), String> {
+    let mut tagged_file = read_from_path(path).map_err(|e| format!("无法读取标签: {}", e))?;
+    if tagged_file.primary_tag_mut().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let Some(tag) = tagged_file.primary_tag_mut() else {
+        return Err("该格式不支持写入标签".to_string());
+    };
+    tag.set_title(title.to_string());
+    if artist.is_empty() {
+        tag.remove_artist();
+    } else {
+        tag.set_artist(artist.to_string());
+    }
+    tagged_file.save_to_path(path, WriteOptions::default()).map_err(|e| format!("写入标签失败: {}", e))
+}
+
+/// ReplayGain 标签值（增益为 dB，峰值为线性幅度，1.0 为满幅）
+pub struct ReplayGainTags {
+    pub track_gain: f64,
+    pub track_peak: f64,
+    pub album_gain: f64,
+    pub album_peak: f64,
+}
+
+/// 主标签中是否已有单曲与专辑增益（gain 子命令据此跳过已分析的文件）
+pub fn has_replaygain(path: &Path) -> bool {
+    let Ok(tagged_file) = read_from_path(path) else { return false };
+    tagged_file
+        .primary_tag()
+        .is_some_and(|tag| tag.get_string(&ItemKey::ReplayGainTrackGain).is_some() && tag.get_string(&ItemKey::ReplayGainAlbumGain).is_some())
+}
+
+/// 把 ReplayGain 增益与峰值写入主标签（REPLAYGAIN_TRACK_GAIN 等，具体字段名由标签格式决定）；
+/// 没有主标签时新建一个
+pub fn write_replaygain(path: &Path, tags: &ReplayGainTags) -> Result<(), String> {
+    let mut tagged_file = read_from_path(path).map_err(|e| format!("无法读取标签: {}", e))?;
+    if tagged_file.primary_tag_mut().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let Some(tag) = tagged_file.primary_tag_mut() else {
+        return Err("该格式不支持写入标签".to_string());
+    };
+    let values = [
+        (ItemKey::ReplayGainTrackGain, format!("{:+.2} dB", tags.track_gain)),
+        (ItemKey::ReplayGainTrackPeak, format!("{:.6}", tags.track_peak)),
+        (ItemKey::ReplayGainAlbumGain, format!("{:+.2} dB", tags.album_gain)),
+        (ItemKey::ReplayGainAlbumPeak, format!("{:.6}", tags.album_peak)),
+    ];
+    for (key, value) in values {
+        if !tag.insert_text(key, value) {
+            return Err("该标签格式不支持 ReplayGain 字段".to_string());
+        }
+    }
+    tagged_file.save_to_path(path, WriteOptions::default()).map_err(|e| format!("写入标签失败: {}", e))
+}
+
+/// 读取主标签中的艺术家与专辑（用于 --artist/--album 筛选），缺失或为空时为 None
+pub fn get_artist_album_tags(path: &Path) -> (Option<String>, Option<String>) {
+    let Ok(tagged_file) = read_from_path(path) else {
+        return (None, None);
+    };
+    let Some(tag) = tagged_file.primary_tag().or_else(|| tagged_file.first_tag()) else {
+        return (None, None);
+    };
+    let non_empty = |s: Option<std::borrow::Cow<'_, str>>| s.map(|s| s.to_string()).filter(|s| !s.trim().is_empty());
+    (non_empty(tag.artist()), non_empty(tag.album()))
 }
 
 /// 使用 symphonia 库，通过探测媒体流来获取音频文件的总时长。
 pub fn get_total_duration(path: &Path) -> Duration {
     // 尝试打开文件并创建 MediaSource
-    let source = match std::fs::File::open(path) {
-        Ok(file) => Box::new(file) as Box<dyn MediaSource>,
-        Err(_) => return Duration::from_secs(0), // 无法打开则返回 0
-    };
-    
+    match File::open(path) {
+        Ok(file) => duration_from_source(Box::new(file), path),
+        Err(_) => Duration::from_secs(0), // 无法打开则返回 0
+    }
+}
+
+/// 从已打开的媒体源探测总时长；`path` 只用于提供扩展名提示
+fn duration_from_source(source: Box<dyn MediaSource>, path: &Path) -> Duration {
     // 创建媒体源流
     let media_source_stream = MediaSourceStream::new(source, Default::default());
     
@@ -71,14 +371,390 @@ pub fn get_total_duration(path: &Path) -> Duration {
         Err(_) => return Duration::from_secs(0),
     };
     
-    // 从默认音轨参数中计算总秒数
-    if let Some(track) = probe_result.format.default_track() {
-        if let (Some(n_frames), Some(sample_rate)) = (track.codec_params.n_frames, track.codec_params.sample_rate) {
-            // 计算总秒数: (总帧数 / 采样率)
-            let seconds = (n_frames as f64) / (sample_rate as f64);
-            return Duration::from_secs_f64(seconds);
+    // 从第一条音轨的参数中计算总秒数
+    if let Some(track) = first_audio_track(probe_result.format.as_ref())
+        && let Some(n_frames) = track.codec_params.n_frames
+    {
+        // 按时间基换算（Matroska 等容器的长度以毫秒等单位计，而不是采样帧）；没有时间基时为 总帧数 / 采样率
+        if let Some(time_base) = track.codec_params.time_base {
+            let time = time_base.calc_time(n_frames);
+            return Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac);
+        }
+        if let Some(sample_rate) = track.codec_params.sample_rate {
+            return Duration::from_secs_f64(n_frames as f64 / sample_rate as f64);
         }
     }
     
     Duration::from_secs(0)
-}
\ No newline at end of file
+}
+/// 不依赖扩展名，按文件内容探测音频编码格式，返回其简称（大写，如 MP3）。
+/// 无法识别为音频或本构建无法解码时返回 None。
+pub fn probe_audio_format(path: &Path) -> Option<String> {
+    probe_codec(path).filter(|(_, decodable)| *decodable).map(|(name, _)| name)
+}
+
+/// 容器能识别、但本构建没有解码器的常见编码（只用于诊断时给出名称）
+const UNDECODABLE_CODECS: [(CodecType, &str); 3] = [(CODEC_TYPE_OPUS, "OPUS"), (CODEC_TYPE_AAC, "AAC"), (CODEC_TYPE_ALAC, "ALAC")];
+
+/// 按文件内容探测第一条音轨的编码：(简称（大写）, 本构建能否解码)；无法识别为音频时返回 None
+pub fn probe_codec(path: &Path) -> Option<(String, bool)> {
+    let file = std::fs::File::open(path).ok()?;
+    let media_source_stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let probe_result = symphonia::default::get_probe()
+        .format(&Hint::new(), media_source_stream, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()?;
+    let codec = first_audio_track(probe_result.format.as_ref())?.codec_params.codec;
+    match symphonia::default::get_codecs().get_codec(codec) {
+        Some(descriptor) => Some((descriptor.short_name.to_uppercase(), true)),
+        None => UNDECODABLE_CODECS.iter().find(|(c, _)| *c == codec).map(|(_, name)| (name.to_string(), false)),
+    }
+}
+
+// --- FLAC 内嵌 CUE ---
+const FLAC_BLOCK_STREAMINFO: u8 = 0;
+const FLAC_BLOCK_VORBIS_COMMENT: u8 = 4;
+const FLAC_BLOCK_CUESHEET: u8 = 5;
+/// CUE 时间戳中每秒的帧数（CD 帧）
+const CUE_FRAMES_PER_SECOND: u64 = 75;
+
+/// 读取 FLAC 内嵌的 CUE 分轨信息，返回按起始时间排序的 (起点, 标题)。
+/// 优先使用 Vorbis 注释中的 `CUESHEET` 文本（带标题），其次使用二进制 CUESHEET 块（无标题）。
+/// 非 FLAC 文件、没有内嵌 CUE 或分轨不足两首时返回 None。
+pub fn read_embedded_cuesheet(path: &Path) -> Option<Vec<(Duration, String)>> {
+    let blocks = read_flac_metadata_blocks(path)?;
+    let from_text = blocks
+        .iter()
+        .find(|(kind, _)| *kind == FLAC_BLOCK_VORBIS_COMMENT)
+        .and_then(|(_, data)| find_vorbis_comment(data, "CUESHEET"))
+        .and_then(|text| parse_cue_text(&text));
+    let mut tracks = from_text.or_else(|| {
+        let sample_rate = blocks
+            .iter()
+            .find(|(kind, _)| *kind == FLAC_BLOCK_STREAMINFO)
+            .and_then(|(_, data)| streaminfo_sample_rate(data))?;
+        let (_, data) = blocks.iter().find(|(kind, _)| *kind == FLAC_BLOCK_CUESHEET)?;
+        parse_cuesheet_block(data, sample_rate)
+    })?;
+    tracks.sort_by_key(|(start, _)| *start);
+    tracks.dedup_by_key(|(start, _)| *start);
+    (tracks.len() >= 2).then_some(tracks)
+}
+
+/// 读取 FLAC 文件头部的所有元数据块，返回 (块类型, 内容)；不是 FLAC 时返回 None
+fn read_flac_metadata_blocks(path: &Path) -> Option<Vec<(u8, Vec<u8>)>> {
+    use std::io::Read;
+    let mut file = std::io::BufReader::new(std::fs::File::open(path).ok()?);
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic).ok()?;
+    if &magic != b"fLaC" {
+        return None;
+    }
+    let mut blocks = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header).ok()?;
+        let is_last = header[0] & 0x80 != 0;
+        let kind = header[0] & 0x7f;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut data = vec![0u8; len];
+        file.read_exact(&mut data).ok()?;
+        // 图片等与分轨无关的大块不保留
+        if matches!(kind, FLAC_BLOCK_STREAMINFO | FLAC_BLOCK_VORBIS_COMMENT | FLAC_BLOCK_CUESHEET) {
+            blocks.push((kind, data));
+        }
+        if is_last {
+            return Some(blocks);
+        }
+    }
+}
+
+/// 从 STREAMINFO 块中取出采样率（第 10 字节起的 20 位）
+fn streaminfo_sample_rate(data: &[u8]) -> Option<u32> {
+    let bytes = data.get(10..13)?;
+    let rate = ((bytes[0] as u32) << 12) | ((bytes[1] as u32) << 4) | ((bytes[2] as u32) >> 4);
+    (rate > 0).then_some(rate)
+}
+
+/// 在 Vorbis 注释块中查找指定字段（字段名不区分大小写）
+fn find_vorbis_comment(data: &[u8], field: &str) -> Option<String> {
+    let read_u32 = |pos: usize| -> Option<u32> { Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?)) };
+    let vendor_len = read_u32(0)? as usize;
+    let mut pos = 4 + vendor_len;
+    let count = read_u32(pos)?;
+    pos += 4;
+    for _ in 0..count {
+        let len = read_u32(pos)? as usize;
+        pos += 4;
+        let comment = String::from_utf8_lossy(data.get(pos..pos + len)?);
+        pos += len;
+        if let Some((name, value)) = comment.split_once('=')
+            && name.eq_ignore_ascii_case(field)
+        {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// 解析 CUE 文本中的 `TRACK`、`TITLE`、`INDEX 01 mm:ss:ff`
+fn parse_cue_text(text: &str) -> Option<Vec<(Duration, String)>> {
+    let mut tracks = Vec::new();
+    let mut in_track = false;
+    let mut title = String::new();
+    for line in text.lines().map(str::trim) {
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match keyword.to_ascii_uppercase().as_str() {
+            "TRACK" => {
+                in_track = true;
+                title.clear();
+            }
+            "TITLE" if in_track => title = rest.trim().trim_matches('"').to_string(),
+            "INDEX" if in_track => {
+                let mut parts = rest.split_whitespace();
+                if parts.next().and_then(|n| n.parse::<u32>().ok()) == Some(1)
+                    && let Some(start) = parts.next().and_then(parse_cue_time)
+                {
+                    tracks.push((start, title.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+    (!tracks.is_empty()).then_some(tracks)
+}
+
+/// 解析 CUE 时间戳 `mm:ss:ff`（ff 为 1/75 秒的帧）
+fn parse_cue_time(s: &str) -> Option<Duration> {
+    let mut parts = s.split(':').map(|p| p.parse::<u64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || seconds >= 60 || frames >= CUE_FRAMES_PER_SECOND {
+        return None;
+    }
+    let total_frames = (minutes * 60 + seconds) * CUE_FRAMES_PER_SECOND + frames;
+    Some(Duration::from_secs_f64(total_frames as f64 / CUE_FRAMES_PER_SECOND as f64))
+}
+
+/// 解析二进制 CUESHEET 块：每个音轨的起点为音轨偏移 + 1 号索引点偏移（以采样为单位）。
+/// 引出音轨（编号 170 或 255）不作为分轨。
+fn parse_cuesheet_block(data: &[u8], sample_rate: u32) -> Option<Vec<(Duration, String)>> {
+    let read_u64 = |pos: usize| -> Option<u64> { Some(u64::from_be_bytes(data.get(pos..pos + 8)?.try_into().ok()?)) };
+    // 128 字节目录号 + 8 字节引入采样数 + 1 字节标志 + 258 字节保留
+    let mut pos = 128 + 8 + 1 + 258;
+    let track_count = *data.get(pos)?;
+    pos += 1;
+    let mut tracks = Vec::new();
+    for _ in 0..track_count {
+        let offset = read_u64(pos)?;
+        let number = *data.get(pos + 8)?;
+        // 8 字节偏移 + 1 字节编号 + 12 字节 ISRC + 1 字节标志 + 13 字节保留
+        pos += 8 + 1 + 12 + 1 + 13;
+        let index_count = *data.get(pos)?;
+        pos += 1;
+        let mut start = None;
+        for _ in 0..index_count {
+            let index_offset = read_u64(pos)?;
+            if *data.get(pos + 8)? == 1 {
+                start = Some(offset + index_offset);
+            }
+            pos += 8 + 1 + 3;
+        }
+        if number != 170
+            && number != 255
+            && let Some(samples) = start
+        {
+            tracks.push((Duration::from_secs_f64(samples as f64 / sample_rate as f64), String::new()));
+        }
+    }
+    (!tracks.is_empty()).then_some(tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{flac_cuesheet, flac_streaminfo, flac_vorbis_comment, flac_with_blocks, mkv_with_video, silent_wav, TempDir};
+    use std::fs;
+
+    const CUE_TEXT: &str = "PERFORMER \"Various\"\r\n\
+        TITLE \"Live Album\"\r\n\
+        FILE \"album.flac\" WAVE\r\n\
+        \x20 TRACK 01 AUDIO\r\n\
+        \x20   TITLE \"Opening\"\r\n\
+        \x20   INDEX 01 00:00:00\r\n\
+        \x20 TRACK 02 AUDIO\r\n\
+        \x20   TITLE \"第二首\"\r\n\
+        \x20   INDEX 00 03:58:00\r\n\
+        \x20   INDEX 01 04:00:37\r\n\
+        \x20 TRACK 03 AUDIO\r\n\
+        \x20   INDEX 01 61:30:74\r\n";
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn embedded_cue_text_provides_titles() {
+        let dir = TempDir::new();
+        // 文本与二进制 CUESHEET 同时存在时使用带标题的文本
+        let flac = dir.write(
+            "album.flac",
+            flac_with_blocks(&[
+                (FLAC_BLOCK_STREAMINFO, flac_streaminfo(44100)),
+                (FLAC_BLOCK_VORBIS_COMMENT, flac_vorbis_comment(&["TITLE=Live Album", &format!("cuesheet={}", CUE_TEXT)])),
+                (FLAC_BLOCK_CUESHEET, flac_cuesheet(&[(1, 0, &[(1, 0)]), (2, 44100, &[(1, 0)])])),
+            ]),
+        );
+        assert_eq!(
+            read_embedded_cuesheet(&flac).unwrap(),
+            [(secs(0.0), "Opening".to_string()), (secs(240.0 + 37.0 / 75.0), "第二首".to_string()), (secs(3690.0 + 74.0 / 75.0), String::new())]
+        );
+    }
+
+    #[test]
+    fn binary_cuesheet_uses_sample_offsets() {
+        let dir = TempDir::new();
+        let flac = dir.write(
+            "album.flac",
+            flac_with_blocks(&[
+                (FLAC_BLOCK_STREAMINFO, flac_streaminfo(48000)),
+                (1, vec![0; 16]), // PADDING
+                (
+                    FLAC_BLOCK_CUESHEET,
+                    flac_cuesheet(&[
+                        (1, 0, &[(1, 0)]),
+                        // 起点为音轨偏移 + 1 号索引点偏移，0 号索引（前导间隙）不算
+                        (2, 480_000, &[(0, 0), (1, 24_000)]),
+                        (3, 9_600_000, &[(1, 0)]),
+                        (170, 14_400_000, &[(1, 0)]),
+                    ]),
+                ),
+            ]),
+        );
+        assert_eq!(read_embedded_cuesheet(&flac).unwrap(), [(secs(0.0), String::new()), (secs(10.5), String::new()), (secs(200.0), String::new())]);
+    }
+
+    #[test]
+    fn cuesheet_needs_a_flac_with_two_tracks() {
+        let dir = TempDir::new();
+        let single = dir.write(
+            "single.flac",
+            flac_with_blocks(&[(FLAC_BLOCK_STREAMINFO, flac_streaminfo(44100)), (FLAC_BLOCK_CUESHEET, flac_cuesheet(&[(1, 0, &[(1, 0)]), (170, 44100, &[])]))]),
+        );
+        assert_eq!(read_embedded_cuesheet(&single), None);
+        let plain = dir.write("plain.flac", flac_with_blocks(&[(FLAC_BLOCK_STREAMINFO, flac_streaminfo(44100))]));
+        assert_eq!(read_embedded_cuesheet(&plain), None);
+        assert_eq!(read_embedded_cuesheet(&dir.write("a.wav", silent_wav(10))), None);
+        // 元数据块被截断时不应出错
+        let mut truncated = flac_with_blocks(&[(FLAC_BLOCK_STREAMINFO, flac_streaminfo(44100)), (FLAC_BLOCK_CUESHEET, flac_cuesheet(&[(1, 0, &[(1, 0)]), (2, 44100, &[(1, 0)])]))]);
+        truncated.truncate(truncated.len() - 20);
+        assert_eq!(read_embedded_cuesheet(&dir.write("truncated.flac", truncated)), None);
+    }
+
+    #[test]
+    fn written_tags_round_trip() {
+        let dir = TempDir::new();
+        let fixture = dir.write("fixture.wav", silent_wav(1500));
+        let pattern = FilenamePattern::default();
+        // 每个用例都在独立的副本上写入，夹具本身保持不变
+        for (name, title, artist) in [
+            ("ascii.wav", "Station ID", "Radio 1"),
+            ("cjk.wav", "第一章：序曲 🎵", "周杰伦 & 方文山"),
+            ("no-artist.wav", "Jingle – 30s", ""),
+        ] {
+            let copy = dir.join(name);
+            fs::copy(&fixture, &copy).unwrap();
+            write_title_artist(&copy, title, artist).unwrap();
+            assert_eq!(get_title_artist_info(&copy, &pattern), (title.to_string(), artist.to_string()), "{}", name);
+            let metadata = read_track_metadata(&copy, &pattern);
+            assert_eq!((metadata.title.as_str(), metadata.artist.as_str()), (title, artist));
+            // 写入标签不影响音频本身
+            assert_eq!(metadata.duration, Duration::from_millis(1500));
+        }
+
+        // 再次写入覆盖原有的值，艺术家为空时删除该字段
+        let copy = dir.join("cjk.wav");
+        write_title_artist(&copy, "改过的标题", "").unwrap();
+        assert_eq!(get_title_artist_info(&copy, &pattern), ("改过的标题".to_string(), String::new()));
+        assert_eq!(fs::read(&fixture).unwrap(), silent_wav(1500));
+        assert!(write_title_artist(&dir.join("missing.wav"), "x", "y").is_err());
+    }
+
+    #[test]
+    fn video_container_uses_its_audio_track() {
+        let dir = TempDir::new();
+        let video = dir.write("clip.mkv", mkv_with_video(Some(1500)));
+        // 视频轨在前，格式与时长取自后面的音轨
+        assert_eq!(probe_audio_format(&video).as_deref(), Some("PCM_S16LE"));
+        assert_eq!(get_total_duration(&video), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn video_without_audio_has_nothing_to_play() {
+        let dir = TempDir::new();
+        let video = dir.write("silent-film.mkv", mkv_with_video(None));
+        assert_eq!(probe_audio_format(&video), None);
+        assert_eq!(get_total_duration(&video), Duration::ZERO);
+    }
+
+    #[test]
+    fn merged_read_matches_separate_reads() {
+        let dir = TempDir::new();
+        let tagged = dir.write("tagged.wav", silent_wav(700));
+        write_title_artist(&tagged, "标签标题", "标签艺术家").unwrap();
+        let fixtures = [
+            dir.write("Tester - Ping.wav", silent_wav(1200)),
+            dir.write("01. Track Number.wav", silent_wav(300)),
+            tagged,
+            dir.write("no extension", silent_wav(500)),
+            dir.write(
+                "comments.flac",
+                flac_with_blocks(&[(FLAC_BLOCK_STREAMINFO, flac_streaminfo(44100)), (FLAC_BLOCK_VORBIS_COMMENT, flac_vorbis_comment(&["TITLE=Flac Title", "ARTIST=Flac Artist"]))]),
+            ),
+            dir.write("clip.mkv", mkv_with_video(Some(900))),
+            dir.write("empty.mp3", b""),
+            dir.join("missing.ogg"),
+        ];
+        for pattern in [FilenamePattern::default(), FilenamePattern::parse("{title} - {artist}").unwrap()] {
+            for path in &fixtures {
+                let merged = read_track_metadata(path, &pattern);
+                let (title, artist) = get_title_artist_info(path, &pattern);
+                assert_eq!((merged.title, merged.artist, merged.duration), (title, artist, get_total_duration(path)), "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn strips_leading_track_numbers_from_file_names() {
+        // (文件名, 默认模式下的 (标题, 艺术家))
+        let cases = [
+            ("01 - Song Title.mp3", "Song Title", ""),
+            ("02. Song Title.flac", "Song Title", ""),
+            ("03.Song Title.ogg", "Song Title", ""),
+            ("04 Song Title.mp3", "Song Title", ""),
+            ("05-Song Title.mp3", "Song Title", ""),
+            ("06_Song Title.mp3", "Song Title", ""),
+            ("101. Disc One Closer.mp3", "Disc One Closer", ""),
+            ("07 - 周杰伦 - 晴天.flac", "晴天", "周杰伦"),
+            ("Artist - 08 Not A Prefix.mp3", "08 Not A Prefix", "Artist"),
+            // 一位或四位数字多半是标题的一部分
+            ("7 Rings.mp3", "7 Rings", ""),
+            ("2001 A Space Odyssey.mp3", "2001 A Space Odyssey", ""),
+            // 去掉编号后为空、或编号后没有分隔时保留原样
+            ("01.mp3", "01", ""),
+            ("09 - .mp3", "09 - ", ""),
+            ("10cc - Dreadlock Holiday.mp3", "Dreadlock Holiday", "10cc"),
+        ];
+        let pattern = FilenamePattern::default();
+        for (name, title, artist) in cases {
+            assert_eq!(title_artist_fallback(Path::new(name), &pattern, None, None), (title.to_string(), artist.to_string()), "{}", name);
+        }
+
+        // --keep-track-number 保留编号
+        let keep = FilenamePattern::default().keep_track_number();
+        assert_eq!(title_artist_fallback(Path::new("01 - Song Title.mp3"), &keep, None, None), ("Song Title".to_string(), "01".to_string()));
+        assert_eq!(title_artist_fallback(Path::new("04 Song Title.mp3"), &keep, None, None), ("04 Song Title".to_string(), String::new()));
+        // 模式中含 {track} 时由模式自行处理
+        let track = FilenamePattern::parse("{track} - {title}").unwrap();
+        assert_eq!(title_artist_fallback(Path::new("01 - Song Title.mp3"), &track, None, None), ("Song Title".to_string(), String::new()));
+        assert_eq!(split_track_number("12 Song"), Some((12, "Song")));
+    }
+}
@@ -0,0 +1,100 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use crate::metadata::Template;
+
+/// 默认的窗口名模板
+pub const DEFAULT_WINDOW_TITLE_FORMAT: &str = "♪ {title}";
+
+/// 当前所在的终端复用器
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    /// 通过环境变量检测：tmux 设置 TMUX，screen 设置 STY（tmux 优先，嵌套时以内层为准）
+    fn detect() -> Option<Self> {
+        let set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+        if set("TMUX") {
+            Some(Multiplexer::Tmux)
+        } else if set("STY") {
+            Some(Multiplexer::Screen)
+        } else {
+            None
+        }
+    }
+}
+
+/// 执行 tmux 命令；设置了 TMUX_PANE 时指定目标窗口，避免改到用户当前切换到的其他窗口
+fn tmux(subcommand: &str, args: &[&str]) -> Option<String> {
+    let mut command = Command::new("tmux");
+    command.arg(subcommand);
+    if let Ok(pane) = std::env::var("TMUX_PANE") {
+        command.args(["-t", &pane]);
+    }
+    command.args(args);
+    let output = command.stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+}
+
+/// `--set-tmux-title`：切歌时把 tmux/screen 的窗口名设置为按模板生成的标题，退出时恢复。
+/// 未检测到终端复用器时不做任何事。
+pub struct WindowTitle {
+    mux: Option<Multiplexer>,
+    template: Template,
+    /// 原来的窗口名（退出时恢复）
+    original: Option<String>,
+}
+
+impl WindowTitle {
+    pub fn new(template: Template, verbose: bool) -> Self {
+        let mux = Multiplexer::detect();
+        let original = match mux {
+            Some(Multiplexer::Tmux) => tmux("display-message", &["-p", "#W"]),
+            // screen 无法查询当前窗口名，退出时恢复为 shell 名称
+            Some(Multiplexer::Screen) => std::env::var("SHELL")
+                .ok()
+                .and_then(|s| s.rsplit('/').next().map(str::to_string)),
+            None => None,
+        };
+        if verbose {
+            match mux {
+                Some(m) => eprintln!("[详细]检测到终端复用器 {:?}，将同步窗口名", m),
+                None => eprintln!("[详细]未检测到 tmux/screen，--set-tmux-title 不生效"),
+            }
+        }
+        WindowTitle { mux, template, original }
+    }
+
+    /// 按模板设置窗口名，`lookup` 提供占位符的取值
+    pub fn update(&self, lookup: impl Fn(&str) -> Option<String>) {
+        if self.mux.is_some() {
+            self.set(&self.template.render(lookup));
+        }
+    }
+
+    fn set(&self, name: &str) {
+        match self.mux {
+            Some(Multiplexer::Tmux) => {
+                tmux("rename-window", &[name]);
+            }
+            Some(Multiplexer::Screen) => {
+                // screen 的窗口名转义序列：ESC k 名称 ESC \
+                let mut stdout = io::stdout();
+                let _ = write!(stdout, "\x1bk{}\x1b\\", name);
+                let _ = stdout.flush();
+            }
+            None => {}
+        }
+    }
+}
+
+impl Drop for WindowTitle {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take() {
+            self.set(&original);
+        }
+    }
+}
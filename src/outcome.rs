@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+/// 判定「听过」的阈值：播放进度达到时长的该比例即算听过
+const PLAYED_FRACTION: f64 = 0.5;
+/// 判定「听过」的阈值：播放超过该时长即算听过（长曲目、或时长未知时使用）
+const PLAYED_MIN_POSITION: Duration = Duration::from_secs(240);
+
+/// 自然结束时允许的时长误差：实际播放到的位置比报告的时长短不超过 3 秒或时长的 2%（取较大者）时视为正常
+const EARLY_END_TOLERANCE: Duration = Duration::from_secs(3);
+const EARLY_END_TOLERANCE_FRACTION: f64 = 0.02;
+
+/// 自然结束时解码器是否明显早于报告的时长结束（如 VBR 头信息错误的 MP3 报告的时长偏长）。
+/// 仍按正常结束处理，只用于记录诊断与修正时长；时长未知时返回 false
+pub fn ended_early(position: Duration, duration: Duration) -> bool {
+    let tolerance = duration.mul_f64(EARLY_END_TOLERANCE_FRACTION).max(EARLY_END_TOLERANCE);
+    duration.saturating_sub(position) > tolerance
+}
+
+/// 一首歌结束时的结果：最近播放等功能统一按它区分「听过」与「跳过」
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaybackOutcome {
+    /// 自然播放结束
+    Completed,
+    /// 在达到「听过」阈值前被切走（切走时的位置）
+    SkippedEarly(Duration),
+    /// 达到「听过」阈值后被切走（切走时的位置）
+    SkippedLate(Duration),
+    /// 加载或解码失败（原因）
+    Failed(String),
+}
+
+impl PlaybackOutcome {
+    /// 根据结束方式与位置判定结果；`duration` 为 0 表示时长未知
+    pub fn classify(finished: bool, position: Duration, duration: Duration) -> Self {
+        if finished {
+            return PlaybackOutcome::Completed;
+        }
+        let threshold = if duration.is_zero() {
+            PLAYED_MIN_POSITION
+        } else {
+            duration.mul_f64(PLAYED_FRACTION).min(PLAYED_MIN_POSITION)
+        };
+        if position >= threshold {
+            PlaybackOutcome::SkippedLate(position)
+        } else {
+            PlaybackOutcome::SkippedEarly(position)
+        }
+    }
+
+    /// 是否算作「听过」
+    pub fn counts_as_played(&self) -> bool {
+        matches!(self, PlaybackOutcome::Completed | PlaybackOutcome::SkippedLate(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PlaybackOutcome::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn classifies_by_position_and_duration() {
+        // (自然结束, 位置, 时长, 结果)
+        let cases = [
+            (true, secs(0), secs(180), Completed),
+            (true, secs(10), secs(0), Completed),
+            // 短曲：一半为界
+            (false, secs(0), secs(180), SkippedEarly(secs(0))),
+            (false, secs(89), secs(180), SkippedEarly(secs(89))),
+            (false, secs(90), secs(180), SkippedLate(secs(90))),
+            (false, secs(179), secs(180), SkippedLate(secs(179))),
+            // 长曲：播放 4 分钟即可
+            (false, secs(239), secs(3600), SkippedEarly(secs(239))),
+            (false, secs(240), secs(3600), SkippedLate(secs(240))),
+            // 时长恰为 8 分钟时两个阈值相同
+            (false, secs(239), secs(480), SkippedEarly(secs(239))),
+            (false, secs(240), secs(480), SkippedLate(secs(240))),
+            // 时长未知：只看播放时长
+            (false, secs(239), secs(0), SkippedEarly(secs(239))),
+            (false, secs(240), secs(0), SkippedLate(secs(240))),
+        ];
+        for (finished, position, duration, expected) in cases {
+            assert_eq!(PlaybackOutcome::classify(finished, position, duration), expected, "{:?} / {:?}", position, duration);
+        }
+    }
+
+    #[test]
+    fn only_completed_and_late_skips_count() {
+        assert!(Completed.counts_as_played());
+        assert!(SkippedLate(secs(100)).counts_as_played());
+        assert!(!SkippedEarly(secs(5)).counts_as_played());
+        assert!(!Failed("解码失败".to_string()).counts_as_played());
+    }
+
+    #[test]
+    fn early_end_allows_small_errors() {
+        // (位置, 时长, 是否提前结束)：误差上限为 3 秒与时长的 2% 中较大者
+        let cases = [
+            (secs(177), secs(180), false),
+            (secs(176), secs(180), true),
+            (secs(3528), secs(3600), false),
+            (secs(3527), secs(3600), true),
+            (secs(0), secs(0), false),
+            (secs(200), secs(180), false),
+        ];
+        for (position, duration, expected) in cases {
+            assert_eq!(ended_early(position, duration), expected, "{:?} / {:?}", position, duration);
+        }
+    }
+
+    #[test]
+    fn early_end_tolerance_is_the_larger_of_three_seconds_and_two_percent() {
+        let secs = Duration::from_secs;
+        // 150 秒时两种容差相等
+        assert!(!ended_early(secs(147), secs(150)));
+        assert!(ended_early(Duration::from_millis(146_999), secs(150)));
+        // 更短的歌按 3 秒
+        assert!(!ended_early(secs(57), secs(60)));
+        assert!(ended_early(secs(56), secs(60)));
+        // 更长的歌按 2%：10 分钟允许 12 秒
+        assert!(!ended_early(secs(588), secs(600)));
+        assert!(ended_early(Duration::from_millis(587_999), secs(600)));
+        // 播放到时长以后（报告的时长偏短）不算提前
+        assert!(!ended_early(secs(605), secs(600)));
+    }
+}
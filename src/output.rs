@@ -0,0 +1,281 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+// 音频播放库（cpal 为其底层的音频设备库）
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{
+    self, BufferSize, FromSample, SampleFormat, SampleRate, SizedSample, StreamConfig, SupportedBufferSize, SupportedStreamConfig,
+};
+use rodio::source::UniformSourceIterator;
+use rodio::{OutputStream, Sink};
+
+/// 暂停超过 `--pause-timeout` 后的处理方式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseTimeoutAction {
+    /// 退出播放器
+    Exit,
+    /// 释放音频设备进入休眠，按键后重新打开设备并从暂停处继续
+    Release,
+}
+
+/// verbose 模式下等待第一次音频回调（用于报告实际缓冲大小）的最长时间
+const NEGOTIATION_WAIT: Duration = Duration::from_millis(500);
+
+/// 打开的音频输出；播放期间必须保持存活（只持有、不读取）
+#[allow(dead_code)]
+pub enum AudioOutput {
+    /// rodio 默认方式打开的输出流（设备默认缓冲大小）
+    Default(OutputStream),
+    /// 指定缓冲大小、自行构建的 cpal 输出流
+    Custom(cpal::Stream),
+}
+
+/// 第一次音频回调时测得的实际缓冲信息
+#[derive(Clone, Copy)]
+struct Negotiated {
+    frames: usize,
+    /// 回调时刻到该缓冲实际出声的时间差（部分后端不提供）
+    delay: Option<Duration>,
+}
+
+/// 输出流的配置请求：缓冲延迟（`--latency`/`--buffer-ms`）与采样率（`--sample-rate`），
+/// 都为 None 时沿用 rodio 的默认方式打开
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputRequest {
+    pub latency_ms: Option<u32>,
+    pub sample_rate: Option<u32>,
+}
+
+impl OutputRequest {
+    fn is_default(&self) -> bool {
+        self.latency_ms.is_none() && self.sample_rate.is_none()
+    }
+}
+
+/// 按请求在设备上选定的配置
+struct ChosenConfig {
+    supported: SupportedStreamConfig,
+    /// 缓冲帧数；None 表示设备默认
+    frames: Option<u32>,
+    /// 请求无法满足、已回退时的提示
+    warnings: Vec<String>,
+}
+
+impl ChosenConfig {
+    fn describe(&self) -> String {
+        format!(
+            "{}Hz，{} 声道，{}，缓冲 {}",
+            self.supported.sample_rate().0,
+            self.supported.channels(),
+            self.supported.sample_format(),
+            self.frames.map_or("设备默认".to_string(), |frames| format!("{} 帧", frames)),
+        )
+    }
+}
+
+/// 在设备上选择配置：采样率优先在与默认配置声道数、样本格式相同的范围中查找，设备不支持时回退为默认采样率；
+/// 缓冲帧数按延迟换算，并限制在设备支持的范围内
+fn choose_config(device: &cpal::Device, request: OutputRequest) -> Result<ChosenConfig, Box<dyn std::error::Error>> {
+    let default = device.default_output_config()?;
+    let mut warnings = Vec::new();
+    let supported = match request.sample_rate {
+        Some(rate) if rate != default.sample_rate().0 => {
+            let ranges: Vec<_> = device.supported_output_configs()?.collect();
+            let preferred = ranges.iter().filter(|r| r.channels() == default.channels() && r.sample_format() == default.sample_format());
+            match preferred.chain(ranges.iter()).find_map(|r| (*r).try_with_sample_rate(SampleRate(rate))) {
+                Some(config) => config,
+                None => {
+                    warnings.push(format!("设备不支持 {}Hz 采样率，改用默认的 {}Hz", rate, default.sample_rate().0));
+                    default
+                }
+            }
+        }
+        _ => default,
+    };
+    let frames = request.latency_ms.map(|latency_ms| {
+        let requested = (supported.sample_rate().0 as u64 * latency_ms as u64 / 1000).max(1) as u32;
+        let frames = match *supported.buffer_size() {
+            SupportedBufferSize::Range { min, max } => requested.clamp(min, max),
+            SupportedBufferSize::Unknown => requested,
+        };
+        if frames != requested {
+            warnings.push(format!("请求的缓冲 {} 帧超出设备支持范围，已调整为 {} 帧", requested, frames));
+        }
+        frames
+    });
+    Ok(ChosenConfig { supported, frames, warnings })
+}
+
+/// 不打开输出流，给出按请求会使用的输出配置（用于 --build-info 与 doctor）
+pub fn describe_output(request: OutputRequest) -> Result<String, String> {
+    let device = cpal::default_host().default_output_device().ok_or("未找到可用的音频输出设备")?;
+    let chosen = choose_config(&device, request).map_err(|e| e.to_string())?;
+    let mut text = chosen.describe();
+    if request.is_default() {
+        text.push_str("（设备默认配置）");
+    }
+    for warning in &chosen.warnings {
+        text.push_str(&format!("；{}", warning));
+    }
+    Ok(text)
+}
+
+/// `--list-devices`：列出默认音频主机的输出设备（`*` 为默认设备），`verbose` 时附上各设备支持的配置
+pub fn print_devices(verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+    println!("音频主机: {}", host.id().name());
+    for device in host.output_devices()? {
+        let name = device.name().unwrap_or_else(|_| "（无法读取名称）".to_string());
+        let mark = if default_name.as_deref() == Some(name.as_str()) { "*" } else { " " };
+        println!("{} {}", mark, name);
+        if !verbose {
+            continue;
+        }
+        match device.supported_output_configs() {
+            Ok(configs) => {
+                for config in configs {
+                    let buffer = match *config.buffer_size() {
+                        SupportedBufferSize::Range { min, max } => format!("{}-{} 帧", min, max),
+                        SupportedBufferSize::Unknown => "未知".to_string(),
+                    };
+                    println!(
+                        "    {} 声道  {}-{}Hz  {}  缓冲 {}",
+                        config.channels(),
+                        config.min_sample_rate().0,
+                        config.max_sample_rate().0,
+                        config.sample_format(),
+                        buffer
+                    );
+                }
+            }
+            Err(e) => println!("    无法读取支持的配置: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// 默认音频主机的输出设备名称（与 `--list-devices` 的枚举相同）及其中默认设备的位置
+pub fn output_device_names() -> Result<(Vec<String>, Option<usize>), Box<dyn std::error::Error>> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+    let names: Vec<String> = host.output_devices()?.filter_map(|d| d.name().ok()).collect();
+    let default_index = names.iter().position(|name| Some(name) == default_name.as_ref());
+    Ok((names, default_index))
+}
+
+/// 按名称查找输出设备；None 时为默认设备
+fn find_device(name: Option<&str>) -> Result<cpal::Device, Box<dyn std::error::Error>> {
+    let host = cpal::default_host();
+    match name {
+        None => Ok(host.default_output_device().ok_or("未找到可用的音频输出设备")?),
+        Some(name) => Ok(host
+            .output_devices()?
+            .find(|d| d.name().is_ok_and(|n| n == name))
+            .ok_or_else(|| format!("未找到音频输出设备 {}", name))?),
+    }
+}
+
+/// 打开默认输出设备并返回 Sink。
+/// 请求为默认时沿用 rodio 的默认配置；否则按请求选择采样率与缓冲大小（无法满足时回退并提示），
+/// 自行构建 cpal 输出流（设备可能进一步调整缓冲，verbose 模式下报告实际值）。
+pub fn open_output(request: OutputRequest, verbose: bool) -> Result<(AudioOutput, Sink), Box<dyn std::error::Error>> {
+    open_output_on(None, request, verbose)
+}
+
+/// 同 open_output，但打开指定名称的输出设备（None 时为默认设备）
+pub fn open_output_on(
+    device_name: Option<&str>,
+    request: OutputRequest,
+    verbose: bool,
+) -> Result<(AudioOutput, Sink), Box<dyn std::error::Error>> {
+    if request.is_default() {
+        let (stream, handle) = match device_name {
+            None => OutputStream::try_default()?,
+            Some(_) => OutputStream::try_from_device(&find_device(device_name)?)?,
+        };
+        return Ok((AudioOutput::Default(stream), Sink::try_new(&handle)?));
+    }
+
+    let device = find_device(device_name)?;
+    let chosen = choose_config(&device, request)?;
+    for warning in &chosen.warnings {
+        eprintln!("[警告]{}", warning);
+    }
+    let supported = &chosen.supported;
+    let sample_rate = supported.sample_rate().0;
+    let channels = supported.channels();
+    let buffer_size = chosen.frames.map_or(BufferSize::Default, BufferSize::Fixed);
+    let config = StreamConfig { channels, sample_rate: SampleRate(sample_rate), buffer_size };
+
+    // Sink 的输出队列直接由输出回调拉取，转换为设备的声道数与采样率
+    let (sink, queue) = Sink::new_idle();
+    let source = UniformSourceIterator::<_, f32>::new(queue, channels, sample_rate);
+    let negotiated = Arc::new(Mutex::new(None));
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, source, negotiated.clone())?,
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, source, negotiated.clone())?,
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, source, negotiated.clone())?,
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, source, negotiated.clone())?,
+        other => return Err(format!("不支持的输出采样格式: {}", other).into()),
+    };
+    stream.play()?;
+
+    if verbose {
+        let deadline = Instant::now() + NEGOTIATION_WAIT;
+        let result = loop {
+            let current = *negotiated.lock().unwrap_or_else(|e| e.into_inner());
+            if current.is_some() || Instant::now() >= deadline {
+                break current;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let ms = |frames: usize| frames as f64 * 1000.0 / sample_rate as f64;
+        let requested = match (request.latency_ms, chosen.frames) {
+            (Some(latency_ms), Some(frames)) => format!("{}ms（{} 帧）", latency_ms, frames),
+            _ => "设备默认".to_string(),
+        };
+        eprintln!("[详细]输出配置: {}", chosen.describe());
+        match result {
+            Some(n) => eprintln!(
+                "[详细]输出缓冲: 请求 {}，实际 {} 帧 ≈ {:.1}ms{}",
+                requested,
+                n.frames,
+                ms(n.frames),
+                n.delay.map_or(String::new(), |d| format!("，设备报告的输出延迟 {:.1}ms", d.as_secs_f64() * 1000.0)),
+            ),
+            None => eprintln!("[详细]输出缓冲: 请求 {}，未能测得实际值", requested),
+        }
+    }
+    Ok((AudioOutput::Custom(stream), sink))
+}
+
+/// 以指定的样本类型构建输出流，回调中从 `source` 拉取样本，队列为空时输出静音
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut source: impl Iterator<Item = f32> + Send + 'static,
+    negotiated: Arc<Mutex<Option<Negotiated>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels.max(1) as usize;
+    let mut reported = false;
+    device.build_output_stream::<T, _, _>(
+        config,
+        move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+            if !reported {
+                reported = true;
+                let timestamp = info.timestamp();
+                let delay = timestamp.playback.duration_since(&timestamp.callback);
+                *negotiated.lock().unwrap_or_else(|e| e.into_inner()) = Some(Negotiated { frames: data.len() / channels, delay });
+            }
+            for sample in data.iter_mut() {
+                *sample = T::from_sample_(source.next().unwrap_or(0.0));
+            }
+        },
+        |err| eprintln!("[错误]音频输出流出错: {}", err),
+        None,
+    )
+}
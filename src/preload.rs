@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// 某个索引最近一次预加载请求的状态
+struct Slot<T> {
+    generation: u64,
+    /// 已交付、尚未取走的结果
+    result: Option<T>,
+    /// 预加载线程正在重试（如网络共享暂时断开），等待方至少等到这一刻
+    extended_until: Option<Instant>,
+}
+
+struct State<T> {
+    next_generation: u64,
+    slots: HashMap<usize, Slot<T>>,
+}
+
+type Shared<T> = Arc<(Mutex<State<T>>, Condvar)>;
+
+fn lock<T>(shared: &Shared<T>) -> MutexGuard<'_, State<T>> {
+    shared.0.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 预加载结果的请求/应答表：每个播放列表索引只保留最近一次请求，
+/// 被取代或作废的请求在交付时直接丢弃，主循环只等待当前歌曲那一次请求的结果，
+/// 不会因其他索引的结果先到而耗尽等待时间、把当前歌曲误判为加载超时。
+pub struct PreloadSlots<T> {
+    shared: Shared<T>,
+}
+
+impl<T> PreloadSlots<T> {
+    pub fn new() -> Self {
+        PreloadSlots { shared: Arc::new((Mutex::new(State { next_generation: 0, slots: HashMap::new() }), Condvar::new())) }
+    }
+
+    /// 登记一次对 `index` 的预加载请求，取代该索引此前的请求（已交付的结果立即丢弃，进行中的在交付时丢弃）
+    pub fn request(&self, index: usize) -> PreloadTicket<T> {
+        let mut state = lock(&self.shared);
+        let generation = state.next_generation;
+        state.next_generation += 1;
+        state.slots.insert(index, Slot { generation, result: None, extended_until: None });
+        PreloadTicket { shared: self.shared.clone(), index, generation }
+    }
+
+    /// 等待 `index` 最近一次请求的结果，最多等待 `timeout`，预加载线程延长过时等到延长后的时刻
+    /// （超时或没有该索引的请求时为 None）。
+    /// 其他索引的请求随之作废，已完成的结果（持有打开的文件与解码器）立即释放。
+    pub fn wait(&self, index: usize, timeout: Duration) -> Option<T> {
+        let mut deadline = Instant::now() + timeout;
+        let mut state = lock(&self.shared);
+        state.slots.retain(|&i, _| i == index);
+        loop {
+            match state.slots.get_mut(&index) {
+                None => break None,
+                Some(slot) => {
+                    if let Some(result) = slot.result.take() {
+                        state.slots.remove(&index);
+                        break Some(result);
+                    }
+                    deadline = deadline.max(slot.extended_until.unwrap_or(deadline));
+                }
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) else { break None };
+            state = self.shared.1.wait_timeout(state, remaining).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+
+    /// 作废所有请求（如删除歌曲后索引整体移动），已完成的结果立即释放，进行中的在交付时丢弃
+    pub fn clear(&self) {
+        lock(&self.shared).slots.clear();
+    }
+
+    /// 对每个已完成、尚未取走的结果调用 `f`（如记录预加载得到的时长）
+    pub fn for_each_ready(&self, mut f: impl FnMut(usize, &T)) {
+        let state = lock(&self.shared);
+        for (index, slot) in &state.slots {
+            if let Some(result) = &slot.result {
+                f(*index, result);
+            }
+        }
+    }
+}
+
+/// 一次预加载请求的凭据，由预加载线程持有，完成后交付结果
+pub struct PreloadTicket<T> {
+    shared: Shared<T>,
+    index: usize,
+    generation: u64,
+}
+
+impl<T> PreloadTicket<T> {
+    /// 让等待这次请求的一方至少再等 `by`（如开始退避重试时），请求已被取代或作废时无效。
+    /// 返回的函数可以交给读取元数据等其他线程使用
+    pub fn extender(&self) -> impl Fn(Duration) + Send + 'static
+    where
+        T: Send + 'static,
+    {
+        let (shared, index, generation) = (self.shared.clone(), self.index, self.generation);
+        move |by| {
+            let mut state = lock(&shared);
+            if let Some(slot) = state.slots.get_mut(&index)
+                && slot.generation == generation
+            {
+                let until = Instant::now() + by;
+                slot.extended_until = Some(slot.extended_until.map_or(until, |current| current.max(until)));
+                shared.1.notify_all();
+            }
+        }
+    }
+
+    /// 交付结果；该请求已被取代或作废时直接丢弃
+    pub fn fulfill(self, result: T) {
+        let mut state = lock(&self.shared);
+        if let Some(slot) = state.slots.get_mut(&self.index)
+            && slot.generation == self.generation
+        {
+            slot.result = Some(result);
+            self.shared.1.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// 在 `delay` 毫秒后交付结果的慢速预加载
+    fn fulfill_after<T: Send + 'static>(ticket: PreloadTicket<T>, delay: u64, result: T) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(delay));
+            ticket.fulfill(result);
+        })
+    }
+
+    #[test]
+    fn superseded_request_is_dropped() {
+        let slots = PreloadSlots::new();
+        let old = slots.request(3);
+        let new = slots.request(3);
+        // 被取代的请求先完成，也不会被当作结果
+        let handles = [fulfill_after(old, 10, "old"), fulfill_after(new, 60, "new")];
+        assert_eq!(slots.wait(3, Duration::from_secs(2)), Some("new"));
+        handles.into_iter().for_each(|h| h.join().unwrap());
+
+        let slots = PreloadSlots::new();
+        let old = slots.request(5);
+        let _pending = slots.request(5);
+        old.fulfill("old");
+        assert_eq!(slots.wait(5, Duration::from_millis(50)), None);
+        assert_eq!(slots.wait(6, Duration::from_millis(10)), None::<&str>);
+    }
+
+    #[test]
+    fn other_indexes_do_not_exhaust_the_wait() {
+        let slots = PreloadSlots::new();
+        let wanted = slots.request(0);
+        // 其他索引的结果在等待期间不断到达，每次都会唤醒等待方
+        let mut handles: Vec<_> = (1..40).map(|i| fulfill_after(slots.request(i), i as u64 * 4, i)).collect();
+        handles.push(fulfill_after(wanted, 200, 0));
+        assert_eq!(slots.wait(0, Duration::from_millis(400)), Some(0));
+        handles.into_iter().for_each(|h| h.join().unwrap());
+    }
+
+    #[test]
+    fn extender_keeps_the_wait_alive() {
+        let slots = PreloadSlots::new();
+        let ticket = slots.request(1);
+        let extend = ticket.extender();
+        // 退避重试：超过原本的 50ms 超时才交付，期间不断顺延
+        let handle = thread::spawn(move || {
+            for _ in 0..4 {
+                extend(Duration::from_millis(80));
+                thread::sleep(Duration::from_millis(40));
+            }
+            ticket.fulfill("recovered");
+        });
+        assert_eq!(slots.wait(1, Duration::from_millis(50)), Some("recovered"));
+        handle.join().unwrap();
+
+        // 被取代的请求不能顺延新请求的等待
+        let slots = PreloadSlots::new();
+        let old = slots.request(2);
+        let _new = slots.request(2);
+        old.extender()(Duration::from_secs(60));
+        let started = Instant::now();
+        assert_eq!(slots.wait(2, Duration::from_millis(30)), None::<&str>);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn rapid_skips_with_slow_preloads() {
+        let slots = PreloadSlots::new();
+        let mut handles = Vec::new();
+        // 固定种子的伪随机延迟，结果按乱序交付
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut delay = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % 30
+        };
+        for round in 0..30u64 {
+            let index = (round % 7) as usize;
+            // 快速连按：同一索引先后请求多次，只有最后一次有效
+            for attempt in 0..3u64 {
+                let ticket = slots.request(index);
+                handles.push(fulfill_after(ticket, delay(), (index, round, attempt)));
+            }
+            for other in [index + 1, index + 2] {
+                handles.push(fulfill_after(slots.request(other), delay(), (other, round, 0)));
+            }
+            assert_eq!(slots.wait(index, Duration::from_secs(2)), Some((index, round, 2)), "round {}", round);
+        }
+        handles.into_iter().for_each(|h| h.join().unwrap());
+    }
+
+    #[test]
+    fn clear_drops_ready_and_pending_results() {
+        let slots = PreloadSlots::new();
+        let ready = slots.request(2);
+        let pending = slots.request(4);
+        ready.fulfill("two");
+        // 删除歌曲后索引整体前移：旧索引上的结果与进行中的请求都不能再落到新的行上
+        slots.clear();
+        pending.fulfill("four");
+        let mut seen = Vec::new();
+        slots.for_each_ready(|index, result| seen.push((index, *result)));
+        assert!(seen.is_empty(), "{:?}", seen);
+        assert_eq!(slots.wait(4, Duration::from_millis(10)), None);
+        slots.request(3).fulfill("three");
+        assert_eq!(slots.wait(3, Duration::from_millis(10)), Some("three"));
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// 播放列表中各曲目的时长表：随预加载逐步填充，用于估算整个列表的剩余时间
+#[derive(Debug, Default)]
+pub struct DurationTable {
+    durations: Vec<Option<Duration>>,
+}
+
+impl DurationTable {
+    pub fn new(len: usize) -> Self {
+        DurationTable { durations: vec![None; len] }
+    }
+
+    /// 记录某首歌曲的时长（0 表示未知，不记录）
+    pub fn record(&mut self, index: usize, duration: Duration) {
+        if duration.is_zero() {
+            return;
+        }
+        if index >= self.durations.len() {
+            self.durations.resize(index + 1, None);
+        }
+        self.durations[index] = Some(duration);
+    }
+
+    /// 列表被截短时丢弃多余的记录
+    pub fn truncate(&mut self, len: usize) {
+        self.durations.truncate(len);
+    }
+
+    /// 队列中删除了若干首（见 `remove_indexes`）：删除对应的记录，其后的记录前移
+    pub fn remove(&mut self, indexes: &[usize]) {
+        for &index in indexes.iter().rev() {
+            if index < self.durations.len() {
+                self.durations.remove(index);
+            }
+        }
+    }
+
+    /// 估算从当前位置到列表结束的剩余时间。
+    /// 未知时长的歌曲按已知歌曲的平均时长估算，此时第二个返回值为 true（近似值）。
+    pub fn remaining(&self, current_index: usize, position: Duration, current_total: Duration) -> (Duration, bool) {
+        let known: Vec<Duration> = self.durations.iter().flatten().copied().collect();
+        let average = if known.is_empty() {
+            Duration::ZERO
+        } else {
+            known.iter().sum::<Duration>() / known.len() as u32
+        };
+        let mut approximate = current_total.is_zero();
+        let mut total = current_total.saturating_sub(position);
+        for duration in self.durations.iter().skip(current_index + 1) {
+            match duration {
+                Some(d) => total += *d,
+                None => {
+                    approximate = true;
+                    total += average;
+                }
+            }
+        }
+        (total, approximate)
+    }
+}
+
+/// 撤销栈最多保留的修改数
+const UNDO_LIMIT: usize = 50;
+
+/// 一次可撤销的队列修改
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueEdit {
+    /// 在队列末尾加入了 `count` 首，加入前队列长度为 `start`
+    Appended { start: usize, count: usize },
+}
+
+impl QueueEdit {
+    /// 在队列上执行逆操作，返回描述撤销内容的提示；当前歌曲受影响或队列已变化时拒绝撤销
+    pub fn revert<T>(self, queue: &mut Vec<T>, current: usize) -> Result<String, String> {
+        match self {
+            QueueEdit::Appended { start, count } => {
+                if queue.len() != start + count {
+                    return Err("队列已变化，无法撤销".to_string());
+                }
+                if current >= start {
+                    return Err("正在播放加入的歌曲，无法撤销".to_string());
+                }
+                queue.truncate(start);
+                Ok(format!("已撤销: 加入的 {} 首", count))
+            }
+        }
+    }
+
+    /// 队列删除了 `removed`（升序）中的歌曲后按新的索引改写本次修改；修改涉及的歌曲都已删除时返回 None
+    fn after_removal(self, removed: &[usize]) -> Option<QueueEdit> {
+        match self {
+            QueueEdit::Appended { start, count } => {
+                let before = removed.iter().filter(|&&i| i < start).count();
+                let inside = removed.iter().filter(|&&i| (start..start + count).contains(&i)).count();
+                (count > inside).then_some(QueueEdit::Appended { start: start - before, count: count - inside })
+            }
+        }
+    }
+}
+
+/// 本次运行内的队列修改记录（U 键撤销最近一次），超过上限时丢弃最早的
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    edits: VecDeque<QueueEdit>,
+}
+
+impl UndoStack {
+    pub fn push(&mut self, edit: QueueEdit) {
+        if self.edits.len() == UNDO_LIMIT {
+            self.edits.pop_front();
+        }
+        self.edits.push_back(edit);
+    }
+
+    /// 队列中的歌曲被删除（如 `--check-playlist-health` 移除已不存在的文件）后调整各修改的索引，使其仍可撤销
+    pub fn after_removal(&mut self, removed: &[usize]) {
+        self.edits = self.edits.drain(..).filter_map(|edit| edit.after_removal(removed)).collect();
+    }
+
+    /// 撤销最近一次修改；没有可撤销的修改时返回 None。撤销失败的修改会被丢弃（已无法还原）
+    pub fn undo<T>(&mut self, queue: &mut Vec<T>, current: usize) -> Option<Result<String, String>> {
+        self.edits.pop_back().map(|edit| edit.revert(queue, current))
+    }
+}
+
+/// 删除队列中的若干首（`indexes` 升序、不重复，不含当前歌曲），`current` 随之调整为当前歌曲的新索引
+pub fn remove_indexes<T>(queue: &mut Vec<T>, current: &mut usize, indexes: &[usize]) {
+    for &index in indexes.iter().rev() {
+        queue.remove(index);
+        if index < *current {
+            *current -= 1;
+        }
+    }
+}
+
+/// 沿行进方向接下来的至多 `count` 首的索引（按播放顺序，不含当前歌曲；循环时绕回也不会重复）
+pub fn upcoming_indexes(current: usize, total: usize, direction: Direction, wrap: bool, loop_start: usize, count: usize) -> Vec<usize> {
+    let mut indexes = Vec::new();
+    let mut index = current;
+    while indexes.len() < count {
+        match step_index(index, total, direction, wrap, loop_start) {
+            Some(next) if next != current && !indexes.contains(&next) => {
+                indexes.push(next);
+                index = next;
+            }
+            _ => break,
+        }
+    }
+    indexes
+}
+
+/// 列表长度从 `old_total` 变为 `new_total`（如追加或撤销追加）后下一首的索引，与原来不同时才返回，
+/// 调用方需为它重新预加载（如倒序循环时绕回的最后一首随追加而变化）；没有变化或已没有下一首时为 None
+pub fn next_after_resize(current: usize, old_total: usize, new_total: usize, direction: Direction, wrap: bool, loop_start: usize) -> Option<usize> {
+    let next = step_index(current, new_total, direction, wrap, loop_start)?;
+    (step_index(current, old_total, direction, wrap, loop_start) != Some(next)).then_some(next)
+}
+
+/// 按方向连续走 `steps` 步；到达列表一端且不回绕时停在该端，一步也走不了时返回 None
+pub fn skip_index(current: usize, total: usize, direction: Direction, steps: usize, wrap: bool, loop_start: usize) -> Option<usize> {
+    let mut index = step_index(current, total, direction, wrap, loop_start)?;
+    for _ in 1..steps {
+        match step_index(index, total, direction, wrap, loop_start) {
+            Some(next) => index = next,
+            None => break,
+        }
+    }
+    Some(index)
+}
+
+/// `--range` 指定的播放区间（从 1 开始计数，含两端）；`end` 为 None 表示到列表末尾
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaylistRange {
+    start: usize,
+    end: Option<usize>,
+}
+
+impl PlaylistRange {
+    /// 解析 `50-120` 或 `200-`（作为 clap 的 value_parser）
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (start, end) = s.split_once('-').ok_or_else(|| format!("区间格式应为 起点-终点 或 起点-（如 50-120、200-），而不是 '{}'", s))?;
+        let number = |text: &str| {
+            text.trim().parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(|| format!("'{}' 不是有效的序号（从 1 开始）", text.trim()))
+        };
+        let start = number(start)?;
+        let end = if end.trim().is_empty() { None } else { Some(number(end)?) };
+        if let Some(end) = end
+            && end < start
+        {
+            return Err(format!("起点 {} 大于终点 {}，要倒序播放请使用 --reverse", start, end));
+        }
+        Ok(PlaylistRange { start, end })
+    }
+
+    /// 只保留区间内的歌曲；终点超出列表时截到末尾，起点超出列表时报错
+    pub fn slice<T>(&self, items: &mut Vec<T>) -> Result<(), String> {
+        if self.start > items.len() {
+            return Err(format!("列表只有 {} 首，起点 {} 超出范围", items.len(), self.start));
+        }
+        let end = self.end.unwrap_or(items.len()).min(items.len());
+        items.truncate(end);
+        items.drain(..self.start - 1);
+        Ok(())
+    }
+}
+
+/// 列表播放完毕（非循环模式）后的处理方式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionPolicy {
+    /// 直接退出
+    Exit,
+    /// 显示完成提示，等待按键从头重播或退出
+    Wait,
+}
+
+/// 播放列表的行进方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    #[default]
+    Forward,
+    Backward,
+}
+
+impl Direction {
+    pub fn reversed(self) -> Self {
+        match self {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        }
+    }
+
+    /// 当前歌曲在行进顺序中的位置（从 0 开始），用于状态行的序号
+    pub fn position(self, index: usize, total: usize) -> usize {
+        match self {
+            Direction::Forward => index,
+            Direction::Backward => total.saturating_sub(1).saturating_sub(index),
+        }
+    }
+}
+
+/// 按方向计算下一首的索引；到达列表一端时，`wrap` 为 true 则回绕，否则返回 None。
+/// 向前回绕到 `loop_start`（跳过只在首轮播放的开场曲），向后回绕到最后一首。
+pub fn step_index(current: usize, total: usize, direction: Direction, wrap: bool, loop_start: usize) -> Option<usize> {
+    if total == 0 {
+        return None;
+    }
+    match direction {
+        Direction::Forward if current + 1 < total => Some(current + 1),
+        Direction::Forward => wrap.then_some(loop_start.min(total - 1)),
+        Direction::Backward if current > 0 => Some((current - 1).min(total - 1)),
+        Direction::Backward => wrap.then_some(total - 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_reverts_append() {
+        let mut queue = vec!["a", "b"];
+        let mut undo = UndoStack::default();
+        undo.push(QueueEdit::Appended { start: 2, count: 2 });
+        queue.extend(["c", "d"]);
+        assert_eq!(undo.undo(&mut queue, 1), Some(Ok("已撤销: 加入的 2 首".to_string())));
+        assert_eq!(queue, ["a", "b"]);
+        assert_eq!(undo.undo(&mut queue, 1), None);
+    }
+
+    #[test]
+    fn undo_refuses_when_playing_an_appended_track() {
+        let mut queue = vec!["a", "b", "c"];
+        let mut undo = UndoStack::default();
+        undo.push(QueueEdit::Appended { start: 2, count: 1 });
+        assert!(undo.undo(&mut queue, 2).unwrap().is_err());
+        assert_eq!(queue, ["a", "b", "c"]);
+        // 拒绝的修改被丢弃
+        assert_eq!(undo.undo(&mut queue, 0), None);
+    }
+
+    #[test]
+    fn undo_refuses_after_length_change() {
+        let mut queue = vec!["a", "b", "c"];
+        let mut undo = UndoStack::default();
+        undo.push(QueueEdit::Appended { start: 2, count: 1 });
+        queue.push("d");
+        assert_eq!(undo.undo(&mut queue, 0), Some(Err("队列已变化，无法撤销".to_string())));
+        assert_eq!(queue.len(), 4);
+    }
+
+    #[test]
+    fn undo_stack_keeps_the_latest_50() {
+        let mut queue: Vec<usize> = Vec::new();
+        let mut undo = UndoStack::default();
+        for i in 0..60 {
+            undo.push(QueueEdit::Appended { start: i, count: 1 });
+            queue.push(i);
+        }
+        let mut undone = 0;
+        while let Some(result) = undo.undo(&mut queue, 0) {
+            assert!(result.is_ok());
+            undone += 1;
+        }
+        assert_eq!(undone, UNDO_LIMIT);
+        assert_eq!(queue.len(), 10);
+    }
+
+    #[test]
+    fn remove_indexes_follows_the_current_track() {
+        let mut queue: Vec<char> = "abcdefg".chars().collect();
+        let mut current = 3; // d
+        remove_indexes(&mut queue, &mut current, &[0, 2, 4, 6]);
+        assert_eq!(queue, ['b', 'd', 'f']);
+        assert_eq!(queue[current], 'd');
+        // 只删除当前歌曲之后的不影响索引
+        let mut current = 0;
+        remove_indexes(&mut queue, &mut current, &[1, 2]);
+        assert_eq!((queue, current), (vec!['b'], 0));
+    }
+
+    #[test]
+    fn step_index_in_both_directions() {
+        use Direction::*;
+        // (当前, 总数, 方向, 回绕, 循环起点) -> 下一首
+        for (current, total, direction, wrap, loop_start, expected) in [
+            (0, 5, Forward, false, 0, Some(1)),
+            (4, 5, Forward, false, 0, None),
+            (4, 5, Forward, true, 0, Some(0)),
+            // 循环从 --loop-start 处重新开始，超出列表时取最后一首
+            (4, 5, Forward, true, 2, Some(2)),
+            (4, 5, Forward, true, 9, Some(4)),
+            (3, 5, Backward, false, 0, Some(2)),
+            (0, 5, Backward, false, 0, None),
+            (0, 5, Backward, true, 0, Some(4)),
+            (0, 5, Backward, true, 2, Some(4)),
+            // 列表缩短后当前索引越界：倒序时回到最后一首
+            (7, 5, Backward, false, 0, Some(4)),
+            (0, 1, Forward, true, 0, Some(0)),
+            (0, 1, Backward, false, 0, None),
+            (0, 0, Forward, true, 0, None),
+            (0, 0, Backward, true, 0, None),
+        ] {
+            assert_eq!(step_index(current, total, direction, wrap, loop_start), expected, "{} {} {:?} {} {}", current, total, direction, wrap, loop_start);
+        }
+    }
+
+    #[test]
+    fn skip_index_stops_at_the_ends() {
+        use Direction::*;
+        assert_eq!(skip_index(1, 5, Forward, 3, false, 0), Some(4));
+        assert_eq!(skip_index(1, 5, Forward, 10, false, 0), Some(4));
+        assert_eq!(skip_index(4, 5, Forward, 2, false, 0), None);
+        assert_eq!(skip_index(3, 5, Forward, 4, true, 0), Some(2));
+        assert_eq!(skip_index(1, 5, Backward, 3, true, 0), Some(3));
+        assert_eq!(skip_index(1, 5, Backward, 3, false, 0), Some(0));
+    }
+
+    #[test]
+    fn position_follows_the_traversal_order() {
+        assert_eq!(Direction::Forward.position(1, 5), 1);
+        assert_eq!(Direction::Backward.position(4, 5), 0);
+        assert_eq!(Direction::Backward.position(0, 5), 4);
+        assert_eq!(Direction::Backward.position(0, 0), 0);
+        assert_eq!(Direction::Forward.reversed(), Direction::Backward);
+        assert_eq!(Direction::Backward.reversed().reversed(), Direction::Backward);
+    }
+
+    #[test]
+    fn upcoming_indexes_wrap_without_repeats() {
+        assert_eq!(upcoming_indexes(3, 5, Direction::Forward, true, 0, 10), [4, 0, 1, 2]);
+        assert_eq!(upcoming_indexes(3, 5, Direction::Forward, false, 0, 10), [4]);
+        assert_eq!(upcoming_indexes(1, 5, Direction::Forward, false, 0, 2), [2, 3]);
+        assert_eq!(upcoming_indexes(1, 5, Direction::Backward, true, 0, 10), [0, 4, 3, 2]);
+        assert_eq!(upcoming_indexes(1, 5, Direction::Backward, false, 0, 10), [0]);
+        assert_eq!(upcoming_indexes(0, 1, Direction::Forward, true, 0, 3), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn removal_keeps_pending_undos_valid() {
+        // 0-3 为原有歌曲，4-5 与 6-8 为先后两次加入的歌曲
+        let mut queue: Vec<usize> = (0..9).collect();
+        let mut current = 3;
+        let mut undo = UndoStack::default();
+        undo.push(QueueEdit::Appended { start: 4, count: 2 });
+        undo.push(QueueEdit::Appended { start: 6, count: 3 });
+        // 健康检查移除了 1（当前歌曲之前）、5 与 7（分别属于两次加入）
+        let removed = [1, 5, 7];
+        remove_indexes(&mut queue, &mut current, &removed);
+        undo.after_removal(&removed);
+        assert_eq!(current, 2);
+        assert_eq!(undo.undo(&mut queue, current), Some(Ok("已撤销: 加入的 2 首".to_string())));
+        assert_eq!(queue, [0, 2, 3, 4]);
+        assert_eq!(undo.undo(&mut queue, current), Some(Ok("已撤销: 加入的 1 首".to_string())));
+        assert_eq!(queue, [0, 2, 3]);
+    }
+
+    #[test]
+    fn removing_every_appended_track_drops_the_edit() {
+        let mut queue: Vec<usize> = (0..4).collect();
+        let mut current = 0;
+        let mut undo = UndoStack::default();
+        undo.push(QueueEdit::Appended { start: 3, count: 1 });
+        remove_indexes(&mut queue, &mut current, &[3]);
+        undo.after_removal(&[3]);
+        assert_eq!(undo.undo(&mut queue, current), None);
+        assert_eq!(queue, [0, 1, 2]);
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(PlaylistRange::parse("50-120"), Ok(PlaylistRange { start: 50, end: Some(120) }));
+        assert_eq!(PlaylistRange::parse(" 200 - "), Ok(PlaylistRange { start: 200, end: None }));
+        assert_eq!(PlaylistRange::parse("7-7"), Ok(PlaylistRange { start: 7, end: Some(7) }));
+        for bad in ["", "50", "-120", "0-10", "a-b", "5-x", "1.5-3"] {
+            assert!(PlaylistRange::parse(bad).is_err(), "{}", bad);
+        }
+        // 倒序区间提示改用 --reverse
+        assert!(PlaylistRange::parse("120-50").unwrap_err().contains("--reverse"));
+    }
+
+    #[test]
+    fn slices_inclusive_ranges() {
+        let slice = |range: &str, len: usize| {
+            let mut items: Vec<usize> = (1..=len).collect();
+            PlaylistRange::parse(range).unwrap().slice(&mut items).map(|()| items)
+        };
+        assert_eq!(slice("3-5", 10), Ok(vec![3, 4, 5]));
+        assert_eq!(slice("8-", 10), Ok(vec![8, 9, 10]));
+        assert_eq!(slice("1-1", 10), Ok(vec![1]));
+        assert_eq!(slice("10-", 10), Ok(vec![10]));
+        // 终点超出列表时截到末尾
+        assert_eq!(slice("9-50", 10), Ok(vec![9, 10]));
+        // 起点超出列表时报错
+        assert_eq!(slice("11-", 10), Err("列表只有 10 首，起点 11 超出范围".to_string()));
+        assert!(slice("1-", 0).is_err());
+    }
+
+    #[test]
+    fn observed_duration_replaces_the_reported_one() {
+        let secs = Duration::from_secs;
+        let mut durations = DurationTable::new(3);
+        durations.record(0, secs(200));
+        durations.record(1, secs(180));
+        durations.record(2, secs(240));
+        assert_eq!(durations.remaining(0, secs(0), secs(200)), (secs(620), false));
+        // 第 2 首的解码器在 170 秒处就结束了：之后按实际时长估算
+        durations.record(1, secs(170));
+        assert_eq!(durations.remaining(0, secs(0), secs(200)), (secs(610), false));
+        // 时长未知时保留已有的记录
+        durations.record(1, Duration::ZERO);
+        assert_eq!(durations.remaining(0, secs(100), secs(200)), (secs(510), false));
+    }
+
+    #[test]
+    fn next_track_changes_when_the_queue_grows_or_shrinks() {
+        use Direction::*;
+        // (当前, 原总数, 新总数, 方向, 回绕) -> 需要重新预加载的下一首
+        for (current, old_total, new_total, direction, wrap, expected) in [
+            // 原本是最后一首：下一首变为新加入的第一首
+            (4, 5, 7, Forward, false, Some(5)),
+            (4, 5, 7, Forward, true, Some(5)),
+            // 下一首不变
+            (2, 5, 7, Forward, false, None),
+            (2, 5, 7, Backward, true, None),
+            // 倒序循环停在第一首：绕回的最后一首随追加而变化
+            (0, 5, 7, Backward, true, Some(6)),
+            (0, 5, 7, Backward, false, None),
+            // 撤销追加：绕回的目标变回原来的最后一首；顺序播放时已没有下一首
+            (0, 7, 5, Backward, true, Some(4)),
+            (4, 7, 5, Forward, false, None),
+            (4, 7, 5, Forward, true, Some(0)),
+            // 单曲循环加入歌曲后不再重播自己
+            (0, 1, 3, Forward, true, Some(1)),
+        ] {
+            assert_eq!(next_after_resize(current, old_total, new_total, direction, wrap, 0), expected, "{} {}->{} {:?} {}", current, old_total, new_total, direction, wrap);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::buildinfo::json_string;
+use crate::timing::PreloadTiming;
+
+/// 会话中的一次状态变化
+pub enum Transition {
+    /// 开始播放一首歌曲（附预加载各阶段耗时）
+    TrackStarted { index: usize, path: String, timing: PreloadTiming },
+    /// 主音量变化（0-100）；`source` 为 key（音量键）、mute、unmute、duck（H 键）、ipc（`mddplayer duck`）；
+    /// 压低时记录折算后实际听到的音量
+    Volume { volume: u8, source: &'static str },
+    /// 单曲增益变化
+    TrackGain { db: f32 },
+    Paused,
+    Resumed,
+    /// 切歌；`trigger` 为 key（方向键，可一次跳过多首）或 error（加载失败后跳过）
+    Skip { from: usize, to: usize, trigger: &'static str },
+    /// 暂停超时后释放了音频设备
+    DeviceReleased,
+    /// 打开音频设备（暂停超时后唤醒或 F8 切换）；None 为默认设备
+    DeviceOpened { name: Option<String> },
+    /// 从队列中删除了一首；`reason` 为 missing（--check-playlist-health 发现文件已不存在）
+    Removed { index: usize, path: String, reason: &'static str },
+}
+
+/// 字段值：数字与布尔值原样输出，文本在 JSON 中加引号
+enum Value {
+    Number(String),
+    Text(String),
+}
+
+impl Transition {
+    /// 事件名与各字段
+    fn fields(&self) -> (&'static str, Vec<(&'static str, Value)>) {
+        let ms = |d: Duration| Value::Number(format!("{:.1}", d.as_secs_f64() * 1000.0));
+        match self {
+            Transition::TrackStarted { index, path, timing } => (
+                "track",
+                vec![
+                    ("index", Value::Number((index + 1).to_string())),
+                    ("path", Value::Text(path.clone())),
+                    ("metadata_ms", ms(timing.metadata)),
+                    ("open_ms", ms(timing.open)),
+                    ("decoder_ms", ms(timing.decoder)),
+                ],
+            ),
+            Transition::Volume { volume, source } => {
+                ("volume", vec![("volume", Value::Number(volume.to_string())), ("source", Value::Text(source.to_string()))])
+            }
+            Transition::TrackGain { db } => ("gain", vec![("db", Value::Number(format!("{:.1}", db)))]),
+            Transition::Paused => ("pause", Vec::new()),
+            Transition::Resumed => ("resume", Vec::new()),
+            Transition::Skip { from, to, trigger } => (
+                "skip",
+                vec![
+                    ("from", Value::Number((from + 1).to_string())),
+                    ("to", Value::Number((to + 1).to_string())),
+                    ("trigger", Value::Text(trigger.to_string())),
+                ],
+            ),
+            Transition::DeviceReleased => ("device_released", Vec::new()),
+            Transition::DeviceOpened { name } => {
+                ("device", vec![("name", Value::Text(name.clone().unwrap_or_else(|| "default".to_string())))])
+            }
+            Transition::Removed { index, path, reason } => (
+                "removed",
+                vec![
+                    ("index", Value::Number((index + 1).to_string())),
+                    ("path", Value::Text(path.clone())),
+                    ("reason", Value::Text(reason.to_string())),
+                ],
+            ),
+        }
+    }
+}
+
+/// 会话状态变化记录：`--verbose` 时退出后以 `键=值` 逐行输出到 stderr，
+/// `--dump-session` 时把完整记录写成 JSON 数组。与 TimingLog 一样在 Drop 时输出（终端已恢复正常模式）。
+pub struct SessionLog {
+    started: Instant,
+    verbose: bool,
+    dump: Option<PathBuf>,
+    entries: Vec<(Duration, Transition)>,
+}
+
+impl SessionLog {
+    pub fn new(verbose: bool, dump: Option<PathBuf>) -> Self {
+        SessionLog { started: Instant::now(), verbose, dump, entries: Vec::new() }
+    }
+
+    /// 记录一次状态变化（时间为相对会话开始的偏移）
+    pub fn record(&mut self, transition: Transition) {
+        if self.verbose || self.dump.is_some() {
+            self.entries.push((self.started.elapsed(), transition));
+        }
+    }
+
+    /// 单行 `t=秒 event=事件 键=值…`；文本值含空白时加引号
+    fn to_line(at: Duration, transition: &Transition) -> String {
+        let (event, fields) = transition.fields();
+        let mut line = format!("t={:.3} event={}", at.as_secs_f64(), event);
+        for (key, value) in fields {
+            match value {
+                Value::Number(n) => line.push_str(&format!(" {}={}", key, n)),
+                Value::Text(t) if t.contains(char::is_whitespace) || t.is_empty() => line.push_str(&format!(" {}={}", key, json_string(&t))),
+                Value::Text(t) => line.push_str(&format!(" {}={}", key, t)),
+            }
+        }
+        line
+    }
+
+    /// 完整记录的 JSON 数组，每个元素为 `{"t":秒,"event":事件,…}`
+    fn to_json(&self) -> String {
+        let items: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(at, transition)| {
+                let (event, fields) = transition.fields();
+                let mut item = format!("{{\"t\":{:.3},\"event\":{}", at.as_secs_f64(), json_string(event));
+                for (key, value) in fields {
+                    let value = match value {
+                        Value::Number(n) => n,
+                        Value::Text(t) => json_string(&t),
+                    };
+                    item.push_str(&format!(",{}:{}", json_string(key), value));
+                }
+                item.push('}');
+                item
+            })
+            .collect();
+        format!("[{}]\n", items.join(",\n"))
+    }
+}
+
+impl Drop for SessionLog {
+    fn drop(&mut self) {
+        if self.verbose {
+            for (at, transition) in &self.entries {
+                eprintln!("[详细][会话]{}", SessionLog::to_line(*at, transition));
+            }
+        }
+        if let Some(path) = &self.dump
+            && let Err(e) = fs::write(path, self.to_json())
+        {
+            eprintln!("[错误]无法写入会话记录 '{}': {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn timing(ms: u64) -> PreloadTiming {
+        let ms = Duration::from_millis(ms);
+        PreloadTiming { metadata: ms, open: ms * 2, decoder: ms * 3, completed_at: Instant::now() }
+    }
+
+    /// 一段有代表性的会话：开播、调音量、暂停、按键切歌、加载失败跳过、设备释放与重开、删除缺失文件
+    fn sequence() -> Vec<Transition> {
+        vec![
+            Transition::TrackStarted { index: 0, path: "/music/a b.flac".to_string(), timing: timing(4) },
+            Transition::Volume { volume: 45, source: "key" },
+            Transition::TrackGain { db: -1.5 },
+            Transition::Paused,
+            Transition::Resumed,
+            Transition::Skip { from: 0, to: 3, trigger: "key" },
+            Transition::Skip { from: 3, to: 4, trigger: "error" },
+            Transition::DeviceReleased,
+            Transition::DeviceOpened { name: None },
+            Transition::Removed { index: 6, path: "/music/\"gone\".mp3".to_string(), reason: "missing" },
+        ]
+    }
+
+    #[test]
+    fn lines_and_json_keep_every_field() {
+        let mut log = SessionLog::new(false, None);
+        log.entries = sequence().into_iter().enumerate().map(|(i, t)| (Duration::from_millis(250 * i as u64), t)).collect();
+        let lines: Vec<String> = log.entries.iter().map(|(at, t)| SessionLog::to_line(*at, t)).collect();
+        assert_eq!(
+            lines,
+            [
+                "t=0.000 event=track index=1 path=\"/music/a b.flac\" metadata_ms=4.0 open_ms=8.0 decoder_ms=12.0",
+                "t=0.250 event=volume volume=45 source=key",
+                "t=0.500 event=gain db=-1.5",
+                "t=0.750 event=pause",
+                "t=1.000 event=resume",
+                "t=1.250 event=skip from=1 to=4 trigger=key",
+                "t=1.500 event=skip from=4 to=5 trigger=error",
+                "t=1.750 event=device_released",
+                "t=2.000 event=device name=default",
+                "t=2.250 event=removed index=7 path=/music/\"gone\".mp3 reason=missing",
+            ]
+        );
+        let json = log.to_json();
+        assert!(json.starts_with("[{\"t\":0.000,\"event\":\"track\",\"index\":1,\"path\":\"/music/a b.flac\",\"metadata_ms\":4.0,"), "{}", json);
+        assert!(json.contains("{\"t\":1.250,\"event\":\"skip\",\"from\":1,\"to\":4,\"trigger\":\"key\"},\n"), "{}", json);
+        assert!(json.ends_with("{\"t\":2.250,\"event\":\"removed\",\"index\":7,\"path\":\"/music/\\\"gone\\\".mp3\",\"reason\":\"missing\"}]\n"), "{}", json);
+        assert_eq!(json.matches("\"event\"").count(), 10);
+    }
+
+    #[test]
+    fn records_only_when_requested_and_dumps_on_exit() {
+        let mut quiet = SessionLog::new(false, None);
+        quiet.record(Transition::Paused);
+        assert!(quiet.entries.is_empty());
+
+        let dir = TempDir::new();
+        let dump = dir.join("session.json");
+        let mut log = SessionLog::new(false, Some(dump.clone()));
+        for transition in sequence() {
+            log.record(transition);
+        }
+        assert!(log.entries.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        drop(log);
+        let json = std::fs::read_to_string(&dump).unwrap();
+        let events: Vec<&str> = json.split("\"event\":\"").skip(1).map(|rest| rest.split('"').next().unwrap()).collect();
+        assert_eq!(events, ["track", "volume", "gain", "pause", "resume", "skip", "skip", "device_released", "device", "removed"]);
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+// 随机数
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+/// 随机模式的打乱方式
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ShuffleMode {
+    /// 普通打乱
+    #[default]
+    Plain,
+    /// 打乱后尽量拉开同一艺术家（无标签时为同一目录）歌曲的间隔
+    Smart,
+}
+
+/// 打乱切片；指定种子时结果可复现
+pub fn shuffle_with_seed<T>(items: &mut [T], seed: Option<u64>) {
+    match seed {
+        Some(seed) => items.shuffle(&mut StdRng::seed_from_u64(seed)),
+        None => items.shuffle(&mut rand::thread_rng()),
+    }
+}
+
+/// 按分组键重新排列，尽量避免相邻两项的键相同（尽力而为）。
+/// 默认按原顺序取下一项，只跳过与上一项同组的项；当某个分组剩余过多、
+/// 不优先安排就会被迫相邻时，改为先取该分组。同组内保持原有的相对顺序，结果完全由输入决定。
+/// 只要某个分组的数量不超过其余分组总和 + 1，结果中就不会出现相邻重复。
+/// 返回新顺序中各项在原切片中的下标。
+pub fn spread_by_key<K: Eq + Hash>(keys: &[K]) -> Vec<usize> {
+    // 按首次出现的顺序分组，每组内为原下标（升序）
+    let mut group_of: HashMap<&K, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, key) in keys.iter().enumerate() {
+        let group = *group_of.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+    // 每组下一个待取的位置
+    let mut cursor = vec![0usize; groups.len()];
+    let mut last_group: Option<usize> = None;
+    let mut order = Vec::with_capacity(keys.len());
+    while order.len() < keys.len() {
+        let left = keys.len() - order.len();
+        let remaining = |g: usize| groups[g].len() - cursor[g];
+        let next_index = |g: usize| groups[g][cursor[g]];
+        let candidates: Vec<usize> = (0..groups.len()).filter(|&g| remaining(g) > 0 && Some(g) != last_group).collect();
+        // 剩余最多的分组超过剩余总数的一半时必须先取它，否则按原顺序取最靠前的一项
+        let busiest = candidates.iter().copied().max_by(|&a, &b| remaining(a).cmp(&remaining(b)).then(next_index(b).cmp(&next_index(a))));
+        let group = match busiest {
+            Some(g) if 2 * remaining(g) > left - 1 => g,
+            _ => match candidates.iter().copied().min_by_key(|&g| next_index(g)) {
+                Some(g) => g,
+                // 只剩与上一项同组的歌曲，无法避免相邻
+                None => match last_group {
+                    Some(g) => g,
+                    None => break,
+                },
+            },
+        };
+        order.push(next_index(group));
+        cursor[group] += 1;
+        last_group = Some(group);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    /// 检查 `order` 是 `keys` 下标的一个排列，且同组内保持原有的相对顺序
+    fn assert_stable_permutation(keys: &[u8], order: &[usize]) {
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..keys.len()).collect::<Vec<_>>(), "{:?}", keys);
+        for key in keys {
+            let in_group: Vec<_> = order.iter().copied().filter(|&i| keys[i] == *key).collect();
+            assert!(in_group.is_sorted(), "{:?} -> {:?}", keys, order);
+        }
+    }
+
+    fn adjacent_repeats(keys: &[u8], order: &[usize]) -> usize {
+        order.windows(2).filter(|w| keys[w[0]] == keys[w[1]]).count()
+    }
+
+    #[test]
+    fn spreads_whenever_possible() {
+        let mut rng = StdRng::seed_from_u64(20240601);
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..40);
+            let artists = rng.gen_range(1..6);
+            // 偏斜的分布：前几个艺术家的歌曲更多
+            let keys: Vec<u8> = (0..len).map(|_| rng.gen_range(0..artists).min(rng.gen_range(0..artists))).collect();
+            let order = spread_by_key(&keys);
+            assert_stable_permutation(&keys, &order);
+            let largest = (0..artists).map(|a| keys.iter().filter(|&&k| k == a).count()).max().unwrap_or(0);
+            let others = keys.len() - largest;
+            // 最大的分组不超过其余分组总和 + 1 时不会相邻；否则相邻次数为不可避免的最小值
+            let unavoidable = largest.saturating_sub(others + 1);
+            assert_eq!(adjacent_repeats(&keys, &order), unavoidable, "{:?} -> {:?}", keys, order);
+            assert_eq!(spread_by_key(&keys), order);
+        }
+    }
+
+    #[test]
+    fn keeps_an_already_spread_order() {
+        let keys = [1, 2, 3, 1, 2, 3, 4];
+        assert_eq!(spread_by_key(&keys), (0..keys.len()).collect::<Vec<_>>());
+        assert_eq!(spread_by_key(&[7, 7, 7, 8]), [0, 3, 1, 2]);
+        assert_eq!(spread_by_key::<u8>(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn seeded_shuffle_is_reproducible() {
+        let shuffled = |seed| {
+            let mut items: Vec<u32> = (0..100).collect();
+            shuffle_with_seed(&mut items, Some(seed));
+            items
+        };
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), shuffled(43));
+        let mut sorted = shuffled(42);
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+    }
+}
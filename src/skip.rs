@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+/// 合并切歌按键：按住方向键或快速连按会产生一串按键事件。
+/// 默认模式下窗口内只有第一次按键生效；累积模式（`--skip-accumulate`）下
+/// 连按结束（超过窗口没有新的按键）后按净次数一次跳过多首，左右键互相抵消。
+pub struct SkipCoalescer {
+    window: Duration,
+    accumulate: bool,
+    /// 默认模式：最近一次生效的时刻
+    last_accepted: Option<Instant>,
+    /// 累积模式：尚未执行的净步数（正数向前）
+    pending: i32,
+    /// 累积模式：最近一次按键的时刻
+    last_press: Option<Instant>,
+}
+
+impl SkipCoalescer {
+    pub fn new(window: Duration, accumulate: bool) -> Self {
+        SkipCoalescer { window, accumulate, last_accepted: None, pending: 0, last_press: None }
+    }
+
+    /// 处理一次切歌按键（`delta` 为 +1 或 -1），需要立即切歌时返回步数
+    pub fn press(&mut self, delta: i32, now: Instant) -> Option<i32> {
+        if self.accumulate {
+            self.pending = self.pending.saturating_add(delta);
+            self.last_press = Some(now);
+            return None;
+        }
+        if self.last_accepted.is_some_and(|t| now.duration_since(t) < self.window) {
+            return None;
+        }
+        self.last_accepted = Some(now);
+        Some(delta)
+    }
+
+    /// 累积模式下尚未执行的净步数（用于提示）
+    pub fn pending(&self) -> i32 {
+        self.pending
+    }
+
+    /// 累积模式下连按结束时返回累积的步数（抵消为 0 时不切歌）；默认模式总是返回 None
+    pub fn poll(&mut self, now: Instant) -> Option<i32> {
+        let last_press = self.last_press?;
+        if now.duration_since(last_press) < self.window {
+            return None;
+        }
+        self.last_press = None;
+        let steps = std::mem::take(&mut self.pending);
+        (steps != 0).then_some(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(150);
+
+    /// 按时间顺序送入按键 (毫秒, ±1)，并像主循环一样每 10 毫秒轮询一次，直到 `until` 毫秒；
+    /// 返回实际切歌的 (毫秒, 步数)
+    fn feed(accumulate: bool, presses: &[(u64, i32)], until: u64) -> Vec<(u64, i32)> {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut skips = SkipCoalescer::new(WINDOW, accumulate);
+        let mut presses = presses.iter().peekable();
+        let mut out = Vec::new();
+        for ms in (0..=until).step_by(10) {
+            while let Some(&&(t, delta)) = presses.peek()
+                && t <= ms
+            {
+                presses.next();
+                out.extend(skips.press(delta, at(t)).map(|steps| (t, steps)));
+            }
+            out.extend(skips.poll(at(ms)).map(|steps| (ms, steps)));
+        }
+        out
+    }
+
+    /// 按住方向键时终端以固定间隔重复发送按键
+    fn held(delta: i32, from: u64, count: u64, every: u64) -> Vec<(u64, i32)> {
+        (0..count).map(|i| (from + i * every, delta)).collect()
+    }
+
+    #[test]
+    fn burst_counts_once_by_default() {
+        // 按住 1 秒：每个窗口内只有第一次生效
+        assert_eq!(feed(false, &held(1, 0, 31, 33), 1500), [(0, 1), (165, 1), (330, 1), (495, 1), (660, 1), (825, 1), (990, 1)]);
+        // 单击与隔开的两次单击
+        assert_eq!(feed(false, &[(0, 1)], 500), [(0, 1)]);
+        assert_eq!(feed(false, &[(0, 1), (149, 1), (400, -1)], 600), [(0, 1), (400, -1)]);
+        // 重复事件与按下几乎同时到达（部分终端单击产生两个事件）只算一次
+        assert_eq!(feed(false, &[(0, 1), (1, 1)], 300), [(0, 1)]);
+    }
+
+    #[test]
+    fn accumulates_rapid_presses_into_one_jump() {
+        // 3 次快速连按：停下一个窗口后跳 3 首
+        assert_eq!(feed(true, &[(0, 1), (80, 1), (160, 1)], 600), [(310, 3)]);
+        // 左右键互相抵消，抵消为 0 时不切歌
+        assert_eq!(feed(true, &[(0, 1), (50, 1), (100, -1)], 600), [(250, 1)]);
+        assert_eq!(feed(true, &[(0, 1), (50, -1)], 600), []);
+        // 两串连按之间隔开超过窗口：分别执行
+        assert_eq!(feed(true, &[(0, -1), (60, -1), (500, 1)], 900), [(210, -2), (650, 1)]);
+    }
+
+    #[test]
+    fn pending_steps_are_visible_until_polled() {
+        let now = Instant::now();
+        let mut skips = SkipCoalescer::new(WINDOW, true);
+        assert_eq!(skips.press(1, now), None);
+        assert_eq!(skips.press(1, now + Duration::from_millis(50)), None);
+        assert_eq!(skips.pending(), 2);
+        assert_eq!(skips.poll(now + Duration::from_millis(100)), None);
+        assert_eq!(skips.poll(now + Duration::from_millis(200)), Some(2));
+        assert_eq!(skips.pending(), 0);
+        assert_eq!(skips.poll(now + Duration::from_millis(400)), None);
+    }
+}
@@ -0,0 +1,743 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+use crate::listened::{ListenTracker, Segments};
+use crate::metadata::get_total_duration;
+use crate::utils::Track;
+use crate::volume::{TRACK_GAIN_MAX_DB, TRACK_GAIN_MIN_DB};
+
+// --- 常量定义 ---
+const TRACK_GAIN_FILE: &str = "track_gain.tsv"; // 单曲增益存储文件名
+
+/// 本地数据目录（如 Linux 下的 ~/.local/share/mddplayer），无法确定时返回 None
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("mddplayer"))
+}
+
+/// 统一用规范化后的绝对路径作为键，不同的相对路径写法指向同一首歌
+fn track_key(track: &Path) -> PathBuf {
+    fs::canonicalize(track).unwrap_or_else(|_| track.to_path_buf())
+}
+
+/// 数据文件旁的锁文件（文件名加 `.lock`），只用于加锁，内容为空
+fn lock_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    file.with_file_name(name)
+}
+
+/// 在独占锁内读取数据文件的最新内容（不存在时为空），由 `apply` 合并本次的修改后整体写回。
+/// 同时运行的多个实例（或线程）依次完成读取-合并-写回，不会覆盖彼此的修改；
+/// 写回时先写临时文件再改名，不加锁的读取方也不会读到写了一半的文件。
+fn update_locked(file: &Path, apply: impl FnOnce(&str) -> String) -> io::Result<()> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // 锁随文件句柄关闭而释放
+    let lock = OpenOptions::new().create(true).truncate(false).write(true).open(lock_path(file))?;
+    lock.lock()?;
+    let current = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let content = apply(&current);
+    let mut temp_name = file.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = file.with_file_name(temp_name);
+    let result = fs::write(&temp, content).and_then(|()| fs::rename(&temp, file));
+    // 写到一半（如磁盘已满）或改名失败时不留下临时文件
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// 在锁内删除数据文件（不存在时视为成功）
+fn remove_locked(file: &Path) -> io::Result<()> {
+    let lock = OpenOptions::new().create(true).truncate(false).write(true).open(lock_path(file))?;
+    lock.lock()?;
+    match fs::remove_file(file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// 整体替换数据目录下的一个文件（后写入的为准），供存储层以外的模块（如没有 Unix 套接字的平台上 `status` 的端口文件）使用
+#[cfg(not(unix))]
+pub fn replace_data_file(name: &str, content: &str) -> io::Result<PathBuf> {
+    let file = data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "无法确定数据目录"))?.join(name);
+    update_locked(&file, |_| content.to_string())?;
+    Ok(file)
+}
+
+/// 按文件路径保存的单曲增益（dB），存储格式为每行 `增益<TAB>路径`。
+/// 多个实例同时修改时按歌曲合并：各自只改动自己设置的那一首，同一首以后写入的为准。
+pub struct TrackGainStore {
+    file: Option<PathBuf>,
+    gains: HashMap<PathBuf, f32>,
+}
+
+impl TrackGainStore {
+    /// 从数据目录加载；文件不存在或无法解析的行会被忽略
+    pub fn load() -> Self {
+        let file = data_dir().map(|d| d.join(TRACK_GAIN_FILE));
+        let gains = file.as_ref().and_then(|f| fs::read_to_string(f).ok()).map(|content| Self::parse(&content)).unwrap_or_default();
+        TrackGainStore { file, gains }
+    }
+
+    fn parse(content: &str) -> HashMap<PathBuf, f32> {
+        let mut gains = HashMap::new();
+        for line in content.lines() {
+            if let Some((db, path)) = line.split_once('\t')
+                && let Ok(db) = db.trim().parse::<f32>()
+            {
+                gains.insert(PathBuf::from(path), db.clamp(TRACK_GAIN_MIN_DB, TRACK_GAIN_MAX_DB));
+            }
+        }
+        gains
+    }
+
+    /// 获取歌曲的增益，未设置时为 0 dB
+    pub fn get(&self, track: &Path) -> f32 {
+        self.gains.get(&track_key(track)).copied().unwrap_or(0.0)
+    }
+
+    /// 设置歌曲的增益并立即写回磁盘（同时取得其他实例的修改）；0 dB 视为清除
+    pub fn set(&mut self, track: &Path, db: f32) -> io::Result<()> {
+        let key = track_key(track);
+        let apply = |gains: &mut HashMap<PathBuf, f32>| {
+            if db == 0.0 {
+                gains.remove(&key);
+            } else {
+                gains.insert(key.clone(), db.clamp(TRACK_GAIN_MIN_DB, TRACK_GAIN_MAX_DB));
+            }
+        };
+        // 写回失败时本次运行内仍然生效
+        apply(&mut self.gains);
+        let Some(file) = &self.file else { return Ok(()) };
+        update_locked(file, |current| {
+            let mut gains = Self::parse(current);
+            apply(&mut gains);
+            let mut content = String::new();
+            for (path, db) in &gains {
+                content.push_str(&format!("{}\t{}\n", db, path.display()));
+            }
+            self.gains = gains;
+            content
+        })
+    }
+}
+
+/// 片头跳过记录的存储文件名（仅 --remember-trims 时使用）
+const INTRO_TRIM_FILE: &str = "intro_trim.tsv";
+
+/// 按目录记录的片头跳过时长：同一目录（专辑）的歌曲开播时跳过开头。
+/// 默认只在本次运行内有效；`persist` 时从数据目录加载并在修改后写回，格式为每行 `秒数<TAB>目录`。
+/// 多个实例同时修改时按目录合并，同一目录以后写入的为准。
+pub struct IntroTrimStore {
+    file: Option<PathBuf>,
+    trims: HashMap<PathBuf, Duration>,
+}
+
+impl IntroTrimStore {
+    pub fn load(persist: bool) -> Self {
+        let file = if persist { data_dir().map(|d| d.join(INTRO_TRIM_FILE)) } else { None };
+        let trims = file.as_ref().and_then(|f| fs::read_to_string(f).ok()).map(|content| Self::parse(&content)).unwrap_or_default();
+        IntroTrimStore { file, trims }
+    }
+
+    fn parse(content: &str) -> HashMap<PathBuf, Duration> {
+        let mut trims = HashMap::new();
+        for line in content.lines() {
+            if let Some((secs, dir)) = line.split_once('\t')
+                && let Ok(secs) = secs.trim().parse::<f64>()
+                && secs > 0.0
+            {
+                trims.insert(PathBuf::from(dir), Duration::from_secs_f64(secs));
+            }
+        }
+        trims
+    }
+
+    /// 歌曲所在目录的键
+    fn folder_key(track: &Path) -> Option<PathBuf> {
+        track_key(track).parent().map(Path::to_path_buf)
+    }
+
+    /// 获取歌曲所在目录的片头跳过时长
+    pub fn get(&self, track: &Path) -> Option<Duration> {
+        Self::folder_key(track).and_then(|key| self.trims.get(&key).copied())
+    }
+
+    /// 设置歌曲所在目录的片头跳过时长；None 表示清除
+    pub fn set(&mut self, track: &Path, trim: Option<Duration>) -> io::Result<()> {
+        let Some(key) = Self::folder_key(track) else { return Ok(()) };
+        let apply = |trims: &mut HashMap<PathBuf, Duration>| {
+            match trim {
+                Some(trim) => trims.insert(key.clone(), trim),
+                None => trims.remove(&key),
+            };
+        };
+        apply(&mut self.trims);
+        let Some(file) = &self.file else { return Ok(()) };
+        update_locked(file, |current| {
+            let mut trims = Self::parse(current);
+            apply(&mut trims);
+            let mut content = String::new();
+            for (dir, trim) in &trims {
+                content.push_str(&format!("{:.3}\t{}\n", trim.as_secs_f64(), dir.display()));
+            }
+            self.trims = trims;
+            content
+        })
+    }
+}
+
+/// 界面模式的存储文件名（仅 --remember-modes 时使用）
+const UI_MODE_FILE: &str = "ui_mode";
+
+/// 读取上次保存的界面模式：Some(true) 为纯净模式，未保存或无法读取时为 None
+pub fn load_ui_mode() -> Option<bool> {
+    let content = fs::read_to_string(data_dir()?.join(UI_MODE_FILE)).ok()?;
+    match content.trim() {
+        "clean" => Some(true),
+        "full" => Some(false),
+        _ => None,
+    }
+}
+
+/// 保存界面模式（F2 切换后）；多个实例以最后切换的为准
+pub fn save_ui_mode(clean: bool) -> io::Result<()> {
+    let Some(dir) = data_dir() else { return Ok(()) };
+    update_locked(&dir.join(UI_MODE_FILE), |_| if clean { "clean\n" } else { "full\n" }.to_string())
+}
+
+/// 最近播放过的输入路径的存储文件名，每行一个，最近的在前
+const RECENT_INPUTS_FILE: &str = "recent_inputs";
+/// 最多记住的输入路径数
+const RECENT_INPUTS_LIMIT: usize = 5;
+
+/// 读取最近播放过的输入路径（不带参数启动时列在提示中）
+pub fn load_recent_inputs() -> Vec<String> {
+    data_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(RECENT_INPUTS_FILE)).ok())
+        .map(|content| parse_recent_inputs(&content))
+        .unwrap_or_default()
+}
+
+fn parse_recent_inputs(content: &str) -> Vec<String> {
+    content.lines().filter(|l| !l.is_empty()).map(str::to_string).collect()
+}
+
+/// 把本次的输入路径（规范化后；通配符原样保存）记到最前面，去掉重复的旧记录。
+/// 在锁内基于最新的列表修改，同时启动的多个实例都会留下记录
+pub fn remember_input(input: &str) -> io::Result<()> {
+    let Some(dir) = data_dir() else { return Ok(()) };
+    let input = track_key(Path::new(input)).to_string_lossy().into_owned();
+    update_locked(&dir.join(RECENT_INPUTS_FILE), |current| {
+        let mut inputs = parse_recent_inputs(current);
+        inputs.retain(|i| *i != input);
+        inputs.insert(0, input);
+        inputs.truncate(RECENT_INPUTS_LIMIT);
+        inputs.join("\n") + "\n"
+    })
+}
+
+/// 上次退出时的播放位置的存储文件名，内容为三行：输入路径、歌曲路径、位置（毫秒）
+const RESUME_FILE: &str = "resume";
+
+/// 上次退出时的播放位置（不带参数启动时可从这里继续）
+#[derive(Debug, Clone)]
+pub struct ResumeState {
+    pub input: String,
+    pub track: PathBuf,
+    pub position: Duration,
+}
+
+impl ResumeState {
+    pub fn load() -> Option<Self> {
+        Self::read(&data_dir()?.join(RESUME_FILE))
+    }
+
+    fn read(file: &Path) -> Option<Self> {
+        let content = fs::read_to_string(file).ok()?;
+        let mut lines = content.lines();
+        let input = lines.next().filter(|l| !l.is_empty())?.to_string();
+        let track = PathBuf::from(lines.next()?);
+        let position = Duration::from_millis(lines.next()?.trim().parse().ok()?);
+        Some(ResumeState { input, track, position })
+    }
+
+    /// 上次的歌曲在列表中的位置（只认整首，不认章节）；按规范化后的路径比较，
+    /// 上次以相对路径启动、这次按保存的绝对路径扫描时也能找到
+    pub fn track_index(&self, playlist: &[Track]) -> Option<usize> {
+        let key = track_key(&self.track);
+        playlist.iter().position(|t| t.start.is_none() && (t.path == self.track || track_key(&t.path) == key))
+    }
+}
+
+/// 播放过程中持续更新当前歌曲与位置，退出时（Drop）写入上次播放位置；
+/// 整个列表播放完毕后调用 finish，删除记录（下次没有可继续的位置）。
+/// 只保存一个位置，多个实例以最后退出的为准
+pub struct ResumeRecorder {
+    file: Option<PathBuf>,
+    input: String,
+    /// 调用方给出的歌曲路径、规范化后的路径（保存用）与位置
+    current: Option<(PathBuf, PathBuf, Duration)>,
+    finished: bool,
+}
+
+impl ResumeRecorder {
+    pub fn new(input: &str) -> Self {
+        Self::with_file(data_dir().map(|d| d.join(RESUME_FILE)), input)
+    }
+
+    fn with_file(file: Option<PathBuf>, input: &str) -> Self {
+        ResumeRecorder { file, input: track_key(Path::new(input)).to_string_lossy().into_owned(), current: None, finished: false }
+    }
+
+    /// 更新当前歌曲与位置；换了歌曲时才规范化路径
+    pub fn update(&mut self, track: &Path, position: Duration) {
+        match &mut self.current {
+            Some((path, _, pos)) if path == track => *pos = position,
+            _ => self.current = Some((track.to_path_buf(), track_key(track), position)),
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for ResumeRecorder {
+    fn drop(&mut self) {
+        let Some(file) = &self.file else { return };
+        // 保存失败不影响退出，下次只是无法继续
+        match &self.current {
+            _ if self.finished => {
+                let _ = remove_locked(file);
+            }
+            Some((_, track, position)) => {
+                let content = format!("{}\n{}\n{}\n", self.input, track.display(), position.as_millis());
+                let _ = update_locked(file, |_| content);
+            }
+            None => {}
+        }
+    }
+}
+
+/// 已听完歌曲记录的存储文件名（仅 --no-repeat-across-sessions 时使用）
+const PLAYED_FILE: &str = "played.tsv";
+
+/// 一条已听完记录。文件改名后路径失效，改按大小、时长与章节起点（指纹）认出同一首歌
+#[derive(Debug, Clone)]
+struct PlayedEntry {
+    input: PathBuf,
+    path: PathBuf,
+    start_ms: u64,
+    size: u64,
+    duration_ms: u64,
+}
+
+impl PlayedEntry {
+    /// 每行 `输入<TAB>章节起点毫秒<TAB>大小<TAB>时长毫秒<TAB>路径`
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(5, '\t');
+        let input = PathBuf::from(fields.next()?);
+        let start_ms = fields.next()?.parse().ok()?;
+        let size = fields.next()?.parse().ok()?;
+        let duration_ms = fields.next()?.parse().ok()?;
+        let path = PathBuf::from(fields.next()?);
+        Some(PlayedEntry { input, path, start_ms, size, duration_ms })
+    }
+
+    fn fingerprint(&self) -> (u64, u64, u64) {
+        (self.size, self.duration_ms, self.start_ms)
+    }
+}
+
+/// 章节起点（毫秒），整首为 0
+fn start_ms(track: &Track) -> u64 {
+    track.start.map_or(0, |start| start.as_millis() as u64)
+}
+
+/// 文件的大小与时长（毫秒）；文件不存在时为 None
+fn file_fingerprint(path: &Path) -> Option<(u64, u64)> {
+    let size = fs::metadata(path).ok()?.len();
+    Some((size, get_total_duration(path).as_millis() as u64))
+}
+
+/// `--no-repeat-across-sessions`：按输入路径（规范化后）记录已听完的歌曲，之后运行同一输入时跳过它们。
+/// 多个实例同时记录时取并集，不会丢失彼此的记录
+pub struct PlayedStore {
+    file: Option<PathBuf>,
+    input: PathBuf,
+    /// 所有输入的记录（保存时整体写回）
+    entries: Vec<PlayedEntry>,
+}
+
+impl PlayedStore {
+    pub fn load(input: &Path) -> Self {
+        let file = data_dir().map(|d| d.join(PLAYED_FILE));
+        let entries = file.as_ref().and_then(|f| fs::read_to_string(f).ok()).map(|content| Self::parse(&content)).unwrap_or_default();
+        PlayedStore { file, input: track_key(input), entries }
+    }
+
+    fn parse(content: &str) -> Vec<PlayedEntry> {
+        content.lines().filter_map(PlayedEntry::parse).collect()
+    }
+
+    fn own_entries(&self) -> impl Iterator<Item = &PlayedEntry> {
+        self.entries.iter().filter(|e| e.input == self.input)
+    }
+
+    /// 去掉本输入中已听完的歌曲，其余保持原有顺序
+    pub fn exclude(&self, tracks: Vec<Track>) -> Vec<Track> {
+        let known: HashSet<(&Path, u64)> = self.own_entries().map(|e| (e.path.as_path(), e.start_ms)).collect();
+        // 路径已不存在的记录（文件可能被改名）只能按指纹匹配，每条记录至多认出一首；
+        // 先比较大小，相同时才读取时长
+        let mut orphans: Vec<(u64, u64, u64)> = self.own_entries().filter(|e| !e.path.exists()).map(PlayedEntry::fingerprint).collect();
+        let orphan_sizes: HashSet<u64> = orphans.iter().map(|(size, _, _)| *size).collect();
+        tracks
+            .into_iter()
+            .filter(|track| {
+                let path = track_key(&track.path);
+                let start = start_ms(track);
+                if known.contains(&(path.as_path(), start)) {
+                    return false;
+                }
+                match fs::metadata(&path) {
+                    Ok(meta) if orphan_sizes.contains(&meta.len()) => {
+                        let duration_ms = get_total_duration(&path).as_millis() as u64;
+                        match orphans.iter().position(|f| *f == (meta.len(), duration_ms, start)) {
+                            Some(i) => {
+                                orphans.swap_remove(i);
+                                false
+                            }
+                            None => true,
+                        }
+                    }
+                    _ => true,
+                }
+            })
+            .collect()
+    }
+
+    /// 记录一首已听完的歌曲，返回是否为新记录（已记录过或文件已不存在时为 false）
+    pub fn record(&mut self, track: &Track) -> io::Result<bool> {
+        let path = track_key(&track.path);
+        let start = start_ms(track);
+        if self.own_entries().any(|e| e.path == path && e.start_ms == start) {
+            return Ok(false);
+        }
+        let Some((size, duration_ms)) = file_fingerprint(&path) else { return Ok(false) };
+        let input = self.input.clone();
+        let apply = |entries: &mut Vec<PlayedEntry>| {
+            // 其他实例可能已经记录了这一首
+            if entries.iter().any(|e| e.input == input && e.path == path && e.start_ms == start) {
+                return;
+            }
+            // 改名前留下的旧记录由新路径的记录取代
+            entries.retain(|e| !(e.input == input && !e.path.exists() && e.fingerprint() == (size, duration_ms, start)));
+            entries.push(PlayedEntry { input: input.clone(), path: path.clone(), start_ms: start, size, duration_ms });
+        };
+        apply(&mut self.entries);
+        self.save(apply)?;
+        Ok(true)
+    }
+
+    /// `reset-progress`：清除该输入的全部记录，返回清除的条数
+    pub fn reset(input: &Path) -> io::Result<usize> {
+        let mut store = Self::load(input);
+        let own = store.input.clone();
+        let mut removed = 0;
+        if store.entries.iter().any(|e| e.input == own) {
+            store.save(|entries| {
+                let before = entries.len();
+                entries.retain(|e| e.input != own);
+                removed = before - entries.len();
+            })?;
+        }
+        Ok(removed)
+    }
+
+    /// 在锁内对磁盘上的最新记录执行 `apply` 后写回，并以合并结果更新内存中的记录
+    fn save(&mut self, apply: impl FnOnce(&mut Vec<PlayedEntry>)) -> io::Result<()> {
+        let Some(file) = &self.file else { return Ok(()) };
+        update_locked(file, |current| {
+            let mut entries = Self::parse(current);
+            apply(&mut entries);
+            let content = Self::format(&entries);
+            self.entries = entries;
+            content
+        })
+    }
+
+    fn format(entries: &[PlayedEntry]) -> String {
+        let mut content = String::new();
+        for e in entries {
+            content.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                e.input.display(),
+                e.start_ms,
+                e.size,
+                e.duration_ms,
+                e.path.display()
+            ));
+        }
+        content
+    }
+}
+
+/// 长音频听过的区间的存储文件名
+const LISTENED_FILE: &str = "listened.tsv";
+/// 超过该时长没有再播放的文件，其记录在加载时清除
+const LISTENED_EXPIRY: Duration = Duration::from_secs(90 * 24 * 3600);
+
+/// 长音频（如 DJ 混音）听过的区间，按文件路径保存，用于分段收听时找到没听过的部分。
+/// 存储格式为每行 `最近播放的 Unix 秒数<TAB>区间<TAB>路径`，区间见 `Segments::format`。
+/// 正在播放的区间在跳转、切歌时写入；退出时（Drop）写入最后一段。
+/// 多个实例同时记录同一文件时区间取并集。
+pub struct ListenedStore {
+    file: Option<PathBuf>,
+    entries: HashMap<PathBuf, (u64, Segments)>,
+    /// 正在跟踪的歌曲（调用方给出的路径，避免每次都规范化）
+    playing: Option<(PathBuf, ListenTracker)>,
+}
+
+/// 当前的 Unix 秒数
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+impl ListenedStore {
+    /// 从数据目录加载，丢弃超过 LISTENED_EXPIRY 没有播放的记录
+    pub fn load() -> Self {
+        let file = data_dir().map(|d| d.join(LISTENED_FILE));
+        let entries = file.as_ref().and_then(|f| fs::read_to_string(f).ok()).map(|content| Self::parse(&content)).unwrap_or_default();
+        ListenedStore { file, entries, playing: None }
+    }
+
+    fn parse(content: &str) -> HashMap<PathBuf, (u64, Segments)> {
+        let oldest = unix_now().saturating_sub(LISTENED_EXPIRY.as_secs());
+        let mut entries = HashMap::new();
+        for line in content.lines() {
+            let mut fields = line.splitn(3, '\t');
+            if let (Some(seen), Some(segments), Some(path)) = (fields.next(), fields.next(), fields.next())
+                && let Ok(seen) = seen.parse::<u64>()
+                && seen >= oldest
+            {
+                entries.insert(PathBuf::from(path), (seen, Segments::parse(segments)));
+            }
+        }
+        entries
+    }
+
+    /// 歌曲听过的区间，包括正在播放、尚未写入的一段
+    pub fn heard(&self, track: &Path) -> Segments {
+        let mut segments = self.entries.get(&track_key(track)).map(|(_, segments)| segments.clone()).unwrap_or_default();
+        if let Some((path, tracker)) = &self.playing
+            && path == track
+            && let Some((start, end)) = tracker.current()
+        {
+            segments.add(start, end);
+        }
+        segments
+    }
+
+    /// 记录歌曲最新的播放位置：换了歌曲或发生跳转时写入刚结束的区间
+    pub fn observe(&mut self, track: &Path, position: Duration) -> io::Result<()> {
+        if self.playing.as_ref().is_none_or(|(path, _)| path != track) {
+            self.finish()?;
+            self.playing = Some((track.to_path_buf(), ListenTracker::default()));
+        }
+        let Some((_, tracker)) = &mut self.playing else { return Ok(()) };
+        match tracker.observe(position) {
+            Some((start, end)) => self.add(track, start, end),
+            None => Ok(()),
+        }
+    }
+
+    /// 歌曲结束或切走：写入正在播放的区间
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.playing.take() {
+            Some((track, mut tracker)) => match tracker.finish() {
+                Some((start, end)) => self.add(&track, start, end),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// 记录一段听过的区间并立即写回磁盘（与其他实例记录的区间合并）
+    fn add(&mut self, track: &Path, start: Duration, end: Duration) -> io::Result<()> {
+        let key = track_key(track);
+        let now = unix_now();
+        let apply = |entries: &mut HashMap<PathBuf, (u64, Segments)>| {
+            let entry = entries.entry(key.clone()).or_default();
+            entry.0 = entry.0.max(now);
+            entry.1.add(start, end);
+        };
+        apply(&mut self.entries);
+        let Some(file) = &self.file else { return Ok(()) };
+        update_locked(file, |current| {
+            let mut entries = Self::parse(current);
+            apply(&mut entries);
+            let mut content = String::new();
+            for (path, (seen, segments)) in entries.iter().filter(|(_, (_, segments))| !segments.is_empty()) {
+                content.push_str(&format!("{}\t{}\t{}\n", seen, segments.format(), path.display()));
+            }
+            self.entries = entries;
+            content
+        })
+    }
+}
+
+impl Drop for ListenedStore {
+    fn drop(&mut self) {
+        // 保存失败不影响退出，下次只是少了最后一段
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::thread;
+
+    #[test]
+    fn track_gain_persists_by_path() {
+        let dir = TempDir::new();
+        let loud = dir.write("music/太吵的歌 01.mp3", b"");
+        let other = dir.write("music/other.mp3", b"");
+        let file = dir.join(TRACK_GAIN_FILE);
+        let mut store = TrackGainStore { file: Some(file.clone()), gains: HashMap::new() };
+        assert_eq!(store.get(&loud), 0.0);
+        store.set(&loud, -4.0).unwrap();
+        store.set(&other, 30.0).unwrap();
+        assert_eq!(store.get(&loud), -4.0);
+        // 超出范围时截断到 ±12dB
+        assert_eq!(store.get(&other), TRACK_GAIN_MAX_DB);
+
+        // 重新加载（相当于下次启动）后按路径取回；同一文件的不同写法对应同一条记录
+        let reloaded = TrackGainStore { file: Some(file.clone()), gains: TrackGainStore::parse(&fs::read_to_string(&file).unwrap()) };
+        assert_eq!(reloaded.get(&dir.join("music/../music/太吵的歌 01.mp3")), -4.0);
+        assert_eq!(reloaded.get(&other), TRACK_GAIN_MAX_DB);
+
+        // 设为 0dB 即清除
+        store.set(&other, 0.0).unwrap();
+        let content = fs::read_to_string(&file).unwrap();
+        assert_eq!(content.lines().count(), 1);
+        assert_eq!(TrackGainStore::parse(&content).get(&fs::canonicalize(&loud).unwrap()), Some(&-4.0));
+        assert_eq!(TrackGainStore::parse("x\tbad\n-40\t/a.mp3\n").get(Path::new("/a.mp3")), Some(&TRACK_GAIN_MIN_DB));
+    }
+
+    #[test]
+    fn concurrent_writers_keep_every_update() {
+        let dir = TempDir::new();
+        let file = dir.join(TRACK_GAIN_FILE);
+        let writers: Vec<_> = (0..2)
+            .map(|writer| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    // 每个线程相当于一个实例：各自加载、各自写回
+                    let mut store = TrackGainStore { file: Some(file), gains: HashMap::new() };
+                    for i in 0..100 {
+                        store.set(Path::new(&format!("/nonexistent/{}-{}", writer, i)), 1.0).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let gains = TrackGainStore::parse(&fs::read_to_string(&file).unwrap());
+        assert_eq!(gains.len(), 200);
+        // 只留下数据文件与锁文件，没有残留的临时文件
+        let mut names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["track_gain.tsv", "track_gain.tsv.lock"]);
+    }
+
+    #[test]
+    fn listened_segments_persist_and_expire() {
+        let dir = TempDir::new();
+        let mix = dir.write("mixes/两小时混音.mp3", b"");
+        let file = dir.join(LISTENED_FILE);
+        let secs = Duration::from_secs;
+        let mut store = ListenedStore { file: Some(file.clone()), entries: HashMap::new(), playing: None };
+        for position in 0..=20 {
+            store.observe(&mix, secs(position)).unwrap();
+        }
+        // 正在播放的一段尚未写入，但已计入听过的部分
+        assert!(!file.exists());
+        assert_eq!(store.heard(&mix).format(), "0-20000");
+        // 跳转后写入刚结束的一段
+        for position in 600..=610 {
+            store.observe(&mix, secs(position)).unwrap();
+        }
+        assert_eq!(ListenedStore::parse(&fs::read_to_string(&file).unwrap()).len(), 1);
+        // 退出时写入最后一段
+        drop(store);
+
+        let content = fs::read_to_string(&file).unwrap();
+        let reloaded = ListenedStore { file: Some(file.clone()), entries: ListenedStore::parse(&content), playing: None };
+        assert_eq!(reloaded.heard(&dir.join("mixes/../mixes/两小时混音.mp3")).format(), "0-20000,600000-610000");
+
+        // 超过 90 天没有播放的记录在加载时清除
+        let now = unix_now();
+        let stale = format!("{}\t0-60000\t/old.mp3\n{}\t0-60000\t/recent.mp3\nbad\t0-1\t/x.mp3\n", now - LISTENED_EXPIRY.as_secs() - 60, now - 3600);
+        let entries = ListenedStore::parse(&stale);
+        assert_eq!(entries.keys().collect::<Vec<_>>(), [Path::new("/recent.mp3")]);
+    }
+
+    /// 从当前目录到 `path` 的相对路径（相当于在音乐目录的上级以 `mddplayer music` 启动）
+    fn relative_to_cwd(path: &Path) -> PathBuf {
+        let cwd = std::env::current_dir().unwrap();
+        let mut relative: PathBuf = cwd.components().skip(1).map(|_| "..").collect();
+        relative.push(path.strip_prefix("/").unwrap());
+        relative
+    }
+
+    #[test]
+    fn resume_round_trips_a_relative_input() {
+        let dir = TempDir::new();
+        let a = dir.write("music/A.wav", crate::test_support::silent_wav(100));
+        let b = dir.write("music/B.wav", crate::test_support::silent_wav(100));
+        let file = dir.join(RESUME_FILE);
+        let music = relative_to_cwd(&dir.join("music"));
+        let scan = crate::utils::ScanOptions { quiet: true, ..Default::default() };
+        let playlist = crate::utils::get_playlist_from_input(music.to_str().unwrap(), &scan).unwrap();
+        assert!(playlist[1].path.is_relative());
+        assert_eq!(fs::canonicalize(&playlist[1].path).unwrap(), fs::canonicalize(&b).unwrap());
+
+        let mut recorder = ResumeRecorder::with_file(Some(file.clone()), music.to_str().unwrap());
+        recorder.update(&playlist[0].path, Duration::from_secs(10));
+        recorder.update(&playlist[1].path, Duration::from_secs(3));
+        drop(recorder);
+
+        // 下次按保存的（绝对）输入路径扫描，仍能找到上次的歌曲
+        let state = ResumeState::read(&file).unwrap();
+        assert_eq!(Path::new(&state.input), fs::canonicalize(dir.join("music")).unwrap());
+        assert_eq!(state.position, Duration::from_secs(3));
+        let rescanned = crate::utils::get_playlist_from_input(&state.input, &scan).unwrap();
+        assert!(rescanned[1].path.is_absolute());
+        assert_eq!(state.track_index(&rescanned), Some(1));
+        // 上次以相对路径启动时保存的旧记录也能对上
+        let old = ResumeState { track: playlist[1].path.clone(), ..state.clone() };
+        assert_eq!(old.track_index(&rescanned), Some(1));
+        assert_eq!(ResumeState { track: a.with_file_name("gone.wav"), ..state }.track_index(&rescanned), None);
+
+        // 列表播放完毕时删除记录
+        let mut recorder = ResumeRecorder::with_file(Some(file.clone()), music.to_str().unwrap());
+        recorder.update(&playlist[1].path, Duration::from_secs(5));
+        recorder.finish();
+        drop(recorder);
+        assert!(ResumeState::read(&file).is_none());
+    }
+}
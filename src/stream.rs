@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+// 随机数
+use rand::Rng;
+use rand::rngs::ThreadRng;
+// 引入 ignore 库解析 gitignore 风格的忽略规则
+use ignore::gitignore::Gitignore;
+
+use crate::utils::{audio_extension, has_ignore_marker, load_ignore_rules};
+
+/// 单次抽取时最多从根目录重新下探的次数，超过则认为曲库中已没有可播放的文件
+const MAX_DESCENT_ATTEMPTS: usize = 64;
+
+/// 流式随机播放：不预先扫描整个曲库，而是每次从根目录随机逐层下探选出一首。
+/// 每层只读取当前目录，因此即使曲库很大也能立即开始播放。
+pub struct RandomWalker {
+    root: PathBuf,
+    rules: Gitignore,
+    /// 已确认不含可播放文件的目录，下探时不再进入
+    dead_dirs: HashSet<PathBuf>,
+    rng: ThreadRng,
+}
+
+impl RandomWalker {
+    pub fn new(root: &Path, verbose: bool) -> Self {
+        RandomWalker {
+            root: root.to_path_buf(),
+            rules: load_ignore_rules(root, verbose),
+            dead_dirs: HashSet::new(),
+            rng: rand::thread_rng(),
+        }
+    }
+
+    /// 列出目录中可选的条目：未被忽略的子目录与支持的音频文件
+    fn candidates(&self, dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+        entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                let is_dir = path.is_dir();
+                if self.rules.matched(path, is_dir).is_ignore() {
+                    return false;
+                }
+                if is_dir {
+                    !self.dead_dirs.contains(path) && !has_ignore_marker(path)
+                } else {
+                    audio_extension(path).is_some()
+                }
+            })
+            .collect()
+    }
+
+    /// 随机选出下一首歌曲；曲库中没有可播放的文件时返回 None
+    pub fn next_track(&mut self) -> Option<PathBuf> {
+        for _ in 0..MAX_DESCENT_ATTEMPTS {
+            let mut dir = self.root.clone();
+            loop {
+                let candidates = self.candidates(&dir);
+                if candidates.is_empty() {
+                    // 死胡同：记下后从根目录重新下探；根目录本身为空则放弃
+                    if dir == self.root {
+                        return None;
+                    }
+                    self.dead_dirs.insert(dir);
+                    break;
+                }
+                let pick = candidates[self.rng.gen_range(0..candidates.len())].clone();
+                if pick.is_dir() {
+                    dir = pick;
+                } else {
+                    return Some(pick);
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,231 @@
+//! 单元测试共用的辅助工具
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 测试用的临时目录（各测试互不相同），离开作用域时连同内容删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!("mddplayer-unit-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// 创建文件（连同所需的上级目录），返回其路径
+    pub fn write(&self, name: &str, content: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+/// 单声道 16 位 8kHz、时长 `millis` 毫秒的静音 WAV 文件内容
+pub fn silent_wav(millis: u32) -> Vec<u8> {
+    let data_len = millis * 16;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // 声道数
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&16000u32.to_le_bytes()); // 每秒字节数
+    wav.extend_from_slice(&2u16.to_le_bytes()); // 每帧字节数
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    wav
+}
+
+/// 只含元数据块的 FLAC 文件内容：`blocks` 为 (块类型, 内容)，依次写出，最后一块带结束标志
+pub fn flac_with_blocks(blocks: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let mut flac = b"fLaC".to_vec();
+    for (i, (kind, data)) in blocks.iter().enumerate() {
+        let last = if i + 1 == blocks.len() { 0x80 } else { 0 };
+        flac.push(kind | last);
+        flac.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]);
+        flac.extend_from_slice(data);
+    }
+    flac
+}
+
+/// STREAMINFO 块内容（34 字节），只填写采样率
+pub fn flac_streaminfo(sample_rate: u32) -> Vec<u8> {
+    let mut data = vec![0u8; 34];
+    data[10] = (sample_rate >> 12) as u8;
+    data[11] = (sample_rate >> 4) as u8;
+    data[12] = ((sample_rate & 0xf) << 4) as u8;
+    data
+}
+
+/// CUESHEET 块中的一条音轨：(音轨编号, 音轨偏移, [(索引号, 索引偏移)])，偏移以采样为单位
+pub type CueTrack<'a> = (u8, u64, &'a [(u8, u64)]);
+
+/// 二进制 CUESHEET 块内容
+pub fn flac_cuesheet(tracks: &[CueTrack]) -> Vec<u8> {
+    let mut data = vec![0u8; 128 + 8 + 1 + 258];
+    data.push(tracks.len() as u8);
+    for (number, offset, indexes) in tracks {
+        data.extend_from_slice(&offset.to_be_bytes());
+        data.push(*number);
+        data.extend_from_slice(&[0u8; 12 + 1 + 13]);
+        data.push(indexes.len() as u8);
+        for (index, index_offset) in *indexes {
+            data.extend_from_slice(&index_offset.to_be_bytes());
+            data.push(*index);
+            data.extend_from_slice(&[0u8; 3]);
+        }
+    }
+    data
+}
+
+/// Vorbis 注释块内容，`comments` 为 `字段=值`
+pub fn flac_vorbis_comment(comments: &[&str]) -> Vec<u8> {
+    let vendor = b"mddplayer-test";
+    let mut data = (vendor.len() as u32).to_le_bytes().to_vec();
+    data.extend_from_slice(vendor);
+    data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        data.extend_from_slice(comment.as_bytes());
+    }
+    data
+}
+
+/// EBML 元素：ID 原样写出，长度固定用 8 字节的变长整数
+fn ebml(id: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut element = id.to_vec();
+    element.push(0x01);
+    element.extend_from_slice(&(data.len() as u64).to_be_bytes()[1..]);
+    element.extend_from_slice(data);
+    element
+}
+
+/// 一条视频轨（编码为 symphonia 不认识的 VP8，不含数据）加上可选的一条音轨的 Matroska 文件内容。
+/// 音轨为单声道 16 位 8kHz、时长 `audio_millis` 毫秒的静音 PCM（每块 100 毫秒），容器报告的时长取同一值
+pub fn mkv_with_video(audio_millis: Option<u32>) -> Vec<u8> {
+    let uint = |id: &[u8], value: u64| ebml(id, &value.to_be_bytes());
+    let float = |id: &[u8], value: f64| ebml(id, &value.to_be_bytes());
+    let header = [
+        uint(&[0x42, 0x86], 1),
+        uint(&[0x42, 0xF7], 1),
+        uint(&[0x42, 0xF2], 4),
+        uint(&[0x42, 0xF3], 8),
+        ebml(&[0x42, 0x82], b"matroska"),
+        uint(&[0x42, 0x87], 4),
+        uint(&[0x42, 0x85], 2),
+    ]
+    .concat();
+
+    let millis = audio_millis.unwrap_or(1000);
+    let info = ebml(&[0x15, 0x49, 0xA9, 0x66], &[uint(&[0x2A, 0xD7, 0xB1], 1_000_000), float(&[0x44, 0x89], millis as f64)].concat());
+    let video = ebml(
+        &[0xAE],
+        &[
+            uint(&[0xD7], 1),
+            uint(&[0x73, 0xC5], 1),
+            uint(&[0x83], 1),
+            ebml(&[0x86], b"V_VP8"),
+            ebml(&[0xE0], &[uint(&[0xB0], 16), uint(&[0xBA], 16)].concat()),
+        ]
+        .concat(),
+    );
+    let mut tracks = video;
+    let mut segment = Vec::new();
+    if let Some(millis) = audio_millis {
+        tracks.extend(ebml(
+            &[0xAE],
+            &[
+                uint(&[0xD7], 2),
+                uint(&[0x73, 0xC5], 2),
+                uint(&[0x83], 2),
+                ebml(&[0x86], b"A_PCM/INT/LIT"),
+                ebml(&[0xE1], &[float(&[0xB5], 8000.0), uint(&[0x9F], 1), uint(&[0x62, 0x64], 16)].concat()),
+            ]
+            .concat(),
+        ));
+        // 一个簇，每块的头部为：音轨 2、相对簇的时间（毫秒）、关键帧
+        let mut cluster = uint(&[0xE7], 0);
+        for start in (0..millis).step_by(100) {
+            let mut block = vec![0x82];
+            block.extend_from_slice(&(start as i16).to_be_bytes());
+            block.push(0x80);
+            block.resize(block.len() + (millis - start).min(100) as usize * 16, 0);
+            cluster.extend(ebml(&[0xA3], &block));
+        }
+        segment.extend(ebml(&[0x1F, 0x43, 0xB6, 0x75], &cluster));
+    }
+    let segment = [info, ebml(&[0x16, 0x54, 0xAE, 0x6B], &tracks), segment].concat();
+    [ebml(&[0x1A, 0x45, 0xDF, 0xA3], &header), ebml(&[0x18, 0x53, 0x80, 0x67], &segment)].concat()
+}
+
+/// Ogg 页面校验和（CRC-32，多项式 0x04C11DB7，不反转、初值为 0）
+fn ogg_crc(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// 一个 Ogg 页面：`flags` 为页头类型（2 开始、4 结束），每个数据包都小于 255 字节
+fn ogg_page(flags: u8, granule: u64, sequence: u32, packets: &[&[u8]]) -> Vec<u8> {
+    let mut page = b"OggS".to_vec();
+    page.push(0);
+    page.push(flags);
+    page.extend_from_slice(&granule.to_le_bytes());
+    page.extend_from_slice(&1u32.to_le_bytes()); // 流序号
+    page.extend_from_slice(&sequence.to_le_bytes());
+    page.extend_from_slice(&[0; 4]); // 校验和，最后填入
+    page.push(packets.len() as u8);
+    page.extend(packets.iter().map(|packet| packet.len() as u8));
+    for packet in packets {
+        page.extend_from_slice(packet);
+    }
+    let crc = ogg_crc(&page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+    page
+}
+
+/// 1 秒的单声道 Ogg Opus 文件内容（50 个 20 毫秒的空帧），本构建的解码器都不支持
+pub fn ogg_opus() -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.extend_from_slice(&[1, 1]); // 版本、声道数
+    head.extend_from_slice(&312u16.to_le_bytes()); // 预跳过
+    head.extend_from_slice(&48000u32.to_le_bytes());
+    head.extend_from_slice(&[0, 0, 0]); // 增益、声道映射
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&4u32.to_le_bytes());
+    tags.extend_from_slice(b"test");
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    // TOC 0xF8：CELT 全频带 20 毫秒、一帧；帧长为 0
+    let frames = vec![&[0xF8u8][..]; 50];
+    [ogg_page(2, 0, 0, &[&head]), ogg_page(0, 0, 1, &[&tags]), ogg_page(4, 48000 + 312, 2, &frames)].concat()
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,265 @@
+use crossterm::style::Color;
+
+/// 可着色的界面元素及其在 `--theme-color` 中的名称
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    Header,
+    Title,
+    Artist,
+    Time,
+    Volume,
+    ProgressFilled,
+    ProgressEmpty,
+    Error,
+    Toast,
+}
+
+const ELEMENTS: [(Element, &str); 9] = [
+    (Element::Header, "header"),
+    (Element::Title, "title"),
+    (Element::Artist, "artist"),
+    (Element::Time, "time"),
+    (Element::Volume, "volume"),
+    (Element::ProgressFilled, "progress-filled"),
+    (Element::ProgressEmpty, "progress-empty"),
+    (Element::Error, "error"),
+    (Element::Toast, "toast"),
+];
+
+/// 内置主题
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThemeName {
+    /// 适合深色背景的终端
+    Dark,
+    /// 适合浅色背景的终端
+    Light,
+}
+
+impl ThemeName {
+    /// 内置主题的颜色，顺序与 ELEMENTS 一致（只用 16 色，任何终端都能显示）
+    fn colors(self) -> [Color; 9] {
+        match self {
+            ThemeName::Dark => [
+                Color::Cyan,
+                Color::White,
+                Color::Grey,
+                Color::Green,
+                Color::Yellow,
+                Color::Green,
+                Color::DarkGrey,
+                Color::Red,
+                Color::Magenta,
+            ],
+            ThemeName::Light => [
+                Color::DarkBlue,
+                Color::Black,
+                Color::DarkGrey,
+                Color::DarkGreen,
+                Color::DarkYellow,
+                Color::DarkGreen,
+                Color::Grey,
+                Color::DarkRed,
+                Color::DarkMagenta,
+            ],
+        }
+    }
+}
+
+/// 终端支持的颜色数量
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ColorSupport {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// 按 COLORTERM/TERM 环境变量推断（Windows Terminal 设置 WT_SESSION，支持真彩色）
+    fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default().to_lowercase();
+        let colorterm = var("COLORTERM");
+        if colorterm.contains("truecolor") || colorterm.contains("24bit") || std::env::var_os("WT_SESSION").is_some() {
+            ColorSupport::TrueColor
+        } else if var("TERM").contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+}
+
+/// 启动时解析好的主题：每个元素对应一个终端可显示的颜色
+#[derive(Debug, Clone)]
+pub struct Theme {
+    colors: [Color; 9],
+}
+
+impl Theme {
+    /// 以内置主题为基础，按 `key=color` 覆盖个别元素；颜色可为名称（red、dark_blue）、
+    /// 256 色序号（0-255）或 `#rrggbb`，并按终端能力降级为最接近的 256/16 色
+    pub fn load(name: ThemeName, overrides: &[String]) -> Result<Self, String> {
+        Self::resolve(name, overrides, ColorSupport::detect())
+    }
+
+    fn resolve(name: ThemeName, overrides: &[String], support: ColorSupport) -> Result<Self, String> {
+        let mut colors = name.colors();
+        for entry in overrides {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("主题颜色应写作 元素=颜色（如 title=#ffaa00），而不是 '{}'", entry))?;
+            let key = key.trim();
+            let index = ELEMENTS.iter().position(|(_, name)| *name == key).ok_or_else(|| {
+                let names: Vec<&str> = ELEMENTS.iter().map(|(_, name)| *name).collect();
+                format!("未知的主题元素 '{}'（可用: {}）", key, names.join(", "))
+            })?;
+            let color = parse_color(value.trim()).ok_or_else(|| format!("主题元素 {} 的颜色 '{}' 无法识别", key, value.trim()))?;
+            colors[index] = degrade(color, support);
+        }
+        Ok(Theme { colors })
+    }
+
+    pub fn color(&self, element: Element) -> Color {
+        let index = ELEMENTS.iter().position(|(e, _)| *e == element).unwrap_or(0);
+        self.colors[index]
+    }
+}
+
+/// 16 色的名称与近似 RGB 值（用于降级时计算最接近的颜色）
+const ANSI16: [(Color, &str, (u8, u8, u8)); 16] = [
+    (Color::Black, "black", (0, 0, 0)),
+    (Color::DarkRed, "dark_red", (128, 0, 0)),
+    (Color::DarkGreen, "dark_green", (0, 128, 0)),
+    (Color::DarkYellow, "dark_yellow", (128, 128, 0)),
+    (Color::DarkBlue, "dark_blue", (0, 0, 128)),
+    (Color::DarkMagenta, "dark_magenta", (128, 0, 128)),
+    (Color::DarkCyan, "dark_cyan", (0, 128, 128)),
+    (Color::Grey, "grey", (192, 192, 192)),
+    (Color::DarkGrey, "dark_grey", (128, 128, 128)),
+    (Color::Red, "red", (255, 0, 0)),
+    (Color::Green, "green", (0, 255, 0)),
+    (Color::Yellow, "yellow", (255, 255, 0)),
+    (Color::Blue, "blue", (0, 0, 255)),
+    (Color::Magenta, "magenta", (255, 0, 255)),
+    (Color::Cyan, "cyan", (0, 255, 255)),
+    (Color::White, "white", (255, 255, 255)),
+];
+
+/// 解析颜色：名称（大小写、`-`/`_` 不敏感，gray 同 grey）、256 色序号或 `#rrggbb`
+fn parse_color(s: &str) -> Option<Color> {
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? });
+    }
+    if let Ok(index) = s.parse::<u8>() {
+        return Some(Color::AnsiValue(index));
+    }
+    let name = s.to_lowercase().replace('-', "_").replace("gray", "grey");
+    ANSI16.iter().find(|(_, n, _)| *n == name).map(|(color, _, _)| *color)
+}
+
+/// 把颜色降级为终端能显示的形式
+fn degrade(color: Color, support: ColorSupport) -> Color {
+    match color {
+        Color::Rgb { r, g, b } if support == ColorSupport::Ansi256 => Color::AnsiValue(nearest_256((r, g, b))),
+        Color::Rgb { r, g, b } if support == ColorSupport::Ansi16 => nearest_16((r, g, b)),
+        Color::AnsiValue(index) if support == ColorSupport::Ansi16 => nearest_16(ansi256_rgb(index)),
+        other => other,
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> Color {
+    ANSI16.iter().min_by_key(|(_, _, c)| distance(rgb, *c)).map_or(Color::White, |(color, _, _)| *color)
+}
+
+/// 256 色中 6×6×6 色块每一级的亮度
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// 256 色序号对应的 RGB 值
+fn ansi256_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI16[index as usize].2,
+        16..=231 => {
+            let i = index - 16;
+            (CUBE_LEVELS[(i / 36) as usize], CUBE_LEVELS[(i / 6 % 6) as usize], CUBE_LEVELS[(i % 6) as usize])
+        }
+        _ => {
+            let v = 8 + (index - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+/// 在 6×6×6 色块与 24 级灰阶中找最接近的 256 色序号（不使用前 16 色，其实际颜色因终端而异）
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255u8).min_by_key(|&index| distance(rgb, ansi256_rgb(index))).unwrap_or(16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 解析后的主题表，每行 `元素 = 颜色`
+    fn table(name: ThemeName, overrides: &[&str], support: ColorSupport) -> String {
+        let overrides: Vec<String> = overrides.iter().map(|s| s.to_string()).collect();
+        let theme = Theme::resolve(name, &overrides, support).unwrap();
+        ELEMENTS.iter().map(|&(element, key)| format!("{} = {:?}\n", key, theme.color(element))).collect()
+    }
+
+    #[test]
+    fn built_in_themes() {
+        assert_eq!(
+            table(ThemeName::Dark, &[], ColorSupport::TrueColor),
+            "header = Cyan\ntitle = White\nartist = Grey\ntime = Green\nvolume = Yellow\n\
+             progress-filled = Green\nprogress-empty = DarkGrey\nerror = Red\ntoast = Magenta\n"
+        );
+        assert_eq!(
+            table(ThemeName::Light, &[], ColorSupport::Ansi16),
+            "header = DarkBlue\ntitle = Black\nartist = DarkGrey\ntime = DarkGreen\nvolume = DarkYellow\n\
+             progress-filled = DarkGreen\nprogress-empty = Grey\nerror = DarkRed\ntoast = DarkMagenta\n"
+        );
+    }
+
+    #[test]
+    fn overrides_degrade_to_the_terminal() {
+        let overrides = ["title=#ffaa00", " time = 208 ", "toast=Dark-Gray", "error=GREY"];
+        let expect = |title: &str, time: &str| {
+            format!(
+                "header = Cyan\ntitle = {}\nartist = Grey\ntime = {}\nvolume = Yellow\n\
+                 progress-filled = Green\nprogress-empty = DarkGrey\nerror = Grey\ntoast = DarkGrey\n",
+                title, time
+            )
+        };
+        assert_eq!(table(ThemeName::Dark, &overrides, ColorSupport::TrueColor), expect("Rgb { r: 255, g: 170, b: 0 }", "AnsiValue(208)"));
+        assert_eq!(table(ThemeName::Dark, &overrides, ColorSupport::Ansi256), expect("AnsiValue(214)", "AnsiValue(208)"));
+        assert_eq!(table(ThemeName::Dark, &overrides, ColorSupport::Ansi16), expect("Yellow", "Yellow"));
+    }
+
+    #[test]
+    fn invalid_entries_name_the_key() {
+        let error = |entry: &str| Theme::resolve(ThemeName::Dark, &[entry.to_string()], ColorSupport::TrueColor).unwrap_err();
+        assert_eq!(error("title=#ffaa0"), "主题元素 title 的颜色 '#ffaa0' 无法识别");
+        assert_eq!(error("volume=256"), "主题元素 volume 的颜色 '256' 无法识别");
+        assert_eq!(error("artist=pink"), "主题元素 artist 的颜色 'pink' 无法识别");
+        assert!(error("footer=red").starts_with("未知的主题元素 'footer'（可用: header, title,"));
+        assert!(error("title").contains("元素=颜色"));
+    }
+
+    #[test]
+    fn palette_round_trips() {
+        // 256 色块与灰阶中的每个颜色都是自己最接近的序号
+        for index in 16..=255u8 {
+            assert_eq!(nearest_256(ansi256_rgb(index)), index);
+        }
+        for (color, _, rgb) in ANSI16 {
+            assert_eq!(nearest_16(rgb), color);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::time::{Duration, Instant};
+
+/// 预加载线程内各阶段的耗时
+#[derive(Debug, Clone, Copy)]
+pub struct PreloadTiming {
+    /// 读取标签与时长
+    pub metadata: Duration,
+    /// 打开文件
+    pub open: Duration,
+    /// 构造解码器（含章节跳转与前导缓冲）
+    pub decoder: Duration,
+    /// 预加载完成的时刻
+    pub completed_at: Instant,
+}
+
+/// 一首歌曲的完整计时记录
+#[derive(Debug, Clone)]
+struct TrackTiming {
+    label: String,
+    preload: PreloadTiming,
+    /// 预加载完成时间相对于开始等待该曲的提前量（毫秒），负数表示播放端等待了预加载
+    slack_ms: f64,
+    /// 从加入 Sink 到开始出声的耗时；未测到（如很快切歌）时为 None
+    audible: Option<Duration>,
+}
+
+/// `--timing` 诊断记录：每首的明细（--verbose）与退出时的 最小/中位/最大 汇总。
+/// 标准错误不是终端（如重定向到文件）时，明细在每首开始出声（或未测到出声就切歌）时立即输出；
+/// 是终端时界面正在使用它，明细与汇总一起在 Drop 时输出，位于退出提示之后（终端已恢复正常模式）。
+pub struct TimingLog {
+    enabled: bool,
+    verbose: bool,
+    /// 明细随播放输出
+    live: bool,
+    entries: Vec<TrackTiming>,
+    /// 已经输出明细的歌曲数
+    reported: usize,
+}
+
+impl TimingLog {
+    pub fn new(enabled: bool, verbose: bool, live: bool) -> Self {
+        TimingLog { enabled, verbose, live, entries: Vec::new(), reported: 0 }
+    }
+
+    /// 记录一首开始播放的歌曲；`wait_started` 为播放端开始等待该曲的时刻
+    pub fn record(&mut self, label: &str, preload: PreloadTiming, wait_started: Instant) {
+        if !self.enabled {
+            return;
+        }
+        let slack_ms = if preload.completed_at <= wait_started {
+            wait_started.duration_since(preload.completed_at).as_secs_f64() * 1000.0
+        } else {
+            -(preload.completed_at.duration_since(wait_started).as_secs_f64() * 1000.0)
+        };
+        // 上一首没有测到出声就被切走：明细到此为止
+        self.print_finished(self.entries.len());
+        self.entries.push(TrackTiming { label: label.to_string(), preload, slack_ms, audible: None });
+    }
+
+    /// 记录最近一首从加入 Sink 到开始出声的耗时
+    pub fn record_audible(&mut self, elapsed: Duration) {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.audible.get_or_insert(elapsed);
+        }
+        self.print_finished(self.entries.len());
+    }
+
+    /// 随播放输出前 `finished` 首中尚未输出的明细
+    fn print_finished(&mut self, finished: usize) {
+        if self.live {
+            for line in self.detail_lines(finished) {
+                eprintln!("{}", line);
+            }
+        }
+    }
+
+    /// 前 `finished` 首中尚未输出的明细（--verbose 时），取出后记为已输出
+    fn detail_lines(&mut self, finished: usize) -> Vec<String> {
+        if !self.verbose || finished <= self.reported {
+            return Vec::new();
+        }
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let lines = self.entries[self.reported..finished]
+            .iter()
+            .map(|entry| {
+                format!(
+                    "[详细][计时]{}: 元数据 {:.1}ms 打开 {:.1}ms 解码器 {:.1}ms 提前量 {:.1}ms 出声 {}",
+                    entry.label,
+                    ms(entry.preload.metadata),
+                    ms(entry.preload.open),
+                    ms(entry.preload.decoder),
+                    entry.slack_ms,
+                    entry.audible.map_or("未测到".to_string(), |d| format!("{:.1}ms", ms(d))),
+                )
+            })
+            .collect();
+        self.reported = finished;
+        lines
+    }
+
+    /// 尚未输出的明细与汇总的各行文本
+    fn report_lines(&mut self) -> Vec<String> {
+        if self.entries.is_empty() {
+            return vec!["[计时]没有可统计的歌曲".to_string()];
+        }
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mut lines = self.detail_lines(self.entries.len());
+        let rows: [(&str, Vec<f64>); 5] = [
+            ("元数据", self.entries.iter().map(|e| ms(e.preload.metadata)).collect()),
+            ("打开", self.entries.iter().map(|e| ms(e.preload.open)).collect()),
+            ("解码器", self.entries.iter().map(|e| ms(e.preload.decoder)).collect()),
+            ("提前量", self.entries.iter().map(|e| e.slack_ms).collect()),
+            ("出声", self.entries.iter().filter_map(|e| e.audible.map(ms)).collect()),
+        ];
+        lines.push(format!("[计时]共 {} 首（毫秒，最小/中位/最大）", self.entries.len()));
+        for (name, samples) in rows {
+            if let Some((min, median, max)) = summarize(&samples) {
+                lines.push(format!("[计时]{}: {:.1} / {:.1} / {:.1}", name, min, median, max));
+            }
+        }
+        lines
+    }
+}
+
+impl Drop for TimingLog {
+    fn drop(&mut self) {
+        if self.enabled {
+            for line in self.report_lines() {
+                eprintln!("{}", line);
+            }
+        }
+    }
+}
+
+/// 计算样本的 (最小, 中位数, 最大)；偶数个样本时中位数取中间两个的平均；空样本返回 None
+pub fn summarize(samples: &[f64]) -> Option<(f64, f64, f64)> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] };
+    Some((sorted[0], median, sorted[sorted.len() - 1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(metadata: u64, open: u64, decoder: u64, completed_at: Instant) -> PreloadTiming {
+        let ms = Duration::from_millis;
+        PreloadTiming { metadata: ms(metadata), open: ms(open), decoder: ms(decoder), completed_at }
+    }
+
+    #[test]
+    fn summarizes_min_median_max() {
+        assert_eq!(summarize(&[]), None);
+        assert_eq!(summarize(&[4.0]), Some((4.0, 4.0, 4.0)));
+        assert_eq!(summarize(&[9.0, 1.0, 5.0]), Some((1.0, 5.0, 9.0)));
+        assert_eq!(summarize(&[8.0, -2.0, 4.0, 1.0]), Some((-2.0, 2.5, 8.0)));
+        assert_eq!(summarize(&[3.0, 3.0, 3.0, 3.0]), Some((3.0, 3.0, 3.0)));
+    }
+
+    #[test]
+    fn report_lists_tracks_and_summary() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut log = TimingLog::new(true, true, false);
+        // 提前 200ms 完成、播放端等了 50ms、刚好同时
+        log.record("a.flac", timing(10, 1, 30, at(100)), at(300));
+        log.record_audible(Duration::from_millis(40));
+        log.record_audible(Duration::from_millis(999)); // 只记第一次
+        log.record("b.mp3", timing(20, 3, 10, at(450)), at(400));
+        log.record("c.ogg", timing(30, 2, 20, at(600)), at(600));
+        log.record_audible(Duration::from_millis(60));
+        let lines = log.report_lines();
+        assert_eq!(
+            lines,
+            [
+                "[详细][计时]a.flac: 元数据 10.0ms 打开 1.0ms 解码器 30.0ms 提前量 200.0ms 出声 40.0ms",
+                "[详细][计时]b.mp3: 元数据 20.0ms 打开 3.0ms 解码器 10.0ms 提前量 -50.0ms 出声 未测到",
+                "[详细][计时]c.ogg: 元数据 30.0ms 打开 2.0ms 解码器 20.0ms 提前量 0.0ms 出声 60.0ms",
+                "[计时]共 3 首（毫秒，最小/中位/最大）",
+                "[计时]元数据: 10.0 / 20.0 / 30.0",
+                "[计时]打开: 1.0 / 2.0 / 3.0",
+                "[计时]解码器: 10.0 / 20.0 / 30.0",
+                "[计时]提前量: -50.0 / 0.0 / 200.0",
+                "[计时]出声: 40.0 / 50.0 / 60.0",
+            ]
+        );
+        // 不加 --verbose 时只有汇总；没有记录时给出提示
+        log.verbose = false;
+        assert_eq!(log.report_lines().len(), 6);
+        log.entries.clear();
+        assert_eq!(log.report_lines(), ["[计时]没有可统计的歌曲"]);
+        // 未启用时不记录
+        let mut disabled = TimingLog::new(false, true, false);
+        disabled.record("a.flac", timing(1, 1, 1, start), start);
+        assert!(disabled.entries.is_empty());
+        // 测试结束时不在标准错误输出汇总
+        log.enabled = false;
+    }
+
+    #[test]
+    fn details_are_taken_once_as_tracks_finish() {
+        let start = Instant::now();
+        let mut log = TimingLog::new(true, true, false);
+        log.record("a.flac", timing(10, 1, 30, start), start);
+        // 还没测到出声：不算结束
+        assert!(log.detail_lines(0).is_empty());
+        log.record_audible(Duration::from_millis(40));
+        assert_eq!(log.detail_lines(1), ["[详细][计时]a.flac: 元数据 10.0ms 打开 1.0ms 解码器 30.0ms 提前量 0.0ms 出声 40.0ms"]);
+        assert!(log.detail_lines(1).is_empty());
+        // 没测到出声就切到下一首
+        log.record("b.mp3", timing(20, 3, 10, start), start);
+        log.record("c.ogg", timing(30, 2, 20, start), start);
+        assert_eq!(log.detail_lines(2), ["[详细][计时]b.mp3: 元数据 20.0ms 打开 3.0ms 解码器 10.0ms 提前量 0.0ms 出声 未测到"]);
+        // 退出时只补上剩下的明细，汇总仍包括全部歌曲
+        let lines = log.report_lines();
+        assert!(lines[0].starts_with("[详细][计时]c.ogg: "), "{:?}", lines);
+        assert_eq!(lines[1], "[计时]共 3 首（毫秒，最小/中位/最大）");
+        // 不加 --verbose 时没有明细
+        let mut quiet = TimingLog::new(true, false, false);
+        quiet.record("a.flac", timing(1, 1, 1, start), start);
+        assert!(quiet.detail_lines(1).is_empty());
+        log.enabled = false;
+        quiet.enabled = false;
+    }
+}
@@ -0,0 +1,182 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::volume::{TRACK_GAIN_MAX_DB, TRACK_GAIN_MIN_DB};
+
+/// 按键没有产生效果的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoOp {
+    VolumeMax,
+    VolumeMin,
+    GainMax,
+    GainMin,
+    /// 沿行进方向已没有上一首（不循环时）
+    FirstTrack,
+    /// 沿行进方向已没有下一首（不循环时）
+    LastTrack,
+    /// 长音频已全部听过
+    AllHeard,
+    /// 不是长音频，没有记录听过的区间
+    NotLongTrack,
+}
+
+impl NoOp {
+    /// 音量已在边界时调整音量无效（取消静音或接管渐强本身就是效果，由调用方先排除）
+    pub fn volume_limit(up: bool, volume: f32) -> Option<Self> {
+        match up {
+            true if volume >= 1.0 => Some(NoOp::VolumeMax),
+            false if volume <= 0.0 => Some(NoOp::VolumeMin),
+            _ => None,
+        }
+    }
+
+    /// 单曲增益已在边界时调整无效
+    pub fn gain_limit(up: bool, gain_db: f32) -> Option<Self> {
+        match up {
+            true if gain_db >= TRACK_GAIN_MAX_DB => Some(NoOp::GainMax),
+            false if gain_db <= TRACK_GAIN_MIN_DB => Some(NoOp::GainMin),
+            _ => None,
+        }
+    }
+
+    /// 切歌没有目标时（不循环且已到列表一端）的原因
+    pub fn track_end(forward: bool) -> Self {
+        if forward { NoOp::LastTrack } else { NoOp::FirstTrack }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            NoOp::VolumeMax => "音量已是最大",
+            NoOp::VolumeMin => "音量已是最小",
+            NoOp::GainMax => "单曲增益已是最大",
+            NoOp::GainMin => "单曲增益已是最小",
+            NoOp::FirstTrack => "已是第一首",
+            NoOp::LastTrack => "已是最后一首",
+            NoOp::AllHeard => "已全部听过",
+            NoOp::NotLongTrack => "只有 20 分钟以上的音频记录听过的部分",
+        }
+    }
+}
+
+/// 状态行右侧的短暂提示（如「音量 45%」「方向: 倒序」）：
+/// 显示固定时长后自动消失，新的提示直接替换旧的。
+pub struct Toast {
+    duration: Duration,
+    /// 当前提示及其到期时刻
+    current: Option<(String, Instant)>,
+    /// 按键无效时是否响铃（`--no-bell` 关闭）
+    bell: bool,
+}
+
+impl Toast {
+    pub fn new(duration: Duration, bell: bool) -> Self {
+        Toast { duration, current: None, bell }
+    }
+
+    /// 按键没有产生效果：显示原因并响铃，让用户知道按键已被接收
+    pub fn reject(&mut self, stdout: &mut impl Write, reason: NoOp, now: Instant) -> io::Result<()> {
+        self.show(reason.message(), now);
+        if self.bell {
+            write!(stdout, "\x07")?;
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 显示一条提示（替换尚未消失的旧提示）
+    pub fn show(&mut self, message: impl Into<String>, now: Instant) {
+        self.current = Some((message.into(), now + self.duration));
+    }
+
+    /// 当前应显示的提示；已到期时返回 None
+    pub fn message(&self, now: Instant) -> Option<&str> {
+        self.current.as_ref().filter(|(_, expiry)| now < *expiry).map(|(message, _)| message.as_str())
+    }
+
+    /// 清除已到期的提示；确有提示被清除时返回 true（调用方据此重绘状态行）
+    pub fn expire(&mut self, now: Instant) -> bool {
+        if self.current.as_ref().is_some_and(|(_, expiry)| now >= *expiry) {
+            self.current = None;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOWN: Duration = Duration::from_millis(1500);
+
+    #[test]
+    fn shows_until_expiry() {
+        let now = Instant::now();
+        let mut toast = Toast::new(SHOWN, false);
+        assert_eq!(toast.message(now), None);
+        assert!(!toast.expire(now));
+
+        toast.show("音量 45%", now);
+        assert_eq!(toast.message(now), Some("音量 45%"));
+        assert_eq!(toast.message(now + SHOWN - Duration::from_millis(1)), Some("音量 45%"));
+        assert_eq!(toast.message(now + SHOWN), None);
+        // 到期前不清除，到期后只报告一次
+        assert!(!toast.expire(now + Duration::from_millis(100)));
+        assert!(toast.expire(now + SHOWN));
+        assert!(!toast.expire(now + SHOWN));
+    }
+
+    #[test]
+    fn newer_toast_replaces_and_restarts_the_timer() {
+        let now = Instant::now();
+        let mut toast = Toast::new(SHOWN, false);
+        toast.show("随机: 开", now);
+        let later = now + Duration::from_millis(1000);
+        toast.show("随机: 关", later);
+        assert_eq!(toast.message(now + SHOWN), Some("随机: 关"));
+        assert!(!toast.expire(now + SHOWN));
+        assert!(toast.expire(later + SHOWN));
+    }
+
+    #[test]
+    fn rejection_rings_the_bell_unless_disabled() {
+        let now = Instant::now();
+        for bell in [true, false] {
+            let mut toast = Toast::new(SHOWN, bell);
+            let mut out = Vec::new();
+            toast.reject(&mut out, NoOp::VolumeMax, now).unwrap();
+            assert_eq!(toast.message(now), Some("音量已是最大"));
+            assert_eq!(out, if bell { b"\x07".to_vec() } else { Vec::new() });
+        }
+    }
+
+    #[test]
+    fn boundary_keys_report_why_nothing_happened() {
+        assert_eq!(NoOp::volume_limit(true, 1.0), Some(NoOp::VolumeMax));
+        assert_eq!(NoOp::volume_limit(true, 0.99), None);
+        assert_eq!(NoOp::volume_limit(false, 0.0), Some(NoOp::VolumeMin));
+        assert_eq!(NoOp::volume_limit(false, 0.01), None);
+        // 到顶时仍可调小，反之亦然
+        assert_eq!(NoOp::volume_limit(false, 1.0), None);
+        assert_eq!(NoOp::volume_limit(true, 0.0), None);
+
+        assert_eq!(NoOp::gain_limit(true, TRACK_GAIN_MAX_DB), Some(NoOp::GainMax));
+        assert_eq!(NoOp::gain_limit(false, TRACK_GAIN_MIN_DB), Some(NoOp::GainMin));
+        assert_eq!(NoOp::gain_limit(true, 0.0), None);
+        assert_eq!(NoOp::gain_limit(false, TRACK_GAIN_MAX_DB), None);
+    }
+
+    #[test]
+    fn skipping_past_the_ends_without_loop() {
+        use crate::queue::{skip_index, Direction};
+        // 主循环按步数的正负选择方向，找不到目标时给出原因
+        let skip = |index: usize, steps: i32| {
+            let direction = if steps > 0 { Direction::Forward } else { Direction::Backward };
+            skip_index(index, 5, direction, steps.unsigned_abs() as usize, false, 0).ok_or(NoOp::track_end(steps > 0))
+        };
+        assert_eq!(skip(0, -1), Err(NoOp::FirstTrack));
+        assert_eq!(skip(4, 1), Err(NoOp::LastTrack));
+        assert_eq!(skip(0, 1), Ok(1));
+        assert_eq!(skip(4, -1), Ok(3));
+    }
+}
@@ -0,0 +1,1022 @@
+use std::io::{self, Write};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+// 终端交互库：用于光标定位与清屏
+use crossterm::{
+    cursor, execute,
+    event::{self, Event, KeyCode, KeyEventKind},
+    style::{Attribute, ContentStyle, Stylize},
+    terminal::{self, ClearType, SetTitle},
+};
+
+use crate::cli::{NAME, URL, VERSION};
+use crate::abloop::AbLoop;
+use crate::confirm::{QuitConfirm, QuitDecision, QUIT_CONFIRM_MESSAGE};
+use crate::history::{RecentPlays, RECENT_ROWS};
+use crate::listened::Segments;
+use crate::metadata::Template;
+use crate::theme::{Element, Theme};
+use crate::volume::{volume_bar, VolumeAccelerator};
+use crate::utils::{sanitize_display, display_width, format_duration, format_position, truncate_string};
+
+/// 音量条的宽度（列）
+const VOLUME_BAR_WIDTH: usize = 8;
+
+/// 界面的输出目标：标准输出与标准错误中是终端的那个
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenTarget {
+    Stdout,
+    Stderr,
+    /// 两者都不是终端（输出被重定向到文件或管道）：改用纯净模式，不调整终端尺寸、不设置标题
+    Plain,
+}
+
+/// 界面的终端配置：输出目标与是否读取按键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenSetup {
+    pub target: ScreenTarget,
+    /// 标准输入是终端：进入原始模式并处理按键。否则不读取按键（如 `mddplayer 目录 < /dev/null`）
+    pub keys: bool,
+}
+
+/// 按标准输入、标准输出、标准错误是否为终端选择界面配置：界面画在输出端是终端的那个上（优先标准输出），
+/// 按键读取只取决于标准输入（原始模式作用于输入端），两者互不影响。
+pub fn choose_screen_target(stdin_is_tty: bool, stdout_is_tty: bool, stderr_is_tty: bool) -> ScreenSetup {
+    let target = match (stdout_is_tty, stderr_is_tty) {
+        (true, _) => ScreenTarget::Stdout,
+        (false, true) => ScreenTarget::Stderr,
+        (false, false) => ScreenTarget::Plain,
+    };
+    ScreenSetup { target, keys: stdin_is_tty }
+}
+
+/// 是否读取按键；标准输入不是终端时关闭，读取按键的地方改为只等待
+static KEY_INPUT: AtomicBool = AtomicBool::new(true);
+
+pub fn set_key_input(enabled: bool) {
+    KEY_INPUT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn key_input_enabled() -> bool {
+    KEY_INPUT.load(Ordering::Relaxed)
+}
+
+/// 等待终端事件，最长 `timeout`；不读取按键时只等待并返回 false
+pub fn poll_event(timeout: Duration) -> io::Result<bool> {
+    if !key_input_enabled() {
+        thread::sleep(timeout);
+        return Ok(false);
+    }
+    event::poll(timeout)
+}
+
+/// 阻塞读取下一个终端事件；不读取按键时返回错误而不是永远阻塞
+/// （需要按键才能继续的策略在启动时已改为不需要按键的策略，这里只是兜底）
+pub fn read_event() -> io::Result<Event> {
+    if !key_input_enabled() {
+        return Err(io::Error::other("标准输入不是终端，无法读取按键"));
+    }
+    event::read()
+}
+
+/// 纯净模式下滤除终端控制序列的状态（序列可能被拆分在多次写入中）
+#[derive(Debug, Default)]
+pub struct PlainFilter {
+    escape: Escape,
+    /// 当前行已有文字（清空状态行时改为换行，每条提示各占一行）
+    line_open: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    #[default]
+    None,
+    /// 刚读到 ESC
+    Start,
+    /// `ESC [` 开始的控制序列，到 0x40–0x7E 的结束字节为止
+    Csi,
+    /// `ESC ]` 开始的操作系统命令（如设置标题），到 BEL 或 `ESC \` 为止
+    Osc,
+}
+
+impl PlainFilter {
+    /// 去掉 `buf` 中的控制序列，返回剩余的文字
+    fn filter(&mut self, buf: &[u8]) -> Vec<u8> {
+        let mut text = Vec::with_capacity(buf.len());
+        for &byte in buf {
+            self.escape = match (self.escape, byte) {
+                (_, 0x1b) => Escape::Start,
+                (Escape::Start, b'[') => Escape::Csi,
+                (Escape::Start, b']') => Escape::Osc,
+                (Escape::Start, _) => Escape::None,
+                (Escape::Csi, 0x40..=0x7e) => Escape::None,
+                (Escape::Csi, _) => Escape::Csi,
+                (Escape::Osc, 0x07) => Escape::None,
+                (Escape::Osc, _) => Escape::Osc,
+                (Escape::None, b'\r') => Escape::None,
+                (Escape::None, _) => {
+                    text.push(byte);
+                    self.line_open = byte != b'\n';
+                    Escape::None
+                }
+            };
+        }
+        text
+    }
+}
+
+/// 界面输出：终端控制序列与界面文本都写到这里（如 `mddplayer 目录 > /dev/null` 时画在标准错误上）
+pub enum Screen {
+    Stdout(io::Stdout),
+    Stderr(io::Stderr),
+    /// 纯净模式：输出不是终端，滤除所有控制序列，只留下文字
+    Plain(io::Stdout, PlainFilter),
+}
+
+impl Screen {
+    pub fn new(target: ScreenTarget) -> Self {
+        match target {
+            ScreenTarget::Stdout => Screen::Stdout(io::stdout()),
+            ScreenTarget::Stderr => Screen::Stderr(io::stderr()),
+            ScreenTarget::Plain => Screen::Plain(io::stdout(), PlainFilter::default()),
+        }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        matches!(self, Screen::Plain(..))
+    }
+}
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Screen::Stdout(out) => out.write(buf),
+            Screen::Stderr(err) => err.write(buf),
+            Screen::Plain(out, filter) => {
+                out.write_all(&filter.filter(buf))?;
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Screen::Stdout(out) => out.flush(),
+            Screen::Stderr(err) => err.flush(),
+            Screen::Plain(out, _) => out.flush(),
+        }
+    }
+}
+
+/// 说明横幅占用的行数（状态行紧接其后）
+pub const BANNER_ROWS: u16 = 10;
+
+/// 终端不超过这个行数时进入单行迷你模式
+pub const MINI_MAX_ROWS: u16 = 2;
+
+/// 生成说明横幅的各行文本
+fn banner_lines() -> [String; BANNER_ROWS as usize] {
+    [
+        format!("=====================【 {} 】======================", NAME),
+        format!(" 版本:v{}          主页:{}", VERSION, URL),
+        "===========================================================".to_string(),
+        "====================【 控 制 说 明 】======================".to_string(),
+        " [P]静音/取消静音   [空格]暂停/播放    [Q/Ctrl+C]退出播放".to_string(),
+        " [←]上一首      [→]下一首    [↑]音量增    [↓]音量减".to_string(),
+        " [E]加入队列  [U]撤销  [W]改标签  [D]反转方向  [H]压低".to_string(),
+        " [J]跳到未听  [T]列表剩余  [M]手动切歌  [A]A-B循环".to_string(),
+        " [数字+R]重复N遍  [Tab]跳片头  [F2]纯净模式  [F8]输出设备".to_string(),
+        "============================================================".to_string(),
+    ]
+}
+
+/// 界面布局：决定横幅是否常驻，以及状态行画在哪里。
+pub struct Layout {
+    /// 纯净模式：不显示横幅
+    pub clean: bool,
+    /// 常驻横幅：横幅占据顶部固定行，状态行固定在横幅下方
+    pub keep_banner: bool,
+    /// 配色（`--theme`）；None 表示不着色
+    pub theme: Option<Theme>,
+    /// 迷你模式：终端只有 1–2 行（如专用的 tmux 窄栏），无论参数如何都只画状态行，也不调整终端尺寸
+    pub mini: bool,
+    /// 终端失去焦点（`--dim-on-unfocus`）：着色的元素改为暗淡显示
+    pub dimmed: bool,
+}
+
+impl Layout {
+    /// 按主题给整段文本着色；未启用主题时原样返回
+    pub fn paint(&self, text: &str, element: Element) -> String {
+        match (&self.theme, self.dimmed) {
+            (Some(theme), true) => text.with(theme.color(element)).dim().to_string(),
+            (Some(theme), false) => text.with(theme.color(element)).to_string(),
+            (None, true) => text.dim().to_string(),
+            (None, false) => text.to_string(),
+        }
+    }
+
+    /// 是否只显示状态行（纯净模式或迷你模式）
+    pub fn is_compact(&self) -> bool {
+        self.clean || self.mini
+    }
+
+    /// 按终端行数决定是否进入迷你模式（切换后由调用方全量重绘）
+    pub fn fit_height(&mut self, rows: u16) {
+        self.mini = rows <= MINI_MAX_ROWS;
+    }
+
+    /// 最近播放区域占用的行数（纯净模式下不显示）
+    fn recent_rows(&self) -> u16 {
+        if self.is_compact() { 0 } else { RECENT_ROWS }
+    }
+
+    /// 界面总行数：横幅 + 最近播放 + 状态行
+    pub fn total_rows(&self) -> u16 {
+        if self.is_compact() { 1 } else { BANNER_ROWS + self.recent_rows() + 1 }
+    }
+
+    /// 状态行所在的绝对行号；None 表示沿用当前行（只回到行首）
+    fn status_row(&self) -> Option<u16> {
+        if self.keep_banner && !self.is_compact() { Some(BANNER_ROWS + self.recent_rows()) } else { None }
+    }
+
+    /// 把光标移动到状态行行首
+    pub fn move_to_status(&self, stdout: &mut Screen) -> io::Result<()> {
+        match self.status_row() {
+            Some(row) => execute!(stdout, cursor::MoveTo(0, row)),
+            None => execute!(stdout, cursor::MoveToColumn(0)),
+        }
+    }
+
+    /// 清空状态行；纯净模式下改为换行（上一条提示留在输出中）
+    pub fn clear_status(&self, stdout: &mut Screen) -> io::Result<()> {
+        if let Screen::Plain(out, filter) = stdout {
+            if filter.line_open {
+                filter.line_open = false;
+                writeln!(out)?;
+            }
+            return Ok(());
+        }
+        self.move_to_status(stdout)?;
+        execute!(stdout, terminal::Clear(ClearType::CurrentLine))
+    }
+
+
+    /// 绘制最近播放区域（横幅下方的固定行），绘制后光标回到原位，不影响状态行
+    pub fn draw_recent(&self, stdout: &mut Screen, recent: &RecentPlays) -> io::Result<()> {
+        if self.recent_rows() == 0 {
+            return Ok(());
+        }
+        let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+        let mut lines = recent.iter().map(|entry| entry.line());
+        execute!(stdout, cursor::SavePosition)?;
+        for row in 0..self.recent_rows() {
+            execute!(stdout, cursor::MoveTo(0, BANNER_ROWS + row), terminal::Clear(ClearType::CurrentLine))?;
+            if let Some(line) = lines.next() {
+                write!(stdout, "{}", truncate_string(&line, terminal_width))?;
+            }
+        }
+        execute!(stdout, cursor::RestorePosition)?;
+        stdout.flush()
+    }
+
+    /// 全量重绘：清屏后按当前布局重新绘制横幅与最近播放，并把光标放到状态行。
+    /// 启动、终端尺寸变化与运行时切换纯净/完整界面都使用它；状态行由调用方随后刷新。
+    pub fn redraw(&self, stdout: &mut Screen, recent: &RecentPlays) -> io::Result<()> {
+        execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        if !self.is_compact() {
+            for (row, line) in banner_lines().iter().enumerate() {
+                execute!(stdout, cursor::MoveTo(0, row as u16))?;
+                write!(stdout, "{}", self.paint(line, Element::Header))?;
+            }
+        }
+        // 非常驻横幅模式下状态行沿用光标所在行，这里先把光标放到横幅与最近播放区域下方
+        execute!(stdout, cursor::MoveTo(0, self.total_rows() - 1))?;
+        self.draw_recent(stdout, recent)
+    }
+}
+
+/// 在状态行显示启动提示（扫描完成到第一首开始播放之间），随后被正常状态行覆盖
+pub fn show_splash(stdout: &mut Screen, layout: &Layout, text: &str) -> io::Result<()> {
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    layout.clear_status(stdout)?;
+    let text = truncate_string(text, terminal_width);
+    if text.starts_with("[错误]") {
+        write!(stdout, "{}", layout.paint(&text, Element::Error))?;
+    } else {
+        write!(stdout, "{}", text)?;
+    }
+    stdout.flush()
+}
+
+/// 在状态行读取一行输入（播放继续进行），输入框预填 `initial`。Enter 确认，Esc 取消（返回 None）。
+pub fn prompt_line(stdout: &mut Screen, layout: &Layout, prompt: &str, initial: &str) -> io::Result<Option<String>> {
+    let mut input = initial.to_string();
+    execute!(stdout, cursor::Show)?;
+    let result = loop {
+        // 输入过长时只显示末尾部分
+        let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+        let available = terminal_width.saturating_sub(display_width(prompt) + 1);
+        let mut shown: Vec<char> = Vec::new();
+        let mut width = 0;
+        for c in sanitize_display(&input).chars().rev() {
+            width += display_width(&c.to_string());
+            if width > available {
+                break;
+            }
+            shown.push(c);
+        }
+        let shown: String = shown.into_iter().rev().collect();
+        layout.clear_status(stdout)?;
+        write!(stdout, "{}{}", prompt, shown)?;
+        stdout.flush()?;
+
+        match read_event()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Some(input),
+                KeyCode::Esc => break None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            },
+            Event::Paste(text) => input.push_str(&text),
+            _ => {}
+        }
+    };
+    execute!(stdout, cursor::Hide)?;
+    layout.clear_status(stdout)?;
+    Ok(result)
+}
+
+/// 在状态行从列表中选择一项（播放继续进行）：↑/↓ 切换，Enter 确认并返回所选位置，Esc/Q 取消返回 None。
+/// 状态行只有一行，每次只显示当前项及其序号。
+pub fn select_item(stdout: &mut Screen, layout: &Layout, prompt: &str, items: &[String], initial: usize) -> io::Result<Option<usize>> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let mut selected = initial.min(items.len() - 1);
+    let result = loop {
+        let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+        let hint = format!(" ({}/{})  [↑↓]选择 [Enter]确认 [Esc]取消", selected + 1, items.len());
+        let available = terminal_width.saturating_sub(display_width(prompt) + display_width(&hint) + 4);
+        layout.clear_status(stdout)?;
+        write!(stdout, "{}‹ {} ›{}", prompt, truncate_string(&sanitize_display(&items[selected]), available), hint)?;
+        stdout.flush()?;
+
+        if let Event::Key(key) = read_event()?
+            && key.kind == KeyEventKind::Press
+        {
+            match key.code {
+                KeyCode::Up => selected = (selected + items.len() - 1) % items.len(),
+                KeyCode::Down => selected = (selected + 1) % items.len(),
+                KeyCode::Enter => break Some(selected),
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('Q') => break None,
+                _ => {}
+            }
+        }
+    };
+    layout.clear_status(stdout)?;
+    Ok(result)
+}
+
+/// `--title-format` 中可用的歌曲信息
+pub struct TitleFields<'a> {
+    pub title: &'a str,
+    pub artist: &'a str,
+    /// 当前歌曲的序号（从 0 开始）
+    pub index: usize,
+    pub total: usize,
+    pub elapsed: Duration,
+    pub duration: Duration,
+}
+
+/// 终端（标签页）标题。状态由播放状态统一推导：暂停时为 `[暂停]`，否则静音时为 `[静音]`，
+/// 默认作为前缀加在歌曲信息前，使用 `--title-format` 时填入 `{state}`。
+/// 只在标题实际变化时发送，避免频繁切歌时部分终端处理标题序列卡顿；`--no-title` 时从不发送。
+pub struct TerminalTitle {
+    enabled: bool,
+    /// `--title-format` 模板；None 时为「状态前缀 + 歌曲信息」
+    format: Option<Template>,
+    /// 模板的重新填充间隔：进度每秒都在变，歌曲与状态不变时只按该间隔刷新
+    interval: Duration,
+    /// 最近一次填充模板时的歌曲与状态，以及填充的时刻
+    rendered: Option<(String, Instant)>,
+    /// 最近一次发送的标题
+    last: Option<String>,
+}
+
+impl TerminalTitle {
+    pub fn new(enabled: bool, format: Option<Template>, interval: Duration) -> Self {
+        TerminalTitle { enabled, format, interval, rendered: None, last: None }
+    }
+
+    /// 按歌曲信息（或启动时的程序名）与当前状态更新标题；`fields` 为 None（尚未开始播放）时不使用模板
+    pub fn update(
+        &mut self,
+        stdout: &mut Screen,
+        caption: &str,
+        fields: Option<&TitleFields>,
+        paused: bool,
+        muted: bool,
+    ) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let state = if paused {
+            "[暂停]"
+        } else if muted {
+            "[静音]"
+        } else {
+            ""
+        };
+        let title = match (&self.format, fields) {
+            (Some(format), Some(fields)) => {
+                let key = format!("{}{}", state, caption);
+                let now = Instant::now();
+                if self.rendered.as_ref().is_some_and(|(last_key, at)| *last_key == key && now.duration_since(*at) < self.interval) {
+                    return Ok(());
+                }
+                self.rendered = Some((key, now));
+                format.render(|name| match name {
+                    "title" => Some(fields.title.to_string()),
+                    "artist" => Some(fields.artist.to_string()),
+                    "index" => Some((fields.index + 1).to_string()),
+                    "total" => Some(fields.total.to_string()),
+                    "elapsed" => Some(format_position(fields.elapsed)),
+                    "duration" => Some(format_position(fields.duration)),
+                    "state" => Some(state.to_string()),
+                    _ => None,
+                })
+            }
+            _ => format!("{}{}", state, caption),
+        };
+        if self.last.as_deref() == Some(title.as_str()) {
+            return Ok(());
+        }
+        execute!(stdout, SetTitle(&title))?;
+        self.last = Some(title);
+        Ok(())
+    }
+}
+
+/// 等待按键的界面上的提示：`--confirm-quit` 下第一次按下退出键后，前面加上「再按一次退出」
+fn with_quit_hint(prompt: &str, quit: &QuitConfirm) -> String {
+    if quit.is_asking(Instant::now()) {
+        format!("{}  {}", QUIT_CONFIRM_MESSAGE, prompt)
+    } else {
+        prompt.to_string()
+    }
+}
+
+/// 暂停超时、已释放音频设备时等待按键：退出键（经 `quit` 判断）退出返回 false，其余任意键恢复播放返回 true
+pub fn wait_for_wake(stdout: &mut Screen, layout: &Layout, quit: &mut QuitConfirm) -> io::Result<bool> {
+    let prompt = "暂停过久，已释放音频设备 —— 按任意键继续播放  [Q]退出";
+    show_splash(stdout, layout, prompt)?;
+    let wake = loop {
+        if let Event::Key(key) = read_event()?
+            && key.kind == KeyEventKind::Press
+        {
+            match quit.press(&key, Instant::now()) {
+                QuitDecision::Quit => break false,
+                QuitDecision::AskAgain => show_splash(stdout, layout, &with_quit_hint(prompt, quit))?,
+                QuitDecision::Ignore => break true,
+            }
+        }
+    };
+    layout.clear_status(stdout)?;
+    Ok(wake)
+}
+
+/// `--alarm` 等待到点：不占用音频设备，状态行每秒刷新倒计时。到点返回 true，退出键取消返回 false。
+/// 每次都按本地时钟重新计算剩余时间，系统休眠唤醒后若已过点立即开始；↑/↓ 预设开始播放时的音量
+pub fn wait_for_alarm(stdout: &mut Screen, layout: &Layout, at: DateTime<Local>, volume: &mut f32, quit: &mut QuitConfirm) -> io::Result<bool> {
+    let mut accel = VolumeAccelerator::new();
+    let ring = loop {
+        let remaining = (at - Local::now()).to_std().unwrap_or(Duration::ZERO);
+        if remaining.is_zero() {
+            break true;
+        }
+        let secs = remaining.as_secs_f64().ceil() as u64;
+        let prompt = format!(
+            "闹钟 {} 开始播放，还有 {}:{:02}:{:02}  音量 {:.0}%  [↑/↓]音量  [Q]取消",
+            at.format("%H:%M"),
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            *volume * 100.0
+        );
+        show_splash(stdout, layout, &with_quit_hint(&prompt, quit))?;
+        if poll_event(remaining.min(Duration::from_secs(1)))?
+            && let Event::Key(key) = read_event()?
+            && key.kind == KeyEventKind::Press
+        {
+            match quit.press(&key, Instant::now()) {
+                QuitDecision::Quit => break false,
+                // 下一轮刷新时带上「再按一次退出」
+                QuitDecision::AskAgain => {}
+                QuitDecision::Ignore => match key.code {
+                    KeyCode::Up => *volume = (*volume + accel.step(Instant::now())).min(1.0),
+                    KeyCode::Down => *volume = (*volume - accel.step(Instant::now())).max(0.0),
+                    _ => {}
+                },
+            }
+        }
+    };
+    layout.clear_status(stdout)?;
+    Ok(ring)
+}
+
+/// 列表播放完毕（`--on-complete wait`）时等待按键：Enter/R 从头重播返回 true，退出键退出返回 false
+pub fn wait_for_replay(stdout: &mut Screen, layout: &Layout, quit: &mut QuitConfirm) -> io::Result<bool> {
+    let prompt = "播放完毕 —— [Enter/R]从头重播  [Q]退出";
+    show_splash(stdout, layout, prompt)?;
+    let replay = loop {
+        if let Event::Key(key) = read_event()?
+            && key.kind == KeyEventKind::Press
+        {
+            match quit.press(&key, Instant::now()) {
+                QuitDecision::Quit => break false,
+                QuitDecision::AskAgain => show_splash(stdout, layout, &with_quit_hint(prompt, quit))?,
+                QuitDecision::Ignore => {
+                    if matches!(key.code, KeyCode::Enter | KeyCode::Char('r') | KeyCode::Char('R')) {
+                        break true;
+                    }
+                }
+            }
+        }
+    };
+    layout.clear_status(stdout)?;
+    Ok(replay)
+}
+
+/// 手动切歌模式下单曲播完后的选择
+pub enum AdvanceChoice {
+    /// 播放下一首
+    Next,
+    /// 重播刚结束的歌曲
+    Replay,
+    /// 退出播放器
+    Quit,
+}
+
+/// 手动切歌模式（`--manual-advance`）下单曲播完时等待按键：→ 播放下一首，← 重播刚结束的歌曲，退出键退出
+pub fn wait_for_advance(stdout: &mut Screen, layout: &Layout, quit: &mut QuitConfirm) -> io::Result<AdvanceChoice> {
+    let prompt = "已结束，按 → 播放下一首  [←]重播  [Q]退出";
+    show_splash(stdout, layout, prompt)?;
+    let choice = loop {
+        if let Event::Key(key) = read_event()?
+            && key.kind == KeyEventKind::Press
+        {
+            match quit.press(&key, Instant::now()) {
+                QuitDecision::Quit => break AdvanceChoice::Quit,
+                QuitDecision::AskAgain => show_splash(stdout, layout, &with_quit_hint(prompt, quit))?,
+                QuitDecision::Ignore => match key.code {
+                    KeyCode::Right => break AdvanceChoice::Next,
+                    KeyCode::Left => break AdvanceChoice::Replay,
+                    _ => {}
+                },
+            }
+        }
+    };
+    layout.clear_status(stdout)?;
+    Ok(choice)
+}
+
+/// 状态行需要展示的全部信息
+pub struct StatusView<'a> {
+    pub current_index: usize,
+    pub total_tracks: usize,
+    pub is_random: bool,
+    /// 流式随机播放：总数未知
+    pub is_stream: bool,
+    pub is_loop: bool,
+    pub title: &'a str,
+    pub artist: &'a str,
+    pub track_path: &'a str,
+    /// 按内容探测出的格式，优先于扩展名显示
+    pub probed_format: Option<&'a str>,
+    /// 解码器输出的声道数，0 表示未知（不显示）
+    pub channels: u16,
+    pub current_time: Duration,
+    pub total_duration: Duration,
+    pub volume: f32,
+    /// 当前歌曲剩余的重复次数
+    pub repeat_remaining: u32,
+    /// 当前歌曲的单曲增益（dB），0 表示未设置
+    pub track_gain_db: f32,
+    /// A-B 循环状态
+    pub ab_loop: AbLoop,
+    /// 是否处于暂停状态（纯净模式下在行首显示暂停符号）
+    pub is_paused: bool,
+    /// 刚调节过音量：音量条高亮（纯净模式下此时才显示音量条）
+    pub volume_flash: bool,
+    /// 状态行开头的提示（如启动音量为 0）；None 表示无
+    pub notice: Option<&'a str>,
+    /// 正在临时压低音量（H 键）
+    pub ducked: bool,
+    /// 当前歌曲按目录记录跳过的片头时长；None 表示未跳过
+    pub intro_trim: Option<Duration>,
+    /// 整个列表的剩余时间（是否为估算值）；None 表示不显示
+    pub queue_remaining: Option<(Duration, bool)>,
+    /// 按键反馈的短暂提示，覆盖在状态行右侧；None 表示无
+    pub toast: Option<&'a str>,
+    /// 尚未听完的歌曲数与输入的总数（--no-repeat-across-sessions）；None 表示不显示
+    pub unheard: Option<(usize, usize)>,
+    /// 当前歌曲是云同步的占位文件
+    pub cloud: bool,
+    /// 长音频听过的区间；None 表示不是长音频（不显示）
+    pub listened: Option<&'a Segments>,
+}
+
+/// 按声道数给出常见的声道布局名称；0（未知）返回 None
+fn channel_label(channels: u16) -> Option<String> {
+    let label = match channels {
+        0 => return None,
+        1 => "单声道".to_string(),
+        2 => "立体声".to_string(),
+        3 => "2.1".to_string(),
+        4 => "四声道".to_string(),
+        6 => "5.1".to_string(),
+        8 => "7.1".to_string(),
+        n => format!("{}声道", n),
+    };
+    Some(label)
+}
+
+/// 长音频听过区间缩略图的格数
+const LISTENED_MAP_WIDTH: usize = 12;
+/// 低于该列数时只显示 `序号/总数 时间 标题`
+const COMPACT_STATUS_WIDTH: usize = 35;
+/// 低于该列数时只显示时间
+const TIME_ONLY_STATUS_WIDTH: usize = 20;
+/// 低于该列数时状态行留空（按键仍然有效）
+const MIN_STATUS_WIDTH: usize = 10;
+
+/// 状态行文本，以及其中各元素所在的字节区间（用于按主题着色）
+#[derive(Default)]
+struct StatusLine {
+    text: String,
+    spans: Vec<(Range<usize>, Element)>,
+}
+
+impl StatusLine {
+    fn plain(text: String) -> Self {
+        StatusLine { text, spans: Vec::new() }
+    }
+
+    /// 追加一段文本，`element` 为其着色所属的元素
+    fn push(&mut self, piece: &str, element: Option<Element>) {
+        let start = self.text.len();
+        self.text.push_str(piece);
+        if let Some(element) = element
+            && !piece.is_empty()
+        {
+            self.spans.push((start..self.text.len(), element));
+        }
+    }
+
+    /// 超过 `width` 时截断（末尾加省略号），被截掉部分的着色区间随之裁剪
+    fn truncate(&mut self, width: usize) {
+        if display_width(&self.text) <= width {
+            return;
+        }
+        self.text = truncate_string(&self.text, width);
+        let kept = self.text.strip_suffix("...").map_or(0, str::len);
+        self.spans.retain_mut(|(range, _)| {
+            range.end = range.end.min(kept);
+            range.start < range.end
+        });
+    }
+
+    /// 生成带颜色转义序列的文本；`highlight` 区间反色显示，`dimmed` 时整行暗淡
+    fn paint(&self, theme: Option<&Theme>, highlight: Option<Range<usize>>, dimmed: bool) -> String {
+        if theme.is_none() && highlight.is_none() && !dimmed {
+            return self.text.clone();
+        }
+        // 按所有区间的端点把文本切成小段，每段的样式是唯一确定的
+        let mut bounds = vec![0, self.text.len()];
+        bounds.extend(self.spans.iter().flat_map(|(range, _)| [range.start, range.end]));
+        bounds.extend(highlight.iter().flat_map(|range| [range.start, range.end]));
+        bounds.sort_unstable();
+        bounds.dedup();
+        let mut out = String::new();
+        for pair in bounds.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let mut style = ContentStyle::new();
+            style.foreground_color = theme.and_then(|theme| {
+                self.spans
+                    .iter()
+                    .find(|(range, _)| range.start <= start && end <= range.end)
+                    .map(|(_, element)| theme.color(*element))
+            });
+            if highlight.as_ref().is_some_and(|range| range.start <= start && end <= range.end) {
+                style.attributes.set(Attribute::Reverse);
+            }
+            if dimmed {
+                style.attributes.set(Attribute::Dim);
+            }
+            let piece = &self.text[start..end];
+            if style == ContentStyle::new() {
+                out.push_str(piece);
+            } else {
+                out.push_str(&style.apply(piece).to_string());
+            }
+        }
+        out
+    }
+}
+
+/// 把提示覆盖在状态行右侧：左侧内容截断到剩余宽度并补齐，结果宽度恰好为 `terminal_width`。
+/// 提示最多占一半宽度，过长时截断。
+fn overlay_toast(line: &mut StatusLine, toast: &str, terminal_width: usize) {
+    let toast = format!(" {} ", truncate_string(toast, (terminal_width / 2).saturating_sub(2)));
+    let base_width = terminal_width.saturating_sub(display_width(&toast));
+    line.truncate(base_width);
+    line.push(&" ".repeat(base_width.saturating_sub(display_width(&line.text))), None);
+    line.push(&toast, Some(Element::Toast));
+}
+
+// 更新进度显示
+pub fn update_progress_display(
+    stdout: &mut Screen,
+    layout: &Layout,
+    view: &StatusView,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // 纯净模式下每次刷新都会留下一行，不显示状态行（曲目变化由主循环另行输出）
+    if stdout.is_plain() {
+        return Ok(());
+    }
+    let terminal_width = terminal::size().map(|(cols, _)| cols).unwrap_or(80) as usize;
+    let (mut line, bar) = render_status_line(layout, view, terminal_width);
+    // 无论哪一档，渲染结果都不能超过终端宽度，否则会折行并使屏幕滚动
+    debug_assert!(display_width(&line.text) <= terminal_width);
+
+    let new_len = display_width(&line.text);
+    let padding_needed = terminal_width.saturating_sub(new_len);
+    line.push(&" ".repeat(padding_needed), None);
+    // 高亮与着色在宽度计算之后再加入（转义序列不占列宽）；音量条被截断时不高亮
+    let highlight = bar
+        .filter(|_| view.volume_flash)
+        .and_then(|bar| line.text.rfind(bar.as_str()).map(|pos| pos..pos + bar.len()));
+
+    layout.move_to_status(stdout)?;
+    write!(stdout, "{}", line.paint(layout.theme.as_ref(), highlight, layout.dimmed))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// 按终端宽度选择显示档位并生成状态行（不含补齐空格），同时返回其中的音量条（用于高亮）。
+/// 返回的文本宽度保证不超过 `terminal_width`。
+fn render_status_line(layout: &Layout, view: &StatusView, terminal_width: usize) -> (StatusLine, Option<String>) {
+    let current_time_str = format_duration(view.current_time);
+    let total_duration_str = format_duration(view.total_duration);
+    let time_str = format!("{}/{}", current_time_str, total_duration_str);
+    if terminal_width < MIN_STATUS_WIDTH {
+        return (StatusLine::default(), None);
+    }
+    if terminal_width < TIME_ONLY_STATUS_WIDTH {
+        let text = if display_width(&time_str) <= terminal_width { time_str } else { current_time_str };
+        return (StatusLine::plain(truncate_string(&text, terminal_width)), None);
+    }
+    if terminal_width < COMPACT_STATUS_WIDTH {
+        let total = if view.is_stream { "∞".to_string() } else { view.total_tracks.to_string() };
+        let head = format!("{}/{} {} ", view.current_index + 1, total, time_str);
+        let title_width = terminal_width.saturating_sub(display_width(&head));
+        let mut line = StatusLine::plain(truncate_string(&format!("{}{}", head, truncate_string(view.title, title_width)), terminal_width));
+        if let Some(toast) = view.toast {
+            overlay_toast(&mut line, toast, terminal_width);
+        }
+        return (line, None);
+    }
+
+    // 纯净模式没有横幅与标题提示，用行首符号表示暂停
+    let pause_str = if layout.is_compact() && view.is_paused { "⏸ " } else { "" };
+    let track_count_str = if view.is_stream {
+        format!("[{}/∞]", view.current_index + 1)
+    } else {
+        format!("[{}/{}]", view.current_index + 1, view.total_tracks)
+    };
+    let ext = match view.probed_format {
+        Some(format) => format.to_string(),
+        None => view.track_path.split('.').next_back().unwrap_or("未知").to_uppercase(),
+    };
+    let channel_str = channel_label(view.channels).map_or(String::new(), |label| format!("[{}]", label));
+    let random_str = if view.is_stream { "流" } else if view.is_random { "随" } else { "顺" };
+    let loop_str = if view.is_loop { "循" } else { "单" };
+    let play_mode_str = format!("{}|{}", random_str, loop_str);
+    // 附加状态段（如重复次数），没有时为空
+    let mut extra_str = String::new();
+    if let Some(notice) = view.notice {
+        extra_str.push_str(&format!("[{}]", notice));
+    }
+    if view.ducked {
+        extra_str.push_str("[压低]");
+    }
+    if view.cloud {
+        extra_str.push_str("[云端文件]");
+    }
+    if view.repeat_remaining > 0 {
+        extra_str.push_str(&format!("[重复×{}]", view.repeat_remaining));
+    }
+    match view.ab_loop {
+        AbLoop::Off => {}
+        AbLoop::StartMarked(a) => extra_str.push_str(&format!("[A {}–]", format_position(a))),
+        AbLoop::Active(a, b) => extra_str.push_str(&format!("[A-B {}–{}]", format_position(a), format_position(b))),
+    }
+    if let Some(trim) = view.intro_trim {
+        extra_str.push_str(&format!("[跳片头{}]", format_position(trim)));
+    }
+    if view.track_gain_db != 0.0 {
+        extra_str.push_str(&format!("[{:+.0}dB]", view.track_gain_db));
+    }
+    if let Some(listened) = view.listened {
+        extra_str.push_str(&format!("[已听{}]", listened.coverage_map(view.total_duration, LISTENED_MAP_WIDTH)));
+    }
+    if let Some((unheard, total)) = view.unheard {
+        extra_str.push_str(&format!("[未听{}/{}]", unheard, total));
+    }
+    if let Some((remaining, approximate)) = view.queue_remaining {
+        extra_str.push_str(&format!("[列表剩余{}{}]", if approximate { "约" } else { "" }, format_position(remaining)));
+    }
+
+    // 音量条：常规布局常驻；纯净模式只在调节音量后短暂出现
+    let bar = (!layout.is_compact() || view.volume_flash).then(|| volume_bar(view.volume, VOLUME_BAR_WIDTH));
+    let volume_str = match &bar {
+        Some(bar) => format!("[{} {:.0}%]", bar, view.volume * 100.0),
+        None => format!("[{:.0}%]", view.volume * 100.0),
+    };
+
+    let display_text_unpadded = format!(
+        "{}{}[{}]{}[{}]{}[][{}]{}",
+        pause_str, track_count_str, play_mode_str, extra_str, ext, channel_str, time_str, volume_str
+    );
+
+    let current_unpadded_width = display_width(&display_text_unpadded);
+    let music_info_width = terminal_width.saturating_sub(current_unpadded_width);
+    let music_info_content = if view.artist.is_empty() {
+        view.title.to_string()
+    } else {
+        format!("{}-{}", view.title, view.artist)
+    };
+    let music_info = if music_info_width < 15 {
+        truncate_string(view.title, music_info_width)
+    } else {
+        truncate_string(&music_info_content, music_info_width)
+    };
+
+    let mut line = StatusLine::plain(format!("{}{}[{}]{}[{}]{}[", pause_str, track_count_str, play_mode_str, extra_str, ext, channel_str));
+    // 未被截断到艺术家部分时，标题与艺术家分别着色
+    let title = sanitize_display(view.title);
+    match music_info.strip_prefix(title.as_str()).and_then(|rest| rest.strip_prefix('-')) {
+        Some(artist) if !view.artist.is_empty() => {
+            line.push(&title, Some(Element::Title));
+            line.push("-", None);
+            line.push(artist, Some(Element::Artist));
+        }
+        _ => line.push(&music_info, Some(Element::Title)),
+    }
+    line.push("][", None);
+    line.push(&time_str, Some(Element::Time));
+    line.push("]", None);
+    match &bar {
+        Some(bar) => {
+            let filled = bar.chars().filter(|&c| c == '█').count();
+            let split = bar.char_indices().nth(filled).map_or(bar.len(), |(i, _)| i);
+            line.push("[", Some(Element::Volume));
+            line.push(&bar[..split], Some(Element::ProgressFilled));
+            line.push(&bar[split..], Some(Element::ProgressEmpty));
+            line.push(&format!(" {:.0}%]", view.volume * 100.0), Some(Element::Volume));
+        }
+        None => line.push(&volume_str, Some(Element::Volume)),
+    }
+
+    // 附加状态段较多时固定字段本身就会超宽，整行截断，保证不折行
+    line.truncate(terminal_width);
+    if let Some(toast) = view.toast {
+        overlay_toast(&mut line, toast, terminal_width);
+    }
+    (line, bar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_view<'a>(title: &'a str, artist: &'a str, listened: &'a Segments) -> StatusView<'a> {
+        StatusView {
+            current_index: 1233,
+            total_tracks: 5000,
+            is_random: true,
+            is_stream: false,
+            is_loop: false,
+            title,
+            artist,
+            track_path: "/music/a.flac",
+            probed_format: None,
+            channels: 6,
+            current_time: Duration::from_secs(3 * 3600 + 61),
+            total_duration: Duration::from_secs(4 * 3600),
+            volume: 1.0,
+            repeat_remaining: 3,
+            track_gain_db: -4.0,
+            ab_loop: AbLoop::Active(Duration::from_secs(60), Duration::from_secs(90)),
+            is_paused: true,
+            volume_flash: true,
+            notice: Some("音量为 0"),
+            ducked: true,
+            intro_trim: Some(Duration::from_secs(12)),
+            queue_remaining: Some((Duration::from_secs(100_000), true)),
+            toast: None,
+            unheard: Some((12, 5000)),
+            cloud: true,
+            listened: Some(listened),
+        }
+    }
+
+    #[test]
+    fn banner_lists_every_key() {
+        let lines = banner_lines();
+        for line in &lines {
+            assert!(display_width(line) <= 60, "{:?}", line);
+        }
+        let text = lines.join("\n");
+        for key in ["[P]", "[空格]", "[Q/Ctrl+C]", "[←]", "[→]", "[↑]", "[↓]", "[E]", "[U]", "[W]", "[D]", "[H]", "[J]", "[T]", "[M]", "[A]", "[数字+R]", "[Tab]", "[F2]", "[F8]"] {
+            assert!(text.contains(key), "横幅缺少 {}", key);
+        }
+    }
+
+    #[test]
+    fn status_line_never_exceeds_the_width() {
+        let listened = Segments::parse("0-600000");
+        let title = "很长的中文标题：αβγ…± 🎵 Live at the Royal Albert Hall (Remastered 2024)";
+        let layouts = [(false, false), (true, false), (false, true)]
+            .map(|(clean, mini)| Layout { clean, keep_banner: false, theme: None, mini, dimmed: false });
+        for layout in &layouts {
+            for (title, artist) in [(title, "周杰伦 & Friends"), (title, ""), ("", ""), ("x", "y")] {
+                let full = status_view(title, artist, &listened);
+                let bare = StatusView { notice: None, ducked: false, cloud: false, repeat_remaining: 0, track_gain_db: 0.0, ab_loop: AbLoop::Off, intro_trim: None, queue_remaining: None, unheard: None, listened: None, is_stream: true, channels: 0, ..status_view(title, artist, &listened) };
+                let toasted = StatusView { toast: Some("已加入收藏：很长的提示文本"), ..status_view(title, artist, &listened) };
+                for view in [&full, &bare, &toasted] {
+                    for width in 5..=120 {
+                        let (line, _) = render_status_line(layout, view, width);
+                        assert!(display_width(&line.text) <= width, "宽度 {}: {:?}", width, line.text);
+                        if width < MIN_STATUS_WIDTH {
+                            assert!(line.text.is_empty(), "宽度 {}: {:?}", width, line.text);
+                        }
+                    }
+                }
+            }
+        }
+        // 各档位的内容
+        let view = status_view(title, "", &listened);
+        let render = |width| render_status_line(&layouts[0], &view, width).0.text;
+        assert_eq!(render(12), "181:01");
+        assert_eq!(render(19), "181:01/240:00");
+        assert!(render(34).starts_with("1234/5000 181:01/240:00 很"));
+        assert!(render(35).starts_with("[1234/5000][随|单]"));
+    }
+
+    #[test]
+    fn screen_target_matrix() {
+        use ScreenTarget::*;
+        // (stdin, stdout, stderr) -> (输出目标, 是否读取按键)
+        let cases = [
+            ((true, true, true), (Stdout, true)),
+            ((true, true, false), (Stdout, true)),
+            ((true, false, true), (Stderr, true)),
+            ((true, false, false), (Plain, true)),
+            ((false, true, true), (Stdout, false)),
+            ((false, true, false), (Stdout, false)),
+            ((false, false, true), (Stderr, false)),
+            ((false, false, false), (Plain, false)),
+        ];
+        for ((stdin, stdout, stderr), (target, keys)) in cases {
+            assert_eq!(
+                choose_screen_target(stdin, stdout, stderr),
+                ScreenSetup { target, keys },
+                "stdin={} stdout={} stderr={}",
+                stdin,
+                stdout,
+                stderr
+            );
+        }
+    }
+
+    #[test]
+    fn plain_filter_strips_control_sequences() {
+        let mut filter = PlainFilter::default();
+        let text = filter.filter(b"\x1b[2J\x1b[1;1H\x1b]0;title\x07\r\x1b[?25l\xe6\x92\xad\x1b]2;x\x1b\\ok\n");
+        assert_eq!(String::from_utf8(text).unwrap(), "播ok\n");
+        assert!(!filter.line_open);
+        // 被拆分在两次写入中的序列
+        assert_eq!(filter.filter(b"a\x1b[3"), b"a");
+        assert_eq!(filter.filter(b"8;5;1mb"), b"b");
+        assert!(filter.line_open);
+    }
+}
@@ -1,22 +1,382 @@
 
 use std::{fs, io, path::{Path, PathBuf}};
-use std::time::Duration;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
 // 引入 unicode_width 库
-use unicode_width::{UnicodeWidthStr, UnicodeWidthChar}; 
+use unicode_width::UnicodeWidthChar;
 // 引入 glob 库用于通配符匹配
 use glob::glob as glob_func;
+// 引入 ignore 库解析 gitignore 风格的忽略规则
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::metadata::{probe_audio_format, read_embedded_cuesheet};
+
+/// 忽略标记文件：子目录中存在任一文件时整个目录被跳过；
+/// 扫描根目录下的 `.mddignore` 则作为 gitignore 风格的规则文件。
+pub const IGNORE_MARKERS: [&str; 2] = [".mddignore", ".nomedia"];
+
+/// 目录扫描选项
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// 递归扫描子目录
+    pub recursive: bool,
+    /// 输出详细日志（到 stderr）
+    pub verbose: bool,
+    /// 不拆分 FLAC 内嵌 CUE，整文件播放
+    pub no_cue_split: bool,
+    /// 不输出“检测到…”等解析提示（播放过程中追加队列时使用）
+    pub quiet: bool,
+    /// 扫描进度回调：每找到 SCAN_PROGRESS_STEP 首时以当前数量调用
+    pub progress: Option<fn(usize)>,
+    /// 最多收集的音频文件数，达到后立即停止扫描
+    pub limit: Option<usize>,
+    /// 扫描过的文件数超过该值时调用 confirm_large；0 表示不检查
+    pub large_threshold: usize,
+    /// 文件数过多时确认是否继续，返回 false 则中止扫描；None 时不确认直接继续
+    pub confirm_large: Option<fn(usize) -> bool>,
+    /// 扫描目录时把其中的 .m3u/.m3u8 播放列表就地展开为其中的歌曲
+    pub expand_playlists: bool,
+}
+
+/// 扫描进度回调的触发间隔（首）
+const SCAN_PROGRESS_STEP: usize = 50;
+/// 支持的音频文件扩展名（小写）
+pub const SUPPORTED_EXTENSIONS: [&str; 6] = ["mp3", "ogg", "flac", "aac", "m4a", "wav"];
+
+/// `--allow-video-containers` 时额外接受的视频容器扩展名（小写），只播放其中的第一条音轨
+pub const VIDEO_CONTAINER_EXTENSIONS: [&str; 3] = ["mp4", "mkv", "webm"];
+/// 视频容器中找不到可播放音轨时的提示
+pub const NO_AUDIO_STREAM_MESSAGE: &str = "视频文件中没有可播放的音轨";
+
+/// 是否接受视频容器（启动时按命令行设置一次，扫描与流式随机都据此判断扩展名）
+static ALLOW_VIDEO_CONTAINERS: AtomicBool = AtomicBool::new(false);
+
+/// 启用 `--allow-video-containers`
+pub fn allow_video_containers() {
+    ALLOW_VIDEO_CONTAINERS.store(true, Ordering::Relaxed);
+}
+
+/// 判断扩展名（不区分大小写）是否为视频容器
+pub fn is_video_container(ext: &str) -> bool {
+    VIDEO_CONTAINER_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+}
+
+/// 下载/写入未完成的临时文件后缀（小写），即使前面带有音频扩展名也一律排除
+pub const TEMP_SUFFIXES: [&str; 3] = ["part", "crdownload", "tmp"];
+
+/// 判断扩展名（不区分大小写）是否为支持的音频格式
+pub fn is_supported_extension(ext: &str) -> bool {
+    let ext = ext.to_lowercase();
+    SUPPORTED_EXTENSIONS.contains(&ext.as_str()) || (ALLOW_VIDEO_CONTAINERS.load(Ordering::Relaxed) && is_video_container(&ext))
+}
+
+/// 判断扩展名（不区分大小写）是否为临时文件后缀
+pub fn is_temp_suffix(ext: &str) -> bool {
+    let ext = ext.to_lowercase();
+    TEMP_SUFFIXES.contains(&ext.as_str())
+}
+
+/// 返回文件可播放的音频扩展名（小写）。
+/// 只检查文件名中最后一个 `.` 之后的部分（大小写不敏感），例如：
+/// `a.MP3` → mp3；`a.flac.part`、`a.mp3.crdownload`、`a.MP3.bak` → None。
+/// 以 `.` 开头且没有其他扩展名的隐藏文件（如 `.mp3`）也不视为音频。
+pub fn audio_extension(path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if is_temp_suffix(&ext) || !is_supported_extension(&ext) {
+        return None;
+    }
+    Some(ext)
+}
+
+/// 疑似暂时性 I/O 错误（如网络共享短暂断开）的重试间隔：共重试 3 次
+pub const TRANSIENT_RETRY_DELAYS: [Duration; 3] = [Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(4)];
+
+/// 判断 I/O 错误是否可能是暂时性的（超时、被中断、网络文件句柄失效等）；
+/// NotFound、PermissionDenied 等确定性错误返回 false，不值得重试
+pub fn is_transient_error(e: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(
+        e.kind(),
+        TimedOut
+            | Interrupted
+            | WouldBlock
+            | ConnectionReset
+            | ConnectionAborted
+            | NotConnected
+            | NetworkDown
+            | NetworkUnreachable
+            | StaleNetworkFileHandle
+            | ResourceBusy
+    )
+}
+
+/// 执行 `op`，遇到暂时性错误时按 `delays` 依次等待后重试；确定性错误或重试用尽时返回最后一次的错误。
+/// 每次开始退避等待前以等待时长调用 `on_retry`（如顺延调用方的超时）
+pub fn retry_transient<T>(delays: &[Duration], mut on_retry: impl FnMut(Duration), mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delays = delays.iter();
+    loop {
+        match op() {
+            Err(e) if is_transient_error(&e) => match delays.next() {
+                Some(delay) => {
+                    on_retry(*delay);
+                    std::thread::sleep(*delay);
+                }
+                None => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
+
+/// 展开路径中的环境变量与开头的 `~`：
+/// 支持 Unix 风格的 `$VAR`、`${VAR}` 与 Windows 风格的 `%VAR%`。
+/// 未设置的变量保持原样，不含变量的路径不做任何改动。
+pub fn expand_path(path: &str) -> String {
+    expand_tilde(&expand_env_vars(path, lookup_var), dirs::home_dir().as_deref())
+}
+
+/// 环境变量名允许的字符
+fn is_var_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// 查找变量值；未设置（或非 UTF-8）时返回 None
+fn lookup_var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// 展开 `$VAR`、`${VAR}`、`%VAR%`（变量值由 `lookup` 给出），未设置的变量按原文保留
+fn expand_env_vars(input: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(pos) = rest.find(['$', '%']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        // (变量名, 整段原文的长度)
+        let var = if let Some(braced) = tail.strip_prefix("${") {
+            braced.find('}').map(|end| (&braced[..end], end + 3))
+        } else if let Some(after) = tail.strip_prefix('$') {
+            let end = after.find(|c: char| !is_var_char(c)).unwrap_or(after.len());
+            Some((&after[..end], end + 1))
+        } else {
+            let after = &tail[1..];
+            after.find('%').filter(|&end| after[..end].chars().all(is_var_char)).map(|end| (&after[..end], end + 2))
+        };
+        match var.filter(|(name, _)| !name.is_empty()).and_then(|(name, len)| lookup(name).map(|value| (value, len))) {
+            Some((value, len)) => {
+                out.push_str(&value);
+                rest = &tail[len..];
+            }
+            None => {
+                // 不是变量或变量未设置：保留该字符，继续向后查找
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 展开路径开头的 `~`（当前用户主目录 `home`）；无法获取主目录或不以 `~` 开头时原样返回
+fn expand_tilde(path: &str, home: Option<&Path>) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => rest,
+        _ => return path.to_string(),
+    };
+    match home {
+        Some(home) => format!("{}{}", home.to_string_lossy(), rest),
+        None => path.to_string(),
+    }
+}
+
+/// 播放列表中的一首歌曲
+#[derive(Debug, Clone)]
+pub struct Track {
+    /// 音频文件路径
+    pub path: PathBuf,
+    /// 播放列表中指定的显示标题（优先于标签中的标题/艺术家）
+    pub title: Option<String>,
+    /// 在文件内的起始位置（章节），None 表示从头播放
+    pub start: Option<Duration>,
+    /// 在文件内的结束位置（章节），None 表示播放到文件末尾
+    pub end: Option<Duration>,
+    /// 扩展名不在白名单、按内容探测出的编码格式（如 MP3）；None 表示按扩展名识别
+    pub probed_format: Option<String>,
+    /// 播放列表注释指定的片头跳过时长，优先于按目录记录的片头跳过
+    pub trim: Option<Duration>,
+    /// 云同步的占位文件（见 is_cloud_placeholder）：读取时才下载内容，预加载需要更早开始、等待更久
+    pub cloud: bool,
+}
+
+impl From<PathBuf> for Track {
+    fn from(path: PathBuf) -> Self {
+        Track { path, title: None, start: None, end: None, probed_format: None, trim: None, cloud: false }
+    }
+}
+
+/// 其他系统上按「有大小却不占磁盘块」判断占位文件时的最小大小（更小的文件可能内联存放在文件系统元数据中）
+#[cfg(unix)]
+const CLOUD_PLACEHOLDER_MIN_LEN: u64 = 4096;
+
+/// 判断是否为云同步的占位文件（OneDrive、Dropbox 等的「仅在线」文件，打开时才开始下载）：
+/// Windows 上按 RECALL_ON_DATA_ACCESS/RECALL_ON_OPEN/OFFLINE 属性判断；
+/// 其他系统上按有大小却未占用磁盘块判断（如 macOS 的 dataless 文件）
+pub fn is_cloud_placeholder(meta: &fs::Metadata) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+        const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+        const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+        meta.file_attributes() & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        meta.is_file() && meta.len() >= CLOUD_PLACEHOLDER_MIN_LEN && meta.blocks() == 0
+    }
+    #[cfg(not(any(windows, unix)))]
+    {
+        let _ = meta;
+        false
+    }
+}
+
+/// 标记列表中的云端占位文件（只读取文件属性，不会触发下载）
+pub fn mark_cloud_placeholders(tracks: &mut [Track]) {
+    for track in tracks {
+        track.cloud = fs::metadata(&track.path).is_ok_and(|meta| is_cloud_placeholder(&meta));
+    }
+}
+
+/// 文件的修改时间与大小，用于发现预加载之后被原地替换（如重新编码）的文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    pub fn from_metadata(meta: &fs::Metadata) -> Self {
+        FileStamp { modified: meta.modified().ok(), len: meta.len() }
+    }
+
+    /// 读取文件当前的状态；文件不存在或无法访问时为 None
+    pub fn read(path: &Path) -> Option<Self> {
+        fs::metadata(path).ok().map(|meta| Self::from_metadata(&meta))
+    }
+}
+
+/// 按内容探测失败时的提示
+pub const UNDECODABLE_MESSAGE: &str = "尝试按音频解码失败，可能不是受支持的格式";
+
+/// 解析输入路径时可能出现的错误，用于给出有针对性的提示。
+#[derive(Debug)]
+pub enum PlaylistError {
+    /// 路径不存在
+    NotFound,
+    /// 没有读取权限
+    PermissionDenied,
+    /// 指定的是不支持的文件类型
+    UnsupportedFile { ext: String },
+    /// 扩展名不在白名单，且按内容探测也无法识别为音频
+    UndecodableFile,
+    /// 目录为空
+    EmptyDirectory,
+    /// 目录中没有支持的音频文件，附带目录中实际出现过的扩展名
+    NoAudioInDirectory { seen_extensions: Vec<String> },
+    /// 通配符模式错误
+    InvalidPattern(String),
+    /// 通配符没有匹配到支持的音频文件
+    NoGlobMatches { pattern: String },
+    /// 播放列表中没有可用的条目：没有任何路径（`missing` 为 0），或列出的 `missing` 个路径都不存在
+    EmptyPlaylist { missing: usize },
+    /// 其他 I/O 错误
+    Io(io::Error),
+}
+
+impl From<io::Error> for PlaylistError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => PlaylistError::NotFound,
+            io::ErrorKind::PermissionDenied => PlaylistError::PermissionDenied,
+            _ => PlaylistError::Io(e),
+        }
+    }
+}
+
+impl std::fmt::Display for PlaylistError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlaylistError::NotFound => write!(f, "路径或文件不存在，请检查拼写"),
+            PlaylistError::PermissionDenied => write!(f, "没有读取权限，请检查文件或目录的访问权限"),
+            PlaylistError::UnsupportedFile { ext } if is_temp_suffix(ext) => {
+                write!(f, "文件 .{} 是未下载完成的临时文件，请等待下载完成后再播放", ext)
+            }
+            PlaylistError::UnsupportedFile { ext } => write!(
+                f,
+                "不支持的文件类型 .{}（支持的格式: {}）",
+                ext,
+                SUPPORTED_EXTENSIONS.join("/")
+            ),
+            PlaylistError::UndecodableFile => write!(
+                f,
+                "{}（支持的格式: {}）",
+                UNDECODABLE_MESSAGE,
+                SUPPORTED_EXTENSIONS.join("/")
+            ),
+            PlaylistError::EmptyDirectory => write!(f, "目录是空的"),
+            PlaylistError::NoAudioInDirectory { seen_extensions } => {
+                if seen_extensions.is_empty() {
+                    write!(f, "目录中没有任何可识别扩展名的文件")
+                } else {
+                    write!(
+                        f,
+                        "目录中没有支持的音频文件，发现的文件类型: {}（支持的格式: {}）",
+                        seen_extensions.iter().map(|e| format!(".{}", e)).collect::<Vec<_>>().join(" "),
+                        SUPPORTED_EXTENSIONS.join("/")
+                    )
+                }
+            }
+            PlaylistError::InvalidPattern(e) => write!(f, "通配符模式错误: {}", e),
+            PlaylistError::NoGlobMatches { pattern } => write!(
+                f,
+                "通配符 '{}' 没有匹配到支持的音频文件（支持的格式: {}），请检查目录与扩展名",
+                pattern,
+                SUPPORTED_EXTENSIONS.join("/")
+            ),
+            PlaylistError::EmptyPlaylist { missing: 0 } => write!(f, "播放列表为空，不包含任何路径"),
+            PlaylistError::EmptyPlaylist { missing } => write!(
+                f,
+                "播放列表中的 {} 个路径都不存在（相对路径以当前目录为基准），请检查文件是否已移动",
+                missing
+            ),
+            PlaylistError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PlaylistError {}
+
+/// 输出输入解析过程中的提示（quiet 时不输出）
+fn announce(scan: &ScanOptions, message: &str) {
+    if !scan.quiet {
+        println!("{}", message);
+    }
+}
+
 // ----------------------------------------------------
 // --- 新增工具函数：智能解析输入 ---
 // ----------------------------------------------------
 /// 根据输入字符串智能判断其类型（文件、目录、播放列表文件或通配符），
 /// 并返回生成的音频文件列表。
-pub fn get_playlist_from_input(input: &str) -> Result<Vec<PathBuf>, io::Error> {
-    // 1. 检查是否为通配符模式 (*.mp3, *.flac)
-    // ⚠️ 注意：Rust 的 std::fs 目前不直接支持 shell 通配符展开。
-    // 这里我们将使用 glob 库来实现，您需要在 Cargo.toml 中添加 `glob = "0.3"`
-    // 并将 `use glob::glob;` 添加到文件顶部。
-    // 我们先假设您已在 main.rs 顶部添加了 use glob::glob;
-    // 如果没有，可以先跳过通配符解析，只实现文件/目录/文本判断。
+pub fn get_playlist_from_input(input: &str, scan: &ScanOptions) -> Result<Vec<Track>, PlaylistError> {
+    // 0. 展开环境变量与 `~`（如 `$HOME/Music`、`%USERPROFILE%\Music`）
+    let input = &expand_path(input);
+    // 1. 检查是否为通配符模式 (*.mp3, *.flac)，使用 glob 库展开
     if input.contains('*') {
         // 处理通配符，例如 "songs/*.mp3"
         let mut paths = Vec::new();
@@ -25,56 +385,196 @@ pub fn get_playlist_from_input(input: &str) -> Result<Vec<PathBuf>, io::Error> {
                 for entry in entries {
                     match entry {
                         Ok(path) => {
-                            if path.is_file() {
+                            // 通配符同样只接受支持的音频格式，避免把未下载完成的文件加入列表
+                            if path.is_file() && audio_extension(&path).is_some() {
                                 paths.push(path);
                             }
                         },
                         Err(e) => eprintln!("通配符匹配错误: {:?}", e),
                     }
                 }
-                return Ok(paths);
+                if paths.is_empty() {
+                    return Err(PlaylistError::NoGlobMatches { pattern: input.clone() });
+                }
+                return Ok(audio_files_to_tracks(paths, scan));
             },
-            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("通配符模式错误: {}", e))),
+            Err(e) => return Err(PlaylistError::InvalidPattern(e.to_string())),
         }
     }
     // 2. 尝试将输入转换为 PathBuf
     let path = PathBuf::from(input);
-    // 3. 判断路径是否存在
-    if !path.exists() {
-        return Err(io::Error::new(io::ErrorKind::NotFound, format!("路径或文件不存在: {}", input)));
-    }
+    // 3. 判断路径是否存在（区分不存在与无权限）
+    let meta = fs::metadata(&path)?;
     // 4. 判断类型
-    if path.is_dir() {
+    if meta.is_dir() {
         // 如果是目录，扫描目录下的所有音频文件
-        println!("检测到目录，扫描音频文件...");
-        scan_audio_files(&path) // 假设此函数在 utils 中
-    } else if path.is_file() {
+        announce(scan, "检测到目录，扫描音频文件...");
+        let files = scan_audio_files(&path, scan)?;
+        if files.is_empty() {
+            return Err(classify_empty_directory(&path)?);
+        }
+        Ok(audio_files_to_tracks(files, scan))
+    } else if meta.is_file() {
         // 检查文件扩展名，判断是音频媒体文件还是播放列表文件
         let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
-        if ext == "txt" {
+        if ext == "txt" && let Some(chapters) = read_chapter_file(&path) {
+            // 时间戳章节文件：查找同名音频文件，按章节拆分
+            let audio = find_chapter_audio(&path).ok_or_else(|| {
+                PlaylistError::Io(io::Error::new(io::ErrorKind::NotFound, "未找到与章节文件同名的音频文件"))
+            })?;
+            announce(scan, &format!("检测到章节文件，共 {} 个章节...", chapters.len()));
+            Ok(chapters_to_tracks(&audio, chapters))
+        } else if ext == "txt" || ext == "m3u" || ext == "m3u8" {
             // 如果是播放列表或文本文件，尝试解析播放列表
-            println!("检测到播放列表文件，开始解析...");
-            read_playlist_file(&path) // 假设此函数在 utils 中
+            announce(scan, "检测到播放列表文件，开始解析...");
+            let tracks = match read_playlist_file(&path) {
+                Ok(tracks) => tracks,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => return Err(PlaylistError::EmptyPlaylist { missing: 0 }),
+                Err(e) => return Err(e.into()),
+            };
+            if !tracks.iter().any(|track| track.path.exists()) {
+                return Err(PlaylistError::EmptyPlaylist { missing: tracks.len() });
+            }
+            Ok(tracks)
+        } else if audio_extension(&path).is_some() {
+            // 同名 .txt 为时间戳章节文件时，按章节拆分
+            if let Some(chapters) = read_chapter_file(&path.with_extension("txt")) {
+                announce(scan, &format!("检测到单个音频文件及其章节文件，共 {} 个章节...", chapters.len()));
+                return Ok(chapters_to_tracks(&path, chapters));
+            }
+            let tracks = audio_file_tracks(path, scan);
+            if tracks.len() > 1 {
+                announce(scan, &format!("检测到内嵌 CUE 的音频文件，共 {} 轨...", tracks.len()));
+            } else {
+                announce(scan, "检测到单个音频文件，作为单曲播放...");
+            }
+            Ok(tracks)
+        } else if !is_temp_suffix(&ext) {
+            // 用户明确指定的文件不受扩展名白名单限制：按内容探测，能识别就尝试播放
+            let format = probe_audio_format(&path).ok_or(PlaylistError::UndecodableFile)?;
+            announce(scan, &format!("检测到扩展名未知的文件，按内容识别为 {}，尝试播放...", format));
+            Ok(vec![Track { probed_format: Some(format), ..Track::from(path) }])
         } else {
-            // 默认视为单个音频文件
-            println!("检测到单个音频文件，作为单曲播放...");
-            Ok(vec![path])
+            Err(PlaylistError::UnsupportedFile { ext })
+        }
+    } else {
+        // 其他类型 (如设备文件等，这里简化处理为无法解析)
+        Err(PlaylistError::Io(io::Error::new(io::ErrorKind::InvalidInput, "无法识别的路径类型")))
+    }
+}
+
+/// 目录扫描不到音频时，区分“目录为空”和“只有不支持的格式”，并收集出现过的扩展名。
+fn classify_empty_directory(dir: &Path) -> io::Result<PlaylistError> {
+    let mut has_entries = false;
+    let mut seen_extensions: Vec<String> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        has_entries = true;
+        if path.is_file() && let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+            let ext = ext.to_lowercase();
+            if !seen_extensions.contains(&ext) {
+                seen_extensions.push(ext);
+            }
         }
+    }
+    if !has_entries {
+        return Ok(PlaylistError::EmptyDirectory);
+    }
+    seen_extensions.sort();
+    Ok(PlaylistError::NoAudioInDirectory { seen_extensions })
+}
+
+/// 控制字符的替换字符，显示宽度固定为 1
+const CONTROL_REPLACEMENT: char = '\u{FFFD}';
+
+/// 清理用于终端显示的文本：制表符、换行等空白控制字符替换为空格，
+/// 其他控制字符（包括 ESC）替换为 `�`，避免破坏状态行或注入终端转义序列。
+pub fn sanitize_display(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            c if !c.is_control() => c,
+            c if c.is_whitespace() => ' ',
+            _ => CONTROL_REPLACEMENT,
+        })
+        .collect()
+}
+
+/// 歧义宽度字符（如 …、±、希腊字母、█）的计宽方式（`--cjk-width`）
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CjkWidth {
+    /// 按语言环境判断：中文、日文、韩文环境下按双宽计算
+    Auto,
+    /// 按双宽计算（歧义宽度字符显示为两列的 CJK 终端）
+    On,
+    /// 按单宽计算
+    Off,
+}
+
+/// 是否按 CJK 规则计算歧义宽度字符
+static CJK_WIDTH: AtomicBool = AtomicBool::new(false);
+
+/// 按 `--cjk-width` 设置计宽方式，之后所有 display_width/truncate_string 都使用该规则
+pub fn set_cjk_width(mode: CjkWidth) {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()));
+    CJK_WIDTH.store(cjk_width_enabled(mode, locale.as_deref()), Ordering::Relaxed);
+}
+
+/// `mode` 下是否按 CJK 规则计宽；`locale` 为 LC_ALL、LC_CTYPE、LANG 中第一个非空的值，
+/// Auto 时按它是否为中文、日文或韩文判断
+fn cjk_width_enabled(mode: CjkWidth, locale: Option<&str>) -> bool {
+    match mode {
+        CjkWidth::On => true,
+        CjkWidth::Off => false,
+        CjkWidth::Auto => locale.is_some_and(|locale| {
+            let locale = locale.to_lowercase();
+            ["zh", "ja", "ko"].iter().any(|prefix| locale.starts_with(prefix))
+        }),
+    }
+}
+
+/// 东亚宽度为歧义的希腊字母与西里尔字母。unicode-width 的 width_cjk 把字母一律按单宽计算，
+/// 而 CJK 终端（及其使用的 CJK 字体）把它们显示为两列
+fn is_ambiguous_letter(c: char) -> bool {
+    matches!(c, '\u{391}'..='\u{3A1}' | '\u{3A3}'..='\u{3A9}' | '\u{3B1}'..='\u{3C1}' | '\u{3C3}'..='\u{3C9}' | '\u{401}' | '\u{410}'..='\u{44F}' | '\u{451}')
+}
+
+/// 单个字符的显示宽度（清理后的文本中不再有控制字符，组合字符为 0）
+fn char_width(c: char, cjk: bool) -> usize {
+    if c.is_control() {
+        1
+    } else if cjk && is_ambiguous_letter(c) {
+        2
+    } else if cjk {
+        c.width_cjk().unwrap_or(0)
     } else {
-        // 其他类型 (如符号链接等，这里简化处理为无法解析)
-        Err(io::Error::new(io::ErrorKind::InvalidInput, "无法识别的路径类型"))
+        c.width().unwrap_or(0)
     }
 }
 
+/// 文本的显示宽度：逐字符相加，与 truncate_string 的截断计算保持一致
+pub fn display_width(s: &str) -> usize {
+    display_width_as(s, CJK_WIDTH.load(Ordering::Relaxed))
+}
+
+fn display_width_as(s: &str, cjk: bool) -> usize {
+    s.chars().map(|c| char_width(c, cjk)).sum()
+}
+
 /// 根据终端显示宽度截断字符串，并在末尾添加 "..."。
+/// 输入会先经过 sanitize_display，返回值不含控制字符。
 pub fn truncate_string(s: &str, max_width: usize) -> String {
+    truncate_string_as(s, max_width, CJK_WIDTH.load(Ordering::Relaxed))
+}
+
+fn truncate_string_as(s: &str, max_width: usize, cjk: bool) -> String {
+    let s = &sanitize_display(s);
     // 1. 保留 3 个列宽给 "..."
     let ellipsis_width = 3;
     if max_width < ellipsis_width { return String::new(); }
     // 1. 获取最大显示宽度
     let max_content_width = max_width.saturating_sub(ellipsis_width);
-    // 2. 检查原始字符串的显示宽度 (使用 .width() 替代 UnicodeWidthChar::width)
-    let original_display_width = s.width(); // 🌟 直接在 &str 上调用 .width()
+    // 2. 检查原始字符串的显示宽度（与下方逐字符计算使用同一规则）
+    let original_display_width = display_width_as(s, cjk);
     // 如果原始字符串的显示宽度已经小于等于最大内容宽度，则直接返回
     if original_display_width <= max_width {
         return s.to_string();
@@ -83,8 +583,7 @@ pub fn truncate_string(s: &str, max_width: usize) -> String {
     let mut current_width = 0; // 🎯 修复 E0425：声明并初始化宽度变量
     let mut truncated_string = String::new();
     for c in s.chars() {
-        // 现在直接在 char 上调用 .width()
-        let char_width = c.width().unwrap_or(0);
+        let char_width = char_width(c, cjk);
         // 如果加上这个字符后超过了可容纳的最大内容宽度，则停止
         if current_width + char_width > max_content_width {
             break; 
@@ -98,7 +597,9 @@ pub fn truncate_string(s: &str, max_width: usize) -> String {
 }
 
 /// 递归/非递归扫描指定路径，返回支持的音频文件列表。
-pub fn scan_audio_files(input_path: &Path) -> io::Result<Vec<PathBuf>> {
+/// 含有 `.mddignore` 或 `.nomedia` 的子目录会被整体跳过；
+/// 根目录下的 `.mddignore` 按 gitignore 规则（支持 `!` 取反与 `dir/` 目录规则）过滤扫描结果。
+pub fn scan_audio_files(input_path: &Path, opts: &ScanOptions) -> io::Result<Vec<PathBuf>> {
     let mut files = Vec::new();
     
     // 如果是单个文件，直接添加
@@ -110,42 +611,480 @@ pub fn scan_audio_files(input_path: &Path) -> io::Result<Vec<PathBuf>> {
     
     // 如果是目录，遍历并筛选文件
     if input_path.is_dir() {
-        for entry in fs::read_dir(input_path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                    let ext = ext.to_lowercase();
-                    // 核心筛选逻辑：仅添加支持的音频格式
-                    if ext == "mp3" || ext == "ogg" || ext == "flac" || ext == "aac" || ext == "m4a" || ext == "wav" { 
-                        files.push(path);
-                    }
+        let rules = load_ignore_rules(input_path, opts.verbose);
+        let mut walk = Walk { files, skipped_dirs: 0, visited: 0, confirmed: false, dirs: HashSet::new() };
+        walk.dirs.extend(fs::canonicalize(input_path).ok());
+        if walk_dir(input_path, &rules, opts, &mut walk)? == ControlFlow::Break(WalkStop::Aborted) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "文件数过多，已取消扫描"));
+        }
+        if opts.verbose && walk.skipped_dirs > 0 {
+            eprintln!("[详细]因忽略规则或标记文件跳过了 {} 个目录", walk.skipped_dirs);
+        }
+        files = walk.files;
+    }
+
+    Ok(files)
+}
+
+/// 目录遍历的累计状态
+struct Walk {
+    files: Vec<PathBuf>,
+    skipped_dirs: usize,
+    /// 已扫描的文件数（含非音频文件）
+    visited: usize,
+    /// 已确认过继续扫描（只询问一次）
+    confirmed: bool,
+    /// 已进入过的目录（实际路径），指向祖先或已扫描目录的符号链接不再重复进入
+    dirs: HashSet<PathBuf>,
+}
+
+impl Walk {
+    /// 加入一首音频文件，按需报告进度；达到 --limit 时返回 Break
+    fn push(&mut self, path: PathBuf, opts: &ScanOptions) -> ControlFlow<WalkStop> {
+        self.files.push(path);
+        if let Some(report) = opts.progress
+            && self.files.len().is_multiple_of(SCAN_PROGRESS_STEP)
+        {
+            report(self.files.len());
+        }
+        if opts.limit.is_some_and(|limit| self.files.len() >= limit) {
+            return ControlFlow::Break(WalkStop::Limit);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// 提前结束遍历的原因
+#[derive(Debug, PartialEq, Eq)]
+enum WalkStop {
+    /// 达到 --limit
+    Limit,
+    /// 文件数过多，用户选择不继续
+    Aborted,
+}
+
+/// 读取扫描根目录下的 `.mddignore` 规则，不存在或解析失败时返回空规则
+pub fn load_ignore_rules(root: &Path, verbose: bool) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    let rule_file = root.join(IGNORE_MARKERS[0]);
+    if rule_file.is_file()
+        && let Some(e) = builder.add(&rule_file)
+        && verbose
+    {
+        eprintln!("[详细]读取忽略规则 {} 时出错: {}", rule_file.display(), e);
+    }
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+/// 判断目录中是否存在忽略标记文件
+pub fn has_ignore_marker(dir: &Path) -> bool {
+    IGNORE_MARKERS.iter().any(|marker| dir.join(marker).is_file())
+}
+
+/// 遍历单个目录，按需递归进入子目录；达到上限或被中止时返回 Break
+fn walk_dir(dir: &Path, rules: &Gitignore, opts: &ScanOptions, walk: &mut Walk) -> io::Result<ControlFlow<WalkStop>> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_dir = path.is_dir();
+        if rules.matched(&path, is_dir).is_ignore() {
+            if is_dir {
+                walk.skipped_dirs += 1;
+            }
+            continue;
+        }
+        if is_dir {
+            if !opts.recursive {
+                continue;
+            }
+            if has_ignore_marker(&path) {
+                if opts.verbose {
+                    eprintln!("[详细]跳过含有忽略标记的目录: {}", path.display());
                 }
+                walk.skipped_dirs += 1;
+                continue;
+            }
+            // 符号链接可能绕回祖先目录（无限递归）或指向已扫描过的目录（重复加入）
+            if let Ok(real) = fs::canonicalize(&path)
+                && !walk.dirs.insert(real)
+            {
+                if opts.verbose {
+                    eprintln!("[详细]跳过已扫描过的目录: {}", path.display());
+                }
+                continue;
+            }
+            // 子目录读取失败（如无权限）时跳过，不影响其余目录
+            match walk_dir(&path, rules, opts, walk) {
+                Ok(ControlFlow::Break(stop)) => return Ok(ControlFlow::Break(stop)),
+                Ok(ControlFlow::Continue(())) => {}
+                Err(e) if opts.verbose => eprintln!("[详细]无法读取目录 {}: {}", path.display(), e),
+                Err(_) => {}
+            }
+        } else if path.is_file() {
+            walk.visited += 1;
+            if opts.large_threshold > 0 && walk.visited > opts.large_threshold && !walk.confirmed {
+                walk.confirmed = true;
+                if let Some(confirm) = opts.confirm_large
+                    && !confirm(opts.large_threshold)
+                {
+                    return Ok(ControlFlow::Break(WalkStop::Aborted));
+                }
+            }
+            if opts.expand_playlists && is_m3u(&path) {
+                if let ControlFlow::Break(stop) = expand_playlist(&path, opts, walk, &mut Vec::new()) {
+                    return Ok(ControlFlow::Break(stop));
+                }
+                continue;
+            }
+            if audio_extension(&path).is_none() {
+                continue;
+            }
+            // 核心筛选逻辑：仅添加支持的音频格式（规则见 audio_extension）
+            if let ControlFlow::Break(stop) = walk.push(path, opts) {
+                return Ok(ControlFlow::Break(stop));
             }
         }
     }
+    Ok(ControlFlow::Continue(()))
+}
 
-    Ok(files)
+/// 是否为 .m3u/.m3u8 播放列表
+fn is_m3u(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()).is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"))
+}
+
+/// `--expand-playlists`：把播放列表中的歌曲加入扫描结果，条目中的播放列表递归展开。
+/// 相对路径以所在播放列表的目录为基准；`stack` 为正在展开的播放列表，
+/// 引用自身或引用链绕回时跳过该条目，避免无限展开。
+fn expand_playlist(playlist: &Path, opts: &ScanOptions, walk: &mut Walk, stack: &mut Vec<PathBuf>) -> ControlFlow<WalkStop> {
+    let key = fs::canonicalize(playlist).unwrap_or_else(|_| playlist.to_path_buf());
+    if stack.contains(&key) {
+        if opts.verbose {
+            eprintln!("[详细]播放列表循环引用，跳过: {}", playlist.display());
+        }
+        return ControlFlow::Continue(());
+    }
+    let tracks = match read_playlist_file(playlist) {
+        Ok(tracks) => tracks,
+        Err(e) => {
+            if opts.verbose {
+                eprintln!("[详细]无法展开播放列表 {}: {}", playlist.display(), e);
+            }
+            return ControlFlow::Continue(());
+        }
+    };
+    let base = playlist.parent().unwrap_or(Path::new(""));
+    stack.push(key);
+    for track in tracks {
+        let path = if track.path.is_relative() { base.join(&track.path) } else { track.path };
+        let flow = if is_m3u(&path) {
+            expand_playlist(&path, opts, walk, stack)
+        } else if path.is_file() && audio_extension(&path).is_some() {
+            walk.push(path, opts)
+        } else {
+            ControlFlow::Continue(())
+        };
+        if flow.is_break() {
+            stack.pop();
+            return flow;
+        }
+    }
+    stack.pop();
+    ControlFlow::Continue(())
+}
+
+/// 解析时间戳 `H:MM:SS`、`MM:SS` 或 `M:SS`（秒可带小数）。
+pub fn parse_timestamp(s: &str) -> Option<Duration> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let secs: f64 = parts[parts.len() - 1].parse().ok().filter(|v: &f64| (0.0..60.0).contains(v))?;
+    let mins: u64 = parts[parts.len() - 2].parse().ok()?;
+    let hours: u64 = if parts.len() == 3 { parts[0].parse().ok()? } else { 0 };
+    if parts.len() == 3 && mins >= 60 {
+        return None;
+    }
+    Some(Duration::from_secs(hours * 3600 + mins * 60) + Duration::from_secs_f64(secs))
+}
+
+/// 解析带单位的时长 `1h30m`、`25m`、`90s`（单位不区分大小写，可组合），或时间戳写法 `1:30:00`（见 parse_timestamp）。
+/// 不带单位的纯数字由调用方按各自的默认单位解释，这里返回 None
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    if s.contains(':') {
+        return parse_timestamp(s);
+    }
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let value: u64 = digits.parse().ok()?;
+        total = total.saturating_add(value.saturating_mul(unit));
+        digits.clear();
+    }
+    (!s.is_empty() && digits.is_empty()).then(|| Duration::from_secs(total))
+}
+
+/// 解析章节行：`00:00 Intro`、`03:12 - Track 2`，返回 (起始时间, 标题)。
+fn parse_chapter_line(line: &str) -> Option<(Duration, String)> {
+    let (stamp, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let start = parse_timestamp(stamp)?;
+    let title = rest.trim().trim_start_matches(['-', '–', '—', '|']).trim();
+    Some((start, title.to_string()))
+}
+
+/// 读取时间戳章节文件：所有非空、非 `#` 注释行都以时间戳开头时才视为章节文件。
+/// 章节按起始时间排序，重复的起始时间只保留第一个。
+pub fn read_chapter_file(path: &Path) -> Option<Vec<(Duration, String)>> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut chapters = Vec::new();
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        chapters.push(parse_chapter_line(line)?);
+    }
+    if chapters.is_empty() {
+        return None;
+    }
+    chapters.sort_by_key(|(start, _)| *start);
+    chapters.dedup_by_key(|(start, _)| *start);
+    Some(chapters)
+}
+
+/// 查找与章节文件同名（同目录、同文件名主干）的音频文件
+fn find_chapter_audio(chapter_file: &Path) -> Option<PathBuf> {
+    SUPPORTED_EXTENSIONS
+        .iter()
+        .map(|ext| chapter_file.with_extension(ext))
+        .find(|candidate| candidate.is_file())
+}
+
+/// 把章节转换为同一文件内的多个区间曲目，每个章节播放到下一章节的起点
+fn chapters_to_tracks(audio: &Path, chapters: Vec<(Duration, String)>) -> Vec<Track> {
+    let ends: Vec<Option<Duration>> = chapters.iter().skip(1).map(|(start, _)| Some(*start)).chain([None]).collect();
+    chapters
+        .into_iter()
+        .zip(ends)
+        .enumerate()
+        .map(|(i, ((start, title), end))| Track {
+            title: Some(if title.is_empty() { format!("第 {} 章", i + 1) } else { title }),
+            start: Some(start),
+            end,
+            ..Track::from(audio.to_path_buf())
+        })
+        .collect()
 }
-/// 从 .txt 文件中读取播放列表路径，每行一个路径。
-pub fn read_playlist_file(path: &Path) -> io::Result<Vec<PathBuf>> {
+
+/// 把单个音频文件转换为曲目：内嵌 CUE 的 FLAC 按分轨拆分（`--no-cue-split` 时整文件播放）
+fn audio_file_tracks(path: PathBuf, scan: &ScanOptions) -> Vec<Track> {
+    if !scan.no_cue_split
+        && audio_extension(&path).as_deref() == Some("flac")
+        && let Some(cue) = read_embedded_cuesheet(&path)
+    {
+        return chapters_to_tracks(&path, cue);
+    }
+    // 视频容器按其中音轨的编码显示（如 VORBIS），而不是 MKV/MP4
+    if audio_extension(&path).is_some_and(|ext| is_video_container(&ext)) {
+        let probed_format = probe_audio_format(&path);
+        return vec![Track { probed_format, ..Track::from(path) }];
+    }
+    vec![Track::from(path)]
+}
+
+/// 批量转换扫描到的音频文件
+fn audio_files_to_tracks(files: Vec<PathBuf>, scan: &ScanOptions) -> Vec<Track> {
+    files.into_iter().flat_map(|path| audio_file_tracks(path, scan)).collect()
+}
+
+/// 播放列表扩展注释的前缀。写在条目之前，为下一条路径补充信息，例如
+/// `#MDD:start=12.000 end=240.500 trim=8.000 title=标题`；其他播放器会把它当作普通注释忽略。
+const ANNOTATION_PREFIX: &str = "#MDD:";
+
+/// `#MDD:` 注释中的信息
+#[derive(Debug, Default, PartialEq)]
+struct Annotation {
+    title: Option<String>,
+    start: Option<Duration>,
+    end: Option<Duration>,
+    trim: Option<Duration>,
+}
+
+impl Annotation {
+    /// 解析以空白分隔的 `键=值`：时间以秒为单位，`title` 须放在最后，其值取到行尾（可含空格）。
+    /// 未知的键、无法解析的值与缺少 `=` 的项直接忽略，手工编辑出错时只丢弃出错的项。
+    fn parse(text: &str) -> Self {
+        let mut annotation = Annotation::default();
+        let mut rest = text.trim();
+        while !rest.is_empty() {
+            if let Some(title) = rest.strip_prefix("title=") {
+                annotation.title = Some(unescape_title(title.trim())).filter(|t| !t.is_empty());
+                break;
+            }
+            let (item, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            rest = tail.trim_start();
+            let Some((key, value)) = item.split_once('=') else { continue };
+            let seconds = value.parse::<f64>().ok().filter(|s| s.is_finite() && *s >= 0.0).map(Duration::from_secs_f64);
+            match key {
+                "start" => annotation.start = seconds,
+                "end" => annotation.end = seconds,
+                "trim" => annotation.trim = seconds,
+                _ => {}
+            }
+        }
+        // 结束位置不在起始位置之后时无法播放，只保留起始位置
+        if let (Some(start), Some(end)) = (annotation.start, annotation.end)
+            && end <= start
+        {
+            annotation.end = None;
+        }
+        annotation
+    }
+
+    /// 把注释中的信息补充到条目上（注释中的标题优先于 `| 标题` 与 #EXTINF）
+    fn apply(self, track: Track) -> Track {
+        Track {
+            title: self.title.or(track.title),
+            start: self.start.or(track.start),
+            end: self.end.or(track.end),
+            trim: self.trim.or(track.trim),
+            ..track
+        }
+    }
+
+    /// 生成曲目对应的注释行；没有需要保留的信息时为 None
+    fn format(track: &Track) -> Option<String> {
+        let mut items: Vec<String> = [("start", track.start), ("end", track.end), ("trim", track.trim)]
+            .iter()
+            .filter_map(|(key, value)| value.map(|v| format!("{}={:.3}", key, v.as_secs_f64())))
+            .collect();
+        if let Some(title) = &track.title {
+            items.push(format!("title={}", escape_title(title)));
+        }
+        (!items.is_empty()).then(|| format!("{}{}", ANNOTATION_PREFIX, items.join(" ")))
+    }
+}
+
+/// 注释中的标题：反斜杠、制表符与换行写作 `\\`、`\t`、`\n`、`\r`，其他控制字符替换为 `�`，读回时还原
+fn escape_title(title: &str) -> String {
+    let mut out = String::with_capacity(title.len());
+    for c in title.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push(CONTROL_REPLACEMENT),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// escape_title 的逆操作；其他反斜杠（如手工写入的 `AC\DC`）原样保留
+fn unescape_title(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let escaped = match (c, chars.peek()) {
+            ('\\', Some('\\')) => '\\',
+            ('\\', Some('t')) => '\t',
+            ('\\', Some('n')) => '\n',
+            ('\\', Some('r')) => '\r',
+            _ => {
+                out.push(c);
+                continue;
+            }
+        };
+        chars.next();
+        out.push(escaped);
+    }
+    out
+}
+
+/// 把播放列表写为 m3u：`#EXTINF` 供其他播放器显示标题，`#MDD:` 注释保留标题、章节区间与片头跳过，
+/// 用 read_playlist_file 读回时还原为相同的曲目
+pub fn write_playlist_file(path: &Path, tracks: &[Track]) -> io::Result<()> {
+    let mut content = String::from("#EXTM3U\n");
+    for track in tracks {
+        if let Some(annotation) = Annotation::format(track) {
+            content.push_str(&format!("{}\n", annotation));
+        }
+        if let Some(title) = &track.title {
+            content.push_str(&format!("#EXTINF:-1,{}\n", sanitize_display(title)));
+        }
+        content.push_str(&format!("{}\n", track.path.display()));
+    }
+    fs::write(path, content)
+}
+
+/// 从 .txt / .m3u 文件中读取播放列表，每行一个路径。
+/// txt 中可用 `路径 | 显示标题` 指定标题；m3u 中 `#EXTINF:时长,标题` 为下一条路径提供标题；
+/// `#MDD:` 注释（见 ANNOTATION_PREFIX）为下一条路径补充标题、章节区间与片头跳过。
+pub fn read_playlist_file(path: &Path) -> io::Result<Vec<Track>> {
     // 尝试将整个文件内容读取为字符串
     let content = fs::read_to_string(path)?;
-    
-    let files: Vec<PathBuf> = content
-        .lines()              // 按行迭代
-        .map(|line| line.trim()) // 移除每行首尾空白
-        .filter(|line| !line.is_empty()) // 忽略空行
-        .map(|line| PathBuf::from(line)) // 将字符串转换为 PathBuf
-        .collect();
-    
+
+    let mut files: Vec<Track> = Vec::new();
+    let mut pending_title: Option<String> = None; // 来自 #EXTINF 的标题，作用于下一条路径
+    let mut pending_annotation: Option<Annotation> = None; // 来自 #MDD: 的注释，作用于下一条路径
+    for line in content.lines().map(|line| line.trim()) {
+        if line.is_empty() {
+            continue; // 忽略空行
+        }
+        if let Some(text) = line.strip_prefix(ANNOTATION_PREFIX) {
+            pending_annotation = Some(Annotation::parse(text));
+            continue;
+        }
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:时长,标题
+            pending_title = info.split_once(',').map(|(_, t)| t.trim().to_string()).filter(|t| !t.is_empty());
+            continue;
+        }
+        if line.starts_with('#') {
+            continue; // 其他 m3u 指令或注释
+        }
+        let (entry, title) = split_title_suffix(line);
+        let track = Track { title: title.or(pending_title.take()), ..Track::from(PathBuf::from(expand_path(entry))) };
+        files.push(match pending_annotation.take() {
+            Some(annotation) => annotation.apply(track),
+            None => track,
+        });
+        pending_title = None;
+    }
+
     if files.is_empty() {
         return Err(io::Error::new(io::ErrorKind::InvalidData, "播放列表文件为空或不包含有效路径。"));
     }
-    
+
     Ok(files)
 }
 
+/// 拆分 `路径 | 显示标题` 语法。
+/// 若整行本身就是存在的文件（路径中含有 `|`），则不拆分；
+/// 否则从左到右尝试每个 `|`，取第一个左侧为存在文件的位置，都不存在时按第一个 `|` 拆分。
+fn split_title_suffix(line: &str) -> (&str, Option<String>) {
+    if !line.contains('|') || Path::new(line).is_file() {
+        return (line, None);
+    }
+    let candidates: Vec<usize> = line.match_indices('|').map(|(i, _)| i).collect();
+    let pos = candidates
+        .iter()
+        .copied()
+        .find(|&i| Path::new(line[..i].trim()).is_file())
+        .unwrap_or(candidates[0]);
+    let entry = line[..pos].trim();
+    let title = line[pos + 1..].trim();
+    (entry, (!title.is_empty()).then(|| title.to_string()))
+}
+
 /// 将 Duration 格式化为 "MM:SS" 字符串。
 pub fn format_duration(duration: Duration) -> String {
     let secs = duration.as_secs();
@@ -154,4 +1093,661 @@ pub fn format_duration(duration: Duration) -> String {
     } else {
         "??:??".to_string()
     }
-}
\ No newline at end of file
+}
+
+/// 将 Duration 格式化为 "M:SS" 的播放位置（0 秒显示为 0:00）。
+pub fn format_position(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    const SHORT_DELAYS: [Duration; 3] = [Duration::from_millis(1), Duration::from_millis(2), Duration::from_millis(4)];
+
+    /// 前 `failures` 次以 `kind` 失败、之后成功的操作，记录调用次数
+    fn flaky(kind: io::ErrorKind, failures: usize, calls: &mut usize) -> impl FnMut() -> io::Result<&'static str> + '_ {
+        move || {
+            *calls += 1;
+            if *calls <= failures { Err(io::Error::from(kind)) } else { Ok("ok") }
+        }
+    }
+
+    #[test]
+    fn transient_errors_are_retried_until_success() {
+        let mut calls = 0;
+        let mut waits = Vec::new();
+        let result = retry_transient(&SHORT_DELAYS, |delay| waits.push(delay), flaky(io::ErrorKind::TimedOut, 2, &mut calls));
+        assert_eq!(result.unwrap(), "ok");
+        assert_eq!(calls, 3);
+        assert_eq!(waits, SHORT_DELAYS[..2]);
+    }
+
+    #[test]
+    fn retries_give_up_after_the_last_delay() {
+        let mut calls = 0;
+        let result = retry_transient(&SHORT_DELAYS, |_| {}, flaky(io::ErrorKind::StaleNetworkFileHandle, 10, &mut calls));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::StaleNetworkFileHandle);
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn not_found_fails_fast() {
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::PermissionDenied, io::ErrorKind::InvalidData] {
+            let mut calls = 0;
+            let mut retried = false;
+            let result = retry_transient(&SHORT_DELAYS, |_| retried = true, flaky(kind, 1, &mut calls));
+            assert_eq!(result.unwrap_err().kind(), kind);
+            assert_eq!(calls, 1);
+            assert!(!retried);
+        }
+    }
+
+    #[test]
+    fn transient_error_kinds() {
+        use io::ErrorKind::*;
+        for kind in [TimedOut, Interrupted, WouldBlock, ConnectionReset, NetworkDown, StaleNetworkFileHandle, ResourceBusy] {
+            assert!(is_transient_error(&io::Error::from(kind)), "{:?}", kind);
+        }
+        for kind in [NotFound, PermissionDenied, InvalidData, UnexpectedEof, IsADirectory] {
+            assert!(!is_transient_error(&io::Error::from(kind)), "{:?}", kind);
+        }
+    }
+
+    fn scan() -> ScanOptions {
+        ScanOptions { quiet: true, ..ScanOptions::default() }
+    }
+
+    fn playlist_error(input: &Path) -> PlaylistError {
+        get_playlist_from_input(input.to_str().unwrap(), &scan()).unwrap_err()
+    }
+
+    #[test]
+    fn missing_path_is_not_found() {
+        let dir = TempDir::new();
+        assert!(matches!(playlist_error(&dir.join("no-such-folder")), PlaylistError::NotFound));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_path_is_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new();
+        let song = dir.write("locked/song.mp3", b"");
+        let locked = dir.join("locked");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // root 不受权限位限制，此时只能验证错误类型的映射
+        if fs::metadata(&song).is_err() {
+            assert!(matches!(playlist_error(&song), PlaylistError::PermissionDenied));
+        }
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(matches!(PlaylistError::from(io::Error::from(io::ErrorKind::PermissionDenied)), PlaylistError::PermissionDenied));
+    }
+
+    #[test]
+    fn partial_download_is_unsupported_file() {
+        let dir = TempDir::new();
+        match playlist_error(&dir.write("song.mp3.part", b"ID3")) {
+            PlaylistError::UnsupportedFile { ext } => assert_eq!(ext, "part"),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(playlist_error(&dir.write("notes.xyz", b"not audio")), PlaylistError::UndecodableFile));
+    }
+
+    #[test]
+    fn empty_directory() {
+        let dir = TempDir::new();
+        assert!(matches!(playlist_error(dir.path()), PlaylistError::EmptyDirectory));
+    }
+
+    #[test]
+    fn directory_without_audio_lists_seen_extensions() {
+        let dir = TempDir::new();
+        dir.write("a.wma", b"");
+        dir.write("b.WMA", b"");
+        dir.write("cover.jpg", b"");
+        dir.write("sub/c.ape", b"");
+        let error = playlist_error(dir.path());
+        match &error {
+            PlaylistError::NoAudioInDirectory { seen_extensions } => assert_eq!(seen_extensions, &["jpg", "wma"]),
+            other => panic!("{:?}", other),
+        }
+        assert!(error.to_string().contains(".jpg .wma"), "{}", error);
+
+        let only_dirs = TempDir::new();
+        fs::create_dir(only_dirs.join("empty")).unwrap();
+        assert!(matches!(playlist_error(only_dirs.path()), PlaylistError::NoAudioInDirectory { seen_extensions } if seen_extensions.is_empty()));
+    }
+
+    #[test]
+    fn glob_without_matches() {
+        let dir = TempDir::new();
+        dir.write("a.wma", b"");
+        let pattern = dir.join("*.mp3");
+        match playlist_error(&pattern) {
+            PlaylistError::NoGlobMatches { pattern: p } => assert_eq!(p, pattern.to_str().unwrap()),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn playlist_without_usable_entries() {
+        let dir = TempDir::new();
+        let empty = dir.write("empty.m3u", "#EXTM3U\n# 只有注释\n\n");
+        assert!(matches!(playlist_error(&empty), PlaylistError::EmptyPlaylist { missing: 0 }));
+        let gone = dir.write("gone.m3u", format!("{}\n{}\n", dir.join("x.mp3").display(), dir.join("y.mp3").display()));
+        let error = playlist_error(&gone);
+        assert!(matches!(error, PlaylistError::EmptyPlaylist { missing: 2 }));
+        assert!(error.to_string().contains("2 个路径都不存在"));
+        // 只要有一个条目存在就正常返回全部条目
+        dir.write("x.mp3", b"");
+        assert_eq!(get_playlist_from_input(gone.to_str().unwrap(), &scan()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn audio_extension_checks_only_the_last_suffix() {
+        for (name, expected) in [
+            ("a.mp3", Some("mp3")),
+            ("a.MP3", Some("mp3")),
+            ("B.FlAc", Some("flac")),
+            ("live.2024.m4a", Some("m4a")),
+            ("a.bak.wav", Some("wav")),
+            ("dir.mp3/a.ogg", Some("ogg")),
+            ("a.MP3.bak", None),
+            ("a.mp3.txt", None),
+            ("a", None),
+            ("a.", None),
+            (".mp3", None),
+            ("a.mp 3", None),
+        ] {
+            assert_eq!(audio_extension(Path::new(name)).as_deref(), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn temp_suffixes_are_never_audio() {
+        for suffix in TEMP_SUFFIXES {
+            for name in [format!("a.flac.{}", suffix), format!("a.MP3.{}", suffix.to_uppercase()), format!("a.{}", suffix)] {
+                assert_eq!(audio_extension(Path::new(&name)), None, "{:?}", name);
+            }
+            assert!(is_temp_suffix(suffix) && is_temp_suffix(&suffix.to_uppercase()));
+        }
+        assert!(TEMP_SUFFIXES.contains(&"part") && TEMP_SUFFIXES.contains(&"crdownload"));
+        assert!(!is_temp_suffix("mp3") && !is_temp_suffix("partial"));
+    }
+
+    #[test]
+    fn splits_display_titles() {
+        let dir = TempDir::new();
+        let piped = dir.write("Live | Unplugged.mp3", b"");
+        let piped = piped.to_str().unwrap();
+        let missing = dir.join("jingle.mp3");
+        let missing = missing.to_str().unwrap();
+        let owned = |title: &str| Some(title.to_string());
+        assert_eq!(split_title_suffix("song.mp3"), ("song.mp3", None));
+        assert_eq!(split_title_suffix(&format!("{} |  电台 Jingle #3 ", missing)), (missing, owned("电台 Jingle #3")));
+        assert_eq!(split_title_suffix(&format!("{} |", missing)), (missing, None));
+        // 都不存在时按第一个 `|` 拆分，标题中可以再含 `|`
+        assert_eq!(split_title_suffix(&format!("{} | A | B", missing)), (missing, owned("A | B")));
+        // 路径本身含 `|`：整行是存在的文件时不拆分，否则取左侧为存在文件的 `|`
+        assert_eq!(split_title_suffix(piped), (piped, None));
+        assert_eq!(split_title_suffix(&format!("{} | Encore", piped)), (piped, owned("Encore")));
+    }
+
+    #[test]
+    fn playlist_titles_from_txt_and_m3u() {
+        let dir = TempDir::new();
+        let txt = dir.write("show.txt", "/ads/spot1.mp3 | 广告 1\n/ads/spot2.mp3\n");
+        let m3u = dir.write("show.m3u", "#EXTM3U\n#EXTINF:30,Station ID\n/ads/id.mp3\n/ads/untitled.mp3\n#EXTINF:-1,被覆盖\n/ads/jingle.mp3 | Jingle\n");
+        let titles = |path: &Path| read_playlist_file(path).unwrap().into_iter().map(|t| (t.path, t.title)).collect::<Vec<_>>();
+        let entry = |path: &str, title: Option<&str>| (PathBuf::from(path), title.map(String::from));
+        assert_eq!(titles(&txt), [entry("/ads/spot1.mp3", Some("广告 1")), entry("/ads/spot2.mp3", None)]);
+        // #EXTINF 只作用于下一条路径；同一条目上 `| 标题` 优先
+        assert_eq!(titles(&m3u), [entry("/ads/id.mp3", Some("Station ID")), entry("/ads/untitled.mp3", None), entry("/ads/jingle.mp3", Some("Jingle"))]);
+    }
+
+    /// 含 `count` 首歌曲（分布在两层子目录中）和一些非音频文件的目录
+    fn music_tree(count: usize) -> TempDir {
+        let dir = TempDir::new();
+        for i in 0..count {
+            dir.write(&format!("disc{}/cd{}/{:02}.mp3", i % 3, i % 2, i), b"");
+        }
+        dir.write("cover.jpg", b"");
+        dir.write("disc0/notes.txt", b"");
+        dir
+    }
+
+    #[test]
+    fn limit_stops_the_scan() {
+        let dir = music_tree(10);
+        let recursive = ScanOptions { recursive: true, ..scan() };
+        assert_eq!(scan_audio_files(dir.path(), &recursive).unwrap().len(), 10);
+        for limit in [1, 3, 9, 10, 50] {
+            let opts = ScanOptions { limit: Some(limit), ..recursive.clone() };
+            assert_eq!(scan_audio_files(dir.path(), &opts).unwrap().len(), limit.min(10), "--limit {}", limit);
+        }
+    }
+
+    #[test]
+    fn confirms_once_past_the_threshold() {
+        use std::sync::atomic::AtomicUsize;
+        // confirm_large 是函数指针，用各测试独占的计数器记录调用
+        static DECLINED: AtomicUsize = AtomicUsize::new(0);
+        static ACCEPTED: AtomicUsize = AtomicUsize::new(0);
+        fn decline(threshold: usize) -> bool {
+            assert_eq!(threshold, 4);
+            DECLINED.fetch_add(1, Ordering::SeqCst);
+            false
+        }
+        fn accept(threshold: usize) -> bool {
+            assert_eq!(threshold, 4);
+            ACCEPTED.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+        fn unexpected(_: usize) -> bool {
+            panic!("未超过阈值时不应询问");
+        }
+
+        let dir = music_tree(10);
+        let opts = ScanOptions { recursive: true, large_threshold: 4, ..scan() };
+        let error = scan_audio_files(dir.path(), &ScanOptions { confirm_large: Some(decline), ..opts.clone() }).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(DECLINED.load(Ordering::SeqCst), 1);
+
+        assert_eq!(scan_audio_files(dir.path(), &ScanOptions { confirm_large: Some(accept), ..opts.clone() }).unwrap().len(), 10);
+        assert_eq!(ACCEPTED.load(Ordering::SeqCst), 1);
+
+        // 共 12 个文件（含非音频文件）：阈值不小于它时不询问；0 表示不检查；--limit 先到时也不询问
+        for opts in [
+            ScanOptions { large_threshold: 12, ..opts.clone() },
+            ScanOptions { large_threshold: 0, ..opts.clone() },
+            ScanOptions { limit: Some(2), large_threshold: 4, ..opts.clone() },
+        ] {
+            let opts = ScanOptions { confirm_large: Some(unexpected), ..opts };
+            assert!(scan_audio_files(dir.path(), &opts).is_ok());
+        }
+        // 没有确认回调（如 --yes）时直接继续
+        assert_eq!(scan_audio_files(dir.path(), &opts).unwrap().len(), 10);
+    }
+
+    /// 递归扫描 `dir`，返回相对路径（已排序）
+    fn scanned(dir: &TempDir) -> Vec<String> {
+        let opts = ScanOptions { recursive: true, ..scan() };
+        let mut found: Vec<String> = scan_audio_files(dir.path(), &opts)
+            .unwrap()
+            .iter()
+            .map(|path| path.strip_prefix(dir.path()).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn marker_files_skip_directories() {
+        let dir = TempDir::new();
+        for name in ["a.mp3", "samples/kick.wav", "ringtones/sub/r.ogg", "album/cd1/01.flac", "album/cd2/01.flac"] {
+            dir.write(name, b"");
+        }
+        dir.write("samples/.mddignore", b"");
+        dir.write("ringtones/.nomedia", b"");
+        dir.write("album/cd2/.nomedia", b"");
+        assert_eq!(scanned(&dir), ["a.mp3", "album/cd1/01.flac"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directory_loops_are_entered_once() {
+        let dir = TempDir::new();
+        dir.write("music/a.mp3", b"");
+        dir.write("music/sub/b.mp3", b"");
+        // 指向根目录（祖先）与指向同级已有目录的链接
+        std::os::unix::fs::symlink(dir.path(), dir.join("music/sub/up")).unwrap();
+        std::os::unix::fs::symlink(dir.join("music/sub"), dir.join("music/sub/self")).unwrap();
+        assert_eq!(scanned(&dir), ["music/a.mp3", "music/sub/b.mp3"]);
+    }
+
+    #[test]
+    fn root_ignore_rules_support_negation_and_directories() {
+        let dir = TempDir::new();
+        for name in [
+            "keep.mp3",
+            "live/show.mp3",
+            "live/encore.mp3",
+            "live/2019/a.mp3",
+            "live/2019/b.mp3",
+            "demos/d.mp3",
+            "misc/demos.mp3",
+            "misc/demos/x.mp3",
+            "tmp.flac",
+            "sub/tmp.flac",
+        ] {
+            dir.write(name, b"");
+        }
+        dir.write(
+            ".mddignore",
+            "# 注释行\n\
+             live/*\n\
+             !live/encore.mp3\n\
+             !live/2019/\n\
+             live/2019/b.mp3\n\
+             demos/\n\
+             /tmp.flac\n",
+        );
+        // `demos/` 只匹配目录（不影响 demos.mp3）；`/tmp.flac` 只匹配根目录下的文件；
+        // 重新包含的目录中仍可再次排除
+        assert_eq!(scanned(&dir), ["keep.mp3", "live/2019/a.mp3", "live/encore.mp3", "misc/demos.mp3", "sub/tmp.flac"]);
+    }
+
+    #[test]
+    fn embedded_cue_splits_unless_disabled() {
+        use crate::test_support::{flac_cuesheet, flac_streaminfo, flac_with_blocks};
+        let dir = TempDir::new();
+        let flac = dir.write(
+            "album.flac",
+            flac_with_blocks(&[(0, flac_streaminfo(44100)), (5, flac_cuesheet(&[(1, 0, &[(1, 0)]), (2, 441_000, &[(1, 0)]), (3, 882_000, &[(1, 0)])]))]),
+        );
+        let tracks = audio_file_tracks(flac.clone(), &scan());
+        let ranges: Vec<_> = tracks.iter().map(|t| (t.path.clone(), t.title.clone(), t.start, t.end)).collect();
+        let secs = Duration::from_secs;
+        assert_eq!(
+            ranges,
+            [
+                (flac.clone(), Some("第 1 章".to_string()), Some(secs(0)), Some(secs(10))),
+                (flac.clone(), Some("第 2 章".to_string()), Some(secs(10)), Some(secs(20))),
+                (flac.clone(), Some("第 3 章".to_string()), Some(secs(20)), None),
+            ]
+        );
+        let whole = audio_file_tracks(flac.clone(), &ScanOptions { no_cue_split: true, ..scan() });
+        assert_eq!(whole.len(), 1);
+        assert_eq!((whole[0].start, whole[0].end, whole[0].title.as_deref()), (None, None, None));
+        // 扩展名不是 .flac 的文件不检查内嵌 CUE
+        let renamed = dir.join("album.ogg");
+        fs::copy(&flac, &renamed).unwrap();
+        assert_eq!(audio_file_tracks(renamed, &scan()).len(), 1);
+    }
+
+    fn fake_env(name: &str) -> Option<String> {
+        match name {
+            "MUSIC" => Some("/data/music".into()),
+            "USER_DIR" => Some("我的音乐".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_variables() {
+        for (input, expected) in [
+            ("$MUSIC/jazz", "/data/music/jazz"),
+            ("${MUSIC}2024", "/data/music2024"),
+            ("%MUSIC%\\mix", "/data/music\\mix"),
+            ("/home/$USER_DIR/a.mp3", "/home/我的音乐/a.mp3"),
+            ("$MUSIC-$USER_DIR", "/data/music-我的音乐"),
+            // 未设置的变量按原文保留
+            ("$UNDEFINED/x", "$UNDEFINED/x"),
+            ("${UNDEFINED}/x", "${UNDEFINED}/x"),
+            ("%UNDEFINED%/x", "%UNDEFINED%/x"),
+            // 不构成变量的 `$`、`%` 是普通字符
+            ("price$", "price$"),
+            ("$ 5/100%", "$ 5/100%"),
+            ("a$$MUSIC", "a$/data/music"),
+            ("${MUSIC", "${MUSIC"),
+            ("${}", "${}"),
+            ("50% off %MUSIC%", "50% off /data/music"),
+            ("plain/path.mp3", "plain/path.mp3"),
+        ] {
+            assert_eq!(expand_env_vars(input, fake_env), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn expands_leading_tilde() {
+        let home = Some(Path::new("/home/张三"));
+        for (input, expected) in [
+            ("~", "/home/张三"),
+            ("~/x", "/home/张三/x"),
+            ("~\\x", "/home/张三\\x"),
+            // 其他用户的主目录与中间的 `~` 不展开
+            ("~bob/x", "~bob/x"),
+            ("a/~/x", "a/~/x"),
+            ("x~", "x~"),
+        ] {
+            assert_eq!(expand_tilde(input, home), expected, "{:?}", input);
+        }
+        assert_eq!(expand_tilde("~/x", None), "~/x");
+        // 变量先于 `~` 展开
+        assert_eq!(expand_tilde(&expand_env_vars("~/$MUSIC", fake_env), home), "/home/张三//data/music");
+    }
+
+    #[test]
+    fn file_stamp_detects_replaced_files() {
+        let dir = TempDir::new();
+        let song = dir.write("song.flac", b"old audio");
+        let preloaded = FileStamp::read(&song).unwrap();
+        assert_eq!(FileStamp::read(&song), Some(preloaded));
+
+        // 重新编码后大小改变
+        fs::write(&song, b"re-encoded audio").unwrap();
+        let resized = FileStamp::read(&song).unwrap();
+        assert_ne!(resized, preloaded);
+
+        // 大小相同、只有修改时间改变
+        let file = fs::File::options().write(true).open(&song).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)).unwrap();
+        let touched = FileStamp::read(&song).unwrap();
+        assert_ne!(touched, resized);
+        assert_eq!(touched, FileStamp::from_metadata(&file.metadata().unwrap()));
+
+        fs::remove_file(&song).unwrap();
+        assert_eq!(FileStamp::read(&song), None);
+    }
+
+    fn expand(playlist: &Path) -> Vec<PathBuf> {
+        let mut walk = Walk { files: Vec::new(), skipped_dirs: 0, visited: 0, confirmed: false, dirs: HashSet::new() };
+        assert_eq!(expand_playlist(playlist, &scan(), &mut walk, &mut Vec::new()), ControlFlow::Continue(()));
+        // 条目按所在播放列表的目录拼接（如 `lists/../music/one.mp3`），比较时统一为实际路径
+        walk.files.iter().map(|path| fs::canonicalize(path).unwrap()).collect()
+    }
+
+    #[test]
+    fn playlist_cycles_terminate() {
+        let dir = TempDir::new();
+        let one = fs::canonicalize(dir.write("music/one.mp3", b"")).unwrap();
+        let two = fs::canonicalize(dir.write("music/two.flac", b"")).unwrap();
+        let a = dir.write("lists/a.m3u", "../music/one.mp3\nb.m3u\n");
+        let b = dir.write("lists/b.m3u", "#EXTM3U\n../music/two.flac\na.m3u\n../music/one.mp3\n");
+        let own = dir.write("lists/self.m3u8", format!("{}\nself.m3u8\n{}\n", one.display(), two.display()));
+        // a → b → a：回到 a 时跳过，b 中其余条目照常加入
+        assert_eq!(expand(&a), [one.clone(), two.clone(), one.clone()]);
+        assert_eq!(expand(&b), [two.clone(), one.clone(), one.clone()]);
+        assert_eq!(expand(&own), [one.clone(), two.clone()]);
+
+        // 扫描目录时每个播放列表各自展开一次，出现在多个播放列表中的歌曲每次都加入
+        let opts = ScanOptions { expand_playlists: true, ..scan() };
+        let found: Vec<_> = scan_audio_files(&dir.join("lists"), &opts).unwrap().iter().map(|path| fs::canonicalize(path).unwrap()).collect();
+        assert_eq!(found.iter().filter(|p| **p == one).count(), 5);
+        assert_eq!(found.iter().filter(|p| **p == two).count(), 3);
+        assert!(scan_audio_files(&dir.join("lists"), &scan()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn nested_playlists_expand_in_order() {
+        let dir = TempDir::new();
+        let songs: Vec<_> = ["1.mp3", "2.ogg", "3.wav", "4.m4a"].iter().map(|name| fs::canonicalize(dir.write(&format!("songs/{}", name), b"")).unwrap()).collect();
+        let top = dir.write("top.m3u", "songs/1.mp3\nmid/mid.m3u\nsongs/4.m4a\n");
+        dir.write("mid/mid.m3u", "../songs/2.ogg\ndeep/deep.m3u\nmissing.mp3\n");
+        dir.write("mid/deep/deep.m3u", "../../songs/3.wav\nnot-audio.txt\n");
+        dir.write("mid/deep/not-audio.txt", b"");
+        assert_eq!(expand(&top), songs);
+    }
+
+    #[test]
+    fn ambiguous_width_follows_the_setting() {
+        let zh = Some("zh_CN.UTF-8");
+        for (mode, locale, cjk) in [
+            (CjkWidth::On, None, true),
+            (CjkWidth::On, Some("en_US.UTF-8"), true),
+            (CjkWidth::Off, zh, false),
+            (CjkWidth::Auto, zh, true),
+            (CjkWidth::Auto, Some("ja_JP.UTF-8"), true),
+            (CjkWidth::Auto, Some("KO_KR"), true),
+            (CjkWidth::Auto, Some("en_US.UTF-8"), false),
+            (CjkWidth::Auto, Some("C"), false),
+            (CjkWidth::Auto, None, false),
+        ] {
+            assert_eq!(cjk_width_enabled(mode, locale), cjk, "{:?} {:?}", mode, locale);
+        }
+
+        // (文本, 单宽计宽, CJK 计宽)
+        for (text, narrow, wide) in [
+            ("…", 1, 2),
+            ("±", 1, 2),
+            ("αβγ", 3, 6),
+            ("Ω…±", 3, 6),
+            ("Жё", 2, 4),
+            // 带重音的希腊字母不属于歧义宽度
+            ("έ", 1, 1),
+            ("abc", 3, 3),
+            ("中文", 4, 4),
+            ("e\u{301}", 1, 1),
+        ] {
+            assert_eq!(display_width_as(text, false), narrow, "{:?}", text);
+            assert_eq!(display_width_as(text, true), wide, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn control_characters_are_replaced_before_layout() {
+        assert_eq!(sanitize_display("a\tb\nc\r\u{0b}d"), "a b c  d");
+        assert_eq!(sanitize_display("x\u{1b}[31my\u{7f}\u{0}\u{85}z"), "x\u{fffd}[31my\u{fffd}\u{fffd} z");
+        // 截断结果不含控制字符，宽度与实际显示一致
+        let title = "Bad\u{1b}[2J\u{7}Tag\ttitle\u{0}";
+        for width in 0..=25 {
+            let truncated = truncate_string(title, width);
+            assert!(!truncated.chars().any(char::is_control), "{:?}", truncated);
+            assert!(display_width(&truncated) <= width, "{} {:?}", width, truncated);
+        }
+        assert_eq!(truncate_string(title, 30), "Bad\u{fffd}[2J\u{fffd}Tag title\u{fffd}");
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_base() {
+        // 组合字符宽度为 0，截断时不会与前面的字母分开
+        let title = "Cafe\u{301} Me\u{301}xico Ha\u{308}agen";
+        assert_eq!(display_width_as(title, false), 18);
+        assert_eq!(truncate_string_as(title, 7, false), "Cafe\u{301}...");
+        assert_eq!(truncate_string_as(title, 10, false), "Cafe\u{301} Me\u{301}...");
+        for cjk in [false, true] {
+            for width in 0..=22 {
+                let truncated = truncate_string_as(title, width, cjk);
+                assert!(display_width_as(&truncated, cjk) <= width, "{} {:?}", width, truncated);
+                assert!(!truncated.contains(" \u{301}") && !truncated.starts_with('\u{301}'), "{:?}", truncated);
+            }
+        }
+        // 未分配的码位按单宽计算，与终端显示的替代字形一致
+        for cjk in [false, true] {
+            assert_eq!(display_width_as("\u{0378}\u{0380}", cjk), 2);
+        }
+    }
+
+    #[test]
+    fn truncation_fits_either_width_setting() {
+        let title = "αβγδε ± 10 … Ωμέγα";
+        assert_eq!(truncate_string_as(title, 20, false), title);
+        assert_eq!(truncate_string_as(title, 20, true), "αβγδε ± 10 ...");
+        assert_eq!(truncate_string_as("……", 4, true), "……");
+        assert_eq!(truncate_string_as("………", 5, true), "…...");
+        for cjk in [false, true] {
+            for width in 0..=30 {
+                let truncated = truncate_string_as(title, width, cjk);
+                assert!(display_width_as(&truncated, cjk) <= width, "{} {} {:?}", cjk, width, truncated);
+            }
+        }
+    }
+
+    fn annotated(title: Option<&str>, start: Option<u64>, end: Option<u64>, trim: Option<u64>) -> Annotation {
+        Annotation { title: title.map(String::from), start: start.map(Duration::from_millis), end: end.map(Duration::from_millis), trim: trim.map(Duration::from_millis) }
+    }
+
+    /// 经过曲目写出再解析，得到的注释应与原来相同
+    fn round_trip(annotation: &Annotation) -> Annotation {
+        let track = Track {
+            title: annotation.title.clone(),
+            start: annotation.start,
+            end: annotation.end,
+            trim: annotation.trim,
+            ..Track::from(PathBuf::from("a.mp3"))
+        };
+        let line = Annotation::format(&track).expect("有信息时应生成注释");
+        assert!(!line.contains(['\n', '\r']), "注释必须保持在一行内: {:?}", line);
+        Annotation::parse(line.strip_prefix(ANNOTATION_PREFIX).unwrap())
+    }
+
+    #[test]
+    fn annotation_round_trips() {
+        for annotation in [
+            annotated(Some("第一章 序曲"), Some(0), Some(754_250), None),
+            annotated(Some("Tab\there\tand  two spaces"), None, None, Some(12_500)),
+            annotated(Some("多行\n标题\r\n续"), Some(1), None, None),
+            annotated(Some(r"AC\DC \t 不是制表符 \\"), None, Some(3_600_000), None),
+            annotated(Some("Ünïcödé – 日本語 🎵 title=x"), Some(61_001), Some(62_002), Some(999)),
+            annotated(None, Some(5_000), None, Some(30_000)),
+        ] {
+            assert_eq!(round_trip(&annotation), annotation);
+        }
+        assert_eq!(Annotation::format(&Track::from(PathBuf::from("a.mp3"))), None);
+    }
+
+    #[test]
+    fn annotation_ignores_unknown_and_bad_items() {
+        assert_eq!(Annotation::parse("rating=5 start=1.5 volume= trim=-3 end=abc bare title=  名字  "), annotated(Some("名字"), Some(1500), None, None));
+        assert_eq!(Annotation::parse("start=inf end=NaN trim=2"), annotated(None, None, None, Some(2000)));
+        // 结束位置不在起始位置之后时只保留起始位置
+        assert_eq!(Annotation::parse("start=10 end=10"), annotated(None, Some(10_000), None, None));
+        assert_eq!(Annotation::parse("title="), Annotation::default());
+        // 手工写入的其他反斜杠原样保留
+        assert_eq!(Annotation::parse(r"title=C:\Music\x"), annotated(Some(r"C:\Music\x"), None, None, None));
+    }
+
+    #[test]
+    fn playlist_file_round_trips() {
+        let dir = TempDir::new();
+        let tracks = vec![
+            Track { title: Some("标题\t带制表符".into()), start: Some(Duration::from_millis(90_500)), end: Some(Duration::from_secs(200)), ..Track::from(dir.join("mix.flac")) },
+            Track { trim: Some(Duration::from_secs(8)), ..Track::from(dir.join("有声书 01.mp3")) },
+            Track::from(dir.join("plain.ogg")),
+        ];
+        let file = dir.join("saved.m3u");
+        write_playlist_file(&file, &tracks).unwrap();
+        let read = read_playlist_file(&file).unwrap();
+        let fields = |t: &Track| (t.path.clone(), t.title.clone(), t.start, t.end, t.trim);
+        assert_eq!(read.iter().map(fields).collect::<Vec<_>>(), tracks.iter().map(fields).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parses_durations_with_units_or_timestamps() {
+        let secs = Duration::from_secs;
+        for (text, expected) in [
+            ("90s", Some(secs(90))),
+            ("25m", Some(secs(1500))),
+            ("1h30m", Some(secs(5400))),
+            ("1H30M15S", Some(secs(5415))),
+            (" 2h ", Some(secs(7200))),
+            ("0m", Some(secs(0))),
+            ("1:30:00", Some(secs(5400))),
+            ("4:05", Some(secs(245))),
+            // 纯数字的单位由调用方决定
+            ("25", None),
+            ("1h30", None),
+            ("", None),
+            ("m", None),
+            ("1d", None),
+            ("-5m", None),
+            ("1:75", None),
+        ] {
+            assert_eq!(parse_duration(text), expected, "{:?}", text);
+        }
+    }
+}
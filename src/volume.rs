@@ -0,0 +1,421 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// --- 常量定义 ---
+const REPEAT_RESET: Duration = Duration::from_millis(300); // 超过该间隔视为松开按键，重置加速
+const REPEATS_PER_LEVEL: u32 = 10; // 每连续多少次按键提升一档步长
+const ACCEL_STEPS: [f32; 3] = [0.01, 0.02, 0.05]; // 各档位的步长：1% → 2% → 5%
+
+/// 音量键长按加速器：根据连续按键的时间戳计算每次调节的步长。
+pub struct VolumeAccelerator {
+    last_press: Option<Instant>,
+    repeats: u32,
+}
+
+impl VolumeAccelerator {
+    pub fn new() -> Self {
+        VolumeAccelerator { last_press: None, repeats: 0 }
+    }
+
+    /// 记录一次音量按键，返回本次应使用的步长。
+    /// 两次按键间隔超过 300ms 时视为重新开始。
+    pub fn step(&mut self, now: Instant) -> f32 {
+        match self.last_press {
+            Some(last) if now.saturating_duration_since(last) <= REPEAT_RESET => self.repeats += 1,
+            _ => self.repeats = 0,
+        }
+        self.last_press = Some(now);
+        let level = ((self.repeats / REPEATS_PER_LEVEL) as usize).min(ACCEL_STEPS.len() - 1);
+        ACCEL_STEPS[level]
+    }
+}
+
+/// 单曲增益的调节范围（dB）
+pub const TRACK_GAIN_MIN_DB: f32 = -12.0;
+pub const TRACK_GAIN_MAX_DB: f32 = 12.0;
+/// 单曲增益每次调节的步长（dB）
+pub const TRACK_GAIN_STEP_DB: f32 = 1.0;
+
+/// 将 dB 转换为线性增益倍数
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// 界面音量百分比与实际增益之间的映射（`--volume-curve`）
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VolumeCurve {
+    /// 增益与百分比成正比（低音量段每 1% 的变化很明显，高音量段几乎听不出）
+    Linear,
+    /// 按听感映射：低音量段增益按平方起步、调节细腻，50% 约为 -6dB，接近满音量时与线性相切
+    Log,
+}
+
+impl VolumeCurve {
+    /// 界面音量（0.0-1.0）换算为增益
+    fn gain(self, volume: f32) -> f32 {
+        let volume = volume.clamp(0.0, 1.0);
+        match self {
+            VolumeCurve::Linear => volume,
+            // 2v⁴ - 5v³ + 4v²：过 (0, 0)、(0.5, 0.5)、(1, 1)，单调递增，两端斜率分别为 0 和 1
+            VolumeCurve::Log => volume * volume * (4.0 - 5.0 * volume + 2.0 * volume * volume),
+        }
+    }
+
+    /// `gain` 的逆映射：增益乘以 `gain_factor` 后对应的界面音量。
+    /// log 映射不是幂函数，界面音量不能简单相乘，按二分法求解
+    fn scale(self, volume: f32, gain_factor: f32) -> f32 {
+        let target = self.gain(volume) * gain_factor.clamp(0.0, 1.0);
+        match self {
+            VolumeCurve::Linear => target,
+            VolumeCurve::Log => {
+                let (mut low, mut high) = (0.0f32, volume.clamp(0.0, 1.0));
+                for _ in 0..32 {
+                    let mid = (low + high) / 2.0;
+                    if self.gain(mid) < target { low = mid } else { high = mid }
+                }
+                high
+            }
+        }
+    }
+
+    /// 界面音量相对满音量的衰减（dB）；先按显示的整数百分比取整，避免 100% 显示为 -0.0dB。0% 时为 None（-∞）
+    fn db(self, volume: f32) -> Option<f32> {
+        let gain = self.gain((volume * 100.0).round() / 100.0);
+        (gain > 0.0).then(|| 20.0 * gain.log10())
+    }
+}
+
+/// 是否使用线性映射（启动时按 `--volume-curve` 设置）
+static LINEAR_VOLUME: AtomicBool = AtomicBool::new(false);
+
+/// 设置界面音量的映射方式，之后所有 ui_volume_to_gain 都使用该规则
+pub fn set_volume_curve(curve: VolumeCurve) {
+    LINEAR_VOLUME.store(curve == VolumeCurve::Linear, Ordering::Relaxed);
+}
+
+fn volume_curve() -> VolumeCurve {
+    if LINEAR_VOLUME.load(Ordering::Relaxed) { VolumeCurve::Linear } else { VolumeCurve::Log }
+}
+
+/// 把界面显示的音量（0.0-1.0，即百分比）换算为 Sink 的增益；
+/// 写入 Sink 的音量都经过这里，界面、命令行与状态输出始终使用百分比
+pub fn ui_volume_to_gain(volume: f32) -> f32 {
+    volume_curve().gain(volume)
+}
+
+/// 把作用在增益上的倍数（如渐强进行到一半时的 0.5）折算进界面音量，
+/// 接管时主音量换成返回值，Sink 的实际增益保持不变
+pub fn scale_ui_volume(volume: f32, gain_factor: f32) -> f32 {
+    volume_curve().scale(volume, gain_factor)
+}
+
+/// 界面音量相对满音量的衰减（dB），与 ui_volume_to_gain 使用同一映射；0% 时为 None（-∞）
+pub fn ui_volume_to_db(volume: f32) -> Option<f32> {
+    volume_curve().db(volume)
+}
+
+/// 音量的 dB 读数，如 `-6.9dB`；0% 时为 `-∞dB`
+pub fn format_volume_db(volume: f32) -> String {
+    match ui_volume_to_db(volume) {
+        Some(db) => format!("{:.1}dB", db),
+        None => "-∞dB".to_string(),
+    }
+}
+
+/// 计算实际写入 Sink 的音量：主音量（按 ui_volume_to_gain 换算）× 以 dB 表示的增益偏移 × 临时压低倍数。
+/// 多个 dB 偏移（如单曲增益与 ReplayGain）由调用方相加后传入；`duck` 为 [`Duck::factor`]
+/// 与 [`FadeIn::factor`] 之积，不修改主音量本身，因此过渡结束后音量完全恢复。
+pub fn effective_volume(master: f32, gain_db: f32, duck: f32) -> f32 {
+    (ui_volume_to_gain(master) * db_to_gain(gain_db) * duck.clamp(0.0, 1.0)).max(0.0)
+}
+
+/// 临时压低时的音量倍数
+const DUCK_LEVEL: f32 = 0.2;
+/// 压低与恢复的过渡时长
+const DUCK_RAMP: Duration = Duration::from_millis(300);
+
+/// 临时压低音量（如有人说话时）：在 300ms 内平滑降到 20%，保持到取消后再平滑恢复。
+pub struct Duck {
+    active: bool,
+    /// 本次过渡开始时的倍数
+    ramp_from: f32,
+    /// 本次过渡开始的时刻；None 表示没有进行中的过渡
+    ramp_started: Option<Instant>,
+}
+
+impl Duck {
+    pub fn new() -> Self {
+        Duck { active: false, ramp_from: 1.0, ramp_started: None }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// 开始压低或恢复（从当前倍数平滑过渡，过渡中途反向也不会跳变）
+    pub fn set(&mut self, active: bool, now: Instant) {
+        if active == self.active {
+            return;
+        }
+        self.ramp_from = self.factor(now);
+        self.active = active;
+        self.ramp_started = Some(now);
+    }
+
+    /// 当前的音量倍数
+    pub fn factor(&self, now: Instant) -> f32 {
+        let target = if self.active { DUCK_LEVEL } else { 1.0 };
+        match self.ramp_started {
+            // 过渡结束后直接取目标值，恢复时精确回到 1（不留浮点误差）
+            Some(started) if now.saturating_duration_since(started) < DUCK_RAMP => {
+                let progress = now.saturating_duration_since(started).as_secs_f32() / DUCK_RAMP.as_secs_f32();
+                self.ramp_from + (target - self.ramp_from) * progress
+            }
+            _ => target,
+        }
+    }
+
+    /// 过渡期间（含结束的那一次）返回新的倍数，供调用方写入 Sink；没有过渡时返回 None
+    pub fn poll(&mut self, now: Instant) -> Option<f32> {
+        let started = self.ramp_started?;
+        let factor = self.factor(now);
+        if now.saturating_duration_since(started) >= DUCK_RAMP {
+            self.ramp_started = None;
+        }
+        Some(factor)
+    }
+
+    /// 过渡结束后实际听到的音量，折算为界面百分比（压低时增益为主音量的 20%），用于会话记录
+    pub fn settled_volume(&self, master: f32) -> f32 {
+        if self.active { scale_ui_volume(master, DUCK_LEVEL) } else { master }
+    }
+}
+
+/// 启动渐强（`--fade-in-start`）：第一首开始播放时，音量倍数在给定时长内从 0 线性升到 1。
+/// 每个进程只进行一次；手动调节音量会立即结束渐强。
+pub struct FadeIn {
+    duration: Duration,
+    /// 渐强开始的时刻；None 表示尚未开始
+    started: Option<Instant>,
+    /// 已结束（或未启用）
+    done: bool,
+}
+
+impl FadeIn {
+    /// 时长为 0 表示不启用
+    pub fn new(duration: Duration) -> Self {
+        FadeIn { duration, started: None, done: duration.is_zero() }
+    }
+
+    /// 第一首开始播放时调用；之后再调用不会重新开始
+    pub fn start(&mut self, now: Instant) {
+        if !self.done && self.started.is_none() {
+            self.started = Some(now);
+        }
+    }
+
+    /// 当前的音量倍数（开始之前为 0）
+    pub fn factor(&self, now: Instant) -> f32 {
+        if self.done {
+            return 1.0;
+        }
+        match self.started {
+            Some(started) => (now.saturating_duration_since(started).as_secs_f32() / self.duration.as_secs_f32()).min(1.0),
+            None => 0.0,
+        }
+    }
+
+    /// 手动调节音量时结束渐强：返回结束时的倍数，调用方据此换算主音量，避免音量跳变；
+    /// 没有进行中的渐强时返回 None
+    pub fn cancel(&mut self, now: Instant) -> Option<f32> {
+        if self.done {
+            return None;
+        }
+        let factor = self.factor(now);
+        self.done = true;
+        Some(factor)
+    }
+
+    /// 渐强期间（含结束的那一次）返回新的倍数，供调用方写入 Sink；其余时候返回 None
+    pub fn poll(&mut self, now: Instant) -> Option<f32> {
+        let started = self.started.filter(|_| !self.done)?;
+        let factor = self.factor(now);
+        if now.saturating_duration_since(started) >= self.duration {
+            self.done = true;
+        }
+        Some(factor)
+    }
+}
+
+/// 生成固定宽度的音量条，如 `█████░░░`（音量超出 0~1 时按边界显示）
+pub fn volume_bar(volume: f32, width: usize) -> String {
+    let filled = ((volume.clamp(0.0, 1.0) * width as f32).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    /// 从 `start` 起每隔 `gap` 按一次，共 `count` 次，返回各次的步长
+    fn presses(accel: &mut VolumeAccelerator, start: Instant, gap: Duration, count: u32) -> Vec<f32> {
+        (0..count).map(|i| accel.step(start + gap * i)).collect()
+    }
+
+    #[test]
+    fn held_key_accelerates() {
+        let start = Instant::now();
+        let mut accel = VolumeAccelerator::new();
+        let steps = presses(&mut accel, start, Duration::from_millis(30), 35);
+        // 第 0-9 次重复 1%，第 10-19 次 2%，之后 5%
+        for (repeat, step) in steps.iter().enumerate() {
+            let expected = match repeat {
+                0..10 => 0.01,
+                10..20 => 0.02,
+                _ => 0.05,
+            };
+            assert_close(*step, expected);
+        }
+    }
+
+    #[test]
+    fn pause_resets_acceleration() {
+        let start = Instant::now();
+        let mut accel = VolumeAccelerator::new();
+        presses(&mut accel, start, Duration::from_millis(50), 25);
+        let last = start + Duration::from_millis(50 * 24);
+        // 间隔恰好 300ms 仍算连续
+        assert_close(accel.step(last + REPEAT_RESET), 0.05);
+        // 超过 300ms 视为松开后重新按下
+        let again = last + REPEAT_RESET + Duration::from_millis(301);
+        assert_close(accel.step(again), 0.01);
+        assert_eq!(presses(&mut accel, again + Duration::from_millis(40), Duration::from_millis(40), 9), [0.01; 9]);
+        assert_close(accel.step(again + Duration::from_millis(400)), 0.02);
+        // 时间戳倒退（不应出现）时当作连续按键而不是出错
+        assert_close(accel.step(again), 0.02);
+    }
+
+    #[test]
+    fn curve_maps_pinned_points() {
+        for (volume, log, linear) in [(1.0, 1.0, 1.0), (0.5, 0.5, 0.5), (0.1, 0.0352, 0.1), (0.0, 0.0, 0.0)] {
+            assert_close(VolumeCurve::Log.gain(volume), log);
+            assert_close(VolumeCurve::Linear.gain(volume), linear);
+        }
+    }
+
+    #[test]
+    fn curve_clamps_out_of_range_volume() {
+        for curve in [VolumeCurve::Log, VolumeCurve::Linear] {
+            assert_close(curve.gain(1.5), 1.0);
+            assert_close(curve.gain(-0.2), 0.0);
+        }
+    }
+
+    #[test]
+    fn scale_inverts_the_curve() {
+        // 渐强中途接管：主音量换成折算后的值，增益应与接管前的 gain(m)·f 相同
+        for curve in [VolumeCurve::Log, VolumeCurve::Linear] {
+            for master in [1.0, 0.75, 0.5, 0.1] {
+                for factor in [0.0, 0.25, 0.5, 0.9, 1.0] {
+                    assert_close(curve.gain(curve.scale(master, factor)), curve.gain(master) * factor);
+                }
+            }
+        }
+        assert_close(VolumeCurve::Log.scale(1.0, 0.5), 0.5);
+        assert_close(VolumeCurve::Linear.scale(0.5, 0.25), 0.125);
+    }
+
+    #[test]
+    fn db_readout_anchors() {
+        for curve in [VolumeCurve::Log, VolumeCurve::Linear] {
+            assert_eq!(curve.db(1.0), Some(0.0));
+            assert_eq!(curve.db(0.0), None);
+            // 取整到显示的百分比：0.4% 显示为 0%
+            assert_eq!(curve.db(0.004), None);
+        }
+        // 两种映射下 50% 都约为 -6dB；log 在低音量段衰减更多
+        assert_eq!(format!("{:.1}", VolumeCurve::Log.db(0.5).unwrap()), "-6.0");
+        assert_eq!(format!("{:.1}", VolumeCurve::Linear.db(0.5).unwrap()), "-6.0");
+        assert_eq!(format!("{:.1}", VolumeCurve::Log.db(0.45).unwrap()), "-7.2");
+        assert_eq!(format!("{:.1}", VolumeCurve::Log.db(0.1).unwrap()), "-29.1");
+        assert_eq!(format!("{:.1}", VolumeCurve::Linear.db(0.1).unwrap()), "-20.0");
+    }
+
+    #[test]
+    fn format_volume_db_marks_silence() {
+        // 测试中不修改全局映射，使用默认的 log
+        assert_eq!(format_volume_db(0.0), "-∞dB");
+        assert_eq!(format_volume_db(1.0), "0.0dB");
+        assert_eq!(format_volume_db(0.5), "-6.0dB");
+    }
+
+    #[test]
+    fn effective_volume_combines_modifiers() {
+        // 主音量 × 增益偏移（+6.02dB ≈ ×2）× 压低与渐强之积
+        let master = volume_curve().gain(0.5);
+        assert_close(effective_volume(0.5, 6.0206, 0.2 * 0.5), master * 2.0 * 0.1);
+        assert_close(effective_volume(0.5, -6.0206, 1.0), master * 0.5);
+        assert_close(effective_volume(1.0, 0.0, 1.0), 1.0);
+        // 倍数超出 0–1 时截断
+        assert_close(effective_volume(1.0, 0.0, 1.5), 1.0);
+        assert_close(effective_volume(1.0, 0.0, -0.5), 0.0);
+        assert_close(effective_volume(0.0, 6.0, 1.0), 0.0);
+        // 单曲增益与 ReplayGain 以 dB 相加，等于两者的线性倍数相乘
+        assert_close(effective_volume(0.8, -4.0 + 2.5, 1.0), effective_volume(0.8, -4.0, 1.0) * db_to_gain(2.5));
+        assert_close(effective_volume(0.8, TRACK_GAIN_MIN_DB, 1.0), volume_curve().gain(0.8) * 0.251_188_6);
+    }
+
+    #[test]
+    fn settled_volume_matches_the_ducked_gain() {
+        let mut duck = Duck::new();
+        assert_close(duck.settled_volume(0.8), 0.8);
+        duck.set(true, Instant::now());
+        assert_close(ui_volume_to_gain(duck.settled_volume(0.8)), ui_volume_to_gain(0.8) * DUCK_LEVEL);
+    }
+
+    #[test]
+    fn duck_ramps_over_300ms() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut duck = Duck::new();
+        assert_eq!(duck.poll(start), None);
+        duck.set(true, start);
+        assert_close(duck.factor(start), 1.0);
+        assert_close(duck.factor(at(150)), 0.6);
+        assert_close(duck.factor(at(300)), DUCK_LEVEL);
+        assert_close(duck.factor(at(1000)), DUCK_LEVEL);
+        // 过渡结束的那一次仍返回倍数，之后不再返回
+        assert_close(duck.poll(at(100)).unwrap(), 1.0 - 0.8 / 3.0);
+        assert_close(duck.poll(at(300)).unwrap(), DUCK_LEVEL);
+        assert_eq!(duck.poll(at(310)), None);
+    }
+
+    #[test]
+    fn duck_reverses_mid_ramp_without_jump() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut duck = Duck::new();
+        duck.set(true, start);
+        duck.set(false, at(150));
+        assert!(!duck.is_active());
+        // 从反向时的 0.6 开始恢复，300ms 后回到 1
+        assert_close(duck.factor(at(150)), 0.6);
+        assert_close(duck.factor(at(300)), 0.8);
+        assert_close(duck.factor(at(450)), 1.0);
+    }
+
+    #[test]
+    fn duck_restores_exactly() {
+        let start = Instant::now();
+        let mut duck = Duck::new();
+        duck.set(true, start);
+        duck.set(false, start + Duration::from_millis(400));
+        let restored = duck.factor(start + Duration::from_millis(700));
+        assert_eq!(restored, 1.0);
+        assert_eq!(effective_volume(0.75, -3.0, restored), effective_volume(0.75, -3.0, 1.0));
+    }
+}
@@ -0,0 +1,66 @@
+//! 集成测试共用的辅助工具：临时目录、静音 WAV 与运行播放器
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+
+pub const BIN: &str = env!("CARGO_BIN_EXE_mddplayer");
+
+/// 测试用的临时目录，结束时删除
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("mddplayer-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 运行中的播放器，结束时终止
+pub struct Player(pub Child);
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// 写入一个 `seconds` 秒的静音 WAV（8 kHz、16 位、单声道）
+pub fn write_silence(path: &Path, seconds: u32) {
+    let data_len = 8000 * 2 * seconds;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // 声道数
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&16000u32.to_le_bytes()); // 每秒字节数
+    wav.extend_from_slice(&2u16.to_le_bytes()); // 每帧字节数
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(wav.len() + data_len as usize, 0);
+    fs::write(path, wav).unwrap();
+}
+
+pub fn mddplayer(data_home: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(BIN);
+    command.args(args).env("XDG_DATA_HOME", data_home).stdin(Stdio::null());
+    command
+}
+
+pub fn run(data_home: &Path, args: &[&str]) -> Output {
+    mddplayer(data_home, args).output().unwrap()
+}
@@ -0,0 +1,153 @@
+//! `--simulate`：在没有音频设备的环境中以 100 倍速播放整个列表
+
+mod common;
+
+use std::fs;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{mddplayer, run, write_silence, Player, TempDir};
+
+/// 从 `--dump-session` 的 JSON 中取出每个事件的 (t, event, 整条记录)
+fn events(json: &str) -> Vec<(f64, String, String)> {
+    json.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(",\n")
+        .map(|item| {
+            let field = |key: &str| {
+                let start = item.find(&format!("\"{}\":", key)).unwrap() + key.len() + 3;
+                item[start..].split([',', '}']).next().unwrap().trim_matches('"').to_string()
+            };
+            (field("t").parse().unwrap(), field("event"), item.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn plays_a_virtual_playlist_without_skips() {
+    let temp = TempDir::new("simulate-50");
+    let music = temp.0.join("music");
+    fs::create_dir_all(&music).unwrap();
+    // 50 首 2 秒的歌曲，100 倍速下每首约 20ms，预加载必须及时跟上
+    let tracks: Vec<_> = (1..=50)
+        .map(|i| {
+            let path = music.join(format!("Tester - Song {:02}.wav", i));
+            write_silence(&path, 2);
+            path
+        })
+        .collect();
+    let list = temp.0.join("list.m3u");
+    fs::write(&list, tracks.iter().map(|t| format!("{}\n", t.display())).collect::<String>()).unwrap();
+    let dump = temp.0.join("session.json");
+
+    let child = mddplayer(&temp.0.join("data"), &[list.to_str().unwrap(), "--simulate", "100", "--dump-session", dump.to_str().unwrap()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut player = Player(child);
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let status = loop {
+        if let Some(status) = player.0.try_wait().unwrap() {
+            break status;
+        }
+        assert!(Instant::now() < deadline, "播放器没有在 60 秒内播完列表（接收循环可能卡住）");
+        thread::sleep(Duration::from_millis(50));
+    };
+    assert!(status.success(), "{:?}", status);
+
+    let events = events(&fs::read_to_string(&dump).unwrap());
+    // 没有因加载失败或超时而跳过的歌曲
+    assert!(events.iter().all(|(_, event, _)| event != "skip"), "{:?}", events);
+    let started: Vec<_> = events.iter().filter(|(_, event, _)| event == "track").collect();
+    assert_eq!(started.len(), 50);
+    for (i, ((_, _, item), path)) in started.iter().zip(&tracks).enumerate() {
+        assert!(item.contains(&format!("\"index\":{},", i + 1)), "{}", item);
+        assert!(item.contains(&format!("Song {:02}.wav", i + 1)), "{} {}", item, path.display());
+    }
+    // 预加载按时到达：相邻两首的开始时间不会因等待预加载而拉长
+    for pair in started.windows(2) {
+        let gap = pair[1].0 - pair[0].0;
+        assert!(gap < 2.0, "第 {} 首之后等了 {:.3} 秒", pair[0].2, gap);
+    }
+}
+
+#[test]
+fn loops_a_single_track_without_waiting() {
+    let temp = TempDir::new("simulate-loop-one");
+    let music = temp.0.join("music");
+    let data_home = temp.0.join("data");
+    fs::create_dir_all(&music).unwrap();
+    write_silence(&music.join("Tester - Only.wav"), 30);
+
+    // 15 倍速下每遍约 2 秒（状态每秒刷新一次，一遍中能看到两个位置）；重播前没有预加载时每遍之间会多等 5 秒的接收超时
+    let child = mddplayer(&data_home, &[music.to_str().unwrap(), "--loop", "--simulate", "15"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let _player = Player(child);
+    let status = || {
+        let output = run(&data_home, &["status", "--format", "{index}/{total} {state} {position}"]);
+        output.status.success().then(|| String::from_utf8(output.stdout).unwrap().trim_end().to_string())
+    };
+    let deadline = Instant::now() + Duration::from_secs(15);
+    while status().is_none() {
+        assert!(Instant::now() < deadline, "播放器没有在 15 秒内提供状态");
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let (mut wraps, mut last) = (0, 0);
+    let until = Instant::now() + Duration::from_secs(7);
+    while Instant::now() < until {
+        let line = status().expect("播放器在循环播放中退出");
+        let (counter, rest) = line.split_once(' ').unwrap();
+        assert_eq!(counter, "1/1", "{}", line);
+        assert!(rest.starts_with("playing "), "{}", line);
+        let seconds: u64 = rest.rsplit(':').next().unwrap().parse().unwrap();
+        if seconds < last {
+            wraps += 1;
+        }
+        last = seconds;
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert!(wraps >= 2, "7 秒内只重播了 {} 遍", wraps);
+}
+
+#[test]
+fn stopping_on_errors_still_saves_diagnostics() {
+    let temp = TempDir::new("simulate-abort");
+    let music = temp.0.join("music");
+    fs::create_dir_all(&music).unwrap();
+    let good = music.join("Tester - Good.wav");
+    write_silence(&good, 1);
+    let bad: Vec<_> = (1..=2)
+        .map(|i| {
+            let path = music.join(format!("Tester - Broken {}.wav", i));
+            fs::write(&path, b"not a wav file").unwrap();
+            path
+        })
+        .collect();
+    let list = temp.0.join("list.m3u");
+    fs::write(&list, format!("{}\n{}\n{}\n", good.display(), bad[0].display(), bad[1].display())).unwrap();
+
+    // --on-error abort 与 --max-consecutive-errors 达到上限两种停止方式
+    for (name, policy) in [("abort", &["--on-error", "abort"][..]), ("max-errors", &["--on-error", "skip", "--max-consecutive-errors", "2"][..])] {
+        let data_home = temp.0.join(format!("data-{}", name));
+        let dump = temp.0.join(format!("session-{}.json", name));
+        let mut args = vec![list.to_str().unwrap(), "--simulate", "100", "--timing", "--dump-session", dump.to_str().unwrap()];
+        args.extend_from_slice(policy);
+        let output = run(&data_home, &args);
+        assert_eq!(output.status.code(), Some(1), "{}", name);
+
+        // 会话记录、计时汇总与播放位置在退出前保存，控制套接字已删除
+        let events = events(&fs::read_to_string(&dump).unwrap());
+        assert!(events.iter().any(|(_, event, item)| event == "track" && item.contains("Good.wav")), "{}: {:?}", name, events);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("[计时]共 1 首"), "{}: {}", name, stderr);
+        assert!(data_home.join("mddplayer/resume").exists(), "{}", name);
+        assert!(!data_home.join("mddplayer/ctl.sock").exists(), "{}", name);
+    }
+}
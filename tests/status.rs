@@ -0,0 +1,60 @@
+//! `mddplayer status` / `duck`：启动一个使用模拟输出（`--simulate`）的播放器并查询它
+
+mod common;
+
+use std::fs;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{mddplayer, run, write_silence, Player, TempDir};
+
+#[test]
+fn status_without_player_exits_1_silently() {
+    let temp = TempDir::new("status-none");
+    let output = run(&temp.0, &["status"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(run(&temp.0, &["duck", "on"]).status.code(), Some(1));
+}
+
+#[test]
+fn status_reports_running_player() {
+    let temp = TempDir::new("status-running");
+    let music = temp.0.join("music");
+    let data_home = temp.0.join("data");
+    fs::create_dir_all(&music).unwrap();
+    write_silence(&music.join("Tester - Ping.wav"), 600);
+
+    let player = mddplayer(&data_home, &[music.to_str().unwrap(), "--simulate", "1"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let player = Player(player);
+
+    // 播放器刷新第一次状态之前查询不到内容
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let json = loop {
+        let output = run(&data_home, &["status"]);
+        if output.status.success() {
+            break String::from_utf8(output.stdout).unwrap();
+        }
+        assert!(Instant::now() < deadline, "播放器没有在 15 秒内提供状态");
+        thread::sleep(Duration::from_millis(200));
+    };
+    assert!(json.trim_end().starts_with("{\"state\":\"playing\",\"title\":\"Ping\",\"artist\":\"Tester\""), "{}", json);
+
+    let output = run(&data_home, &["status", "--format", "{artist} – {title} {index}/{total} {duration} {state}"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Tester – Ping 1/1 10:00 playing\n");
+
+    assert!(run(&data_home, &["duck", "on"]).status.success());
+    assert!(run(&data_home, &["duck", "off"]).status.success());
+
+    // 播放器退出后不再有状态
+    drop(player);
+    let output = run(&data_home, &["status"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}